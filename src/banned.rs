@@ -0,0 +1,177 @@
+//! Check for context strings matching a configured denylist.
+//!
+//! Error messages end up in logs and in front of users, so some things don't
+//! belong in them: leftover `TODO`s, ticket numbers, internal hostnames. The
+//! regular expressions under `[banned-context]` in `context-lint.toml` are
+//! matched against the strings of context attributes and the messages of
+//! context method calls (literals and `format!` strings).
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::collector::AnnotatedFunctions;
+use crate::config::ContextMethodsConfig;
+use crate::parse::{ParsedFile, Span};
+use crate::style::for_each_context_message;
+
+/// A context string matching a banned pattern.
+#[derive(Debug, Clone)]
+pub struct BannedContext {
+    /// File where the context string is.
+    pub file: String,
+    /// Line of the function (for attributes) or the method call.
+    pub line: usize,
+    /// The string literal, if its position is known.
+    pub span: Span,
+    /// Where the string is, e.g. ``#[context] on `load` `` or `` `.context()` ``.
+    pub site: String,
+    /// The context string.
+    pub context: String,
+    /// The pattern it matches, as configured.
+    pub pattern: String,
+    /// The part of the string matching it.
+    pub matched: String,
+}
+
+/// The compiled patterns of `[banned-context]`.
+#[derive(Debug, Clone, Default)]
+pub struct Denylist {
+    patterns: Vec<Regex>,
+}
+
+impl Denylist {
+    /// Compile the configured patterns.
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid pattern in [banned-context]: {pattern}"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// The first pattern matching `context`, and the part it matches.
+    fn find(&self, context: &str) -> Option<(&Regex, String)> {
+        self.patterns.iter().find_map(|regex| {
+            regex
+                .find(context)
+                .map(|found| (regex, found.as_str().to_string()))
+        })
+    }
+}
+
+/// Check the context strings of the collected functions.
+pub fn check_annotated(index: &AnnotatedFunctions, denylist: &Denylist) -> Vec<BannedContext> {
+    index
+        .iter()
+        .filter_map(|af| {
+            let context = af.written_context()?;
+            let (regex, matched) = denylist.find(context)?;
+            Some(BannedContext {
+                file: af.file.to_string(),
+                line: af.line,
+                span: af.attr_span,
+                site: format!("#[context] on `{}`", af.name),
+                context: context.to_string(),
+                pattern: regex.as_str().to_string(),
+                matched,
+            })
+        })
+        .collect()
+}
+
+/// Check the messages of the context method calls in a file.
+pub fn check_file(
+    file: &ParsedFile,
+    denylist: &Denylist,
+    methods: &ContextMethodsConfig,
+) -> Vec<BannedContext> {
+    let mut results = Vec::new();
+    if denylist.is_empty() {
+        return results;
+    }
+
+    for_each_context_message(file, methods, |call, message| {
+        let context = message.literal.value();
+        if let Some((regex, matched)) = denylist.find(&context) {
+            let literal = message.literal.span();
+            results.push(BannedContext {
+                file: file.display_path().to_string(),
+                line: call.method.span().start().line,
+                span: Span::new(literal, literal, file.syn_offset()),
+                site: format!("`.{}()`", call.method),
+                context,
+                pattern: regex.as_str().to_string(),
+                matched,
+            });
+        }
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn denylist() -> Denylist {
+        Denylist::new(&[r"(?i)\btodo\b".to_string(), r"JIRA-[0-9]+".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_call_sites() {
+        let source = r#"
+            fn main() -> anyhow::Result<()> {
+                load().context("Loading config (todo: retry)")?;
+                load().with_context(|| format!("Loading {name}, see JIRA-123"))?;
+                load().context("Loading the todolist")?;
+                load().map(|x| "TODO")?;
+                Ok(())
+            }
+        "#;
+        let file = ParsedFile::from_source(Path::new("test.rs"), source.to_string());
+        let results = check_file(&file, &denylist(), &ContextMethodsConfig::default());
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].line, 3);
+        assert_eq!(results[0].site, "`.context()`");
+        assert_eq!(results[0].matched, "todo");
+        assert_eq!(results[0].span.column, 32);
+        assert_eq!(results[1].site, "`.with_context()`");
+        assert_eq!(results[1].pattern, "JIRA-[0-9]+");
+        assert_eq!(results[1].matched, "JIRA-123");
+    }
+
+    #[test]
+    fn test_annotated() {
+        let source = r#"
+            #[context("Syncing TODO")]
+            fn sync() -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            #[context("Loading")]
+            fn load() -> anyhow::Result<()> {
+                Ok(())
+            }
+        "#;
+        let file = ParsedFile::from_source(Path::new("test.rs"), source.to_string());
+        let collected = crate::collector::collect_from_file(&file, &[], &[], &[]);
+        let index = crate::collector::build_index(collected.functions);
+        let results = check_annotated(&index, &denylist());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].site, "#[context] on `sync`");
+        assert_eq!(results[0].context, "Syncing TODO");
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = Denylist::new(&["(unclosed".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("(unclosed"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Baseline files, listing known findings that are not reported, for
+//! `--baseline` and `--update-baseline`.
+//!
+//! Entries are matched by fingerprint, so a finding
+//! moving because of edits above it stays suppressed. Its location in the
+//! file is informational, and `--update-baseline` brings it up to date.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::fingerprint;
+use crate::linter::Finding;
+use crate::report::strip_path;
+
+/// Version of the baseline format.
+pub const BASELINE_VERSION: u32 = 1;
+
+/// The contents of a baseline file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    /// Version of the format (`BASELINE_VERSION`).
+    pub version: u32,
+    /// The known findings, by file and line.
+    pub findings: Vec<Entry>,
+}
+
+/// A known finding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// What the finding is matched by.
+    pub fingerprint: String,
+    /// The check's code (e.g. `CL0001`).
+    pub code: String,
+    /// File of the finding, relative to the workspace root.
+    pub file: String,
+    /// Line of the finding when the entry was written.
+    pub line: usize,
+}
+
+impl Entry {
+    /// The entry of a finding, with its path relative to `strip_prefix`.
+    pub fn new(finding: &Finding, strip_prefix: Option<&str>) -> Self {
+        Self {
+            fingerprint: fingerprint::of_finding(finding, strip_prefix),
+            code: finding.lint().code().to_string(),
+            file: strip_path(finding.file(), strip_prefix).to_string(),
+            line: finding.line(),
+        }
+    }
+}
+
+impl Baseline {
+    /// A baseline of the given entries, sorted by file and line.
+    pub fn new(mut findings: Vec<Entry>) -> Self {
+        findings.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+        Self {
+            version: BASELINE_VERSION,
+            findings,
+        }
+    }
+
+    /// The number of entries with each fingerprint.
+    pub fn counts(&self) -> HashMap<String, (String, usize)> {
+        let mut counts: HashMap<String, (String, usize)> = HashMap::new();
+        for entry in &self.findings {
+            counts
+                .entry(entry.fingerprint.clone())
+                .or_insert((entry.file.clone(), 0))
+                .1 += 1;
+        }
+        counts
+    }
+
+    /// How `updated` differs from this baseline: the number of entries whose
+    /// location changed and of those no longer found.
+    pub fn changes(&self, updated: &Baseline) -> (usize, usize) {
+        let mut old: HashMap<&str, Vec<&Entry>> = HashMap::new();
+        for entry in &self.findings {
+            old.entry(&entry.fingerprint).or_default().push(entry);
+        }
+        let mut moved = 0;
+        for entry in &updated.findings {
+            let Some(entries) = old.get_mut(entry.fingerprint.as_str()) else {
+                continue;
+            };
+            // The same location if any entry has it, else the first one.
+            let idx = entries
+                .iter()
+                .position(|e| e.file == entry.file && e.line == entry.line);
+            match idx {
+                Some(idx) => {
+                    entries.remove(idx);
+                }
+                None if !entries.is_empty() => {
+                    entries.remove(0);
+                    moved += 1;
+                }
+                None => {}
+            }
+        }
+        let removed = old.values().map(Vec::len).sum();
+        (moved, removed)
+    }
+}
+
+/// Read a baseline file.
+pub fn read(path: &Path) -> Result<Baseline> {
+    let json =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Parsing {}", path.display()))
+}
+
+/// Write a baseline file.
+pub fn write(path: &Path, baseline: &Baseline) -> Result<()> {
+    let json = serde_json::to_string_pretty(baseline)?;
+    std::fs::write(path, json + "\n").with_context(|| format!("Writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fingerprint: &str, line: usize) -> Entry {
+        Entry {
+            fingerprint: fingerprint.to_string(),
+            code: "CL0002".to_string(),
+            file: "src/lib.rs".to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = Baseline::new(vec![
+            entry("a", 3),
+            entry("b", 10),
+            entry("b", 20),
+            entry("c", 30),
+        ]);
+        // Two lines added at the top, `c` fixed, one `b` unchanged.
+        let updated = Baseline::new(vec![entry("a", 5), entry("b", 20), entry("b", 12)]);
+        assert_eq!(old.changes(&updated), (2, 1));
+        assert_eq!(old.changes(&old), (0, 0));
+
+        assert_eq!(old.counts()["b"], ("src/lib.rs".to_string(), 2));
+        let lines: Vec<usize> = updated.findings.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [5, 12, 20]);
+    }
+}
@@ -0,0 +1,626 @@
+//! Evaluation of `#[cfg(..)]` predicates, for `--features`, `--all-features`,
+//! `--no-default-features` and `--target`.
+//!
+//! Items whose `cfg` evaluates to false for the selected features and the
+//! target platform (the host by default) are removed from the syntax tree
+//! before any check runs, so nothing is reported for code that isn't compiled.
+//! Predicates that can't be decided from the source alone (`test`,
+//! `debug_assertions`, custom `--cfg` flags, `feature` without any feature
+//! flags) count as enabled.
+
+use std::collections::{BTreeMap, HashSet};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use syn::punctuated::Punctuated;
+use syn::visit_mut::VisitMut;
+use syn::{
+    Attribute, Block, Expr, ExprLit, File, ImplItem, Item, ItemImpl, ItemMod, ItemTrait, Lit, Meta,
+    Stmt, Token, TraitItem,
+};
+
+/// What `cfg` predicates are evaluated against.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    /// The enabled features of the package.
+    pub features: Features,
+    /// The target platform, or `None` to leave platform predicates
+    /// undecided.
+    pub target: Option<Target>,
+}
+
+/// The enabled features of a package.
+#[derive(Debug, Clone)]
+pub enum Features {
+    /// No feature flags were given: `feature` predicates are undecided.
+    Undecided,
+    /// `--all-features`.
+    All,
+    /// The features enabled by the flags, including those they imply.
+    Enabled(HashSet<String>),
+}
+
+impl Features {
+    /// Resolve the features of a package, given its `[features]` table and
+    /// the requested features (either `name` or `package/name`).
+    pub fn resolve(
+        package: &str,
+        table: &BTreeMap<String, Vec<String>>,
+        requested: &[String],
+        all_features: bool,
+        no_default_features: bool,
+    ) -> Self {
+        if all_features {
+            return Features::All;
+        }
+        let mut pending: Vec<String> = Vec::new();
+        if !no_default_features && table.contains_key("default") {
+            pending.push("default".to_string());
+        }
+        for feature in requested {
+            match feature.split_once('/') {
+                Some((pkg, name)) if pkg == package => pending.push(name.to_string()),
+                Some(_) => {}
+                None => pending.push(feature.clone()),
+            }
+        }
+
+        let mut enabled = HashSet::new();
+        while let Some(feature) = pending.pop() {
+            if !enabled.insert(feature.clone()) {
+                continue;
+            }
+            for implied in table.get(&feature).into_iter().flatten() {
+                // `dep:name` enables an optional dependency without a feature
+                // of that name, and `other/feature` and `other?/feature`
+                // enable features of dependencies.
+                if !implied.starts_with("dep:") && !implied.contains('/') {
+                    pending.push(implied.clone());
+                }
+            }
+        }
+        Features::Enabled(enabled)
+    }
+
+    fn contains(&self, feature: &str) -> Option<bool> {
+        match self {
+            Features::Undecided => None,
+            Features::All => Some(true),
+            Features::Enabled(enabled) => Some(enabled.contains(feature)),
+        }
+    }
+}
+
+/// The `key = "value"` predicates describing a target platform.
+const TARGET_KEYS: &[&str] = &[
+    "target_os",
+    "target_family",
+    "target_arch",
+    "target_pointer_width",
+    "target_endian",
+    "target_env",
+    "target_vendor",
+    "target_abi",
+];
+
+/// The `cfg` values of a target platform.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Target {
+    /// The `key = "value"` pairs set, e.g. `target_os = "linux"`.
+    values: Vec<(String, String)>,
+    /// The names set, of `unix` and `windows`.
+    names: Vec<String>,
+    /// The keys whose predicates can be decided.
+    keys: Vec<&'static str>,
+}
+
+impl Target {
+    /// The platform this binary was built for. `target_env`, `target_vendor`
+    /// and `target_abi` are undecided.
+    pub fn host() -> Self {
+        let endian = if cfg!(target_endian = "little") {
+            "little"
+        } else {
+            "big"
+        };
+        let values = [
+            ("target_os", std::env::consts::OS.to_string()),
+            ("target_family", std::env::consts::FAMILY.to_string()),
+            ("target_arch", std::env::consts::ARCH.to_string()),
+            ("target_pointer_width", usize::BITS.to_string()),
+            ("target_endian", endian.to_string()),
+        ];
+        Self {
+            keys: values.iter().map(|(key, _)| *key).collect(),
+            values: values
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+            names: ["unix", "windows"]
+                .into_iter()
+                .filter(|name| *name == std::env::consts::FAMILY)
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// The platform of a target triple, as `rustc --print cfg` describes it.
+    pub fn from_triple(triple: &str) -> Result<Self> {
+        let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+        let output = Command::new(rustc)
+            .args(["--print", "cfg", "--target", triple])
+            .output()
+            .with_context(|| format!("Running rustc for target {triple}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.lines().next().unwrap_or_default();
+            bail!(
+                "Unknown target {triple}: {}",
+                message.trim_start_matches("error: ")
+            );
+        }
+        Ok(Self::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Parse the output of `rustc --print cfg`, one `name` or `key="value"`
+    /// per line.
+    pub fn parse(cfg: &str) -> Self {
+        let mut target = Self {
+            keys: TARGET_KEYS.to_vec(),
+            ..Self::default()
+        };
+        for line in cfg.lines().map(str::trim) {
+            match line.split_once('=') {
+                Some((key, value)) if TARGET_KEYS.contains(&key) => {
+                    let value = value.trim_matches('"').to_string();
+                    target.values.push((key.to_string(), value));
+                }
+                None if line == "unix" || line == "windows" => {
+                    target.names.push(line.to_string());
+                }
+                _ => {}
+            }
+        }
+        target
+    }
+
+    fn has_name(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name)
+    }
+
+    fn has_value(&self, key: &str, value: &str) -> Option<bool> {
+        self.keys
+            .contains(&key)
+            .then(|| self.values.iter().any(|(k, v)| k == key && v == value))
+    }
+}
+
+/// Evaluate a `cfg` predicate: `None` if it can't be decided.
+pub fn eval(meta: &Meta, cfg: &Cfg) -> Option<bool> {
+    match meta {
+        Meta::Path(path) => {
+            let name = path.get_ident()?.to_string();
+            match name.as_str() {
+                "unix" | "windows" => Some(cfg.target.as_ref()?.has_name(&name)),
+                _ => None,
+            }
+        }
+        Meta::NameValue(nv) => {
+            let name = nv.path.get_ident()?.to_string();
+            let Expr::Lit(ExprLit {
+                lit: Lit::Str(value),
+                ..
+            }) = &nv.value
+            else {
+                return None;
+            };
+            let value = value.value();
+            match name.as_str() {
+                "feature" => cfg.features.contains(&value),
+                key => cfg.target.as_ref()?.has_value(key, &value),
+            }
+        }
+        Meta::List(list) => {
+            let args = list
+                .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .ok()?;
+            let values: Vec<Option<bool>> = args.iter().map(|arg| eval(arg, cfg)).collect();
+            let name = list.path.get_ident()?.to_string();
+            match name.as_str() {
+                "all" => {
+                    if values.contains(&Some(false)) {
+                        Some(false)
+                    } else if values.iter().all(|v| *v == Some(true)) {
+                        Some(true)
+                    } else {
+                        None
+                    }
+                }
+                "any" => {
+                    if values.contains(&Some(true)) {
+                        Some(true)
+                    } else if values.iter().all(|v| *v == Some(false)) {
+                        Some(false)
+                    } else {
+                        None
+                    }
+                }
+                "not" if values.len() == 1 => values[0].map(|v| !v),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Whether code with these attributes is compiled: false only if one of its
+/// `#[cfg(..)]` attributes is known to be false.
+pub fn is_enabled(attrs: &[Attribute], cfg: &Cfg) -> bool {
+    !attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr
+                .parse_args::<Meta>()
+                .is_ok_and(|meta| eval(&meta, cfg) == Some(false))
+    })
+}
+
+/// The predicate and the attributes of a `#[cfg_attr(predicate, attrs..)]`
+/// attribute.
+pub fn cfg_attr(attr: &Attribute) -> Option<(Meta, Vec<Meta>)> {
+    if !attr.path().is_ident("cfg_attr") {
+        return None;
+    }
+    let mut args = attr
+        .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+        .ok()?
+        .into_iter();
+    let predicate = args.next()?;
+    Some((predicate, args.collect()))
+}
+
+/// Format a `cfg` predicate the way it is usually written, e.g.
+/// `all(unix, feature = "x")`.
+pub fn format_predicate(meta: &Meta) -> String {
+    let path = |path: &syn::Path| {
+        path.segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("::")
+    };
+    match meta {
+        Meta::Path(p) => path(p),
+        Meta::NameValue(nv) => match &nv.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(value),
+                ..
+            }) => format!("{} = {:?}", path(&nv.path), value.value()),
+            _ => format!("{} = ..", path(&nv.path)),
+        },
+        Meta::List(list) => {
+            let args = match list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated) {
+                Ok(args) => args.iter().map(format_predicate).collect::<Vec<_>>(),
+                Err(_) => vec![list.tokens.to_string()],
+            };
+            format!("{}({})", path(&list.path), args.join(", "))
+        }
+    }
+}
+
+/// Remove the items, statements and match arms that aren't compiled with
+/// `cfg` from a syntax tree, and the `cfg_attr` attributes of functions that
+/// don't apply.
+pub fn strip(file: &mut File, cfg: &Cfg) {
+    Stripper { cfg }.visit_file_mut(file);
+}
+
+struct Stripper<'a> {
+    cfg: &'a Cfg,
+}
+
+impl VisitMut for Stripper<'_> {
+    fn visit_file_mut(&mut self, node: &mut File) {
+        // `#![cfg(..)]` disables the whole file.
+        if !is_enabled(&node.attrs, self.cfg) {
+            node.items.clear();
+        }
+        node.items
+            .retain(|item| is_enabled(item_attrs(item), self.cfg));
+        syn::visit_mut::visit_file_mut(self, node);
+    }
+
+    fn visit_item_mod_mut(&mut self, node: &mut ItemMod) {
+        if let Some((_, items)) = &mut node.content {
+            items.retain(|item| is_enabled(item_attrs(item), self.cfg));
+        }
+        syn::visit_mut::visit_item_mod_mut(self, node);
+    }
+
+    fn visit_item_impl_mut(&mut self, node: &mut ItemImpl) {
+        node.items.retain(|item| {
+            let attrs = match item {
+                ImplItem::Const(item) => &item.attrs,
+                ImplItem::Fn(item) => &item.attrs,
+                ImplItem::Type(item) => &item.attrs,
+                ImplItem::Macro(item) => &item.attrs,
+                _ => return true,
+            };
+            is_enabled(attrs, self.cfg)
+        });
+        syn::visit_mut::visit_item_impl_mut(self, node);
+    }
+
+    fn visit_item_trait_mut(&mut self, node: &mut ItemTrait) {
+        node.items.retain(|item| {
+            let attrs = match item {
+                TraitItem::Const(item) => &item.attrs,
+                TraitItem::Fn(item) => &item.attrs,
+                TraitItem::Type(item) => &item.attrs,
+                TraitItem::Macro(item) => &item.attrs,
+                _ => return true,
+            };
+            is_enabled(attrs, self.cfg)
+        });
+        syn::visit_mut::visit_item_trait_mut(self, node);
+    }
+
+    fn visit_block_mut(&mut self, node: &mut Block) {
+        node.stmts.retain(|stmt| {
+            let attrs = match stmt {
+                Stmt::Local(local) => &local.attrs,
+                Stmt::Item(item) => item_attrs(item),
+                Stmt::Macro(mac) => &mac.attrs,
+                Stmt::Expr(..) => return true,
+            };
+            is_enabled(attrs, self.cfg)
+        });
+        syn::visit_mut::visit_block_mut(self, node);
+    }
+
+    fn visit_item_fn_mut(&mut self, node: &mut syn::ItemFn) {
+        strip_cfg_attrs(&mut node.attrs, self.cfg);
+        syn::visit_mut::visit_item_fn_mut(self, node);
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, node: &mut syn::ImplItemFn) {
+        strip_cfg_attrs(&mut node.attrs, self.cfg);
+        syn::visit_mut::visit_impl_item_fn_mut(self, node);
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, node: &mut syn::TraitItemFn) {
+        strip_cfg_attrs(&mut node.attrs, self.cfg);
+        syn::visit_mut::visit_trait_item_fn_mut(self, node);
+    }
+
+    fn visit_expr_match_mut(&mut self, node: &mut syn::ExprMatch) {
+        node.arms.retain(|arm| is_enabled(&arm.attrs, self.cfg));
+        syn::visit_mut::visit_expr_match_mut(self, node);
+    }
+}
+
+/// Remove the `#[cfg_attr(..)]` attributes whose predicate is known to be
+/// false.
+fn strip_cfg_attrs(attrs: &mut Vec<Attribute>, cfg: &Cfg) {
+    attrs.retain(|attr| {
+        cfg_attr(attr).is_none_or(|(predicate, _)| eval(&predicate, cfg) != Some(false))
+    });
+}
+
+/// The attributes of an item.
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> BTreeMap<String, Vec<String>> {
+        let mut table = BTreeMap::new();
+        table.insert("default".to_string(), vec!["std".to_string()]);
+        table.insert("std".to_string(), vec!["serde?/std".to_string()]);
+        table.insert(
+            "full".to_string(),
+            vec!["std".to_string(), "dep:tokio".to_string()],
+        );
+        table.insert("extra".to_string(), Vec::new());
+        table
+    }
+
+    fn host(features: &Features) -> Cfg {
+        Cfg {
+            features: features.clone(),
+            target: Some(Target::host()),
+        }
+    }
+
+    fn enabled(features: &Features, predicate: &str) -> Option<bool> {
+        eval(&syn::parse_str(predicate).unwrap(), &host(features))
+    }
+
+    #[test]
+    fn test_cfg_attr() {
+        let attr: Attribute =
+            syn::parse_quote!(#[cfg_attr(any(feature = "a", not(test)), context("x"), inline)]);
+        let (predicate, attrs) = cfg_attr(&attr).unwrap();
+        assert_eq!(
+            format_predicate(&predicate),
+            r#"any(feature = "a", not(test))"#
+        );
+        assert_eq!(attrs.len(), 2);
+        assert!(cfg_attr(&syn::parse_quote!(#[cfg(test)])).is_none());
+
+        let mut file: File = syn::parse_str(
+            r#"
+            #[cfg_attr(feature = "std", context("Kept"))]
+            #[cfg_attr(feature = "full", context("Dropped"))]
+            #[cfg_attr(test, context("Undecided"))]
+            fn f() {}
+            "#,
+        )
+        .unwrap();
+        let features = Features::resolve("pkg", &table(), &[], false, false);
+        strip(&mut file, &host(&features));
+        let Item::Fn(f) = &file.items[0] else {
+            panic!("expected fn");
+        };
+        assert_eq!(f.attrs.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let features = Features::resolve("pkg", &table(), &[], false, false);
+        assert_eq!(enabled(&features, r#"feature = "std""#), Some(true));
+        assert_eq!(enabled(&features, r#"feature = "full""#), Some(false));
+
+        let features = Features::resolve("pkg", &table(), &[], false, true);
+        assert_eq!(enabled(&features, r#"feature = "std""#), Some(false));
+
+        let requested = ["pkg/full".to_string(), "other/extra".to_string()];
+        let features = Features::resolve("pkg", &table(), &requested, false, true);
+        assert_eq!(enabled(&features, r#"feature = "std""#), Some(true));
+        assert_eq!(enabled(&features, r#"feature = "tokio""#), Some(false));
+        assert_eq!(enabled(&features, r#"feature = "extra""#), Some(false));
+
+        let features = Features::resolve("pkg", &table(), &[], true, true);
+        assert_eq!(enabled(&features, r#"feature = "anything""#), Some(true));
+    }
+
+    #[test]
+    fn test_eval() {
+        let features = Features::resolve("pkg", &table(), &[], false, false);
+        assert_eq!(
+            enabled(&features, r#"all(feature = "std", not(feature = "full"))"#),
+            Some(true)
+        );
+        assert_eq!(
+            enabled(&features, r#"any(feature = "full", feature = "extra")"#),
+            Some(false)
+        );
+        assert_eq!(enabled(&features, "test"), None);
+        assert_eq!(
+            enabled(&features, r#"all(test, feature = "full")"#),
+            Some(false)
+        );
+        assert_eq!(
+            enabled(&features, r#"any(test, feature = "std")"#),
+            Some(true)
+        );
+        assert_eq!(
+            enabled(
+                &features,
+                &format!(r#"target_os = "{}""#, std::env::consts::OS)
+            ),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_target() {
+        let windows = Target::parse(
+            r#"
+            debug_assertions
+            panic="unwind"
+            target_arch="x86_64"
+            target_env="msvc"
+            target_family="windows"
+            target_feature="sse2"
+            target_os="windows"
+            target_pointer_width="64"
+            target_vendor="pc"
+            windows
+            "#,
+        );
+        let cfg = Cfg {
+            features: Features::Undecided,
+            target: Some(windows),
+        };
+        let enabled = |predicate: &str| eval(&syn::parse_str(predicate).unwrap(), &cfg);
+        assert_eq!(enabled("windows"), Some(true));
+        assert_eq!(enabled("unix"), Some(false));
+        assert_eq!(enabled(r#"target_os = "linux""#), Some(false));
+        assert_eq!(enabled(r#"all(windows, target_env = "msvc")"#), Some(true));
+        assert_eq!(enabled(r#"target_abi = "eabi""#), Some(false));
+        assert_eq!(enabled(r#"target_feature = "avx2""#), None);
+        assert_eq!(enabled(r#"feature = "std""#), None);
+
+        // The host's environment is unknown.
+        let host = host(&Features::Undecided);
+        let env = syn::parse_str(r#"target_env = "gnu""#).unwrap();
+        assert_eq!(eval(&env, &host), None);
+        let any_target = Cfg {
+            features: Features::Undecided,
+            target: None,
+        };
+        assert_eq!(eval(&syn::parse_str("windows").unwrap(), &any_target), None);
+
+        let mut file: File = syn::parse_str(
+            r#"
+            #![cfg(unix)]
+            fn f() {}
+            "#,
+        )
+        .unwrap();
+        strip(&mut file, &cfg);
+        assert!(file.items.is_empty());
+    }
+
+    #[test]
+    fn test_strip() {
+        let mut file: File = syn::parse_str(
+            r#"
+            #[cfg(feature = "full")]
+            fn gated() {}
+            fn kept() {
+                #[cfg(feature = "full")]
+                let x = 1;
+                match y {
+                    #[cfg(feature = "full")]
+                    A => {}
+                    _ => {}
+                }
+            }
+            impl T {
+                #[cfg(not(feature = "std"))]
+                fn no_std() {}
+            }
+            #[cfg(test)]
+            mod tests {}
+            "#,
+        )
+        .unwrap();
+        let features = Features::resolve("pkg", &table(), &[], false, false);
+        strip(&mut file, &host(&features));
+
+        assert_eq!(file.items.len(), 3);
+        let Item::Fn(kept) = &file.items[0] else {
+            panic!("expected fn");
+        };
+        assert_eq!(kept.block.stmts.len(), 1);
+        let Stmt::Expr(Expr::Match(m), _) = &kept.block.stmts[0] else {
+            panic!("expected match");
+        };
+        assert_eq!(m.arms.len(), 1);
+        let Item::Impl(imp) = &file.items[1] else {
+            panic!("expected impl");
+        };
+        assert!(imp.items.is_empty());
+    }
+}
@@ -1,39 +1,151 @@
 //! Pass 2: Find call sites where a `#[context]`-annotated function is called
-//! and the result is additionally wrapped with `.context()` or `.with_context()`.
+//! and the result is additionally wrapped with `.context()` or `.with_context()`
+//! (or eyre's `.wrap_err()` / `.wrap_err_with()`).
 
-use std::path::Path;
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use serde::Serialize;
 use syn::visit::Visit;
-use syn::{Expr, ExprAwait, ExprCall, ExprMethodCall, ExprPath, File};
+use syn::ExprPath;
+use syn::{Block, Expr, ExprAwait, ExprCall, ExprClosure, ExprIf, ExprMatch, ExprMethodCall};
+use syn::{ImplItemFn, ItemFn, ItemImpl, ItemMod, Local, Signature};
 
-use crate::collector::{AnnotatedFunction, AnnotatedFunctions};
+use crate::collector::{type_name, AnnotatedFunction, AnnotatedFunctions, AnnotationSource};
+use crate::config::{ContextMethodsConfig, DEFAULT_CONTEXT_METHODS};
+use crate::fix::{Fix, TextEdit};
+use crate::imports::{collect_imports, qualified_candidates, resolve, Imports};
+use crate::infer::Locals;
+use crate::parse::{ParsedFile, Span};
 
 /// A detected double-context issue.
 #[derive(Debug, Clone)]
 pub struct DoubleContext {
     /// File where the call site is located.
-    pub call_file: String,
-    /// Line number of the `.context()` / `.with_context()` call.
+    pub call_file: Arc<str>,
+    /// Line number of the outer context call.
     pub call_line: usize,
+    /// The span of the outer context call, from the method name to the
+    /// closing parenthesis.
+    pub call_span: Span,
     /// The function name that has `#[context]`.
-    pub function_name: String,
-    /// The context string from the `#[context]` attribute on the function definition.
-    pub inner_context: String,
-    /// The context string from the `.context()` / `.with_context()` at the call site
+    pub function_name: Arc<str>,
+    /// The context string from the `#[context]` attribute on the function
+    /// definition, or from the `[[rules]]` entry selecting it.
+    pub inner_context: Option<String>,
+    /// Whether the definition is annotated or selected by a `[[rules]]` entry.
+    pub inner_source: AnnotationSource,
+    /// The format arguments of the `#[context]` attribute, as written.
+    pub inner_args: Vec<String>,
+    /// The context string from the outer context call at the call site
     /// (best-effort extraction; may be None if it's a complex expression).
     pub outer_context: Option<String>,
     /// File where the annotated function is defined.
-    pub def_file: String,
+    pub def_file: Arc<str>,
     /// Line where the annotated function is defined.
     pub def_line: usize,
-    /// Whether the outer method was `.with_context()` (vs `.context()`).
-    pub is_with_context: bool,
+    /// The span of the `#[context]` attribute of the definition.
+    pub def_span: Span,
+    /// The outer method adding context: `context`, `with_context`, eyre's
+    /// `wrap_err` / `wrap_err_with`, or `map_err` (with a closure wrapping the
+    /// error via `anyhow!` or `eyre!`, or adding context to it).
+    pub outer_method: String,
+    /// Fix removing the redundant outer context call.
+    pub fix: Option<Fix>,
+    /// How the call was matched to the annotated definition.
+    pub match_kind: MatchKind,
+    /// How likely the call refers to the definition, from 0 to 1.
+    pub confidence: f64,
+    /// Further definitions the call may refer to, e.g. same-named functions
+    /// in other modules when matching by name. The definition above is the
+    /// first candidate.
+    pub other_definitions: Vec<Definition>,
+    /// Why the context method looks like it's called on an `Option` rather
+    /// than on the call's `Result`, e.g. "`?`" for `load()?.context(..)` or
+    /// "`.get()`" for `map.get(k).context(..)`. anyhow's `Option::context`
+    /// adds no second context, so such call sites are not reported.
+    pub option_receiver: Option<String>,
+    /// The enclosing function, if it has `#[context]` as well: its context
+    /// stacks a third layer on the same error.
+    pub caller: Option<Definition>,
 }
 
+/// An annotated definition a call site may refer to.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    /// File where the annotated function is defined.
+    pub file: Arc<str>,
+    /// Line where the annotated function is defined.
+    pub line: usize,
+    /// The span of the `#[context]` attribute.
+    pub span: Span,
+    /// The context string from the `#[context]` attribute, or from the
+    /// `[[rules]]` entry selecting the function.
+    pub inner_context: Option<String>,
+    /// Whether the function is annotated or selected by a `[[rules]]` entry.
+    pub source: AnnotationSource,
+}
+
+impl DoubleContext {
+    /// Whether the inner and outer context strings are the same (ignoring
+    /// ASCII case).
+    pub fn is_identical(&self) -> bool {
+        self.outer_context
+            .as_deref()
+            .zip(self.inner_context.as_deref())
+            .is_some_and(|(outer, inner)| inner.eq_ignore_ascii_case(outer))
+    }
+}
+
+/// How a call site was matched to an annotated function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchKind {
+    /// The call's module path resolves to the definition.
+    Path,
+    /// The method's receiver has an inferred type on which the method is
+    /// defined.
+    Type,
+    /// Only the name matches (with heuristics); the call may refer to a
+    /// different function.
+    Name,
+}
+
+/// `Result` combinators that keep an error from the receiver as it is (or
+/// only convert it, for `map_err`), so context added after them still applies
+/// to the receiver's error.
+const RESULT_ADAPTERS: &[&str] = &["map", "map_err", "and_then", "inspect", "inspect_err"];
+
+/// Methods that commonly return an `Option` (from the standard library's
+/// collections, iterators, paths and strings, and `Result::ok`/`err`). A
+/// call to one matched by name only is most likely not the annotated method.
+const OPTION_METHODS: &[&str] = &[
+    "get",
+    "get_mut",
+    "find",
+    "find_map",
+    "first",
+    "last",
+    "next",
+    "nth",
+    "pop",
+    "peek",
+    "position",
+    "max",
+    "min",
+    "ok",
+    "err",
+    "parent",
+    "file_name",
+    "file_stem",
+    "extension",
+    "strip_prefix",
+    "strip_suffix",
+    "split_once",
+    "to_str",
+];
+
 /// Information about a callee extracted from a call expression.
-#[derive(Debug)]
-enum CalleeInfo {
+enum CalleeInfo<'e> {
     /// A free function call with path segments.
     /// e.g., `crate::utils::open_dir_remount_rw(args)` -> segments = ["crate", "utils", "open_dir_remount_rw"]
     FreeFunction {
@@ -41,33 +153,104 @@ enum CalleeInfo {
         path_segments: Vec<String>,
     },
     /// A method call on a receiver.
-    /// e.g., `imp.prepare()` -> name = "prepare"
-    Method { name: String },
+    /// e.g., `imp.prepare()` -> name = "prepare", receiver = `imp`
+    Method { name: String, receiver: &'e Expr },
 }
 
-/// Check a single Rust source file for double-context call sites.
-pub fn check_file(path: &Path, index: &AnnotatedFunctions) -> Result<Vec<DoubleContext>> {
-    let source =
-        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+impl CalleeInfo<'_> {
+    /// The name of the called function or method.
+    fn name(&self) -> &str {
+        match self {
+            CalleeInfo::FreeFunction { name, .. } | CalleeInfo::Method { name, .. } => name,
+        }
+    }
+}
 
-    let syntax: File = match syn::parse_file(&source) {
-        Ok(f) => f,
-        Err(_) => return Ok(Vec::new()),
+/// Where a callee was found in the receiver of a context call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Nesting {
+    /// In the receiver chain itself: `foo().context(..)`.
+    Direct,
+    /// At the end of an awaited `async` block:
+    /// `async { foo().await }.await.context(..)`.
+    AsyncBlock,
+    /// At the end of a closure passed to another call, which may handle or
+    /// replace the error: `run(|| foo()).context(..)`.
+    Closure,
+}
+
+/// Check a single Rust source file for double-context call sites.
+/// `module_path` is the path of the module the file defines, if known.
+pub fn check_file(
+    file: &ParsedFile,
+    module_path: &[String],
+    index: &AnnotatedFunctions,
+    methods: &ContextMethodsConfig,
+) -> Vec<DoubleContext> {
+    let Some(syntax) = &file.syntax else {
+        return Vec::new();
     };
 
     let mut visitor = DoubleContextChecker {
-        file_path: path.to_string_lossy().to_string(),
+        file_path: file.display_path(),
+        source: &file.source,
         index,
+        methods,
+        imports: collect_imports(&syntax.items),
+        module_path: module_path.to_vec(),
+        self_type: None,
+        locals: Locals::default(),
+        caller: None,
         results: Vec::new(),
     };
-    visitor.visit_file(&syntax);
+    visitor.visit_file(syntax);
+
+    visitor.results
+}
 
-    Ok(visitor.results)
+/// Find the annotated functions an expression plausibly calls, looking through
+/// `.await`, `?` and parentheses.
+pub fn annotated_callees<'i>(
+    expr: &Expr,
+    index: &'i AnnotatedFunctions,
+) -> Vec<&'i AnnotatedFunction> {
+    let Some(callee) = DoubleContextChecker::find_callee_in_receiver(expr) else {
+        return Vec::new();
+    };
+    let name = match &callee {
+        CalleeInfo::FreeFunction { name, .. } => name,
+        CalleeInfo::Method { name, .. } => name,
+    };
+    index
+        .get(name)
+        .into_iter()
+        .filter(|af| DoubleContextChecker::is_plausible_match(&callee, af))
+        .collect()
+}
+
+/// The context string of a `.context("...")` or `.with_context(|| "...")`
+/// call, if it can be extracted.
+pub fn context_arg(method_call: &ExprMethodCall) -> Option<String> {
+    DoubleContextChecker::extract_context_arg(method_call)
 }
 
 struct DoubleContextChecker<'a> {
-    file_path: String,
+    file_path: Arc<str>,
+    source: &'a str,
     index: &'a AnnotatedFunctions,
+    /// Which methods count as adding context.
+    methods: &'a ContextMethodsConfig,
+    /// File-level imports, used to resolve callee paths.
+    imports: Imports,
+    /// Path of the current module, including inline `mod` blocks. Empty if
+    /// unknown.
+    module_path: Vec<String>,
+    /// The type of the enclosing `impl` block, for `Self::` paths.
+    self_type: Option<String>,
+    /// Known types of local variables, for method call receivers.
+    locals: Locals,
+    /// The enclosing function's `#[context]`, if it has one.
+    caller: Option<Definition>,
     results: Vec<DoubleContext>,
 }
 
@@ -76,57 +259,275 @@ impl<'a> DoubleContextChecker<'a> {
     /// check whether the receiver chain contains a call to an annotated function.
     fn check_context_call(&mut self, method_call: &ExprMethodCall) {
         let method_name = method_call.method.to_string();
-        let is_with_context = method_name == "with_context";
 
-        if method_name != "context" && !is_with_context {
+        let outer_context = match method_name.as_str() {
+            name if self.methods.is_context_method(name) => Self::extract_context_arg(method_call),
+            // `.map_err(|e| anyhow!("...: {e}"))` and `.map_err(|e| e.context(..))`
+            // wrap the error just like `.context()`.
+            "map_err" => match Self::extract_anyhow_wrap(method_call)
+                .or_else(|| self.extract_context_wrap(method_call))
+            {
+                Some(message) => Some(message),
+                None => return,
+            },
+            // Methods adding context only on the results of the functions
+            // selected by a `[[rules]]` entry.
+            name if self.index.is_rule_method(name) => Self::extract_context_arg(method_call),
+            _ => return,
+        };
+        let rule_method = method_name != "map_err" && !self.methods.is_context_method(&method_name);
+
+        // A variable holding the unwrapped result of a call, as in
+        // `let r = load_config(); ... r.context(..)`, stands for that call.
+        // So does the error bound in an `Err(e)` arm of a `match` or `if let`
+        // on a call, whether it gets context itself or as `Err(e).context(..)`.
+        let initializer = bound_variable(&method_call.receiver)
+            .and_then(|ident| self.locals.initializer(&ident.to_string()))
+            .cloned();
+        let receiver = initializer.as_ref().unwrap_or(&method_call.receiver);
+
+        // Walk the receiver chain to find the underlying function call, or
+        // failing a match, the call a closure or `async` block in it ends with.
+        let direct = Self::find_callee_in_receiver(receiver).map(|c| (c, Nesting::Direct));
+        let lookup = |(callee, nesting)| {
+            let callee = self.resolve_callee(callee);
+            let (mut matches, kind) = self.lookup(&callee)?;
+            // Functions selected by path only add context for their rule's
+            // methods; the others add it for the context methods too.
+            matches.retain(|annotated| {
+                if rule_method || annotated.source == AnnotationSource::Rule {
+                    annotated.methods.contains(&method_name)
+                } else {
+                    true
+                }
+            });
+            if matches.is_empty() {
+                return None;
+            }
+            Some((callee, nesting, matches, kind))
+        };
+        let Some((callee, nesting, matches, match_kind)) = direct
+            .and_then(lookup)
+            .or_else(|| Self::find_nested_callee(receiver).and_then(lookup))
+        else {
             return;
-        }
+        };
 
-        // Walk the receiver chain to find the underlying function call.
-        let callee = match Self::find_callee_in_receiver(&method_call.receiver) {
-            Some(c) => c,
-            None => return,
+        // One finding per call site, listing every candidate definition
+        // (the same definition may be indexed more than once).
+        let mut unique: Vec<&AnnotatedFunction> = Vec::new();
+        for annotated in matches {
+            if !unique.contains(&annotated) {
+                unique.push(annotated);
+            }
+        }
+        let identical = outer_context
+            .as_deref()
+            .zip(unique[0].context_string.as_deref())
+            .is_some_and(|(outer, inner)| inner.eq_ignore_ascii_case(outer));
+        let confidence = confidence(
+            &callee,
+            unique[0],
+            match_kind,
+            nesting,
+            unique.len(),
+            identical,
+        );
+        let annotated_args = unique[0].context_args.clone();
+        // Shared with the definition, unless called through an alias.
+        let function_name = if *unique[0].name == *callee.name() {
+            unique[0].name.clone()
+        } else {
+            callee.name().into()
         };
+        let mut definitions: Vec<Definition> = unique
+            .into_iter()
+            .map(|annotated| Definition {
+                file: annotated.file.clone(),
+                line: annotated.line,
+                span: annotated.attr_span,
+                inner_context: annotated.context_string.clone(),
+                source: annotated.source,
+            })
+            .collect();
+        let first = definitions.remove(0);
 
-        let callee_name = match &callee {
-            CalleeInfo::FreeFunction { name, .. } => name,
-            CalleeInfo::Method { name } => name,
+        // `?` in the receiver chain leaves the `Result` behind, and a method
+        // like `.get()` matched by name only is most likely the standard
+        // library's: either way the context most likely goes on an `Option`.
+        let option_receiver = if unwraps_result(receiver) {
+            Some("`?`".to_string())
+        } else {
+            match &callee {
+                CalleeInfo::Method { name, .. }
+                    if match_kind == MatchKind::Name && OPTION_METHODS.contains(&name.as_str()) =>
+                {
+                    Some(format!("`.{name}()`"))
+                }
+                _ => None,
+            }
         };
 
-        // Check if this function name is in our index of annotated functions.
-        let annotated_fns = match self.index.get(callee_name) {
-            Some(fns) => fns,
-            None => return,
+        let call_span = Span::new(
+            method_call.method.span(),
+            method_call.paren_token.span.close(),
+            crate::fix::syn_offset(self.source),
+        );
+        self.results.push(DoubleContext {
+            call_file: self.file_path.clone(),
+            call_line: call_span.line,
+            fix: self.removal_fix(method_call, &call_span, match_kind, nesting),
+            call_span,
+            function_name,
+            inner_context: first.inner_context,
+            inner_source: first.source,
+            inner_args: annotated_args,
+            outer_context,
+            def_file: first.file,
+            def_line: first.line,
+            def_span: first.span,
+            outer_method: method_name.clone(),
+            match_kind,
+            confidence,
+            other_definitions: definitions,
+            option_receiver,
+            caller: self.caller.clone(),
+        });
+    }
+
+    /// Look a callee up by its qualified path (or a method by its receiver's
+    /// type) first, falling back to plausible matches by name.
+    fn lookup(&self, callee: &CalleeInfo) -> Option<(Vec<&'a AnnotatedFunction>, MatchKind)> {
+        let (qualified, kind) = match callee {
+            CalleeInfo::FreeFunction { path_segments, .. } => (
+                self.qualified_candidates(path_segments)
+                    .iter()
+                    .map(|path| self.index.get_qualified(path))
+                    .find(|matches| !matches.is_empty())
+                    .unwrap_or_default(),
+                MatchKind::Path,
+            ),
+            // Methods of the receiver's type, if it is known. The inference
+            // may be wrong and misses trait methods, so without such a method
+            // the call is matched by name.
+            CalleeInfo::Method { name, receiver } => (
+                self.locals
+                    .infer(receiver, self.self_type.as_deref())
+                    .map(|ty| {
+                        self.index
+                            .get(name)
+                            .into_iter()
+                            .filter(|af| af.is_method && af.self_type.as_deref() == Some(&ty))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                MatchKind::Type,
+            ),
         };
+        let (matches, match_kind) = if qualified.is_empty() {
+            let matches: Vec<&AnnotatedFunction> = self
+                .index
+                .get(callee.name())
+                .into_iter()
+                .filter(|af| Self::is_plausible_match(callee, af))
+                .collect();
+            (matches, MatchKind::Name)
+        } else {
+            (qualified, kind)
+        };
+        if matches.is_empty() {
+            return None;
+        }
+        Some((matches, match_kind))
+    }
 
-        let outer_context = Self::extract_context_arg(method_call);
+    /// Resolve a free function's path through the file's imports, so that
+    /// `storage::open()` after `use crate::pod::storage;` is matched as
+    /// `crate::pod::storage::open()`, and renamed imports find the original name.
+    fn resolve_callee<'e>(&self, callee: CalleeInfo<'e>) -> CalleeInfo<'e> {
+        match callee {
+            CalleeInfo::FreeFunction {
+                name,
+                path_segments,
+            } => {
+                let path_segments = resolve(&path_segments, &self.imports);
+                let name = path_segments.last().cloned().unwrap_or(name);
+                CalleeInfo::FreeFunction {
+                    name,
+                    path_segments,
+                }
+            }
+            method => method,
+        }
+    }
 
-        // Filter annotated functions to plausible matches based on call type.
-        let matches: Vec<&AnnotatedFunction> = annotated_fns
-            .iter()
-            .filter(|af| Self::is_plausible_match(&callee, af))
-            .collect();
+    /// The qualified paths a (resolved) call path may refer to, in the form
+    /// used as index keys, most likely first.
+    fn qualified_candidates(&self, segments: &[String]) -> Vec<String> {
+        qualified_candidates(segments, &self.module_path, self.self_type.as_deref())
+    }
 
-        for annotated in matches {
-            self.results.push(DoubleContext {
-                call_file: self.file_path.clone(),
-                call_line: method_call.method.span().start().line,
-                function_name: callee_name.clone(),
-                inner_context: annotated.context_string.clone(),
-                outer_context: outer_context.clone(),
-                def_file: annotated.file.clone(),
-                def_line: annotated.line,
-                is_with_context,
+    /// Enter a function, returning the previous caller: the index knows
+    /// whether it carries a context attribute, including configured ones.
+    fn enter_fn(&mut self, sig: &Signature) -> Option<Definition> {
+        let caller = self
+            .index
+            .definition(&self.file_path, sig)
+            .map(|af| Definition {
+                file: af.file.clone(),
+                line: af.line,
+                span: af.attr_span,
+                inner_context: af.context_string.clone(),
+                source: af.source,
             });
+        std::mem::replace(&mut self.caller, caller)
+    }
+
+    /// Build a fix removing the outer context call (`span`), along with the
+    /// `.` before it and any whitespace between the receiver and the `.`.
+    /// Only calls directly on a callee matched by path or receiver type are
+    /// fixed: a match by name may be wrong, and around a closure or `async`
+    /// block the outer context also covers the other errors in there.
+    fn removal_fix(
+        &self,
+        method_call: &ExprMethodCall,
+        span: &Span,
+        match_kind: MatchKind,
+        nesting: Nesting,
+    ) -> Option<Fix> {
+        if match_kind == MatchKind::Name || nesting != Nesting::Direct {
+            return None;
+        }
+        // Other methods, like snafu's `whatever_context` or error-stack's
+        // `change_context`, may change the error type, so the call can't
+        // simply be dropped.
+        if !DEFAULT_CONTEXT_METHODS.contains(&method_call.method.to_string().as_str()) {
+            return None;
         }
+        let call = span.bytes()?;
+        let dot =
+            crate::fix::syn_offset(self.source) + method_call.dot_token.span.byte_range().start;
+        if call.start < dot || call.end > self.source.len() {
+            return None;
+        }
+        let end = call.end;
+        let start = self.source[..dot].trim_end().len();
+        Some(Fix {
+            description: format!("remove `.{}(...)`", method_call.method),
+            edits: vec![TextEdit {
+                start,
+                end,
+                replacement: String::new(),
+            }],
+        })
     }
 
     /// Determine if a callee plausibly matches an annotated function.
     ///
-    /// For free function calls with path segments, we require that at least one
-    /// non-trivial path segment from the call site appears in the annotated
-    /// function's file path. This eliminates most false positives from common
-    /// names like `new`, `open`, `parse`, etc.
+    /// For free function calls of common names like `new`, `open`, `parse`,
+    /// etc., we require that the call's qualifying path segments match the
+    /// annotated function's module path (see [`qualifier_matches`]). This
+    /// eliminates most false positives from such names.
     ///
     /// For method calls, we require that the annotated function is also a method
     /// (has a `self` receiver).
@@ -136,42 +537,28 @@ impl<'a> DoubleContextChecker<'a> {
                 path_segments,
                 name,
             } => {
-                let common = is_common_function_name(name);
-
-                if path_segments.len() > 1 {
-                    // Get qualifying segments (all segments except the last, which is
-                    // the function name, and excluding `crate`/`self`/`super`)
-                    let qualifying: Vec<&str> = path_segments[..path_segments.len() - 1]
-                        .iter()
-                        .map(|s| s.as_str())
-                        .filter(|s| *s != "crate" && *s != "self" && *s != "super")
-                        .collect();
-
-                    if !qualifying.is_empty() {
-                        let def_path_lower = annotated.file.to_lowercase();
-                        let path_matches = qualifying.iter().any(|seg| {
-                            let seg_lower = seg.to_lowercase();
-                            def_path_lower.contains(&seg_lower)
-                        });
-
-                        if common {
-                            // For common names (open, new, copy, etc.), REQUIRE
-                            // path match to avoid false positives.
-                            return path_matches;
-                        }
-                        // For distinctive names, path match is nice but not
-                        // required — the name itself is strong enough signal.
-                    }
-                } else if common {
-                    // Unqualified call with a common name — too ambiguous.
+                // Paths into the standard library never reach workspace code.
+                if path_segments
+                    .first()
+                    .is_some_and(|s| matches!(s.as_str(), "std" | "core" | "alloc"))
+                {
                     return false;
                 }
 
-                // Distinctive name (qualified or not): match by name alone.
+                if is_common_function_name(name) {
+                    // For common names (open, new, copy, etc.), REQUIRE a
+                    // path match to avoid false positives: unqualified
+                    // calls are too ambiguous.
+                    return qualifier_matches(path_segments, annotated) == Some(true);
+                }
+
+                // Distinctive name (qualified or not): match by name alone. A
+                // path match is nice but not required — the name itself is
+                // strong enough signal.
                 true
             }
 
-            CalleeInfo::Method { name } => {
+            CalleeInfo::Method { name, .. } => {
                 // For method calls, only match if the annotated function
                 // is also a method (has a `self` receiver).
                 // This filters out cases like `hasher.update()` matching
@@ -198,17 +585,36 @@ impl<'a> DoubleContextChecker<'a> {
     }
 
     /// Walk the receiver expression chain to find the underlying function/method call.
-    fn find_callee_in_receiver(expr: &Expr) -> Option<CalleeInfo> {
+    fn find_callee_in_receiver(expr: &Expr) -> Option<CalleeInfo<'_>> {
         match expr {
+            // A future moved into a task or pinned: `tokio::spawn(foo(args))`,
+            // `spawn_blocking(|| foo(args))` or `Box::pin(foo(args))`
+            Expr::Call(ExprCall { func, args, .. }) if is_task_wrapper(func) => {
+                match args.first()? {
+                    Expr::Closure(closure) => {
+                        Self::find_callee_in_receiver(single_expr(&closure.body)?)
+                    }
+                    arg => Self::find_callee_in_receiver(arg),
+                }
+            }
+
             // Direct function call: `foo(args)` or `module::foo(args)`
             Expr::Call(ExprCall { func, .. }) => Self::extract_callee_from_func(func),
 
             // `.await` on a function call: `foo(args).await`
             Expr::Await(ExprAwait { base, .. }) => Self::find_callee_in_receiver(base),
 
+            // Combinators passing the error through: `foo(args).map(|x| x.len())`
+            Expr::MethodCall(inner_method)
+                if RESULT_ADAPTERS.contains(&inner_method.method.to_string().as_str()) =>
+            {
+                Self::find_callee_in_receiver(&inner_method.receiver)
+            }
+
             // Method call: `receiver.method(args)` — this is the function we care about
             Expr::MethodCall(inner_method) => Some(CalleeInfo::Method {
                 name: inner_method.method.to_string(),
+                receiver: &inner_method.receiver,
             }),
 
             // Parenthesized: `(expr)`
@@ -221,8 +627,50 @@ impl<'a> DoubleContextChecker<'a> {
         }
     }
 
+    /// Find the call a closure or awaited `async` block in the receiver chain
+    /// ends with: `run(|| foo())`, `pool.run(|| foo())` or
+    /// `async { foo().await }.await`. Used when the direct callee doesn't
+    /// match, as the function taking the closure may pass its error through.
+    fn find_nested_callee(expr: &Expr) -> Option<(CalleeInfo<'_>, Nesting)> {
+        match expr {
+            Expr::Await(ExprAwait { base, .. }) => match &**base {
+                Expr::Async(block) => {
+                    let tail = single_stmt(&block.block)?;
+                    Some((Self::find_callee_in_receiver(tail)?, Nesting::AsyncBlock))
+                }
+                base => Self::find_nested_callee(base),
+            },
+            Expr::MethodCall(inner_method)
+                if RESULT_ADAPTERS.contains(&inner_method.method.to_string().as_str()) =>
+            {
+                Self::find_nested_callee(&inner_method.receiver)
+            }
+            Expr::Call(ExprCall { args, .. }) => Self::closure_callee(args),
+            Expr::MethodCall(inner_method) => Self::closure_callee(&inner_method.args),
+            Expr::Paren(paren) => Self::find_nested_callee(&paren.expr),
+            Expr::Try(try_expr) => Self::find_nested_callee(&try_expr.expr),
+            _ => None,
+        }
+    }
+
+    /// The call the first closure argument ends with, looking through an
+    /// `async` block it returns: `|| foo()` or `|| async { foo().await }`.
+    fn closure_callee<'e>(
+        args: impl IntoIterator<Item = &'e Expr>,
+    ) -> Option<(CalleeInfo<'e>, Nesting)> {
+        let closure = args.into_iter().find_map(|arg| match arg {
+            Expr::Closure(closure) => Some(closure),
+            _ => None,
+        })?;
+        let body = match single_expr(&closure.body)? {
+            Expr::Async(block) => single_stmt(&block.block)?,
+            body => body,
+        };
+        Some((Self::find_callee_in_receiver(body)?, Nesting::Closure))
+    }
+
     /// Extract callee information from a call expression's function position.
-    fn extract_callee_from_func(func: &Expr) -> Option<CalleeInfo> {
+    fn extract_callee_from_func(func: &Expr) -> Option<CalleeInfo<'_>> {
         match func {
             Expr::Path(ExprPath { path, .. }) => {
                 let segments: Vec<String> = path
@@ -243,9 +691,13 @@ impl<'a> DoubleContextChecker<'a> {
     /// Try to extract the context string from a `.context("...")` or
     /// `.with_context(|| "...")` call.
     fn extract_context_arg(method_call: &ExprMethodCall) -> Option<String> {
-        let first_arg = method_call.args.first()?;
+        Self::context_message(method_call.args.first()?)
+    }
 
-        match first_arg {
+    /// Try to extract the context string from the message argument of a
+    /// context method.
+    fn context_message(arg: &Expr) -> Option<String> {
+        match arg {
             // .context("literal string")
             Expr::Lit(lit) => {
                 if let syn::Lit::Str(s) = &lit.lit {
@@ -292,6 +744,292 @@ impl<'a> DoubleContextChecker<'a> {
             _ => Some("<complex expression>".to_string()),
         }
     }
+
+    /// If a `.map_err(...)` call wraps the error in a new `anyhow!` message that
+    /// includes the original error, return that message.
+    ///
+    /// Matches closures like `|e| anyhow!("Loading: {e}")` and
+    /// `|err| anyhow::anyhow!("Loading: {}", err)` (also `format_err!` and
+    /// `eyre!`). Closures ignoring the original error replace it rather than
+    /// wrap it.
+    fn extract_anyhow_wrap(method_call: &ExprMethodCall) -> Option<String> {
+        let (param, body) = Self::map_err_closure(method_call)?;
+        let Expr::Macro(mac) = body else {
+            return None;
+        };
+        let is_anyhow =
+            mac.mac.path.segments.last().is_some_and(|s| {
+                s.ident == "anyhow" || s.ident == "format_err" || s.ident == "eyre"
+            });
+        if !is_anyhow {
+            return None;
+        }
+
+        let message = mac
+            .mac
+            .parse_body_with(syn::punctuated::Punctuated::<Expr, syn::Token![,]>::parse_terminated)
+            .ok()
+            .and_then(|args| match args.first() {
+                Some(Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                })) => Some(s.value()),
+                _ => None,
+            });
+
+        let mentions_param = mentions_ident(mac.mac.tokens.clone(), &param)
+            || message.as_ref().is_some_and(|m| {
+                m.contains(&format!("{{{param}}}")) || m.contains(&format!("{{{param}:"))
+            });
+        if !mentions_param {
+            return None;
+        }
+
+        Some(message.unwrap_or_else(|| "<complex expression>".to_string()))
+    }
+
+    /// If a `.map_err(...)` closure adds context to the original error with a
+    /// context method, return the context message.
+    ///
+    /// Matches closures like `|e| e.context("Loading")`,
+    /// `|e| anyhow::Error::from(e).context("Loading")` and
+    /// `|e| anyhow::Error::context(e, "Loading")`.
+    fn extract_context_wrap(&self, method_call: &ExprMethodCall) -> Option<String> {
+        let (param, body) = Self::map_err_closure(method_call)?;
+        let message = match body {
+            Expr::MethodCall(inner) => {
+                if !self.methods.is_context_method(&inner.method.to_string())
+                    || !expr_mentions(&inner.receiver, &param)
+                {
+                    return None;
+                }
+                Self::extract_context_arg(inner)
+            }
+            Expr::Call(call) => {
+                let Expr::Path(func) = &*call.func else {
+                    return None;
+                };
+                let segments = &func.path.segments;
+                let method = segments.last()?.ident.to_string();
+                if segments.len() < 2 || !self.methods.is_context_method(&method) {
+                    return None;
+                }
+                let mut args = call.args.iter();
+                let (Some(error), Some(message)) = (args.next(), args.next()) else {
+                    return None;
+                };
+                if !expr_mentions(error, &param) {
+                    return None;
+                }
+                Self::context_message(message)
+            }
+            _ => return None,
+        };
+        Some(message.unwrap_or_else(|| "<complex expression>".to_string()))
+    }
+
+    /// The parameter name and body of a single-parameter `.map_err(|e| ...)`
+    /// closure, looking through blocks holding a single expression.
+    fn map_err_closure(method_call: &ExprMethodCall) -> Option<(String, &Expr)> {
+        let Some(Expr::Closure(closure)) = method_call.args.first() else {
+            return None;
+        };
+        let [syn::Pat::Ident(param)] = closure.inputs.iter().collect::<Vec<_>>()[..] else {
+            return None;
+        };
+
+        Some((param.ident.to_string(), single_expr(&closure.body)?))
+    }
+}
+
+/// Whether a receiver chain unwraps the call's `Result` with `?` before the
+/// context method, as in `load()?.context(..)`: the context then applies to
+/// the value inside, most likely an `Option`.
+pub fn unwraps_result(expr: &Expr) -> bool {
+    match expr {
+        Expr::Try(_) => true,
+        Expr::Await(ExprAwait { base, .. }) => unwraps_result(base),
+        Expr::Paren(paren) => unwraps_result(&paren.expr),
+        Expr::MethodCall(inner_method)
+            if RESULT_ADAPTERS.contains(&inner_method.method.to_string().as_str()) =>
+        {
+            unwraps_result(&inner_method.receiver)
+        }
+        _ => false,
+    }
+}
+
+/// Look through blocks holding a single expression.
+pub fn single_expr(mut expr: &Expr) -> Option<&Expr> {
+    while let Expr::Block(block) = expr {
+        match block.block.stmts.as_slice() {
+            [syn::Stmt::Expr(inner, None)] => expr = inner,
+            _ => return None,
+        }
+    }
+    Some(expr)
+}
+
+/// The expression a block consists of, if any.
+fn single_stmt(block: &Block) -> Option<&Expr> {
+    match block.stmts.as_slice() {
+        [syn::Stmt::Expr(inner, None)] => single_expr(inner),
+        _ => None,
+    }
+}
+
+/// Whether a called function runs or pins the future (or closure) it is
+/// given, passing its result through: `spawn`, `spawn_blocking`,
+/// `spawn_local` and `Box::pin`.
+fn is_task_wrapper(func: &Expr) -> bool {
+    let Expr::Path(path) = func else {
+        return false;
+    };
+    let segments: Vec<String> = path
+        .path
+        .segments
+        .iter()
+        .map(|seg| seg.ident.to_string())
+        .collect();
+    match segments.as_slice() {
+        [.., last] if matches!(last.as_str(), "spawn" | "spawn_blocking" | "spawn_local") => true,
+        [.., ty, last] => ty == "Box" && last == "pin",
+        _ => false,
+    }
+}
+
+/// The variable an expression consists of, looking through `Err(..)`.
+fn bound_variable(expr: &Expr) -> Option<&proc_macro2::Ident> {
+    match expr {
+        Expr::Path(path) => path.path.get_ident(),
+        Expr::Call(call) => {
+            let Expr::Path(func) = &*call.func else {
+                return None;
+            };
+            if !func.path.is_ident("Err") || call.args.len() != 1 {
+                return None;
+            }
+            match call.args.first() {
+                Some(Expr::Path(path)) => path.path.get_ident(),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Check whether an expression mentions an identifier, including inside macro
+/// invocations.
+fn expr_mentions(expr: &Expr, ident: &str) -> bool {
+    struct Mentions<'i> {
+        ident: &'i str,
+        found: bool,
+    }
+
+    impl<'ast> Visit<'ast> for Mentions<'_> {
+        fn visit_ident(&mut self, node: &'ast proc_macro2::Ident) {
+            self.found |= node == self.ident;
+        }
+
+        fn visit_macro(&mut self, node: &'ast syn::Macro) {
+            self.found |= mentions_ident(node.tokens.clone(), self.ident);
+            syn::visit::visit_macro(self, node);
+        }
+    }
+
+    let mut visitor = Mentions {
+        ident,
+        found: false,
+    };
+    visitor.visit_expr(expr);
+    visitor.found
+}
+
+/// Check whether a token stream mentions an identifier.
+fn mentions_ident(tokens: proc_macro2::TokenStream, ident: &str) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(i) => i == ident,
+        proc_macro2::TokenTree::Group(g) => mentions_ident(g.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Whether the qualifying segments of a call path (all but the last,
+/// excluding `crate`/`self`/`super`) appear, in order, in the module path of
+/// a definition followed by its `impl` type: `storage::open()` and
+/// `pod::Storage::open()` match `open` in `impl Storage` of module
+/// `my_crate::pod::storage`, and also through re-exports further up, but
+/// `db::open()` doesn't match one in `my_crate::dbutil`. `None` if the path
+/// has no qualifying segments or the definition's module is unknown.
+fn qualifier_matches(path_segments: &[String], annotated: &AnnotatedFunction) -> Option<bool> {
+    let qualifying = path_segments[..path_segments.len().saturating_sub(1)]
+        .iter()
+        .filter(|s| !matches!(s.as_str(), "crate" | "self" | "super"));
+    if qualifying.clone().next().is_none() || annotated.module_path.is_empty() {
+        return None;
+    }
+    let mut definition = annotated
+        .module_path
+        .iter()
+        .chain(annotated.self_type.as_ref());
+    Some(
+        qualifying
+            .into_iter()
+            .all(|segment| definition.any(|s| s == segment)),
+    )
+}
+
+/// How likely a call site refers to `annotated`, its first candidate
+/// definition out of `candidates`, from 0 to 1 (rounded to two decimals).
+///
+/// Matches by path or receiver type are near certain. A match by name gains
+/// confidence from qualifying path segments found in the definition's module
+/// path or `impl` type (see [`qualifier_matches`]), from the call and
+/// definition agreeing on being a method or a free function, and from a
+/// distinctive name. Several candidates lower it, as does a callee found
+/// inside a closure or `async` block; identical context strings raise it.
+fn confidence(
+    callee: &CalleeInfo,
+    annotated: &AnnotatedFunction,
+    kind: MatchKind,
+    nesting: Nesting,
+    candidates: usize,
+    identical: bool,
+) -> f64 {
+    let mut score = match kind {
+        MatchKind::Path => 0.95,
+        MatchKind::Type => 0.9,
+        MatchKind::Name => {
+            let (name, is_method_call, path_matches) = match callee {
+                CalleeInfo::FreeFunction {
+                    name,
+                    path_segments,
+                } => (name, false, qualifier_matches(path_segments, annotated)),
+                CalleeInfo::Method { name, .. } => (name, true, None),
+            };
+            let mut score = 0.4;
+            if is_method_call == annotated.is_method {
+                score += 0.1;
+            }
+            if path_matches == Some(true) {
+                score += 0.2;
+            }
+            if !is_common_function_name(name) {
+                score += 0.15;
+            }
+            score
+        }
+    };
+    score -= match nesting {
+        Nesting::Direct => 0.0,
+        Nesting::AsyncBlock => 0.05,
+        Nesting::Closure => 0.2,
+    };
+    score -= 0.1 * candidates.saturating_sub(1) as f64;
+    if identical {
+        score += 0.05;
+    }
+    (score.clamp(0.0, 1.0) * 100.0).round() / 100.0
 }
 
 /// Returns true if a function name is so common that matching by name alone
@@ -349,35 +1087,154 @@ impl<'a, 'ast> Visit<'ast> for DoubleContextChecker<'a> {
         // Continue visiting child expressions to catch nested cases
         syn::visit::visit_expr_method_call(self, node);
     }
+
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        let known = !self.module_path.is_empty();
+        if known {
+            self.module_path.push(node.ident.to_string());
+        }
+        syn::visit::visit_item_mod(self, node);
+        if known {
+            self.module_path.pop();
+        }
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        let prev = std::mem::replace(&mut self.self_type, type_name(&node.self_ty));
+        syn::visit::visit_item_impl(self, node);
+        self.self_type = prev;
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        // Nested functions can't see the enclosing function's locals.
+        let outer = std::mem::take(&mut self.locals);
+        self.locals.push();
+        self.locals
+            .bind_params(&node.sig, self.self_type.as_deref());
+        let caller = self.enter_fn(&node.sig);
+        syn::visit::visit_item_fn(self, node);
+        self.caller = caller;
+        self.locals = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        let outer = std::mem::take(&mut self.locals);
+        self.locals.push();
+        self.locals
+            .bind_params(&node.sig, self.self_type.as_deref());
+        let caller = self.enter_fn(&node.sig);
+        syn::visit::visit_impl_item_fn(self, node);
+        self.caller = caller;
+        self.locals = outer;
+    }
+
+    fn visit_block(&mut self, node: &'ast Block) {
+        self.locals.push();
+        syn::visit::visit_block(self, node);
+        self.locals.pop();
+    }
+
+    fn visit_local(&mut self, node: &'ast Local) {
+        // The initializer is evaluated before the new binding exists.
+        syn::visit::visit_local(self, node);
+        self.locals.bind_local(node, self.self_type.as_deref());
+    }
+
+    fn visit_expr_match(&mut self, node: &'ast ExprMatch) {
+        self.visit_expr(&node.expr);
+        for arm in &node.arms {
+            self.locals.push();
+            self.locals.bind_match(&arm.pat, &node.expr);
+            self.visit_arm(arm);
+            self.locals.pop();
+        }
+    }
+
+    fn visit_expr_if(&mut self, node: &'ast ExprIf) {
+        let Expr::Let(cond) = &*node.cond else {
+            return syn::visit::visit_expr_if(self, node);
+        };
+        self.visit_expr(&cond.expr);
+        self.locals.push();
+        self.locals.bind_match(&cond.pat, &cond.expr);
+        self.visit_block(&node.then_branch);
+        self.locals.pop();
+        if let Some((_, else_branch)) = &node.else_branch {
+            self.visit_expr(else_branch);
+        }
+    }
+
+    fn visit_expr_closure(&mut self, node: &'ast ExprClosure) {
+        // The closure's errors may never reach the enclosing function's
+        // `#[context]`.
+        let caller = self.caller.take();
+        self.locals.push();
+        self.locals.bind_closure(node, self.self_type.as_deref());
+        syn::visit::visit_expr_closure(self, node);
+        self.locals.pop();
+        self.caller = caller;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::collector::AnnotatedFunction;
-    use std::collections::HashMap;
+    use crate::fixtures;
 
     fn make_index(entries: Vec<(&str, &str, bool)>) -> AnnotatedFunctions {
-        let mut map: AnnotatedFunctions = HashMap::new();
-        for (name, ctx, is_method) in entries {
-            map.entry(name.to_string())
-                .or_default()
-                .push(AnnotatedFunction {
-                    name: name.to_string(),
-                    file: "src/mymodule.rs".to_string(),
-                    line: 1,
-                    context_string: ctx.to_string(),
+        crate::collector::build_index(
+            entries
+                .into_iter()
+                .map(|(name, ctx, is_method)| AnnotatedFunction {
                     is_method,
-                });
-        }
-        map
+                    module_path: vec!["my_crate".to_string(), "mymodule".to_string()],
+                    ..fixtures::annotated(name, "src/mymodule.rs", 1, ctx)
+                })
+                .collect(),
+        )
     }
 
     fn check_source(source: &str, index: &AnnotatedFunctions) -> Vec<DoubleContext> {
-        let syntax: File = syn::parse_file(source).unwrap();
+        check_source_with(source, index, &ContextMethodsConfig::default())
+    }
+
+    fn check_source_with(
+        source: &str,
+        index: &AnnotatedFunctions,
+        methods: &ContextMethodsConfig,
+    ) -> Vec<DoubleContext> {
+        check_source_in(source, &[], index, methods)
+    }
+
+    /// Check `source` as part of the module the functions of `make_index`
+    /// are defined in, so that calls of them are matched by path.
+    fn check_in_module(source: &str, index: &AnnotatedFunctions) -> Vec<DoubleContext> {
+        check_source_in(
+            source,
+            &["my_crate", "mymodule"],
+            index,
+            &ContextMethodsConfig::default(),
+        )
+    }
+
+    fn check_source_in(
+        source: &str,
+        module_path: &[&str],
+        index: &AnnotatedFunctions,
+        methods: &ContextMethodsConfig,
+    ) -> Vec<DoubleContext> {
+        let syntax: syn::File = syn::parse_file(source).unwrap();
         let mut visitor = DoubleContextChecker {
-            file_path: "test.rs".to_string(),
+            file_path: "test.rs".into(),
+            source,
             index,
+            methods,
+            imports: collect_imports(&syntax.items),
+            module_path: module_path.iter().map(|s| s.to_string()).collect(),
+            self_type: None,
+            locals: Locals::default(),
+            caller: None,
             results: Vec::new(),
         };
         visitor.visit_file(&syntax);
@@ -396,7 +1253,7 @@ mod tests {
             &index,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].function_name, "load_config");
+        assert_eq!(&*results[0].function_name, "load_config");
         assert_eq!(results[0].outer_context, Some("loading config".to_string()));
     }
 
@@ -412,7 +1269,7 @@ mod tests {
             &index,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].function_name, "fetch_data");
+        assert_eq!(&*results[0].function_name, "fetch_data");
     }
 
     #[test]
@@ -431,7 +1288,7 @@ mod tests {
             &index,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].function_name, "get_global_authfile");
+        assert_eq!(&*results[0].function_name, "get_global_authfile");
     }
 
     #[test]
@@ -446,7 +1303,7 @@ mod tests {
             &index,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].function_name, "prepare");
+        assert_eq!(&*results[0].function_name, "prepare");
     }
 
     #[test]
@@ -483,16 +1340,10 @@ mod tests {
     #[test]
     fn test_common_name_qualified_matching_path() {
         // A qualified call where path segments match the def file should match
-        let mut map: AnnotatedFunctions = HashMap::new();
-        map.entry("open".to_string())
-            .or_default()
-            .push(AnnotatedFunction {
-                name: "open".to_string(),
-                file: "src/podstorage.rs".to_string(),
-                line: 284,
-                context_string: "Opening imgstorage".to_string(),
-                is_method: false,
-            });
+        let map = crate::collector::build_index(vec![AnnotatedFunction {
+            module_path: vec!["my_crate".to_string(), "podstorage".to_string()],
+            ..fixtures::annotated("open", "src/podstorage.rs", 284, "Opening imgstorage")
+        }]);
 
         let results = check_source(
             r#"
@@ -508,16 +1359,10 @@ mod tests {
     #[test]
     fn test_common_name_qualified_non_matching_path() {
         // A qualified call where path segments DON'T match should not match
-        let mut map: AnnotatedFunctions = HashMap::new();
-        map.entry("open".to_string())
-            .or_default()
-            .push(AnnotatedFunction {
-                name: "open".to_string(),
-                file: "src/podstorage.rs".to_string(),
-                line: 284,
-                context_string: "Opening imgstorage".to_string(),
-                is_method: false,
-            });
+        let map = crate::collector::build_index(vec![AnnotatedFunction {
+            module_path: vec!["my_crate".to_string(), "podstorage".to_string()],
+            ..fixtures::annotated("open", "src/podstorage.rs", 284, "Opening imgstorage")
+        }]);
 
         let results = check_source(
             r#"
@@ -530,6 +1375,191 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn test_eyre_wrap_err() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() -> eyre::Result<()> {
+                load_config().wrap_err("Loading")?;
+                load_config().wrap_err_with(|| format!("Loading {}", p))?;
+                load_config().map_err(|e| eyre!("Loading: {e}"))?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        let methods: Vec<&str> = results.iter().map(|r| r.outer_method.as_str()).collect();
+        assert_eq!(methods, ["wrap_err", "wrap_err_with", "map_err"]);
+        assert_eq!(results[0].outer_context.as_deref(), Some("Loading"));
+    }
+
+    #[test]
+    fn test_extra_context_methods() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let source = r#"
+            fn main() -> Result<(), Whatever> {
+                load_config().whatever_context("Loading")?;
+                load_config().with_whatever_context(|_| format!("Loading {}", p))?;
+                Ok(())
+            }
+            "#;
+        assert!(check_source(source, &index).is_empty());
+
+        let methods = ContextMethodsConfig {
+            extra: vec![
+                "whatever_context".to_string(),
+                "with_whatever_context".to_string(),
+            ],
+            ..Default::default()
+        };
+        let results = check_source_with(source, &index, &methods);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].outer_method, "whatever_context");
+        assert_eq!(results[0].outer_context.as_deref(), Some("Loading"));
+        assert_eq!(results[1].outer_method, "with_whatever_context");
+    }
+
+    #[test]
+    fn test_configured_method_names() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let source = r#"
+            fn main() -> Result<()> {
+                load_config().ctx("Loading")?;
+                load_config().context("Loading")?;
+                Ok(())
+            }
+            "#;
+        let methods = ContextMethodsConfig {
+            names: vec!["ctx".to_string()],
+            ..Default::default()
+        };
+        let results = check_source_with(source, &index, &methods);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].outer_method, "ctx");
+        // Configured methods may change the error type.
+        assert!(results[0].fix.is_none());
+    }
+
+    #[test]
+    fn test_rule_methods() {
+        let index = make_index(vec![
+            ("query", "", false),
+            ("wrapped", "Wrapping", false),
+            ("load_config", "Loading config", false),
+        ]);
+        let mut functions: Vec<AnnotatedFunction> = index.iter().cloned().collect();
+        // Selected by `functions = ..`, and annotated by `attribute = ..`.
+        functions[0].context_string = None;
+        functions[0].source = AnnotationSource::Rule;
+        functions[0].methods = vec!["ctx".to_string()];
+        functions[1].methods = vec!["ctx".to_string()];
+        let index = crate::collector::build_index(functions);
+        let source = r#"
+            fn main() -> Result<()> {
+                query("SELECT 1").ctx("Querying")?;
+                load_config().ctx("Loading")?;
+                query("SELECT 2").context("Querying")?;
+                wrapped().ctx("Wrapping")?;
+                wrapped().context("Wrapping")?;
+                Ok(())
+            }
+            "#;
+        let results = check_source(source, &index);
+        let found: Vec<(&str, &str)> = results
+            .iter()
+            .map(|r| (&*r.function_name, r.outer_method.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [("query", "ctx"), ("wrapped", "ctx"), ("wrapped", "context")]
+        );
+    }
+
+    #[test]
+    fn test_error_stack_methods() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let source = r#"
+            fn main() -> error_stack::Result<(), AppError> {
+                load_config().change_context(AppError::Config)?;
+                load_config().attach_printable("Loading config")?;
+                Ok(())
+            }
+            "#;
+        assert!(check_source(source, &index).is_empty());
+
+        let methods = ContextMethodsConfig {
+            error_stack: true,
+            ..Default::default()
+        };
+        let results = check_source_with(source, &index, &methods);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].outer_method, "change_context");
+        assert!(results.iter().all(|r| r.fix.is_none()));
+        assert_eq!(
+            results[0].outer_context.as_deref(),
+            Some("<complex expression>")
+        );
+        assert_eq!(results[1].outer_context.as_deref(), Some("Loading config"));
+    }
+
+    #[test]
+    fn test_imported_module_path() {
+        let map = crate::collector::build_index(vec![AnnotatedFunction {
+            module_path: vec![
+                "my_crate".to_string(),
+                "pod".to_string(),
+                "storage".to_string(),
+            ],
+            ..fixtures::annotated("open", "src/pod/storage.rs", 284, "Opening imgstorage")
+        }]);
+
+        // Common names match once the import reveals the module path.
+        let results = check_source(
+            r#"
+            use crate::pod::storage;
+            use crate::pod::storage::open as open_storage;
+
+            fn main() {
+                storage::open(path).context("Opening storage").unwrap();
+                open_storage(path).context("Opening storage").unwrap();
+            }
+            "#,
+            &map,
+        );
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| &*r.function_name == "open"));
+
+        // Imports from elsewhere rule the match out.
+        let results = check_source(
+            r#"
+            use std::fs::File as storage;
+
+            fn main() {
+                storage::open(path).context("Opening file").unwrap();
+            }
+            "#,
+            &map,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_std_import_not_matched() {
+        let index = make_index(vec![("canonicalize", "Canonicalizing", false)]);
+        let results = check_source(
+            r#"
+            use std::fs::canonicalize;
+
+            fn main() {
+                canonicalize(path).context("Canonicalizing").unwrap();
+            }
+            "#,
+            &index,
+        );
+        assert!(results.is_empty());
+    }
+
     #[test]
     fn test_with_context() {
         let index = make_index(vec![(
@@ -546,7 +1576,23 @@ mod tests {
             &index,
         );
         assert_eq!(results.len(), 1);
-        assert!(results[0].is_with_context);
+        assert_eq!(results[0].outer_method, "with_context");
+    }
+
+    #[test]
+    fn test_call_span() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let source = "fn main() {\n    load_config().context(\"x\").unwrap();\n}\n";
+        let results = check_in_module(source, &index);
+        assert_eq!(results.len(), 1);
+        let span = &results[0].call_span;
+        assert_eq!((span.line, span.column), (2, 19));
+        assert_eq!((span.end_line, span.end_column), (2, 31));
+        assert_eq!(&source[span.bytes().unwrap()], "context(\"x\")");
+
+        // The fix removes exactly the span, with the `.` before it
+        let edit = &results[0].fix.as_ref().unwrap().edits[0];
+        assert_eq!(&source[edit.start..edit.end], ".context(\"x\")");
     }
 
     #[test]
@@ -578,4 +1624,534 @@ mod tests {
         );
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_removal_fix() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let source = r#"
+            fn main() -> Result<()> {
+                let cfg = load_config()
+                    .context("loading config")?;
+                Ok(())
+            }
+            "#;
+        let results = check_in_module(source, &index);
+        assert_eq!(results.len(), 1);
+        let fix = results[0].fix.as_ref().unwrap();
+        let fixed = crate::fix::apply_edits(source, &fix.edits).unwrap();
+        assert!(fixed.contains("let cfg = load_config()?;"));
+    }
+
+    #[test]
+    fn test_removal_fix_only_when_certain() {
+        let index = make_index(vec![
+            ("load_config", "Loading config", false),
+            ("refresh", "Refreshing", true),
+        ]);
+        let results = check_in_module(
+            r#"
+            fn main() -> Result<()> {
+                load_config().context("Loading")?;
+                retry(|| load_config()).context("Loading")?;
+                cache.refresh().context("Refreshing")?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        let found: Vec<(MatchKind, bool)> = results
+            .iter()
+            .map(|r| (r.match_kind, r.fix.is_some()))
+            .collect();
+        // Only the direct call matched by path is fixed: the closure's
+        // context also covers `retry`'s own errors, and `refresh` may be
+        // another type's method.
+        assert_eq!(
+            found,
+            [
+                (MatchKind::Path, true),
+                (MatchKind::Path, false),
+                (MatchKind::Name, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map_err_anyhow() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() -> Result<()> {
+                load_config().map_err(|e| anyhow!("Loading config: {e}"))?;
+                load_config().map_err(|err| anyhow::anyhow!("Loading: {}", err))?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].outer_method, "map_err");
+        assert_eq!(
+            results[0].outer_context,
+            Some("Loading config: {e}".to_string())
+        );
+        assert_eq!(results[1].outer_context, Some("Loading: {}".to_string()));
+    }
+
+    #[test]
+    fn test_map_err_replacing_error_not_flagged() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() -> Result<()> {
+                load_config().map_err(|_| anyhow!("Loading config failed"))?;
+                load_config().map_err(|e| MyError::Config(e))?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_map_err_context_closure() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() -> Result<()> {
+                load_config().map_err(|e| e.context("Loading"))?;
+                load_config().map_err(|e| anyhow::Error::from(e).context("Loading"))?;
+                load_config().map_err(|e| anyhow::Error::context(e, "Loading"))?;
+                load_config().map_err(|e| { other.context("Unrelated") })?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.outer_method == "map_err"));
+        assert!(results
+            .iter()
+            .all(|r| r.outer_context.as_deref() == Some("Loading")));
+    }
+
+    #[test]
+    fn test_look_through_result_combinators() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() -> Result<()> {
+                load_config().map(|x| x.len()).context("Loading")?;
+                load_config().and_then(validate).context("Loading")?;
+                load_config().inspect_err(|e| log(e)).context("Loading")?;
+                load_config().ok().context("Loading")?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        let lines: Vec<usize> = results.iter().map(|r| r.call_line).collect();
+        assert_eq!(lines, [3, 4, 5]);
+    }
+
+    #[test]
+    fn test_look_through_spawn_and_pin() {
+        let index = make_index(vec![("fetch_remote", "Fetching", false)]);
+        let results = check_source(
+            r#"
+            async fn main() -> Result<()> {
+                tokio::spawn(fetch_remote()).await.context("Fetching")?;
+                Box::pin(fetch_remote()).await.context("Fetching")?;
+                spawn_blocking(|| fetch_remote()).await?.context("Fetching")?;
+                tokio::task::spawn_blocking(move || { fetch_remote() }).await?.context("Fetching")?;
+                run(fetch_remote()).await.context("Running")?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        let lines: Vec<usize> = results.iter().map(|r| r.call_line).collect();
+        assert_eq!(lines, [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_look_through_map_err() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() -> Result<()> {
+                load_config().map_err(Into::into).context("Loading")?;
+                load_config().map_err(MyError::from).with_context(|| "Loading")?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].outer_method, "context");
+        assert_eq!(results[1].outer_method, "with_context");
+    }
+
+    fn qualified(name: &str, module_path: &str, self_type: Option<&str>) -> AnnotatedFunction {
+        AnnotatedFunction {
+            module_path: module_path.split("::").map(str::to_string).collect(),
+            self_type: self_type.map(str::to_string),
+            ..fixtures::annotated(name, "src/somewhere.rs", 1, &format!("Running {name}"))
+        }
+    }
+
+    #[test]
+    fn test_qualifier_matches() {
+        let segments =
+            |path: &str| -> Vec<String> { path.split("::").map(str::to_string).collect() };
+        let open = qualified("open", "app::pod::storage", Some("Storage"));
+        let matches = |path: &str| qualifier_matches(&segments(path), &open);
+        assert_eq!(matches("storage::open"), Some(true));
+        assert_eq!(matches("pod::Storage::open"), Some(true));
+        assert_eq!(matches("crate::pod::open"), Some(true));
+        assert_eq!(matches("stor::open"), Some(false));
+        assert_eq!(matches("storage::pod::open"), Some(false));
+        assert_eq!(matches("super::open"), None);
+        assert_eq!(matches("open"), None);
+        let mut unknown = open.clone();
+        unknown.module_path.clear();
+        assert_eq!(
+            qualifier_matches(&segments("storage::open"), &unknown),
+            None
+        );
+    }
+
+    #[test]
+    fn test_qualified_lookup() {
+        let index = crate::collector::build_index(vec![
+            qualified("open", "app::pod::storage", None),
+            qualified("open", "app::other", None),
+            qualified("new", "app::pod", Some("Pod")),
+            qualified("sync", "app", None),
+        ]);
+        let results = check_source_in(
+            r#"
+            use crate::pod::storage;
+
+            mod inner {
+                fn f() {
+                    super::sync().context("Syncing").unwrap();
+                }
+            }
+
+            impl Pod {
+                fn g() {
+                    Self::new().context("Creating").unwrap();
+                }
+            }
+
+            fn main() {
+                storage::open(p).context("Opening").unwrap();
+                self::storage::open(p).context("Opening").unwrap();
+                app::other::open(p).context("Opening").unwrap();
+                sync().context("Syncing").unwrap();
+            }
+            "#,
+            &["app", "pod"],
+            &index,
+            &ContextMethodsConfig::default(),
+        );
+        let found: Vec<(&str, MatchKind)> = results
+            .iter()
+            .map(|r| (&*r.function_name, r.match_kind))
+            .collect();
+        // `super::sync` from `app::pod::inner` is `app::pod::sync`, which
+        // doesn't exist, and `sync` is too common a name to match `app::sync`
+        // without a qualifying path. Neither is the bare `sync()` in
+        // `app::pod`.
+        assert_eq!(
+            found,
+            [
+                ("new", MatchKind::Path),
+                ("open", MatchKind::Path),
+                ("open", MatchKind::Path),
+                ("open", MatchKind::Path),
+            ]
+        );
+        assert_eq!(&*results[2].def_file, "src/somewhere.rs");
+    }
+
+    #[test]
+    fn test_receiver_type_inference() {
+        let method = |self_type: &str, context: &str| AnnotatedFunction {
+            is_method: true,
+            context_string: Some(context.to_string()),
+            ..qualified("prepare", "app", Some(self_type))
+        };
+        let index = crate::collector::build_index(vec![
+            method("Importer", "Importing"),
+            method("Exporter", "Exporting"),
+        ]);
+        let results = check_source(
+            r#"
+            impl Importer {
+                fn run(&self) -> Result<()> {
+                    self.prepare().context("Preparing")?;
+                    Ok(())
+                }
+            }
+
+            fn main() {
+                let imp = Importer::new();
+                imp.prepare().context("Preparing").unwrap();
+            }
+
+            fn export(e: &Exporter, n: usize) {
+                e.prepare().context("Preparing").unwrap();
+                n.prepare().context("Preparing").unwrap();
+                items.iter().for_each(|e| e.prepare().context("Preparing").unwrap());
+            }
+            "#,
+            &index,
+        );
+        let found: Vec<(&str, MatchKind)> = results
+            .iter()
+            .map(|r| (r.inner_context.as_deref().unwrap(), r.match_kind))
+            .collect();
+        // `n` is a `usize`, which has no annotated `prepare` (it may come from
+        // a trait), and the closure's `e` shadows the parameter and has an
+        // unknown type, so both match both methods by name, each reported as
+        // a single finding.
+        assert_eq!(
+            found,
+            [
+                ("Importing", MatchKind::Type),
+                ("Importing", MatchKind::Type),
+                ("Exporting", MatchKind::Type),
+                ("Importing", MatchKind::Name),
+                ("Importing", MatchKind::Name),
+            ]
+        );
+        for result in &results[3..] {
+            let others: Vec<&str> = result
+                .other_definitions
+                .iter()
+                .map(|d| d.inner_context.as_deref().unwrap())
+                .collect();
+            assert_eq!(others, ["Exporting"]);
+        }
+        assert!(results[..3].iter().all(|r| r.other_definitions.is_empty()));
+    }
+
+    #[test]
+    fn test_confidence() {
+        let index = make_index(vec![
+            ("load_config", "Loading config", false),
+            ("open", "Opening", false),
+            ("refresh", "Refreshing", true),
+        ]);
+        let results = check_source(
+            r#"
+            fn main() {
+                load_config().context("Loading config").unwrap();
+                load_config().context("Reading").unwrap();
+                mymodule::open().context("Opening").unwrap();
+                cache.refresh().context("Updating").unwrap();
+            }
+            "#,
+            &index,
+        );
+        let scores: Vec<f64> = results.iter().map(|r| r.confidence).collect();
+        // A distinctive free function, with and without identical context; a
+        // common name qualified by a segment of its module path; a distinctive
+        // method.
+        assert_eq!(scores, [0.7, 0.65, 0.75, 0.65]);
+    }
+
+    #[test]
+    fn test_closures_and_async_blocks() {
+        let index = make_index(vec![
+            ("load_config", "Loading config", false),
+            ("run_job", "Running", false),
+        ]);
+        let results = check_source(
+            r#"
+            async fn main() {
+                retry(|| load_config()).context("Loading config").unwrap();
+                pool.install(|| async { load_config().await }).await.context("Loading config").unwrap();
+                async { load_config().await }.await.context("Loading config").unwrap();
+                load_config().context("Loading config").unwrap();
+                // The function taking the closure is matched first
+                run_job(|| load_config()).context("Running").unwrap();
+                // Closures not at the end of the receiver's body don't count
+                retry(|| { load_config()?; Ok(()) }).context("Loading config").unwrap();
+            }
+            "#,
+            &index,
+        );
+        let found: Vec<(&str, f64)> = results
+            .iter()
+            .map(|r| (&*r.function_name, r.confidence))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("load_config", 0.5),
+                ("load_config", 0.5),
+                ("load_config", 0.65),
+                ("load_config", 0.7),
+                ("run_job", 0.7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_option_receiver() {
+        let index = make_index(vec![
+            ("find_user", "Finding user", false),
+            ("get", "Getting entry", true),
+        ]);
+        let results = check_source(
+            r#"
+            async fn main() -> Result<()> {
+                find_user(id)?.context("No such user")?;
+                find_user(id).await?.context("No such user")?;
+                map.get(&key).context("Missing key")?;
+                find_user(id).context("Looking up user")?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        let receivers: Vec<Option<&str>> = results
+            .iter()
+            .map(|r| r.option_receiver.as_deref())
+            .collect();
+        assert_eq!(
+            receivers,
+            [Some("`?`"), Some("`?`"), Some("`.get()`"), None]
+        );
+    }
+
+    #[test]
+    fn test_annotated_caller() {
+        let entry =
+            |name: &str, line, context: &str| fixtures::annotated(name, "test.rs", line, context);
+        let index = crate::collector::build_index(vec![
+            entry("load_config", 3, "Loading config"),
+            entry("start", 8, "Starting"),
+        ]);
+        let results = check_source(
+            r#"
+            #[context("Loading config")]
+            fn load_config() -> Result<Config> {
+                todo!()
+            }
+
+            #[context("Starting")]
+            fn start() -> Result<()> {
+                load_config().context("Reading config")?;
+                let run = || load_config().context("Reading config");
+                fn helper() -> Result<()> {
+                    load_config().context("Reading config")?;
+                    Ok(())
+                }
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        assert_eq!(results.len(), 3);
+        let caller = results[0].caller.as_ref().unwrap();
+        assert_eq!(
+            (caller.line, caller.inner_context.as_deref().unwrap()),
+            (8, "Starting")
+        );
+        // Neither the closure's error nor the nested function's reaches it.
+        assert!(results[1].caller.is_none());
+        assert!(results[2].caller.is_none());
+    }
+
+    #[test]
+    fn test_duplicate_definitions_merged() {
+        let entry = qualified("load", "app", None);
+        let index = crate::collector::build_index(vec![entry.clone(), entry]);
+        let results = check_source(
+            r#"
+            fn main() {
+                app::load().context("Loading").unwrap();
+            }
+            "#,
+            &index,
+        );
+        assert_eq!(results.len(), 1);
+        assert!(results[0].other_definitions.is_empty());
+    }
+
+    #[test]
+    fn test_strings_shared() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() {
+                load_config().context("Loading").unwrap();
+                load_config().context("Again").unwrap();
+            }
+            "#,
+            &index,
+        );
+        assert_eq!(results.len(), 2);
+        let annotated = &index.get("load_config")[0];
+        // One copy of each name and path, whatever the number of findings.
+        assert!(Arc::ptr_eq(&results[0].call_file, &results[1].call_file));
+        assert!(Arc::ptr_eq(&results[0].function_name, &annotated.name));
+        assert!(Arc::ptr_eq(&results[1].def_file, &annotated.file));
+    }
+
+    #[test]
+    fn test_deferred_context_on_variable() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_in_module(
+            r#"
+            fn main() -> Result<()> {
+                let r = load_config();
+                log("loaded");
+                let cfg = r.context("Loading config")?;
+                let v = load_config()?;
+                v.context("Not a result")?;
+                let r = other();
+                r.context("Shadowed")?;
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(&*results[0].function_name, "load_config");
+        assert_eq!(results[0].call_line, 5);
+        assert!(results[0].fix.is_some());
+    }
+
+    #[test]
+    fn test_context_in_error_arms() {
+        let index = make_index(vec![("load_config", "Loading config", false)]);
+        let results = check_source(
+            r#"
+            fn main() -> Result<()> {
+                match load_config() {
+                    Ok(cfg) => use_it(cfg),
+                    Err(e) => return Err(e).context("Loading config"),
+                }
+                if let Err(e) = load_config() {
+                    return Err(e.context("Loading config"));
+                }
+                match other() {
+                    Err(e) => return Err(e).context("Other"),
+                    Ok(_) => {}
+                }
+                Ok(())
+            }
+            "#,
+            &index,
+        );
+        let lines: Vec<usize> = results.iter().map(|r| r.call_line).collect();
+        assert_eq!(lines, [5, 8]);
+        assert!(results.iter().all(|r| &*r.function_name == "load_config"));
+    }
 }
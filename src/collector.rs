@@ -1,73 +1,432 @@
 //! Pass 1: Collect all functions annotated with `#[context(...)]` from `fn_error_context`.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::visit::Visit;
-use syn::{Attribute, File, ImplItemFn, ItemFn, TraitItemFn};
+use syn::{
+    Attribute, Expr, File, FnArg, ImplItemFn, Item, ItemFn, ItemImpl, ItemMod, ItemTrait, LitStr,
+    Meta, Pat, ReturnType, Signature, Token, TraitItemFn, Type, Visibility,
+};
+
+use crate::cfg;
+use crate::config::ContextAttribute;
+use crate::distance::edit_distance;
+use crate::fix::TextEdit;
+use crate::imports::{collect_reexports, qualified_candidates, ReExport};
+use crate::parse::{ParsedFile, Span};
+use crate::rules::FunctionRule;
+
+/// How many levels of `pub use` chains are followed when building the index.
+const MAX_REEXPORT_DEPTH: usize = 8;
 
 /// Information about a function annotated with `#[context("...")]`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AnnotatedFunction {
     /// The function name.
-    pub name: String,
+    pub name: Arc<str>,
     /// The file path where the function is defined.
-    pub file: String,
+    pub file: Arc<str>,
     /// The line number of the function definition.
     pub line: usize,
-    /// The context string from the `#[context("...")]` attribute.
-    pub context_string: String,
+    /// The context string from the `#[context("...")]` attribute, or the
+    /// `context` of the `[[rules]]` entry selecting the function. `None` if
+    /// neither has one.
+    pub context_string: Option<String>,
+    /// Whether the function is annotated in the source or selected by path.
+    #[serde(default)]
+    pub source: AnnotationSource,
+    /// The format arguments following the context string, as written, e.g.
+    /// `path.display()` or `mode = self.mode`.
+    #[serde(default)]
+    pub context_args: Vec<String>,
     /// Whether this is a method (has a `self` receiver).
     pub is_method: bool,
+    /// Whether the function is `async`.
+    #[serde(default)]
+    pub is_async: bool,
+    /// The visibility as written, e.g. `pub(crate)`, or empty if private.
+    #[serde(default)]
+    pub visibility: String,
+    /// The generic parameters as written, e.g. `<P: AsRef<Path>>`, or empty.
+    #[serde(default)]
+    pub generics: String,
+    /// The names bound by the parameters, `self` for the receiver.
+    #[serde(default)]
+    pub params: Vec<String>,
+    /// Whether the signature plausibly returns a `Result`. `#[context]` on
+    /// anything else is a mistake.
+    pub returns_result: bool,
+    /// Problems with the format string, e.g. placeholders matching neither a
+    /// parameter nor an explicit argument.
+    pub format_errors: Vec<String>,
+    /// Path of the module defining the function, starting with the crate name
+    /// (e.g. `["my_crate", "pod", "storage"]`). Empty if unknown.
+    pub module_path: Vec<String>,
+    /// The type (or trait) of the enclosing `impl` (or `trait`) block, if any.
+    pub self_type: Option<String>,
+    /// The span of the `#[context]` attribute (unknown in index files written
+    /// by older versions).
+    #[serde(default)]
+    pub attr_span: Span,
+    /// The predicate of the `#[cfg_attr(..)]` wrapping the attribute, e.g.
+    /// `feature = "x"`, if the function is only annotated conditionally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfg: Option<String>,
+    /// Placeholders that are likely misspelled parameter names, with the
+    /// edits correcting them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub placeholder_fixes: Vec<PlaceholderFix>,
+    /// Methods adding another layer of context on its result besides the
+    /// `[context-methods]`, from a `[[rules]]` entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
 }
 
-/// A map from function name to all annotated functions with that name.
-/// Multiple functions can share a name (different modules/impls).
-pub type AnnotatedFunctions = HashMap<String, Vec<AnnotatedFunction>>;
+/// How a function is marked as adding context.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum AnnotationSource {
+    /// A context attribute, including configured ones and attribute rules.
+    #[default]
+    Attribute,
+    /// A `[[rules]]` entry selecting functions by path: nothing in the source
+    /// says so, and its context (if any) is the configured one.
+    Rule,
+}
 
-/// Parse a single Rust source file and collect all `#[context(...)]`-annotated functions.
-pub fn collect_from_file(path: &Path) -> Result<Vec<AnnotatedFunction>> {
-    let source =
-        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+/// A format placeholder naming no parameter, but close to one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaceholderFix {
+    /// The index of the error it fixes in
+    /// [`format_errors`](AnnotatedFunction::format_errors).
+    pub error: usize,
+    /// The placeholder's name, e.g. `pth`.
+    pub placeholder: String,
+    /// The parameter it most likely means, e.g. `path`.
+    pub parameter: String,
+    /// Edits replacing each occurrence of the placeholder in the format
+    /// string (empty if the string couldn't be located in the source).
+    pub edits: Vec<TextEdit>,
+}
 
-    let syntax: File = match syn::parse_file(&source) {
-        Ok(f) => f,
-        Err(_) => {
-            // Some files may not parse (e.g., macro-heavy code). Skip them.
-            return Ok(Vec::new());
+impl AnnotatedFunction {
+    /// The context string written in the source, to check like any other
+    /// message. `None` for functions selected by a `[[rules]]` entry.
+    pub fn written_context(&self) -> Option<&str> {
+        match self.source {
+            AnnotationSource::Attribute => self.context_string.as_deref(),
+            AnnotationSource::Rule => None,
         }
+    }
+
+    /// The fully qualified path of the function, e.g.
+    /// `my_crate::pod::storage::Storage::open`, if its module is known.
+    pub fn qualified_name(&self) -> Option<String> {
+        if self.module_path.is_empty() {
+            return None;
+        }
+        let mut segments = self.module_path.clone();
+        segments.extend(self.self_type.clone());
+        segments.push(self.name.to_string());
+        Some(segments.join("::"))
+    }
+
+    /// The annotation as written, e.g. `#[context("Loading {}", path)]`, or
+    /// `[[rules]]` for functions selected by path.
+    pub fn annotation(&self) -> String {
+        let args: String = self
+            .context_args
+            .iter()
+            .map(|arg| format!(", {arg}"))
+            .collect();
+        let message = self
+            .context_string
+            .as_ref()
+            .map(|context| format!("\"{}\"{args}", context.escape_debug()));
+        match (self.source, message) {
+            (AnnotationSource::Attribute, Some(message)) => format!("#[context({message})]"),
+            (AnnotationSource::Attribute, None) => "#[context]".to_string(),
+            (AnnotationSource::Rule, Some(message)) => format!("[[rules]] context = {message}"),
+            (AnnotationSource::Rule, None) => "[[rules]]".to_string(),
+        }
+    }
+
+    /// A condensed signature, with parameter names only, e.g.
+    /// `pub async fn load<P: AsRef<Path>>(path, mode)`.
+    pub fn signature(&self) -> String {
+        let mut signature = String::new();
+        if !self.visibility.is_empty() {
+            signature.push_str(&self.visibility);
+            signature.push(' ');
+        }
+        if self.is_async {
+            signature.push_str("async ");
+        }
+        format!(
+            "{signature}fn {}{}({})",
+            self.name,
+            self.generics,
+            self.params.join(", ")
+        )
+    }
+}
+
+/// A `pub use` declaration in a module with a known path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleReExport {
+    /// Path of the module containing the declaration, starting with the crate
+    /// name.
+    pub module_path: Vec<String>,
+    /// The re-exported name and path.
+    pub reexport: ReExport,
+}
+
+/// Everything pass 1 collects from a file or, combined, from a workspace.
+/// This is also the format of the index files written by `--emit-index`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Collected {
+    /// Annotated functions.
+    pub functions: Vec<AnnotatedFunction>,
+    /// Re-exports in modules with a known path.
+    pub reexports: Vec<ModuleReExport>,
+}
+
+/// The arguments of a `#[context("fmt", args...)]` attribute.
+struct ContextArgs {
+    /// The (unescaped) format string.
+    format: String,
+    /// The span of the format string literal.
+    format_span: proc_macro2::Span,
+    /// Number of positional format arguments.
+    positional: usize,
+    /// Names of explicit `name = value` format arguments.
+    named: Vec<String>,
+    /// All format arguments, in order.
+    args: Vec<Expr>,
+}
+
+/// Index of all annotated functions, keyed by fully qualified path, with a
+/// secondary lookup by bare name. Multiple functions can share a name
+/// (different modules/impls).
+#[derive(Debug, Default)]
+pub struct AnnotatedFunctions {
+    functions: Vec<AnnotatedFunction>,
+    by_path: HashMap<String, Vec<usize>>,
+    by_name: HashMap<Arc<str>, Vec<usize>>,
+    /// The methods adding context only on the results of some functions.
+    rule_methods: HashSet<String>,
+}
+
+impl AnnotatedFunctions {
+    /// All annotated functions with the given qualified path.
+    pub fn get_qualified(&self, path: &str) -> Vec<&AnnotatedFunction> {
+        self.lookup(&self.by_path, path)
+    }
+
+    /// All annotated functions with the given name, in any module.
+    pub fn get(&self, name: &str) -> Vec<&AnnotatedFunction> {
+        self.lookup(&self.by_name, name)
+    }
+
+    /// The entry for the function with signature `sig` defined in `file`,
+    /// if it carries a context attribute, configured custom ones included.
+    pub fn definition(&self, file: &str, sig: &Signature) -> Option<&AnnotatedFunction> {
+        let line = sig.ident.span().start().line;
+        self.get(&sig.ident.to_string())
+            .into_iter()
+            .find(|af| *af.file == *file && af.line == line)
+    }
+
+    /// Iterate over all annotated functions.
+    pub fn iter(&self) -> impl Iterator<Item = &AnnotatedFunction> {
+        self.functions.iter()
+    }
+
+    /// Whether `name` adds context on the results of some functions (see
+    /// [`AnnotatedFunction::methods`]).
+    pub fn is_rule_method(&self, name: &str) -> bool {
+        self.rule_methods.contains(name)
+    }
+
+    /// Make annotated functions reachable through `pub use` re-exports, so
+    /// that `crate::api::load_config` finds a function defined in
+    /// `crate::internal::config` and re-exported from `crate::api`. Chains of
+    /// re-exports are followed.
+    pub fn add_reexports(&mut self, reexports: &[ModuleReExport]) {
+        for _ in 0..MAX_REEXPORT_DEPTH {
+            let mut added = false;
+            for entry in reexports {
+                let reexport = &entry.reexport;
+                let mut alias = entry.module_path.clone();
+                alias.extend(reexport.name.clone());
+                let alias = alias.join("::");
+                for target in qualified_candidates(&reexport.path, &entry.module_path, None) {
+                    let mut aliased: Vec<(String, Vec<usize>)> = Vec::new();
+                    // The re-exported item itself (only for named re-exports)...
+                    if reexport.name.is_some() {
+                        if let Some(indices) = self.by_path.get(&target) {
+                            aliased.push((alias.clone(), indices.clone()));
+                        }
+                    }
+                    // ...and anything inside it, for modules and types.
+                    let prefix = format!("{target}::");
+                    for (path, indices) in &self.by_path {
+                        if let Some(rest) = path.strip_prefix(&prefix) {
+                            aliased.push((format!("{alias}::{rest}"), indices.clone()));
+                        }
+                    }
+                    for (path, indices) in aliased {
+                        let entry = self.by_path.entry(path).or_default();
+                        for idx in indices {
+                            if !entry.contains(&idx) {
+                                entry.push(idx);
+                                added = true;
+                            }
+                        }
+                    }
+                }
+            }
+            if !added {
+                break;
+            }
+        }
+    }
+
+    fn lookup<K>(&self, map: &HashMap<K, Vec<usize>>, key: &str) -> Vec<&AnnotatedFunction>
+    where
+        K: std::borrow::Borrow<str> + std::hash::Hash + Eq,
+    {
+        map.get(key)
+            .into_iter()
+            .flatten()
+            .map(|&idx| &self.functions[idx])
+            .collect()
+    }
+}
+
+/// Collect all `#[context(...)]`-annotated functions of a single Rust source
+/// file, including those carrying one of the configured `attributes` or
+/// selected by one of the `rules`. `module_path` is the path of the module the
+/// file defines, if known; only then are its re-exports collected, and
+/// functions matched against the rules.
+pub fn collect_from_file(
+    file: &ParsedFile,
+    module_path: &[String],
+    attributes: &[ContextAttribute],
+    rules: &[FunctionRule],
+) -> Collected {
+    let Some(syntax) = &file.syntax else {
+        return Collected::default();
     };
 
     let mut visitor = ContextCollector {
-        file_path: path.to_string_lossy().to_string(),
+        file_path: file.display_path(),
+        source: &file.source,
+        base: file.syn_offset(),
+        module_path: module_path.to_vec(),
+        self_type: None,
+        attributes,
+        rules,
         results: Vec::new(),
+        reexports: Vec::new(),
     };
-    visitor.visit_file(&syntax);
+    visitor.visit_file(syntax);
+
+    Collected {
+        functions: visitor.results,
+        reexports: visitor.reexports,
+    }
+}
 
-    Ok(visitor.results)
+/// Write collected annotated functions and re-exports to an index file.
+pub fn write_index(path: &Path, collected: &Collected) -> Result<()> {
+    let json = serde_json::to_string_pretty(collected)?;
+    std::fs::write(path, json).with_context(|| format!("Writing {}", path.display()))
 }
 
-/// Build the full map of annotated functions from a list of collected entries.
+/// Read an index file written by [`write_index`].
+pub fn read_index(path: &Path) -> Result<Collected> {
+    let json =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Parsing {}", path.display()))
+}
+
+/// Build the full index of annotated functions from a list of collected entries.
 pub fn build_index(entries: Vec<AnnotatedFunction>) -> AnnotatedFunctions {
-    let mut map: AnnotatedFunctions = HashMap::new();
-    for entry in entries {
-        map.entry(entry.name.clone()).or_default().push(entry);
+    let mut index = AnnotatedFunctions::default();
+    for (idx, entry) in entries.iter().enumerate() {
+        if let Some(path) = entry.qualified_name() {
+            index.by_path.entry(path).or_default().push(idx);
+        }
+        index
+            .by_name
+            .entry(entry.name.clone())
+            .or_default()
+            .push(idx);
+        index.rule_methods.extend(entry.methods.iter().cloned());
+    }
+    index.functions = entries;
+    index
+}
+
+/// The last path segment of a type, e.g. `Storage` for `crate::Storage<T>`.
+pub fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .map(|seg| seg.ident.to_string()),
+        _ => None,
     }
-    map
 }
 
 /// AST visitor that collects functions with `#[context(...)]` attributes.
-struct ContextCollector {
-    file_path: String,
+struct ContextCollector<'a> {
+    file_path: Arc<str>,
+    /// The file contents.
+    source: &'a str,
+    /// Leading bytes skipped by `syn`, see [`ParsedFile::syn_offset`].
+    base: usize,
+    /// Path of the current module, including inline `mod` blocks.
+    module_path: Vec<String>,
+    /// The type of the enclosing `impl` block, or the enclosing trait.
+    self_type: Option<String>,
+    /// Additional attributes to treat like `#[context]`.
+    attributes: &'a [ContextAttribute],
+    /// Functions adding context by path.
+    rules: &'a [FunctionRule],
     results: Vec<AnnotatedFunction>,
+    reexports: Vec<ModuleReExport>,
 }
 
-impl ContextCollector {
+impl ContextCollector<'_> {
+    /// Record the re-exports among the items of the current module.
+    fn collect_reexports(&mut self, items: &[Item]) {
+        if self.module_path.is_empty() {
+            return;
+        }
+        for reexport in collect_reexports(items) {
+            self.reexports.push(ModuleReExport {
+                module_path: self.module_path.clone(),
+                reexport,
+            });
+        }
+    }
+
     /// Check if an attribute is a `#[context(...)]` or `#[fn_error_context::context(...)]`
-    /// attribute, and if so, extract the context string.
-    fn extract_context_string(attr: &Attribute) -> Option<String> {
-        let path = attr.path();
+    /// attribute, and if so, extract its arguments.
+    fn extract_context_args(meta: &Meta) -> Option<ContextArgs> {
+        let path = meta.path();
 
         let is_context = match path.segments.len() {
             // `#[context("...")]` — requires a `use fn_error_context::context;` import
@@ -83,83 +442,554 @@ impl ContextCollector {
             return None;
         }
 
-        // Extract the context string from the attribute arguments.
         // The attribute takes the form: #[context("format string", args...)]
         // or #[context(move, "format string", args...)]
-        // We want the first string literal.
-        let tokens = match &attr.meta {
-            syn::Meta::List(list) => list.tokens.clone(),
+        let Meta::List(list) = meta else {
+            return None;
+        };
+        list.parse_args_with(|input: ParseStream| {
+            if input.peek(Token![move]) {
+                input.parse::<Token![move]>()?;
+                input.parse::<Token![,]>()?;
+            }
+            let lit: LitStr = input.parse()?;
+            let mut args = ContextArgs {
+                format: lit.value(),
+                format_span: lit.span(),
+                positional: 0,
+                named: Vec::new(),
+                args: Vec::new(),
+            };
+            if input.is_empty() {
+                return Ok(args);
+            }
+            input.parse::<Token![,]>()?;
+            for arg in Punctuated::<Expr, Token![,]>::parse_terminated(input)? {
+                args.args.push(arg.clone());
+                match arg {
+                    Expr::Assign(assign) => match &*assign.left {
+                        Expr::Path(p) if p.path.get_ident().is_some() => {
+                            args.named.push(p.path.segments[0].ident.to_string());
+                        }
+                        _ => args.positional += 1,
+                    },
+                    _ => args.positional += 1,
+                }
+            }
+            Ok(args)
+        })
+        .ok()
+    }
+
+    /// Extract the arguments of a configured context attribute: the message
+    /// is the string at `message-index` among the positional arguments, or the
+    /// value of the `message-key = "..."` argument, or failing that the
+    /// configured `context`.
+    fn extract_custom_args(meta: &Meta, custom: &ContextAttribute) -> Option<ContextArgs> {
+        Self::extract_custom_message(meta, custom).or_else(|| {
+            Some(ContextArgs {
+                format: custom.context.clone()?,
+                format_span: meta.path().span(),
+                positional: 0,
+                named: Vec::new(),
+                args: Vec::new(),
+            })
+        })
+    }
+
+    fn extract_custom_message(meta: &Meta, custom: &ContextAttribute) -> Option<ContextArgs> {
+        let args = match meta {
+            Meta::List(list) => Punctuated::<Expr, Token![,]>::parse_terminated
+                .parse2(list.tokens.clone())
+                .ok()?,
             _ => return None,
         };
 
-        // Find the first string literal in the token stream.
-        for token in tokens {
-            if let proc_macro2::TokenTree::Literal(lit) = token {
-                let repr = lit.to_string();
-                // String literals start and end with '"'
-                if repr.starts_with('"') && repr.ends_with('"') {
-                    // Strip the surrounding quotes
-                    return Some(repr[1..repr.len() - 1].to_string());
+        let string = |expr: &Expr| match expr {
+            Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            }) => Some((s.value(), s.span())),
+            _ => None,
+        };
+
+        let mut format = None;
+        let mut positional = Vec::new();
+        let mut named = Vec::new();
+        let mut format_args = Vec::new();
+        for arg in &args {
+            match arg {
+                Expr::Assign(assign) => {
+                    let Expr::Path(p) = &*assign.left else {
+                        continue;
+                    };
+                    let Some(ident) = p.path.get_ident() else {
+                        continue;
+                    };
+                    if custom.message_key.as_ref().is_some_and(|key| ident == key) {
+                        format = string(&assign.right);
+                    } else {
+                        named.push(ident.to_string());
+                        format_args.push(arg.clone());
+                    }
+                }
+                _ => {
+                    // Positional arguments up to the message aren't
+                    // format arguments.
+                    if custom.message_key.is_some() || positional.len() > custom.message_index {
+                        format_args.push(arg.clone());
+                    }
+                    positional.push(arg);
                 }
             }
         }
 
-        None
+        let positional = match &custom.message_key {
+            Some(_) => positional.len(),
+            None => {
+                format = string(positional.get(custom.message_index)?);
+                positional.len() - custom.message_index - 1
+            }
+        };
+
+        let (format, format_span) = format?;
+        Some(ContextArgs {
+            format,
+            format_span,
+            positional,
+            named,
+            args: format_args,
+        })
+    }
+
+    /// The source text of a syntax node.
+    fn source_text(&self, node: &impl Spanned) -> String {
+        let range = node.span().byte_range();
+        self.source
+            .get(self.base + range.start..self.base + range.end)
+            .map(str::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Edits replacing the placeholder `from` with `to` in the format string
+    /// literal at `span`. Both raw and escaped literals are handled, as names
+    /// never need escaping.
+    fn rename_placeholder(&self, span: proc_macro2::Span, from: &str, to: &str) -> Vec<TextEdit> {
+        let range = span.byte_range();
+        let start = self.base + range.start;
+        let Some(literal) = self.source.get(start..self.base + range.end) else {
+            return Vec::new();
+        };
+        let mut edits = Vec::new();
+        let mut chars = literal.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c != '{' {
+                continue;
+            }
+            if chars.next_if(|&(_, c)| c == '{').is_some() {
+                continue;
+            }
+            let name_start = i + 1;
+            let name_end = literal[name_start..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(literal.len(), |len| name_start + len);
+            if &literal[name_start..name_end] == from
+                && matches!(literal[name_end..].chars().next(), Some('}' | ':'))
+            {
+                edits.push(TextEdit {
+                    start: start + name_start,
+                    end: start + name_end,
+                    replacement: to.to_string(),
+                });
+            }
+        }
+        edits
     }
 
-    fn check_fn(
-        &mut self,
-        attrs: &[Attribute],
-        name: &str,
-        is_method: bool,
-        span_start: proc_macro2::Span,
-    ) {
+    /// The context attribute of a function, or the rule selecting it.
+    fn annotation(&self, attrs: &[Attribute], sig: &Signature) -> Option<Annotation> {
         for attr in attrs {
-            if let Some(context_string) = Self::extract_context_string(attr) {
-                self.results.push(AnnotatedFunction {
-                    name: name.to_string(),
-                    file: self.file_path.clone(),
-                    line: span_start.start().line,
-                    context_string,
-                    is_method,
+            // `#[cfg_attr(predicate, context(..))]` annotates the function
+            // only if the predicate holds.
+            let (metas, cfg) = match cfg::cfg_attr(attr) {
+                Some((predicate, metas)) => (metas, Some(cfg::format_predicate(&predicate))),
+                None => (vec![attr.meta.clone()], None),
+            };
+            let found = metas.iter().find_map(|meta| {
+                if let Some(args) = Self::extract_context_args(meta) {
+                    return Some((Some(args), Vec::new()));
+                }
+                let custom = self
+                    .attributes
+                    .iter()
+                    .find(|custom| custom.matches(meta.path()))?;
+                // Attribute rules annotate functions without a message too.
+                let args = Self::extract_custom_args(meta, custom);
+                if args.is_none() && !custom.rule {
+                    return None;
+                }
+                Some((args, custom.methods.clone()))
+            });
+            if let Some((args, methods)) = found {
+                return Some(Annotation {
+                    args,
+                    context: None,
+                    source: AnnotationSource::Attribute,
+                    cfg,
+                    span: Span::new(
+                        attr.pound_token.span,
+                        attr.bracket_token.span.close(),
+                        self.base,
+                    ),
+                    methods,
+                });
+            }
+        }
+        // Only functions returning a `Result` can add context.
+        if !returns_result(&sig.output) {
+            return None;
+        }
+        let name = sig.ident.to_string();
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(&self.module_path, self.self_type.as_deref(), &name))?;
+        Some(Annotation {
+            args: None,
+            context: rule.context.clone(),
+            source: AnnotationSource::Rule,
+            cfg: None,
+            span: Span::default(),
+            methods: rule.methods.clone(),
+        })
+    }
+
+    fn check_fn(&mut self, attrs: &[Attribute], vis: Option<&Visibility>, sig: &Signature) {
+        let Some(Annotation {
+            args,
+            mut context,
+            source,
+            cfg,
+            span,
+            methods,
+        }) = self.annotation(attrs, sig)
+        else {
+            return;
+        };
+        let params = param_names(sig);
+        let mut format_errors = Vec::new();
+        let mut placeholder_fixes = Vec::new();
+        let mut context_args = Vec::new();
+        if let Some(args) = args {
+            let typos;
+            (format_errors, typos) = check_format_string(&args, &params);
+            placeholder_fixes = typos
+                .into_iter()
+                .map(|(error, placeholder, parameter)| PlaceholderFix {
+                    edits: self.rename_placeholder(args.format_span, &placeholder, &parameter),
+                    error,
+                    placeholder,
+                    parameter,
+                })
+                .collect();
+            context_args = args.args.iter().map(|arg| self.source_text(arg)).collect();
+            context = Some(args.format);
+        }
+        let visibility = match vis {
+            Some(Visibility::Inherited) | None => String::new(),
+            Some(vis) => self.source_text(vis),
+        };
+        let generics = match sig.generics.lt_token {
+            Some(_) => self.source_text(&sig.generics),
+            None => String::new(),
+        };
+        self.results.push(AnnotatedFunction {
+            name: sig.ident.to_string().into(),
+            file: self.file_path.clone(),
+            line: sig.ident.span().start().line,
+            context_string: context,
+            source,
+            context_args,
+            is_method: sig.receiver().is_some(),
+            is_async: sig.asyncness.is_some(),
+            visibility,
+            generics,
+            params,
+            returns_result: returns_result(&sig.output),
+            format_errors,
+            module_path: self.module_path.clone(),
+            self_type: self.self_type.clone(),
+            attr_span: span,
+            cfg,
+            placeholder_fixes,
+            methods,
+        });
+    }
+}
+
+/// How a function adds context: its attribute (with the `#[cfg_attr]`
+/// predicate it depends on, if any), or a `[[rules]]` entry.
+struct Annotation {
+    /// The attribute's arguments; `None` for rules, and attribute rules
+    /// written without a message.
+    args: Option<ContextArgs>,
+    /// The configured context of a rule selecting the function by path.
+    context: Option<String>,
+    source: AnnotationSource,
+    cfg: Option<String>,
+    /// The span of the attribute; unknown for rules.
+    span: Span,
+    methods: Vec<String>,
+}
+
+/// A placeholder argument in a format string.
+#[derive(Debug, PartialEq, Eq)]
+enum Placeholder {
+    /// `{}` — the next positional argument.
+    Next,
+    /// `{0}` — an explicit positional argument.
+    Index(usize),
+    /// `{name}` — a named argument or captured variable.
+    Named(String),
+}
+
+/// Extract the placeholders of a format string, or `None` if it is malformed.
+fn parse_placeholders(format: &str) -> Option<Vec<Placeholder>> {
+    let mut placeholders = Vec::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => spec.push(c),
+                    }
+                }
+                let arg = spec.split(':').next().unwrap_or_default().trim();
+                placeholders.push(if arg.is_empty() {
+                    Placeholder::Next
+                } else if let Ok(index) = arg.parse() {
+                    Placeholder::Index(index)
+                } else {
+                    Placeholder::Named(arg.trim_start_matches("r#").to_string())
                 });
-                break; // Only one #[context] per function
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            }
+            '}' => return None,
+            _ => {}
+        }
+    }
+    Some(placeholders)
+}
+
+/// Collect the identifiers bound by a parameter pattern.
+fn pattern_bindings(pat: &Pat, out: &mut Vec<String>) {
+    match pat {
+        Pat::Ident(ident) => out.push(ident.ident.to_string()),
+        Pat::Type(typed) => pattern_bindings(&typed.pat, out),
+        Pat::Reference(reference) => pattern_bindings(&reference.pat, out),
+        Pat::Tuple(tuple) => tuple.elems.iter().for_each(|p| pattern_bindings(p, out)),
+        Pat::TupleStruct(tuple) => tuple.elems.iter().for_each(|p| pattern_bindings(p, out)),
+        Pat::Struct(st) => st.fields.iter().for_each(|f| pattern_bindings(&f.pat, out)),
+        _ => {}
+    }
+}
+
+/// The names bound by a function's parameters, `self` for the receiver.
+fn param_names(sig: &Signature) -> Vec<String> {
+    let mut params = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            FnArg::Receiver(_) => params.push("self".to_string()),
+            FnArg::Typed(typed) => pattern_bindings(&typed.pat, &mut params),
+        }
+    }
+    params
+}
+
+/// The parameter a misspelled placeholder most likely means: the only one
+/// closest to it, within two edits and not merely as short.
+fn closest_param<'p>(name: &str, params: &'p [String]) -> Option<&'p str> {
+    let mut best: Option<(usize, &str)> = None;
+    let mut tied = false;
+    for param in params {
+        let distance = edit_distance(name, param);
+        if distance > 2 || distance >= name.chars().count().min(param.chars().count()) {
+            continue;
+        }
+        match best {
+            Some((d, _)) if d < distance => {}
+            Some((d, _)) if d == distance => tied = true,
+            _ => {
+                best = Some((distance, param));
+                tied = false;
             }
         }
     }
+    best.filter(|_| !tied).map(|(_, param)| param)
 }
 
-impl<'ast> Visit<'ast> for ContextCollector {
+/// Verify that every placeholder of a `#[context]` format string refers to a
+/// parameter or an explicit argument. Also returns the placeholders that are
+/// likely misspelled parameters, with the index of their error and the
+/// parameter they mean.
+fn check_format_string(
+    args: &ContextArgs,
+    params: &[String],
+) -> (Vec<String>, Vec<(usize, String, String)>) {
+    let Some(placeholders) = parse_placeholders(&args.format) else {
+        return (vec!["malformed format string".to_string()], Vec::new());
+    };
+
+    let mut errors = Vec::new();
+    let mut typos = Vec::new();
+    let mut next = 0;
+    let mut needed = 0;
+    for placeholder in &placeholders {
+        match placeholder {
+            Placeholder::Next => {
+                next += 1;
+                needed = needed.max(next);
+            }
+            Placeholder::Index(index) => needed = needed.max(index + 1),
+            Placeholder::Named(name) => {
+                // SCREAMING_CASE names are most likely captured constants.
+                let is_const = name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c == '_' || c.is_ascii_digit());
+                if !params.contains(name) && !args.named.contains(name) && !is_const {
+                    let mut msg = format!("placeholder `{{{name}}}` matches no parameter");
+                    let param = closest_param(name, params);
+                    if let Some(param) = param {
+                        msg.push_str(&format!("; did you mean `{param}`?"));
+                    }
+                    if !errors.contains(&msg) {
+                        if let Some(param) = param {
+                            typos.push((errors.len(), name.clone(), param.to_string()));
+                        }
+                        errors.push(msg);
+                    }
+                }
+            }
+        }
+    }
+    if needed > args.positional {
+        errors.push(format!(
+            "format string needs {needed} positional argument{} but {} {} given",
+            if needed == 1 { "" } else { "s" },
+            args.positional,
+            if args.positional == 1 { "was" } else { "were" },
+        ));
+    }
+    (errors, typos)
+}
+
+/// Check whether a return type could be a `Result`.
+///
+/// Without type resolution we can't see through arbitrary aliases, so this only
+/// rejects return types that are certainly not a `Result`: no return type,
+/// tuples, references, arrays, and well-known non-`Result` types.
+fn returns_result(output: &ReturnType) -> bool {
+    let ty = match output {
+        ReturnType::Default => return false,
+        ReturnType::Type(_, ty) => ty.as_ref(),
+    };
+    match ty {
+        Type::Tuple(_) | Type::Reference(_) | Type::Array(_) | Type::Slice(_) | Type::Never(_) => {
+            false
+        }
+        Type::Path(type_path) => {
+            let Some(last) = type_path.path.segments.last() else {
+                return true;
+            };
+            !matches!(
+                last.ident.to_string().as_str(),
+                "bool"
+                    | "char"
+                    | "str"
+                    | "u8"
+                    | "u16"
+                    | "u32"
+                    | "u64"
+                    | "u128"
+                    | "usize"
+                    | "i8"
+                    | "i16"
+                    | "i32"
+                    | "i64"
+                    | "i128"
+                    | "isize"
+                    | "f32"
+                    | "f64"
+                    | "String"
+                    | "PathBuf"
+                    | "Vec"
+                    | "Option"
+                    | "HashMap"
+                    | "HashSet"
+                    | "BTreeMap"
+                    | "BTreeSet"
+            )
+        }
+        _ => true,
+    }
+}
+
+impl<'ast> Visit<'ast> for ContextCollector<'_> {
+    fn visit_file(&mut self, node: &'ast File) {
+        self.collect_reexports(&node.items);
+        syn::visit::visit_file(self, node);
+    }
+
     fn visit_item_fn(&mut self, node: &'ast ItemFn) {
-        self.check_fn(
-            &node.attrs,
-            &node.sig.ident.to_string(),
-            node.sig.receiver().is_some(),
-            node.sig.ident.span(),
-        );
+        self.check_fn(&node.attrs, Some(&node.vis), &node.sig);
         // Continue visiting nested items
         syn::visit::visit_item_fn(self, node);
     }
 
     fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
-        self.check_fn(
-            &node.attrs,
-            &node.sig.ident.to_string(),
-            node.sig.receiver().is_some(),
-            node.sig.ident.span(),
-        );
+        self.check_fn(&node.attrs, Some(&node.vis), &node.sig);
         syn::visit::visit_impl_item_fn(self, node);
     }
 
     fn visit_trait_item_fn(&mut self, node: &'ast TraitItemFn) {
-        self.check_fn(
-            &node.attrs,
-            &node.sig.ident.to_string(),
-            node.sig.receiver().is_some(),
-            node.sig.ident.span(),
-        );
+        self.check_fn(&node.attrs, None, &node.sig);
         syn::visit::visit_trait_item_fn(self, node);
     }
+
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        // Inline modules only qualify paths if the file's module is known.
+        let known = !self.module_path.is_empty();
+        if known {
+            self.module_path.push(node.ident.to_string());
+            if let Some((_, items)) = &node.content {
+                self.collect_reexports(items);
+            }
+        }
+        syn::visit::visit_item_mod(self, node);
+        if known {
+            self.module_path.pop();
+        }
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        let prev = std::mem::replace(&mut self.self_type, type_name(&node.self_ty));
+        syn::visit::visit_item_impl(self, node);
+        self.self_type = prev;
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        let prev = self.self_type.replace(node.ident.to_string());
+        syn::visit::visit_item_trait(self, node);
+        self.self_type = prev;
+    }
 }
 
 #[cfg(test)]
@@ -167,10 +997,25 @@ mod tests {
     use super::*;
 
     fn parse_and_collect(source: &str) -> Vec<AnnotatedFunction> {
+        parse_and_collect_with(source, &[], &[])
+    }
+
+    fn parse_and_collect_with(
+        source: &str,
+        attributes: &[ContextAttribute],
+        rules: &[FunctionRule],
+    ) -> Vec<AnnotatedFunction> {
         let syntax: File = syn::parse_file(source).unwrap();
         let mut visitor = ContextCollector {
-            file_path: "test.rs".to_string(),
+            file_path: "test.rs".into(),
+            source,
+            base: 0,
+            module_path: vec!["my_crate".to_string()],
+            self_type: None,
+            attributes,
+            rules,
             results: Vec::new(),
+            reexports: Vec::new(),
         };
         visitor.visit_file(&syntax);
         visitor.results
@@ -189,9 +1034,10 @@ mod tests {
         "#,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].name, "load_config");
-        assert_eq!(results[0].context_string, "Loading config");
+        assert_eq!(&*results[0].name, "load_config");
+        assert_eq!(results[0].context_string.as_deref(), Some("Loading config"));
         assert!(!results[0].is_method);
+        assert!(results[0].returns_result);
     }
 
     #[test]
@@ -205,8 +1051,33 @@ mod tests {
         "#,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].name, "delete_entry");
-        assert_eq!(results[0].context_string, "Deleting entry");
+        assert_eq!(&*results[0].name, "delete_entry");
+        assert_eq!(results[0].context_string.as_deref(), Some("Deleting entry"));
+    }
+
+    #[test]
+    fn test_cfg_attr() {
+        let results = parse_and_collect(
+            r#"
+            #[cfg_attr(all(unix, feature = "ctx"), allow(unused), context("Loading {path}"))]
+            fn load(path: &str) -> Result<()> {
+                Ok(())
+            }
+
+            #[context("Saving")]
+            fn save() -> Result<()> {
+                Ok(())
+            }
+        "#,
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].context_string.as_deref(), Some("Loading {path}"));
+        assert!(results[0].format_errors.is_empty());
+        assert_eq!(
+            results[0].cfg.as_deref(),
+            Some(r#"all(unix, feature = "ctx")"#)
+        );
+        assert_eq!(results[1].cfg, None);
     }
 
     #[test]
@@ -223,8 +1094,25 @@ mod tests {
         );
         assert_eq!(results.len(), 1);
         assert_eq!(
-            results[0].context_string,
-            "Opening {target} with writable mount"
+            results[0].context_string.as_deref(),
+            Some("Opening {target} with writable mount")
+        );
+        assert!(results[0].context_args.is_empty());
+    }
+
+    #[test]
+    fn test_context_args() {
+        let results = parse_and_collect(
+            r#"
+            #[context(move, "Copying {} to {dest} as {}", src.display(), mode = self.mode, user.name())]
+            fn copy(&self, src: &Path, dest: &Path, user: &User) -> Result<()> {
+                Ok(())
+            }
+        "#,
+        );
+        assert_eq!(
+            results[0].context_args,
+            ["src.display()", "mode = self.mode", "user.name()"]
         );
     }
 
@@ -244,7 +1132,7 @@ mod tests {
         "#,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].name, "prepare");
+        assert_eq!(&*results[0].name, "prepare");
         assert!(results[0].is_method);
     }
 
@@ -271,6 +1159,486 @@ mod tests {
         "#,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].context_string, "Deleting {}");
+        assert_eq!(results[0].context_string.as_deref(), Some("Deleting {}"));
+    }
+
+    #[test]
+    fn test_move_keyword() {
+        let results = parse_and_collect(
+            r#"
+            #[context(move, "Reading {}", path.display())]
+            fn read(path: &Path) -> Result<()> {
+                Ok(())
+            }
+        "#,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].context_string.as_deref(), Some("Reading {}"));
+    }
+
+    #[test]
+    fn test_raw_string() {
+        let results = parse_and_collect(
+            r##"
+            #[context(r#"Parsing "quoted" {name}"#)]
+            fn parse_quoted(name: &str) -> Result<()> {
+                Ok(())
+            }
+        "##,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].context_string.as_deref(),
+            Some(r#"Parsing "quoted" {name}"#)
+        );
+    }
+
+    #[test]
+    fn test_escapes_and_unicode() {
+        let results = parse_and_collect(
+            r#"
+            #[context("Tab\there \"quoted\" caf\u{e9} ✓")]
+            fn escapes() -> Result<()> {
+                Ok(())
+            }
+        "#,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].context_string.as_deref(),
+            Some("Tab\there \"quoted\" café ✓")
+        );
+    }
+
+    #[test]
+    fn test_multi_line_literal() {
+        let results = parse_and_collect(
+            r#"
+            #[context("Loading the very long \
+                       configuration file")]
+            fn load() -> Result<()> {
+                Ok(())
+            }
+        "#,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].context_string.as_deref(),
+            Some("Loading the very long configuration file")
+        );
+    }
+
+    #[test]
+    fn test_returns_result() {
+        let results = parse_and_collect(
+            r#"
+            #[context("Unit")]
+            fn unit() {}
+
+            #[context("Count")]
+            fn count() -> usize { 0 }
+
+            #[context("Maybe")]
+            fn maybe() -> Option<u8> { None }
+
+            #[context("Aliased")]
+            fn aliased() -> Fallible<u8> { Ok(0) }
+
+            #[context("Io")]
+            fn io() -> std::io::Result<()> { Ok(()) }
+        "#,
+        );
+        let returns: Vec<(&str, bool)> = results
+            .iter()
+            .map(|r| (&*r.name, r.returns_result))
+            .collect();
+        assert_eq!(
+            returns,
+            [
+                ("unit", false),
+                ("count", false),
+                ("maybe", false),
+                ("aliased", true),
+                ("io", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_placeholders() {
+        assert_eq!(
+            parse_placeholders("{} {0} {name:?} {{escaped}} {r#type}"),
+            Some(vec![
+                Placeholder::Next,
+                Placeholder::Index(0),
+                Placeholder::Named("name".to_string()),
+                Placeholder::Named("type".to_string()),
+            ])
+        );
+        assert_eq!(parse_placeholders("unclosed {"), None);
+        assert_eq!(parse_placeholders("stray }"), None);
+    }
+
+    #[test]
+    fn test_format_placeholders_valid() {
+        let results = parse_and_collect(
+            r#"
+            #[context("Opening {target} as {} for {user}", mode, user = who())]
+            fn open(target: &str, mode: Mode) -> Result<()> {
+                Ok(())
+            }
+
+            #[context("Importing {} into {DEFAULT_ROOT}", self.name)]
+            fn import(&self, (a, b): (u8, u8)) -> Result<()> {
+                Ok(())
+            }
+        "#,
+        );
+        assert_eq!(results.len(), 2);
+        assert!(results[0].format_errors.is_empty());
+        assert!(results[1].format_errors.is_empty());
+    }
+
+    #[test]
+    fn test_format_placeholder_typo() {
+        let source = r#"
+            #[context("Opening {taget} ({{taget}}) as {taget:?}")]
+            fn open(target: &str) -> Result<()> {
+                Ok(())
+            }
+
+            #[context("Stamping {dat}")]
+            fn stamp(data: &[u8], date: u64) -> Result<()> {
+                Ok(())
+            }
+        "#;
+        let results = parse_and_collect(source);
+        assert_eq!(
+            results[0].format_errors,
+            ["placeholder `{taget}` matches no parameter; did you mean `target`?"]
+        );
+        let fixes = &results[0].placeholder_fixes;
+        assert_eq!(fixes.len(), 1);
+        assert_eq!((fixes[0].error, fixes[0].parameter.as_str()), (0, "target"));
+        // The escaped `{{taget}}` is left alone.
+        let edited = crate::fix::apply_edits(source, &fixes[0].edits).unwrap();
+        assert!(edited.contains(r#""Opening {target} ({{taget}}) as {target:?}""#));
+
+        // Equally close to two parameters.
+        assert_eq!(
+            results[1].format_errors,
+            ["placeholder `{dat}` matches no parameter"]
+        );
+        assert!(results[1].placeholder_fixes.is_empty());
+    }
+
+    #[test]
+    fn test_closest_param() {
+        let params = ["path".to_string(), "paths".to_string(), "mode".to_string()];
+        assert_eq!(closest_param("pth", &params), Some("path"));
+        assert_eq!(closest_param("modes", &params), Some("mode"));
+        assert_eq!(closest_param("user", &params), None);
+        // Everything is two edits from a two-letter name.
+        assert_eq!(closest_param("md", &params), None);
+    }
+
+    #[test]
+    fn test_signature_metadata() {
+        let results = parse_and_collect(
+            r#"
+            #[context("Loading")]
+            pub(crate) async fn load<P: AsRef<Path>>(path: P, (a, b): (u8, u8)) -> Result<()>
+            where
+                P: Send,
+            {
+                Ok(())
+            }
+
+            impl Store {
+                #[context("Saving")]
+                fn save(&mut self, _: u8) -> Result<()> {
+                    Ok(())
+                }
+            }
+        "#,
+        );
+        assert!(results[0].is_async);
+        assert_eq!(results[0].visibility, "pub(crate)");
+        assert_eq!(results[0].generics, "<P: AsRef<Path>>");
+        assert_eq!(results[0].params, ["path", "a", "b"]);
+        assert!(!results[1].is_async);
+        assert_eq!(results[1].visibility, "");
+        assert_eq!(results[1].generics, "");
+        assert_eq!(results[1].params, ["self"]);
+        assert_eq!(
+            results[0].signature(),
+            "pub(crate) async fn load<P: AsRef<Path>>(path, a, b)"
+        );
+        assert_eq!(results[1].signature(), "fn save(self)");
+    }
+
+    #[test]
+    fn test_format_missing_positional() {
+        let results = parse_and_collect(
+            r#"
+            #[context("Copying {} to {}", src)]
+            fn copy(src: &str, dst: &str) -> Result<()> {
+                Ok(())
+            }
+        "#,
+        );
+        assert_eq!(
+            results[0].format_errors,
+            ["format string needs 2 positional arguments but 1 was given"]
+        );
+    }
+
+    #[test]
+    fn test_attribute_span() {
+        let source = "#!/usr/bin/env run-cargo-script\n#[context(\"Loading\")]\nfn load() {}\n";
+        let file = ParsedFile::from_source(Path::new("test.rs"), source.to_string());
+        let collected = collect_from_file(&file, &[], &[], &[]);
+        let span = collected.functions[0].attr_span;
+        assert_eq!((span.line, span.column, span.end_column), (2, 1, 22));
+        let bytes = span.bytes().unwrap();
+        assert_eq!(&source[bytes], "#[context(\"Loading\")]");
+    }
+
+    #[test]
+    fn test_custom_attribute_positional() {
+        let attributes = [ContextAttribute {
+            path: "my_macros::err_context".to_string(),
+            message_index: 1,
+            message_key: None,
+            context: None,
+            methods: Vec::new(),
+            rule: false,
+        }];
+        let results = parse_and_collect_with(
+            r#"
+            use my_macros::err_context;
+
+            #[err_context(Level::Warn, "Opening {}", path.display())]
+            fn open(path: &Path) -> Result<()> {
+                Ok(())
+            }
+
+            #[other_macro("Ignored")]
+            fn other() -> Result<()> {
+                Ok(())
+            }
+        "#,
+            &attributes,
+            &[],
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(&*results[0].name, "open");
+        assert_eq!(results[0].context_string.as_deref(), Some("Opening {}"));
+        assert_eq!(results[0].context_args, ["path.display()"]);
+        assert!(results[0].format_errors.is_empty());
+    }
+
+    #[test]
+    fn test_custom_attribute_key() {
+        let attributes = [ContextAttribute {
+            path: "traced".to_string(),
+            message_index: 0,
+            message_key: Some("msg".to_string()),
+            context: None,
+            methods: Vec::new(),
+            rule: false,
+        }];
+        let results = parse_and_collect_with(
+            r#"
+            #[traced(level = "debug", msg = "Syncing {target}")]
+            fn sync(targt: &str) -> Result<()> {
+                Ok(())
+            }
+        "#,
+            &attributes,
+            &[],
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].context_string.as_deref(),
+            Some("Syncing {target}")
+        );
+        assert_eq!(
+            results[0].format_errors,
+            ["placeholder `{target}` matches no parameter; did you mean `targt`?"]
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut config = crate::config::parse(
+            r#"
+            [[rules]]
+            functions = "crate::db::*"
+            methods = ["ctx"]
+
+            [[rules]]
+            attribute = "my::wrap"
+            "#,
+        )
+        .unwrap();
+        let rules = crate::rules::compile(&mut config).unwrap();
+        let results = parse_and_collect_with(
+            r#"
+            mod db {
+                pub fn query(sql: &str) -> Result<Row> {
+                    todo!()
+                }
+
+                pub fn escape(sql: &str) -> String {
+                    todo!()
+                }
+            }
+
+            #[my::wrap]
+            fn wrapped() -> Result<()> {
+                Ok(())
+            }
+
+            fn other() -> Result<()> {
+                Ok(())
+            }
+        "#,
+            &config.context_attributes,
+            &rules,
+        );
+        let names: Vec<&str> = results.iter().map(|r| &*r.name).collect();
+        assert_eq!(names, ["query", "wrapped"]);
+        assert_eq!(results[0].context_string, None);
+        assert_eq!(results[0].source, AnnotationSource::Rule);
+        assert_eq!(results[0].methods, ["ctx"]);
+        assert!(!results[0].attr_span.is_known());
+        assert_eq!(results[1].context_string, None);
+        assert_eq!(results[1].source, AnnotationSource::Attribute);
+        assert!(results[1].attr_span.is_known());
+        assert_eq!(results[0].annotation(), "[[rules]]");
+        assert_eq!(results[1].annotation(), "#[context]");
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        let results = parse_and_collect(
+            r#"
+            #[context("Loading")]
+            fn load() -> Result<()> {
+                Ok(())
+            }
+
+            mod storage {
+                pub struct Storage;
+
+                impl Storage {
+                    #[context("Opening")]
+                    pub fn open() -> Result<Self> {
+                        todo!()
+                    }
+                }
+            }
+        "#,
+        );
+        let names: Vec<String> = results
+            .iter()
+            .filter_map(|af| af.qualified_name())
+            .collect();
+        assert_eq!(
+            names,
+            ["my_crate::load", "my_crate::storage::Storage::open"]
+        );
+
+        let index = build_index(results);
+        assert_eq!(index.get("open").len(), 1);
+        assert_eq!(
+            index
+                .get_qualified("my_crate::storage::Storage::open")
+                .len(),
+            1
+        );
+        assert!(index.get_qualified("my_crate::open").is_empty());
+    }
+
+    #[test]
+    fn test_reexports() {
+        let source = r#"
+            mod internal {
+                pub mod config {
+                    #[context("Loading config")]
+                    pub fn load_config() -> Result<()> {
+                        Ok(())
+                    }
+                }
+            }
+
+            pub mod api {
+                pub use crate::internal::config::load_config;
+                pub use super::internal::config;
+            }
+
+            pub mod prelude {
+                pub use crate::api::*;
+            }
+
+            pub use api::load_config as load;
+        "#;
+        let syntax: File = syn::parse_file(source).unwrap();
+        let mut visitor = ContextCollector {
+            file_path: "test.rs".into(),
+            source,
+            base: 0,
+            module_path: vec!["my_crate".to_string()],
+            self_type: None,
+            attributes: &[],
+            rules: &[],
+            results: Vec::new(),
+            reexports: Vec::new(),
+        };
+        visitor.visit_file(&syntax);
+        assert_eq!(visitor.reexports.len(), 4);
+
+        let mut index = build_index(visitor.results);
+        index.add_reexports(&visitor.reexports);
+        for path in [
+            "my_crate::internal::config::load_config",
+            "my_crate::api::load_config",
+            "my_crate::api::config::load_config",
+            "my_crate::prelude::load_config",
+            "my_crate::prelude::config::load_config",
+            "my_crate::load",
+        ] {
+            assert_eq!(index.get_qualified(path).len(), 1, "{path}");
+        }
+        assert!(index.get_qualified("my_crate::load_config").is_empty());
+    }
+
+    #[test]
+    fn test_index_roundtrip() {
+        let collected = Collected {
+            functions: parse_and_collect(
+                r#"
+                #[context("Fetching {url}")]
+                pub fn fetch(url: &str) -> Result<()> {
+                    Ok(())
+                }
+                "#,
+            ),
+            reexports: Vec::new(),
+        };
+        let dir = crate::fixtures::TempDir::new("index");
+        let path = dir.join("index.json");
+        write_index(&path, &collected).unwrap();
+        let loaded = read_index(&path).unwrap();
+        assert_eq!(loaded.functions.len(), 1);
+        assert_eq!(
+            loaded.functions[0].qualified_name().as_deref(),
+            Some("my_crate::fetch")
+        );
+        assert_eq!(
+            loaded.functions[0].context_string.as_deref(),
+            Some("Fetching {url}")
+        );
     }
 }
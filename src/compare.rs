@@ -0,0 +1,325 @@
+//! Compare two JSON reports, for `cargo context-lint compare`.
+//!
+//! Findings are matched by their fingerprint (see `fingerprint.rs`),
+//! which leaves out the line: a finding that only moved because of edits
+//! around it is unchanged, not fixed and found anew.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::fingerprint;
+
+/// A finding read from a JSON report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReportedFinding {
+    /// The check's code (e.g. `CL0001`).
+    pub code: String,
+    /// File of the finding, as given in the report.
+    pub file: String,
+    /// Line of the finding.
+    pub line: usize,
+    /// One-line description.
+    pub message: String,
+    /// What findings are matched by.
+    pub fingerprint: String,
+}
+
+/// The findings of a new report, relative to an old one.
+#[derive(Debug, Default, Serialize)]
+pub struct Comparison {
+    /// Findings only in the new report.
+    pub new: Vec<ReportedFinding>,
+    /// Findings only in the old report.
+    pub fixed: Vec<ReportedFinding>,
+    /// Findings in both reports (as found in the new one).
+    pub unchanged: Vec<ReportedFinding>,
+}
+
+/// Read the findings of a report written with `--format json`.
+pub fn read_report(path: &Path) -> Result<Vec<ReportedFinding>> {
+    let json =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let report: Value =
+        serde_json::from_str(&json).with_context(|| format!("Parsing {}", path.display()))?;
+    report_findings(&report).with_context(|| format!("Reading the findings of {}", path.display()))
+}
+
+/// The findings of a parsed JSON report.
+pub fn report_findings(report: &Value) -> Result<Vec<ReportedFinding>> {
+    let mut findings = Vec::new();
+    for section in [
+        "double_context",
+        "unattributed",
+        "style",
+        "bare_propagation",
+        "plugin",
+        "banned_context",
+    ] {
+        let Some(warnings) = report[section]["warnings"].as_array() else {
+            // Sections may be missing in reports of older versions.
+            continue;
+        };
+        for warning in warnings {
+            findings.push(
+                reported_finding(section, warning)
+                    .with_context(|| format!("Unexpected {section} warning: {warning}"))?,
+            );
+        }
+    }
+    Ok(findings)
+}
+
+fn reported_finding(section: &str, warning: &Value) -> Option<ReportedFinding> {
+    let str_field = |name: &str| warning[name].as_str().unwrap_or_default().to_string();
+    let location = match section {
+        "double_context" => &warning["call_site"],
+        _ => &warning["location"],
+    };
+    let file = location["file"].as_str()?.to_string();
+    let line = location["line"].as_u64()? as usize;
+    let name = str_field("function_name");
+    let (message, identity) = match section {
+        "double_context" => (
+            format!("double context on `{name}`"),
+            vec![
+                name,
+                str_field("inner_context"),
+                str_field("outer_method"),
+                str_field("outer_context"),
+            ],
+        ),
+        "unattributed" => {
+            let kind = if warning["is_method"].as_bool()? {
+                "method"
+            } else {
+                "fn"
+            };
+            (
+                format!("{kind} returning Result without #[context]: `{name}`"),
+                vec![name],
+            )
+        }
+        "style" => (
+            str_field("message"),
+            vec![str_field("kind"), str_field("message")],
+        ),
+        "plugin" => (
+            str_field("message"),
+            vec![str_field("plugin"), str_field("rule"), str_field("message")],
+        ),
+        "banned_context" => (
+            str_field("message"),
+            vec![str_field("pattern"), str_field("context")],
+        ),
+        _ => (
+            format!("`{name}` propagates errors with bare `?` and adds no context"),
+            vec![name],
+        ),
+    };
+    let code = warning["code"].as_str()?.to_string();
+    // Reports of older versions have no fingerprints.
+    let fingerprint = match warning["fingerprint"].as_str() {
+        Some(fingerprint) => fingerprint.to_string(),
+        None => {
+            let identity: Vec<&str> = identity.iter().map(String::as_str).collect();
+            fingerprint::compute(&code, &file, &identity)
+        }
+    };
+    Some(ReportedFinding {
+        code,
+        file,
+        line,
+        message,
+        fingerprint,
+    })
+}
+
+/// Compare the findings of two reports. A fingerprint occurring more often
+/// in one report than in the other counts as that many new or fixed
+/// findings.
+pub fn compare(old: &[ReportedFinding], new: &[ReportedFinding]) -> Comparison {
+    let mut old_counts: HashMap<&str, usize> = HashMap::new();
+    for finding in old {
+        *old_counts.entry(&finding.fingerprint).or_default() += 1;
+    }
+    let mut comparison = Comparison::default();
+    for finding in new {
+        match old_counts.get_mut(finding.fingerprint.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                comparison.unchanged.push(finding.clone());
+            }
+            _ => comparison.new.push(finding.clone()),
+        }
+    }
+    // The old findings left over are the fixed ones; the last occurrences of
+    // a fingerprint are the ones left over.
+    for finding in old.iter().rev() {
+        if let Some(count) = old_counts.get_mut(finding.fingerprint.as_str()) {
+            if *count > 0 {
+                *count -= 1;
+                comparison.fixed.push(finding.clone());
+            }
+        }
+    }
+    comparison.fixed.reverse();
+    comparison
+}
+
+/// Format a comparison as text: the new and fixed findings, one per line,
+/// and the number of unchanged ones (listed too with `list_unchanged`).
+pub fn format_text(comparison: &Comparison, list_unchanged: bool) -> String {
+    let mut output = String::new();
+    let mut section = |title: &str, findings: &[ReportedFinding], list: bool| {
+        output.push_str(&format!("{title}: {}\n", findings.len()));
+        if list {
+            for finding in findings {
+                output.push_str(&format!(
+                    "  {}:{}: [{}] {}\n",
+                    finding.file, finding.line, finding.code, finding.message
+                ));
+            }
+        }
+    };
+    section("New", &comparison.new, true);
+    section("Fixed", &comparison.fixed, true);
+    section("Unchanged", &comparison.unchanged, list_unchanged);
+    output
+}
+
+/// Format a comparison as JSON.
+pub fn format_json(comparison: &Comparison) -> String {
+    serde_json::to_string_pretty(comparison).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unattributed(name: &str, file: &str, line: usize) -> Value {
+        serde_json::json!({
+            "code": "CL0002",
+            "severity": "error",
+            "function_name": name,
+            "location": { "file": file, "line": line },
+            "is_method": false,
+            "is_pub": true,
+        })
+    }
+
+    fn report(unattributed: Vec<Value>) -> Vec<ReportedFinding> {
+        let report = serde_json::json!({
+            "schema_version": 1,
+            "double_context": {
+                "warnings": [{
+                    "code": "CL0001",
+                    "function_name": "load_config",
+                    "call_site": { "file": "src/main.rs", "line": 10, "column": 5 },
+                    "inner_context": "Loading config",
+                    "outer_method": "context",
+                    "outer_context": "Loading",
+                }],
+                "total": 1,
+            },
+            "unattributed": { "warnings": unattributed, "total": 0 },
+        });
+        report_findings(&report).unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let old = report(vec![
+            unattributed("parse", "src/lib.rs", 3),
+            unattributed("parse", "src/lib.rs", 30),
+            unattributed("load", "src/lib.rs", 50),
+        ]);
+        // Lines shifted, one `parse` and `load` fixed, `save` new.
+        let new = report(vec![
+            unattributed("parse", "src/lib.rs", 5),
+            unattributed("save", "src/lib.rs", 60),
+        ]);
+        assert_eq!(new[0].message, "double context on `load_config`");
+
+        let comparison = compare(&old, &new);
+        let lines = |findings: &[ReportedFinding]| -> Vec<(String, usize)> {
+            findings.iter().map(|f| (f.file.clone(), f.line)).collect()
+        };
+        assert_eq!(lines(&comparison.new), [("src/lib.rs".to_string(), 60)]);
+        assert_eq!(
+            lines(&comparison.fixed),
+            [
+                ("src/lib.rs".to_string(), 30),
+                ("src/lib.rs".to_string(), 50)
+            ]
+        );
+        assert_eq!(
+            lines(&comparison.unchanged),
+            [
+                ("src/main.rs".to_string(), 10),
+                ("src/lib.rs".to_string(), 5)
+            ]
+        );
+
+        assert_eq!(
+            format_text(&comparison, false),
+            "\
+New: 1
+  src/lib.rs:60: [CL0002] fn returning Result without #[context]: `save`
+Fixed: 2
+  src/lib.rs:30: [CL0002] fn returning Result without #[context]: `parse`
+  src/lib.rs:50: [CL0002] fn returning Result without #[context]: `load`
+Unchanged: 2
+"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_matches_report() {
+        use crate::report::{format_combined_json, Findings};
+        use crate::style::{StyleIssue, StyleKind};
+        use crate::unattributed::UnattributedFunction;
+
+        let unattributed = vec![UnattributedFunction {
+            file: "/ws/src/lib.rs".into(),
+            line: 3,
+            name: "parse".into(),
+            is_pub: true,
+            ..Default::default()
+        }];
+        let style = vec![StyleIssue {
+            file: "/ws/src/lib.rs".to_string(),
+            line: 7,
+            kind: StyleKind::LongMessage,
+            message: "context message is 130 characters long".to_string(),
+            fix: None,
+        }];
+        let findings = Findings {
+            unattributed: &unattributed,
+            style: &style,
+            ..Default::default()
+        };
+        let json = format_combined_json(&findings, Some("/ws/"));
+        let reported = report_findings(&serde_json::from_str(&json).unwrap()).unwrap();
+        let fingerprints: Vec<String> = findings
+            .iter()
+            .map(|finding| fingerprint::of_finding(&finding, Some("/ws/")))
+            .collect();
+        assert_eq!(reported.len(), 2);
+        for finding in &reported {
+            assert!(fingerprints.contains(&finding.fingerprint));
+        }
+    }
+
+    #[test]
+    fn test_malformed_report() {
+        let report = serde_json::json!({
+            "unattributed": { "warnings": [{ "code": "CL0002" }] },
+        });
+        assert!(report_findings(&report).is_err());
+        assert!(report_findings(&serde_json::json!({})).unwrap().is_empty());
+    }
+}
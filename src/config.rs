@@ -0,0 +1,541 @@
+//! Configuration loaded from `context-lint.toml`.
+//!
+//! The file is looked up at the workspace root unless `--config` points
+//! elsewhere. Every setting has a default, so the file is optional.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Name of the configuration file looked up at the workspace root.
+pub const CONFIG_FILE_NAME: &str = "context-lint.toml";
+
+/// Top-level configuration.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Which source files are checked.
+    pub paths: PathsConfig,
+    /// Settings for the double-context check.
+    pub double_context: DoubleContextConfig,
+    /// Settings for the unattributed functions check.
+    pub unattributed: UnattributedConfig,
+    /// Settings for the bare `?` propagation check.
+    pub bare_propagation: BarePropagationConfig,
+    /// Settings for the style checks.
+    pub style: StyleConfig,
+    /// Settings for the banned context strings check.
+    pub banned_context: BannedContextConfig,
+    /// Settings for spell checking context strings.
+    pub spelling: SpellingConfig,
+    /// Which receiver methods count as adding context.
+    pub context_methods: ContextMethodsConfig,
+    /// Attribute macros adding context like `fn_error_context::context`.
+    pub context_attributes: Vec<ContextAttribute>,
+    /// WebAssembly modules adding custom checks.
+    pub plugins: Vec<PluginConfig>,
+    /// Functions adding context besides those with a context attribute.
+    pub rules: Vec<RuleConfig>,
+}
+
+/// A rule selecting functions, by path or by attribute, that add context like
+/// `#[context]` does. See `rules.rs`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RuleConfig {
+    /// A glob matched against the functions' paths, e.g. `crate::db::*`.
+    pub functions: Option<String>,
+    /// The path of an attribute, e.g. `my::wrap`.
+    pub attribute: Option<String>,
+    /// Methods adding another layer of context on the functions' results,
+    /// besides the `[context-methods]`.
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// The context the functions add, as shown in findings.
+    pub context: Option<String>,
+}
+
+/// A WebAssembly module adding custom checks, see `plugin.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PluginConfig {
+    /// Path of the `.wasm` file, relative to the workspace root.
+    pub path: PathBuf,
+    /// Name shown with its findings; defaults to the file name without its
+    /// extension.
+    pub name: Option<String>,
+    /// How many WebAssembly instructions (roughly) the plugin may execute per
+    /// file; defaults to 10^9.
+    pub fuel: Option<u64>,
+    /// Passed to the plugin as is.
+    #[serde(default)]
+    pub options: toml::Table,
+}
+
+/// An attribute macro adding context to a function's errors, declared in
+/// addition to `fn_error_context::context`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ContextAttribute {
+    /// Path of the attribute, e.g. `err_context` or `my_macros::err_context`.
+    pub path: String,
+    /// Position of the message among the positional arguments.
+    #[serde(default)]
+    pub message_index: usize,
+    /// Take the message from a `key = "..."` argument instead.
+    pub message_key: Option<String>,
+    /// The context added when the attribute has no message, e.g. for a bare
+    /// `#[traced]`. Without it, such attributes are ignored.
+    pub context: Option<String>,
+    /// Methods adding another layer of context on the result of the
+    /// annotated functions, besides the `[context-methods]`.
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Whether the attribute comes from a `[[rules]]` entry: it then
+    /// annotates functions even without a message or `context`.
+    #[serde(skip)]
+    pub rule: bool,
+}
+
+impl ContextAttribute {
+    /// Whether an attribute path refers to this attribute, either as written in
+    /// the configuration or imported by its last segment.
+    pub fn matches(&self, path: &syn::Path) -> bool {
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let configured: Vec<&str> = self.path.split("::").collect();
+        segments == configured
+            || (segments.len() == 1 && configured.last() == Some(&segments[0].as_str()))
+    }
+}
+
+/// Methods adding context to an error, recognized out of the box.
+pub const DEFAULT_CONTEXT_METHODS: &[&str] =
+    &["context", "with_context", "wrap_err", "wrap_err_with"];
+
+/// Methods of `error-stack`'s `ResultExt` that add a layer to the report.
+const ERROR_STACK_METHODS: &[&str] = &[
+    "change_context",
+    "change_context_lazy",
+    "attach_printable",
+    "attach_printable_lazy",
+];
+
+/// Which receiver methods count as adding context, for both the double-context
+/// and the bare `?` propagation checks.
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ContextMethodsConfig {
+    /// The base set of method names; defaults to anyhow's `context` and
+    /// `with_context` and eyre's `wrap_err` and `wrap_err_with`.
+    pub names: Vec<String>,
+    /// Additional method names, e.g. snafu's `whatever_context` and
+    /// `with_whatever_context`.
+    pub extra: Vec<String>,
+    /// Also treat `error-stack`'s `change_context` and `attach_printable`
+    /// (and their `_lazy` variants) as adding context.
+    pub error_stack: bool,
+}
+
+impl Default for ContextMethodsConfig {
+    fn default() -> Self {
+        Self {
+            names: DEFAULT_CONTEXT_METHODS
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            extra: Vec::new(),
+            error_stack: false,
+        }
+    }
+}
+
+impl ContextMethodsConfig {
+    /// Whether calling the method `name` on a `Result` adds context.
+    pub fn is_context_method(&self, name: &str) -> bool {
+        self.names.iter().any(|m| m == name)
+            || (self.error_stack && ERROR_STACK_METHODS.contains(&name))
+            || self.extra.iter().any(|m| m == name)
+    }
+}
+
+/// Names of directories holding vendored code, skipped by default.
+pub const DEFAULT_VENDORED_DIRS: &[&str] = &["vendor", "third_party", "third-party"];
+
+/// Files larger than this many bytes are skipped by default: hand-written
+/// source is far smaller, and parsing huge generated files is slow.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Glob patterns selecting the source files to check, relative to the
+/// package directory.
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PathsConfig {
+    /// Only check files matching one of these patterns, if any are given.
+    pub include: Vec<String>,
+    /// Never check files matching one of these patterns, e.g. generated code.
+    pub exclude: Vec<String>,
+    /// Names of directories holding vendored code, which are skipped
+    /// wherever they are (unless `--include-vendored` is given).
+    pub vendored: Vec<String>,
+    /// Files larger than this many bytes are skipped; 0 disables the limit.
+    pub max_file_size: u64,
+}
+
+impl PathsConfig {
+    /// The size limit, if any.
+    pub fn max_file_size(&self) -> Option<u64> {
+        (self.max_file_size > 0).then_some(self.max_file_size)
+    }
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            vendored: DEFAULT_VENDORED_DIRS
+                .iter()
+                .map(|dir| dir.to_string())
+                .collect(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        }
+    }
+}
+
+/// Words suggesting a value is a credential, for the `sensitive-data` check.
+/// `init` writes them into the starter config; the check is off without one.
+pub const SUGGESTED_SENSITIVE_WORDS: &[&str] = &[
+    "password",
+    "passwd",
+    "passphrase",
+    "secret",
+    "token",
+    "credential",
+    "credentials",
+    "api_key",
+    "private_key",
+    "access_key",
+];
+
+/// Settings for the banned context strings check.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BannedContextConfig {
+    /// Regular expressions that context strings must not match, e.g.
+    /// `(?i)\btodo\b`.
+    pub patterns: Vec<String>,
+}
+
+/// Settings for spell checking context strings.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SpellingConfig {
+    /// Word lists with one word per line, relative to the workspace root,
+    /// e.g. `/usr/share/dict/words`. Empty disables the check.
+    pub dictionaries: Vec<PathBuf>,
+    /// The project's own words, e.g. names of tools and services.
+    pub words: Vec<String>,
+}
+
+/// The limit `init` writes for the `long-message` check, which is off
+/// without one.
+pub const SUGGESTED_MAX_MESSAGE_LENGTH: usize = 120;
+
+/// Settings for the style checks.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct StyleConfig {
+    /// Flag `#[context]` functions that only delegate to another `#[context]`
+    /// function. Off by default: some codebases stack these layers on purpose.
+    pub delegating_wrapper: bool,
+    /// Words marking values that shouldn't end up in context strings, matched
+    /// against the words of interpolated names (`db_password`, `authToken`)
+    /// and the label before a placeholder (`"token={}"`). Entries of several
+    /// words (`api_key`) match those words in a row. Empty, the default,
+    /// disables the check.
+    pub sensitive_words: Vec<String>,
+    /// Flag context messages longer than this many characters; 0, the
+    /// default, disables the check.
+    pub max_message_length: usize,
+}
+
+/// Settings for the double-context check.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DoubleContextConfig {
+    /// Drop findings whose confidence (from 0 to 1) is below this.
+    pub min_confidence: f64,
+}
+
+/// Settings for the unattributed functions check.
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct UnattributedConfig {
+    /// Result types to treat like `anyhow::Result`, e.g. `crate::Result` or
+    /// `CmdResult`. A single name matches the type under any path.
+    pub result_aliases: Vec<String>,
+    /// Skip functions that only delegate to another function: `#[context]`
+    /// there would mostly duplicate the callee's.
+    pub skip_delegation: bool,
+    /// Only flag `pub` functions.
+    pub only_pub: bool,
+    /// Only flag functions with at least this many statements.
+    pub min_statements: usize,
+    /// Attributes reporting a function's errors well enough to stand in for
+    /// `#[context]`, as `path` or `path(arg)` for attributes that only do so
+    /// with a given argument, e.g. `tracing::instrument(err)`.
+    pub error_attributes: Vec<String>,
+    /// Count functions adding context to every error they return (each `?`,
+    /// returned error and tail expression) as covered, as `#[context]` would
+    /// be redundant. Off by default.
+    pub skip_covered: bool,
+}
+
+impl Default for UnattributedConfig {
+    fn default() -> Self {
+        Self {
+            result_aliases: Vec::new(),
+            skip_delegation: true,
+            only_pub: false,
+            min_statements: 0,
+            error_attributes: vec!["tracing::instrument(err)".to_string()],
+            skip_covered: false,
+        }
+    }
+}
+
+/// Settings for the bare `?` propagation check.
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BarePropagationConfig {
+    /// Only flag functions with at least this many statements.
+    pub min_statements: usize,
+}
+
+impl Default for BarePropagationConfig {
+    fn default() -> Self {
+        Self { min_statements: 5 }
+    }
+}
+
+/// Load the configuration from an explicit path, or from `context-lint.toml`
+/// in the workspace root if it exists.
+pub fn load(explicit: Option<&Path>, workspace_root: &Path) -> Result<Config> {
+    let path = match explicit {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let path = workspace_root.join(CONFIG_FILE_NAME);
+            if !path.exists() {
+                return Ok(Config::default());
+            }
+            path
+        }
+    };
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("Reading {}", path.display()))?;
+    parse(&contents).with_context(|| format!("Parsing {}", path.display()))
+}
+
+/// Parse configuration from TOML text.
+pub fn parse(contents: &str) -> Result<Config> {
+    Ok(toml::from_str(contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let config = parse("").unwrap();
+        assert_eq!(config.bare_propagation.min_statements, 5);
+        assert!(config.unattributed.skip_delegation);
+        assert!(!config.style.delegating_wrapper);
+        assert!(config.style.sensitive_words.is_empty());
+        assert_eq!(config.style.max_message_length, 0);
+    }
+
+    #[test]
+    fn test_bare_propagation() {
+        let config = parse(
+            r#"
+            [bare-propagation]
+            min-statements = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.bare_propagation.min_statements, 10);
+    }
+
+    #[test]
+    fn test_plugins() {
+        let config = parse(
+            r#"
+            [[plugins]]
+            path = "tools/rules.wasm"
+            options = { prefix = "Failed to" }
+
+            [[plugins]]
+            path = "other.wasm"
+            name = "other"
+            fuel = 1000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.plugins.len(), 2);
+        assert_eq!(config.plugins[0].path, PathBuf::from("tools/rules.wasm"));
+        assert_eq!(
+            config.plugins[0].options["prefix"].as_str(),
+            Some("Failed to")
+        );
+        assert_eq!(config.plugins[1].name.as_deref(), Some("other"));
+        assert_eq!(config.plugins[1].fuel, Some(1000));
+    }
+
+    #[test]
+    fn test_unattributed() {
+        let config = parse(
+            r#"
+            [unattributed]
+            result-aliases = ["crate::Result", "CmdResult"]
+            skip-delegation = false
+            only-pub = true
+            min-statements = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.unattributed.result_aliases,
+            ["crate::Result", "CmdResult"]
+        );
+        assert!(!config.unattributed.skip_delegation);
+        assert!(config.unattributed.only_pub);
+        assert_eq!(config.unattributed.min_statements, 3);
+    }
+
+    #[test]
+    fn test_double_context() {
+        let config = parse(
+            r#"
+            [double-context]
+            min-confidence = 0.6
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.double_context.min_confidence, 0.6);
+    }
+
+    #[test]
+    fn test_paths() {
+        let config = parse(
+            r#"
+            [paths]
+            include = ["src/**"]
+            exclude = ["src/generated/**"]
+            max-file-size = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.paths.include, ["src/**"]);
+        assert_eq!(config.paths.exclude, ["src/generated/**"]);
+        assert_eq!(config.paths.max_file_size(), None);
+        assert_eq!(
+            Config::default().paths.max_file_size(),
+            Some(DEFAULT_MAX_FILE_SIZE)
+        );
+    }
+
+    #[test]
+    fn test_style() {
+        let config = parse(
+            r#"
+            [style]
+            delegating-wrapper = true
+            sensitive-words = ["pin"]
+            max-message-length = 80
+            "#,
+        )
+        .unwrap();
+        assert!(config.style.delegating_wrapper);
+        assert_eq!(config.style.sensitive_words, ["pin"]);
+        assert_eq!(config.style.max_message_length, 80);
+    }
+
+    #[test]
+    fn test_context_methods() {
+        let config = parse(
+            r#"
+            [context-methods]
+            extra = ["whatever_context", "with_whatever_context"]
+            "#,
+        )
+        .unwrap();
+        let methods = &config.context_methods;
+        assert!(methods.is_context_method("context"));
+        assert!(methods.is_context_method("wrap_err_with"));
+        assert!(methods.is_context_method("with_whatever_context"));
+        assert!(!methods.is_context_method("map"));
+        assert!(!methods.is_context_method("change_context"));
+    }
+
+    #[test]
+    fn test_context_method_names() {
+        let config = parse(
+            r#"
+            [context-methods]
+            names = ["ctx", "err_context"]
+            "#,
+        )
+        .unwrap();
+        let methods = &config.context_methods;
+        assert!(methods.is_context_method("ctx"));
+        assert!(methods.is_context_method("err_context"));
+        assert!(!methods.is_context_method("context"));
+    }
+
+    #[test]
+    fn test_error_stack_methods() {
+        let config = parse(
+            r#"
+            [context-methods]
+            error-stack = true
+            "#,
+        )
+        .unwrap();
+        let methods = &config.context_methods;
+        assert!(methods.is_context_method("change_context"));
+        assert!(methods.is_context_method("attach_printable_lazy"));
+        assert!(methods.is_context_method("context"));
+    }
+
+    #[test]
+    fn test_context_attributes() {
+        let config = parse(
+            r#"
+            [[context-attributes]]
+            path = "my_macros::err_context"
+
+            [[context-attributes]]
+            path = "traced"
+            message-key = "msg"
+            "#,
+        )
+        .unwrap();
+        let attrs = &config.context_attributes;
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].message_index, 0);
+        assert_eq!(attrs[1].message_key.as_deref(), Some("msg"));
+
+        let path = |s: &str| syn::parse_str::<syn::Path>(s).unwrap();
+        assert!(attrs[0].matches(&path("my_macros::err_context")));
+        assert!(attrs[0].matches(&path("err_context")));
+        assert!(!attrs[0].matches(&path("other::err_context")));
+        assert!(attrs[1].matches(&path("traced")));
+    }
+
+    #[test]
+    fn test_unknown_key_rejected() {
+        assert!(parse("unknown = 1").is_err());
+    }
+}
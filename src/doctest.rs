@@ -0,0 +1,231 @@
+//! Double-context check of the code examples in doc comments, for
+//! `--doc-tests`.
+//!
+//! Rust code blocks are extracted from doc comments the way rustdoc does it:
+//! hidden `# ` lines are kept, and the code is wrapped in `fn main()` unless it
+//! defines one. Findings point at the lines of the doc comment.
+
+use syn::visit::Visit;
+use syn::{Attribute, Expr, ExprLit, Lit, Meta};
+
+use crate::checker::{self, DoubleContext};
+use crate::collector::AnnotatedFunctions;
+use crate::config::ContextMethodsConfig;
+use crate::parse::ParsedFile;
+
+/// Code block attributes that don't make a block something other than Rust.
+const RUST_BLOCK_ATTRIBUTES: &[&str] =
+    &["rust", "no_run", "ignore", "should_panic", "test_harness"];
+
+/// A Rust code block from a doc comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocBlock {
+    /// The code as compiled by rustdoc.
+    pub source: String,
+    /// For each line of `source`, the line of the doc comment it comes from.
+    pub lines: Vec<usize>,
+}
+
+/// Check the code blocks in the doc comments of a file for double context.
+/// Doc-tests are separate crates, so paths in them are resolved as if from
+/// outside the crate.
+pub fn check_file(
+    file: &ParsedFile,
+    index: &AnnotatedFunctions,
+    methods: &ContextMethodsConfig,
+) -> Vec<DoubleContext> {
+    let Some(syntax) = &file.syntax else {
+        return Vec::new();
+    };
+    let mut results = Vec::new();
+    for block in extract_blocks(syntax) {
+        let parsed = ParsedFile::from_source(&file.path, block.source);
+        for mut issue in checker::check_file(&parsed, &[], index, methods) {
+            issue.call_line = block
+                .lines
+                .get(issue.call_line.saturating_sub(1))
+                .copied()
+                .unwrap_or(issue.call_line);
+            // Offsets into the extracted code don't apply to the file.
+            issue.call_span = Default::default();
+            issue.fix = None;
+            issue.caller = None;
+            results.push(issue);
+        }
+    }
+    results
+}
+
+/// Extract the Rust code blocks from all doc comments in a file.
+pub fn extract_blocks(syntax: &syn::File) -> Vec<DocBlock> {
+    let mut collector = DocCollector::default();
+    collector.visit_file(syntax);
+    collector.flush();
+    collector.blocks
+}
+
+/// Gathers the lines of consecutive doc comments.
+#[derive(Default)]
+struct DocCollector {
+    /// The current doc comment: line number and text of each line.
+    lines: Vec<(usize, String)>,
+    blocks: Vec<DocBlock>,
+}
+
+impl DocCollector {
+    /// End the current doc comment and extract its code blocks.
+    fn flush(&mut self) {
+        let lines = std::mem::take(&mut self.lines);
+        let mut block: Option<(bool, DocBlock)> = None;
+        for (line, text) in lines {
+            // rustdoc removes one leading space from `///` comments.
+            let text = text.strip_prefix(' ').unwrap_or(&text);
+            let trimmed = text.trim_start();
+            if let Some(info) = trimmed.strip_prefix("```") {
+                match block.take() {
+                    Some((is_rust, code)) => {
+                        if is_rust {
+                            self.blocks.push(wrap_main(code));
+                        }
+                    }
+                    None => {
+                        let block_code = DocBlock {
+                            source: String::new(),
+                            lines: Vec::new(),
+                        };
+                        block = Some((is_rust_block(info), block_code));
+                    }
+                }
+                continue;
+            }
+            if let Some((_, code)) = &mut block {
+                code.source.push_str(unhide(trimmed).unwrap_or(text));
+                code.source.push('\n');
+                code.lines.push(line);
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for DocCollector {
+    fn visit_attribute(&mut self, attr: &'ast Attribute) {
+        let Some(doc) = doc_text(attr) else {
+            self.flush();
+            return;
+        };
+        let start = attr.pound_token.span.start().line;
+        // A doc comment ends where the next one doesn't follow directly.
+        if self.lines.last().is_some_and(|(line, _)| *line + 1 < start) {
+            self.flush();
+        }
+        for (offset, text) in doc.lines().enumerate() {
+            self.lines.push((start + offset, text.to_string()));
+        }
+    }
+}
+
+/// The text of a `#[doc = "..."]` attribute (which `///` comments are).
+fn doc_text(attr: &Attribute) -> Option<String> {
+    let Meta::NameValue(nv) = &attr.meta else {
+        return None;
+    };
+    if !nv.path.is_ident("doc") {
+        return None;
+    }
+    match &nv.value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => Some(s.value()),
+        _ => None,
+    }
+}
+
+/// Whether a code block with this info string holds Rust code.
+fn is_rust_block(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .all(|token| RUST_BLOCK_ATTRIBUTES.contains(&token) || token.starts_with("edition"))
+}
+
+/// The compiled text of a hidden line (`# code`), if it is one.
+fn unhide(line: &str) -> Option<&str> {
+    if line == "#" {
+        return Some("");
+    }
+    line.strip_prefix("# ")
+}
+
+/// Wrap the code in `fn main()` like rustdoc does, unless it has one.
+fn wrap_main(mut block: DocBlock) -> DocBlock {
+    if block.source.contains("fn main") {
+        return block;
+    }
+    let first = block.lines.first().copied().unwrap_or_default();
+    block.source = format!("fn main() {{\n{}}}\n", block.source);
+    block.lines.insert(0, first);
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::{build_index, collect_from_file};
+
+    #[test]
+    fn test_extract_blocks() {
+        let source = r#"
+/// Loads things.
+///
+/// ```
+/// # use my_crate::load;
+/// load().unwrap();
+/// ```
+///
+/// ```text
+/// not code
+/// ```
+fn documented() {}
+
+/// ```no_run
+/// fn main() {}
+/// ```
+fn other() {}
+"#;
+        let syntax = syn::parse_file(source).unwrap();
+        let blocks = extract_blocks(&syntax);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[0].source,
+            "fn main() {\nuse my_crate::load;\nload().unwrap();\n}\n"
+        );
+        assert_eq!(blocks[0].lines, vec![5, 5, 6]);
+        assert_eq!(blocks[1].source, "fn main() {}\n");
+        assert_eq!(blocks[1].lines, vec![15]);
+    }
+
+    #[test]
+    fn test_check_file() {
+        let lib = ParsedFile::from_source(
+            std::path::Path::new("lib.rs"),
+            r#"
+#[context("Loading")]
+pub fn load() -> anyhow::Result<()> { Ok(()) }
+"#
+            .to_string(),
+        );
+        let collected = collect_from_file(&lib, &["my_crate".to_string()], &[], &[]);
+        let index = build_index(collected.functions);
+
+        let source = r#"
+/// ```
+/// my_crate::load().context("Loading again")?;
+/// ```
+fn documented() {}
+"#;
+        let file = ParsedFile::from_source(std::path::Path::new("lib.rs"), source.to_string());
+        let issues = check_file(&file, &index, &ContextMethodsConfig::default());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].call_line, 3);
+        assert!(issues[0].fix.is_none());
+    }
+}
@@ -0,0 +1,92 @@
+//! Macro expansion of workspace crates, for `--expand`.
+//!
+//! Each library and binary target is expanded with
+//! `rustc -Zunpretty=expanded` (the same mechanism `cargo expand` uses), and
+//! the output is written to a file below the target directory so the checks
+//! can read it like any other source file. Call sites generated by macros
+//! (`async_trait`, derives, `macro_rules!`) are then visible to the parser.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::package::{Package, Target};
+
+/// The expanded source of one crate.
+#[derive(Debug, Clone)]
+pub struct ExpandedCrate {
+    /// Index of the package in the workspace package list.
+    pub package: usize,
+    /// Path of the file holding the expanded source.
+    pub file: PathBuf,
+    /// Module path of the crate root, i.e. the crate name.
+    pub module_path: Vec<String>,
+}
+
+/// Expand the library and binary targets of the selected packages, writing
+/// the output below `target_dir`.
+pub fn expand_packages(
+    packages: &[Package],
+    selected: &[usize],
+    target_dir: &Path,
+) -> Result<Vec<ExpandedCrate>> {
+    let out_dir = target_dir.join("context-lint").join("expand");
+    let mut expanded = Vec::new();
+    for &idx in selected {
+        let package = &packages[idx];
+        for target in &package.targets {
+            let kind = if target.is_lib() {
+                "lib"
+            } else if target.is_bin() {
+                "bin"
+            } else {
+                continue;
+            };
+            let source = expand_target(package, target, target_dir)
+                .with_context(|| format!("Expanding {kind} `{}`", target.name))?;
+            let dir = out_dir.join(&package.name);
+            std::fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+            let file = dir.join(format!("{kind}-{}.rs", target.name));
+            std::fs::write(&file, source).with_context(|| format!("Writing {}", file.display()))?;
+            expanded.push(ExpandedCrate {
+                package: idx,
+                file,
+                module_path: vec![target.name.replace('-', "_")],
+            });
+        }
+    }
+    Ok(expanded)
+}
+
+/// Run `cargo rustc` to print the expanded source of a single target.
+fn expand_target(package: &Package, target: &Target, target_dir: &Path) -> Result<String> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut cmd = Command::new(cargo);
+    cmd.arg("rustc")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(&package.manifest_path)
+        .arg("--profile=check")
+        // Keep the expansion's build artifacts apart from regular builds.
+        .arg("--target-dir")
+        .arg(target_dir.join("context-lint").join("build"));
+    if target.is_lib() {
+        cmd.arg("--lib");
+    } else {
+        cmd.arg("--bin").arg(&target.name);
+    }
+    // `-Zunpretty` is unstable; this is how `cargo expand` enables it on stable
+    // toolchains too.
+    cmd.args(["--", "-Zunpretty=expanded"])
+        .env("RUSTC_BOOTSTRAP", "1");
+
+    let output = cmd.output().context("Running cargo rustc")?;
+    if !output.status.success() {
+        bail!(
+            "cargo rustc failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    String::from_utf8(output.stdout).context("Expanded source is not UTF-8")
+}
@@ -0,0 +1,114 @@
+//! Stable fingerprints of findings.
+//!
+//! A fingerprint is a hash of the check's code, the file (relative to the
+//! workspace root, with `/` separators) and what the finding is about: the
+//! function name and context strings, or a style issue's message. Line
+//! numbers are left out, so the fingerprint of a finding survives edits
+//! elsewhere in the file, and so do suppressions, baselines and comparisons
+//! keyed by it. The hash is FNV-1a, which unlike std's `DefaultHasher` is the
+//! same across Rust versions and platforms.
+
+use crate::linter::Finding;
+use crate::report::strip_path;
+
+/// The fingerprint of a finding, given the prefix its path is shown without
+/// (the workspace root).
+pub fn of_finding(finding: &Finding, strip_prefix: Option<&str>) -> String {
+    let identity = match finding {
+        Finding::DoubleContext(issue) => vec![
+            &*issue.function_name,
+            issue.inner_context.as_deref().unwrap_or_default(),
+            &issue.outer_method,
+            issue.outer_context.as_deref().unwrap_or_default(),
+        ],
+        Finding::Unattributed(issue) => vec![&*issue.name],
+        Finding::Style(issue) => vec![issue.kind.name(), &issue.message],
+        Finding::BarePropagation(issue) => vec![issue.name.as_str()],
+        Finding::Plugin(issue) => vec![issue.plugin.as_str(), &issue.rule, &issue.message],
+        Finding::BannedContext(issue) => vec![issue.pattern.as_str(), &issue.context],
+    };
+    compute(
+        finding.lint().code(),
+        strip_path(finding.file(), strip_prefix),
+        &identity,
+    )
+}
+
+/// The fingerprint of a finding of the check `code` in `file`, identified by
+/// `identity`: 16 hex digits.
+pub fn compute(code: &str, file: &str, identity: &[&str]) -> String {
+    let file = file.replace('\\', "/");
+    let mut hash = Fnv1a::default();
+    hash.write(code);
+    hash.write(&file);
+    for part in identity {
+        hash.write(part);
+    }
+    format!("{:016x}", hash.0)
+}
+
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    /// Hash `text`, followed by a separator so that `("ab", "c")` and
+    /// `("a", "bc")` differ.
+    fn write(&mut self, text: &str) {
+        for byte in text.bytes().chain([0x1f]) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unattributed::UnattributedFunction;
+
+    #[test]
+    fn test_compute() {
+        // Fixed, so fingerprints stored in baselines stay valid.
+        assert_eq!(
+            compute("CL0002", "src/lib.rs", &["parse"]),
+            "609118771fd2cb4b"
+        );
+        assert_eq!(
+            compute("CL0002", "src\\lib.rs", &["parse"]),
+            compute("CL0002", "src/lib.rs", &["parse"])
+        );
+        assert_ne!(
+            compute("CL0001", "src/lib.rs", &["ab", "c"]),
+            compute("CL0001", "src/lib.rs", &["a", "bc"])
+        );
+    }
+
+    #[test]
+    fn test_of_finding() {
+        let mut issue = UnattributedFunction {
+            file: "/ws/src/lib.rs".into(),
+            line: 3,
+            name: "parse".into(),
+            is_pub: true,
+            ..Default::default()
+        };
+        let fingerprint = of_finding(&Finding::Unattributed(&issue), Some("/ws/"));
+        assert_eq!(fingerprint, compute("CL0002", "src/lib.rs", &["parse"]));
+
+        issue.line = 30;
+        assert_eq!(
+            of_finding(&Finding::Unattributed(&issue), Some("/ws/")),
+            fingerprint
+        );
+        issue.name = "load".into();
+        assert_ne!(
+            of_finding(&Finding::Unattributed(&issue), Some("/ws/")),
+            fingerprint
+        );
+    }
+}
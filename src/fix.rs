@@ -0,0 +1,326 @@
+//! Fix engine shared by `--fix` and editor integrations.
+//!
+//! Every fix is expressed as byte-range replacements against the original file
+//! contents. `--fix` applies these edits in place, while `--format lsp` converts the
+//! very same edits into an LSP `WorkspaceEdit`, so CLI and editor fixes can never
+//! disagree.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A single replacement of a byte range in a source file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TextEdit {
+    /// Byte offset where the replaced range starts.
+    pub start: usize,
+    /// Byte offset where the replaced range ends (exclusive).
+    pub end: usize,
+    /// The text inserted in place of the range.
+    pub replacement: String,
+}
+
+/// A suggested fix for a finding.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    /// Short description of what the fix does.
+    pub description: String,
+    /// The edits making up the fix, all in the finding's file.
+    pub edits: Vec<TextEdit>,
+}
+
+/// Edits to apply, grouped per file and sorted by position.
+pub type FixPlan = BTreeMap<String, Vec<TextEdit>>;
+
+/// Collect fixes into a per-file plan.
+///
+/// Identical edits are merged. An edit overlapping an earlier one is dropped,
+/// so a plan can always be applied in a single pass.
+pub fn plan<'a>(fixes: impl IntoIterator<Item = (&'a str, &'a Fix)>) -> FixPlan {
+    let mut plan = FixPlan::new();
+    for (file, fix) in fixes {
+        plan.entry(file.to_string())
+            .or_default()
+            .extend(fix.edits.iter().cloned());
+    }
+
+    for edits in plan.values_mut() {
+        edits.sort();
+        edits.dedup();
+        let mut last_end = 0;
+        edits.retain(|edit| {
+            if edit.start < last_end {
+                return false;
+            }
+            last_end = edit.end;
+            true
+        });
+    }
+
+    plan
+}
+
+/// Whether applying the plan resolves `fix`: each of its edits is either part of
+/// the plan or overlaps a planned edit that rewrites the same code.
+pub fn is_resolved(plan: &FixPlan, file: &str, fix: &Fix) -> bool {
+    plan.get(file).is_some_and(|planned| {
+        fix.edits.iter().all(|edit| {
+            planned
+                .iter()
+                .any(|p| p == edit || (p.start < edit.end && edit.start < p.end))
+        })
+    })
+}
+
+/// Apply sorted, non-overlapping edits to a source string.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> Result<String> {
+    let mut output = String::with_capacity(source.len());
+    let mut pos = 0;
+    for edit in edits {
+        if edit.start < pos || edit.end < edit.start || edit.end > source.len() {
+            bail!("Invalid edit range {}..{}", edit.start, edit.end);
+        }
+        if !source.is_char_boundary(edit.start) || !source.is_char_boundary(edit.end) {
+            bail!("Edit range {}..{} splits a character", edit.start, edit.end);
+        }
+        output.push_str(&source[pos..edit.start]);
+        output.push_str(&edit.replacement);
+        pos = edit.end;
+    }
+    output.push_str(&source[pos..]);
+    Ok(output)
+}
+
+/// Apply a fix plan to the files on disk. Returns the number of edits applied.
+pub fn apply_plan(plan: &FixPlan) -> Result<usize> {
+    let mut applied = 0;
+    for (file, edits) in plan {
+        let path = Path::new(file);
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        let fixed =
+            apply_edits(&source, edits).with_context(|| format!("Fixing {}", path.display()))?;
+        std::fs::write(path, fixed).with_context(|| format!("Writing {}", path.display()))?;
+        applied += edits.len();
+    }
+    Ok(applied)
+}
+
+/// Number of leading bytes `syn::parse_file` skips (a byte order mark and/or a
+/// shebang line). Span byte offsets are relative to the remaining text.
+pub fn syn_offset(source: &str) -> usize {
+    let bom = if source.starts_with('\u{feff}') { 3 } else { 0 };
+    let content = &source[bom..];
+    if content.starts_with("#!") && !content[2..].trim_start().starts_with('[') {
+        return bom + content.find('\n').unwrap_or(content.len());
+    }
+    bom
+}
+
+// ── LSP ─────────────────────────────────────────────────────────────────
+
+/// An LSP `WorkspaceEdit` using the `changes` map form.
+#[derive(Debug, Serialize)]
+pub struct WorkspaceEdit {
+    pub changes: BTreeMap<String, Vec<LspTextEdit>>,
+}
+
+/// An LSP `TextEdit`.
+#[derive(Debug, Serialize)]
+pub struct LspTextEdit {
+    pub range: LspRange,
+    #[serde(rename = "newText")]
+    pub new_text: String,
+}
+
+/// An LSP `Range`.
+#[derive(Debug, Serialize)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+/// An LSP `Position`: zero-based line and UTF-16 code unit offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// Convert a fix plan into an LSP `WorkspaceEdit`, reading each file to compute
+/// line/character positions.
+pub fn workspace_edit(plan: &FixPlan) -> Result<WorkspaceEdit> {
+    let mut changes = BTreeMap::new();
+    for (file, edits) in plan {
+        let path = Path::new(file);
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        changes.insert(file_uri(path), lsp_edits(&source, edits));
+    }
+    Ok(WorkspaceEdit { changes })
+}
+
+/// Convert byte-range edits into LSP text edits against `source`.
+pub fn lsp_edits(source: &str, edits: &[TextEdit]) -> Vec<LspTextEdit> {
+    edits
+        .iter()
+        .map(|edit| LspTextEdit {
+            range: LspRange {
+                start: lsp_position(source, edit.start),
+                end: lsp_position(source, edit.end),
+            },
+            new_text: edit.replacement.clone(),
+        })
+        .collect()
+}
+
+/// Compute the LSP position of a byte offset.
+pub fn lsp_position(source: &str, offset: usize) -> LspPosition {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    LspPosition {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// The `file://` URI of a path, made absolute. Every byte but `/` and the
+/// unreserved characters of RFC 3986 is percent-encoded.
+fn file_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'/' | b'-' | b'.' | b'_' | b'~') {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: usize, end: usize, replacement: &str) -> TextEdit {
+        TextEdit {
+            start,
+            end,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    fn fix(edits: Vec<TextEdit>) -> Fix {
+        Fix {
+            description: "test".to_string(),
+            edits,
+        }
+    }
+
+    /// Apply LSP edits by converting positions back to byte offsets, the way an
+    /// editor would.
+    fn apply_lsp_edits(source: &str, edits: &[LspTextEdit]) -> String {
+        let offset = |pos: &LspPosition| {
+            let line_start: usize = source
+                .split_inclusive('\n')
+                .take(pos.line as usize)
+                .map(str::len)
+                .sum();
+            let mut units = 0;
+            for (i, c) in source[line_start..].char_indices() {
+                if units == pos.character as usize {
+                    return line_start + i;
+                }
+                units += c.len_utf16();
+            }
+            source.len()
+        };
+        let byte_edits: Vec<TextEdit> = edits
+            .iter()
+            .map(|e| edit(offset(&e.range.start), offset(&e.range.end), &e.new_text))
+            .collect();
+        apply_edits(source, &byte_edits).unwrap()
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let source = "let x = load().context(\"x\")?;";
+        let fixed = apply_edits(source, &[edit(14, 27, "")]).unwrap();
+        assert_eq!(fixed, "let x = load()?;");
+    }
+
+    #[test]
+    fn test_apply_edits_rejects_bad_range() {
+        assert!(apply_edits("abc", &[edit(2, 5, "")]).is_err());
+        assert!(apply_edits("é", &[edit(1, 2, "")]).is_err());
+    }
+
+    #[test]
+    fn test_plan_dedups_and_drops_overlaps() {
+        let a = fix(vec![edit(10, 20, "")]);
+        let b = fix(vec![edit(10, 20, "")]);
+        let c = fix(vec![edit(15, 25, "x")]);
+        let d = fix(vec![edit(30, 31, "y")]);
+        let plan = plan([("f.rs", &a), ("f.rs", &b), ("f.rs", &c), ("f.rs", &d)]);
+        assert_eq!(plan["f.rs"], vec![edit(10, 20, ""), edit(30, 31, "y")]);
+    }
+
+    #[test]
+    fn test_is_resolved() {
+        let outer = fix(vec![edit(10, 40, "")]);
+        let inner = fix(vec![edit(20, 30, "x")]);
+        let other = fix(vec![edit(50, 60, "y")]);
+        let plan = plan([("f.rs", &outer), ("f.rs", &inner)]);
+        assert!(is_resolved(&plan, "f.rs", &outer));
+        assert!(is_resolved(&plan, "f.rs", &inner));
+        assert!(!is_resolved(&plan, "f.rs", &other));
+        assert!(!is_resolved(&plan, "g.rs", &outer));
+    }
+
+    #[test]
+    fn test_syn_offset() {
+        assert_eq!(syn_offset("fn main() {}"), 0);
+        assert_eq!(syn_offset("\u{feff}fn main() {}"), 3);
+        assert_eq!(
+            syn_offset("#!/usr/bin/env run-cargo-script\nfn main() {}"),
+            31
+        );
+        assert_eq!(syn_offset("#![allow(unused)]\nfn main() {}"), 0);
+    }
+
+    #[test]
+    fn test_lsp_position_utf16() {
+        let source = "a\né𝄞x";
+        assert_eq!(
+            lsp_position(source, source.find('x').unwrap()),
+            LspPosition {
+                line: 1,
+                character: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(
+            file_uri(Path::new("/src/my crate/50%#1?/é.rs")),
+            "file:///src/my%20crate/50%25%231%3F/%C3%A9.rs"
+        );
+        assert_eq!(file_uri(Path::new("/a-b/c_d.~rs")), "file:///a-b/c_d.~rs");
+    }
+
+    #[test]
+    fn test_cli_and_lsp_edits_identical() {
+        let source = "fn f() {\n    // ünïcödé 𝄞\n    let a = g()\n        .context(\"g\")?;\n}\n";
+        let start = source.find("\n        .context").unwrap();
+        let end = source.find("?;").unwrap();
+        let edits = vec![edit(start, end, "")];
+        let cli = apply_edits(source, &edits).unwrap();
+        let lsp = apply_lsp_edits(source, &lsp_edits(source, &edits));
+        assert_eq!(cli, lsp);
+        assert!(cli.contains("let a = g()?;"));
+    }
+}
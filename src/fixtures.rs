@@ -0,0 +1,83 @@
+//! Values shared by the tests of several modules.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::collector::AnnotatedFunction;
+use crate::package::Package;
+use crate::unattributed::UnattributedFunction;
+
+/// A function returning a `Result`, with `#[context(context)]` at `line` of
+/// `file`.
+pub fn annotated(name: &str, file: &str, line: usize, context: &str) -> AnnotatedFunction {
+    AnnotatedFunction {
+        name: name.into(),
+        file: file.into(),
+        line,
+        context_string: Some(context.to_string()),
+        returns_result: true,
+        ..Default::default()
+    }
+}
+
+/// A package without targets in `dir`, depending on `fn-error-context`.
+pub fn package(name: &str, dir: &str) -> Package {
+    Package {
+        name: name.to_string(),
+        manifest_path: PathBuf::from(dir).join("Cargo.toml"),
+        dir: PathBuf::from(dir),
+        has_fn_error_context: true,
+        ..Default::default()
+    }
+}
+
+/// A private function `f` at line 1 of `file` without `#[context]`.
+pub fn unattributed(file: &str) -> UnattributedFunction {
+    UnattributedFunction {
+        file: file.into(),
+        line: 1,
+        name: "f".into(),
+        ..Default::default()
+    }
+}
+
+/// An empty directory under the system's temporary directory, removed with
+/// everything in it when dropped, even if the test fails.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a directory unique to the test process, named after `name`.
+    pub fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "context-lint-{name}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
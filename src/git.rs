@@ -0,0 +1,155 @@
+//! Asking git which files changed, for `--changed-since` and `hook`, and
+//! what the code looked like at the merge base, for `--deny-new`.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+/// The files that differ from `base` in the working tree of the repository
+/// containing `dir`, including untracked files, as absolute paths.
+pub fn changed_files(dir: &Path, base: &str) -> Result<HashSet<PathBuf>> {
+    let toplevel = toplevel(dir)?;
+
+    // Deleted files have nothing left to check.
+    let diff = git(
+        dir,
+        &[
+            "diff",
+            "--name-only",
+            "--no-renames",
+            "--diff-filter=d",
+            base,
+            "--",
+        ],
+    )
+    .with_context(|| format!("Listing files changed since `{base}`"))?;
+    let untracked = git(
+        dir,
+        &["ls-files", "--others", "--exclude-standard", "--full-name"],
+    )?;
+
+    let mut files = parse_name_list(&toplevel, &diff);
+    files.extend(parse_name_list(&toplevel, &untracked));
+    Ok(files)
+}
+
+/// The files added or modified in the index of the repository containing
+/// `dir`, as absolute paths.
+pub fn staged_files(dir: &Path) -> Result<HashSet<PathBuf>> {
+    let toplevel = toplevel(dir)?;
+    let diff = git(
+        dir,
+        &[
+            "diff",
+            "--cached",
+            "--name-only",
+            "--no-renames",
+            "--diff-filter=d",
+            "--",
+        ],
+    )
+    .context("Listing staged files")?;
+    Ok(parse_name_list(&toplevel, &diff))
+}
+
+/// The root of the working tree of the repository containing `dir`.
+pub fn toplevel(dir: &Path) -> Result<PathBuf> {
+    let toplevel = git(dir, &["rev-parse", "--show-toplevel"])?;
+    Ok(PathBuf::from(toplevel.trim_end()))
+}
+
+/// The commit where `HEAD` and `base` diverged, in the repository containing
+/// `dir`.
+pub fn merge_base(dir: &Path, base: &str) -> Result<String> {
+    let commit = git(dir, &["merge-base", "HEAD", base])
+        .with_context(|| format!("Finding the merge base of HEAD and `{base}`"))?;
+    Ok(commit.trim_end().to_string())
+}
+
+/// Write the files of `commit` to `dest`, leaving the working tree, the
+/// index and `HEAD` alone. Commits never change, so if `dest` exists it is
+/// assumed to hold them from an earlier run.
+pub fn export_tree(dir: &Path, commit: &str, dest: &Path) -> Result<()> {
+    if dest.exists() {
+        return Ok(());
+    }
+    // Check out into a temporary directory with a temporary index, so that
+    // an interrupted export isn't mistaken for a complete one.
+    let partial = dest.with_extension("partial");
+    let index = dest.with_extension("index");
+    let _ = std::fs::remove_dir_all(&partial);
+    let _ = std::fs::remove_file(&index);
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Creating {}", parent.display()))?;
+    }
+    let prefix = format!("--prefix={}/", partial.display());
+    for args in [
+        &["read-tree", commit][..],
+        &["checkout-index", "--all", &prefix][..],
+    ] {
+        run(Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .env("GIT_INDEX_FILE", &index)
+            .args(args))
+        .with_context(|| format!("Exporting {commit} to {}", dest.display()))?;
+    }
+    let _ = std::fs::remove_file(&index);
+    std::fs::rename(&partial, dest)
+        .with_context(|| format!("Renaming {} to {}", partial.display(), dest.display()))
+}
+
+/// The directory git runs the hooks of the repository containing `dir` from.
+pub fn hooks_dir(dir: &Path) -> Result<PathBuf> {
+    let path = git(dir, &["rev-parse", "--git-path", "hooks"])?;
+    Ok(dir.join(path.trim_end()))
+}
+
+/// Parse the output of `git diff --name-only` and the like: one path per
+/// line, relative to the repository root.
+fn parse_name_list(toplevel: &Path, output: &str) -> HashSet<PathBuf> {
+    output
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| toplevel.join(line))
+        .collect()
+}
+
+/// Run a git command in `dir` and return its standard output.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    run(Command::new("git").arg("-C").arg(dir).args(args))
+}
+
+/// Run a git command, given `-C dir` first, and return its standard output.
+fn run(command: &mut Command) -> Result<String> {
+    let output = command.output().context("Running git")?;
+    if !output.status.success() {
+        let args: Vec<_> = command
+            .get_args()
+            .skip(2)
+            .map(|arg| arg.to_string_lossy())
+            .collect();
+        bail!(
+            "git {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    String::from_utf8(output.stdout).context("git output is not UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_list() {
+        let files = parse_name_list(Path::new("/repo"), "src/main.rs\ncrates/a/src/lib.rs\n\n");
+        assert_eq!(files.len(), 2);
+        assert!(files.contains(Path::new("/repo/src/main.rs")));
+        assert!(files.contains(Path::new("/repo/crates/a/src/lib.rs")));
+    }
+}
@@ -0,0 +1,119 @@
+//! Minimal glob patterns for package names and paths.
+//!
+//! `*` matches any sequence of characters except `/`, `**` also matches `/`
+//! (so `src/**/gen.rs` matches `src/gen.rs` and `src/a/b/gen.rs`), and `?`
+//! matches a single character other than `/`. Everything else matches
+//! literally.
+
+/// A glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: Vec<char>,
+}
+
+impl Glob {
+    /// Compile a pattern.
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.chars().collect(),
+        }
+    }
+
+    /// Whether the pattern matches all of `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        match_from(&self.pattern, &text)
+    }
+}
+
+/// Include and exclude patterns for the files below a directory.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    /// If not empty, only paths matching one of these are accepted.
+    pub include: Vec<Glob>,
+    /// Paths matching one of these are rejected.
+    pub exclude: Vec<Glob>,
+    /// Names of directories skipped wherever they are, e.g. `vendor`.
+    pub skip_dirs: Vec<String>,
+}
+
+impl PathFilter {
+    /// Build a filter from pattern strings.
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        Self {
+            include: include.iter().map(|p| Glob::new(p)).collect(),
+            exclude: exclude.iter().map(|p| Glob::new(p)).collect(),
+            skip_dirs: Vec::new(),
+        }
+    }
+
+    /// Whether the contents of a directory with this name are skipped.
+    pub fn skips_dir(&self, name: &str) -> bool {
+        self.skip_dirs.iter().any(|dir| dir == name)
+    }
+
+    /// Whether a relative path (with `/` separators) passes the filter.
+    pub fn accepts(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(path)))
+            && !self.exclude.iter().any(|glob| glob.is_match(path))
+    }
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directory at all.
+            if let ['/', after @ ..] = rest {
+                if match_from(after, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|i| match_from(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| match_from(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(c) if *c != '/') && match_from(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && match_from(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert!(Glob::new("proto-*").is_match("proto-gen"));
+        assert!(Glob::new("*-sys").is_match("openssl-sys"));
+        assert!(Glob::new("exact").is_match("exact"));
+        assert!(!Glob::new("exact").is_match("exactly"));
+        assert!(Glob::new("v?").is_match("v1"));
+        assert!(!Glob::new("v?").is_match("v10"));
+    }
+
+    #[test]
+    fn test_paths() {
+        assert!(Glob::new("src/**").is_match("src/a/b.rs"));
+        assert!(Glob::new("src/*.rs").is_match("src/main.rs"));
+        assert!(!Glob::new("src/*.rs").is_match("src/a/b.rs"));
+        assert!(Glob::new("src/**/gen.rs").is_match("src/gen.rs"));
+        assert!(Glob::new("src/**/gen.rs").is_match("src/a/b/gen.rs"));
+        assert!(Glob::new("**/generated/**").is_match("crates/x/src/generated/a.rs"));
+        assert!(!Glob::new("src/generated/**").is_match("src/other/a.rs"));
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let filter = PathFilter::default();
+        assert!(filter.accepts("build.rs"));
+
+        let filter = PathFilter::new(&["src/**".to_string()], &["src/generated/**".to_string()]);
+        assert!(filter.accepts("src/main.rs"));
+        assert!(!filter.accepts("build.rs"));
+        assert!(!filter.accepts("src/generated/proto.rs"));
+    }
+}
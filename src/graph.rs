@@ -0,0 +1,650 @@
+//! Export the call graph of annotated functions, for `--emit-graph`.
+//!
+//! The graph has an edge for every call into a function annotated with
+//! `#[context]`, from the function containing the call. Edges of calls that
+//! add context of their own (`.context(..)` and friends) carry that context,
+//! so following a path through the graph shows how the layers of an error
+//! chain will stack up.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use syn::visit::Visit;
+use syn::{Expr, ExprCall, ExprMethodCall, ImplItemFn, ItemFn, ItemImpl, Signature};
+
+use crate::checker::{annotated_callees, context_arg, unwraps_result};
+use crate::collector::{type_name, AnnotatedFunction, AnnotatedFunctions};
+use crate::config::ContextMethodsConfig;
+use crate::parse::ParsedFile;
+
+/// Calls into annotated functions, between the functions of a workspace.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CallGraph {
+    /// The annotated functions of the workspace (and of dependencies, if
+    /// called), and the functions calling them.
+    pub nodes: Vec<Node>,
+    /// Calls into annotated functions.
+    pub edges: Vec<Edge>,
+}
+
+/// A function in the call graph.
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    /// Position of the node in [`CallGraph::nodes`].
+    pub id: usize,
+    /// The function name, prefixed with the `impl` type for methods.
+    pub name: String,
+    /// File where the function is defined.
+    pub file: String,
+    /// Line of the function definition.
+    pub line: usize,
+    /// The context string of the `#[context]` attribute, if any.
+    pub context: Option<String>,
+}
+
+/// A call into an annotated function.
+#[derive(Debug, Clone, Serialize)]
+pub struct Edge {
+    /// The calling function.
+    pub from: usize,
+    /// The annotated function called.
+    pub to: usize,
+    /// File of the call site.
+    pub file: String,
+    /// Line of the call site.
+    pub line: usize,
+    /// The method adding context to the call's result, if any.
+    pub method: Option<String>,
+    /// The context string added at the call site, if it can be extracted.
+    pub context: Option<String>,
+}
+
+/// Build the call graph of the given files.
+pub fn build(
+    files: &[ParsedFile],
+    index: &AnnotatedFunctions,
+    methods: &ContextMethodsConfig,
+) -> CallGraph {
+    let mut builder = GraphBuilder {
+        file_path: String::new(),
+        index,
+        methods,
+        self_type: None,
+        caller: None,
+        node_ids: HashMap::new(),
+        graph: CallGraph::default(),
+    };
+    for file in files {
+        let Some(syntax) = &file.syntax else {
+            continue;
+        };
+        builder.file_path = file.display_path().to_string();
+        builder.visit_file(syntax);
+    }
+    builder.graph
+}
+
+/// Write the graph to `path`, as JSON if it ends with `.json` and in
+/// Graphviz's DOT language if it ends with `.dot` or `.gv`.
+pub fn write(path: &Path, graph: &CallGraph) -> Result<()> {
+    let contents = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::to_string_pretty(graph)?,
+        Some("dot" | "gv") => to_dot(graph),
+        _ => bail!(
+            "Unknown graph format for {}, expected a .dot or .json file",
+            path.display()
+        ),
+    };
+    std::fs::write(path, contents).with_context(|| format!("Writing {}", path.display()))
+}
+
+/// Render the graph in Graphviz's DOT language. Annotated functions are
+/// boxes labeled with their context; calls adding a second layer of context
+/// are red.
+pub fn to_dot(graph: &CallGraph) -> String {
+    let mut dot = String::from("digraph context {\n    node [fontname=\"monospace\"];\n");
+    for node in &graph.nodes {
+        let (shape, label) = match &node.context {
+            Some(context) => ("box", format!("{}\n#[context(\"{context}\")]", node.name)),
+            None => ("ellipse", node.name.clone()),
+        };
+        let _ = writeln!(
+            dot,
+            "    n{} [shape={shape}, label=\"{}\", tooltip=\"{}:{}\"];",
+            node.id,
+            escape(&label),
+            escape(&node.file),
+            node.line
+        );
+    }
+    for edge in &graph.edges {
+        let mut attrs = format!("tooltip=\"{}:{}\"", escape(&edge.file), edge.line);
+        if let Some(method) = &edge.method {
+            let label = match &edge.context {
+                Some(context) => format!(".{method}(\"{context}\")"),
+                None => format!(".{method}(..)"),
+            };
+            let _ = write!(attrs, ", color=red, label=\"{}\"", escape(&label));
+        }
+        let _ = writeln!(dot, "    n{} -> n{} [{attrs}];", edge.from, edge.to);
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The error chains a failure in a function can produce: one per path of
+/// calls leading to it, following callers as long as they are annotated
+/// (the graph has no edges into other functions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    /// The function failing.
+    pub node: usize,
+    /// The calls leading to it, innermost first.
+    pub edges: Vec<usize>,
+}
+
+/// At most this many traces are returned by [`traces`], since the number of
+/// paths can grow exponentially.
+const MAX_TRACES: usize = 50;
+
+/// Find the function a `--trace-context` target refers to: a `file:line`
+/// within the function (the file may be given relative to the workspace
+/// root), or a function name, optionally prefixed with its `impl` type.
+pub fn find_node(graph: &CallGraph, target: &str) -> Result<usize> {
+    let location = target
+        .rsplit_once(':')
+        .and_then(|(file, line)| Some((Path::new(file), line.parse::<usize>().ok()?)));
+    let found = match location {
+        Some((file, line)) => graph
+            .nodes
+            .iter()
+            .filter(|node| Path::new(&node.file).ends_with(file) && node.line <= line)
+            .max_by_key(|node| node.line)
+            .map(|node| vec![node]),
+        None => Some(
+            graph
+                .nodes
+                .iter()
+                .filter(|node| {
+                    node.name == target
+                        || node
+                            .name
+                            .rsplit_once("::")
+                            .is_some_and(|(_, name)| name == target)
+                })
+                .collect(),
+        ),
+    };
+    match found.as_deref() {
+        Some([node]) => Ok(node.id),
+        Some([]) | None => bail!(
+            "No annotated function or caller of one found for {target}; \
+             the call graph only has functions with #[context] and their callers"
+        ),
+        Some(nodes) => {
+            let locations: Vec<String> = nodes
+                .iter()
+                .map(|node| format!("{}:{}", node.file, node.line))
+                .collect();
+            bail!(
+                "{target} is ambiguous, give one of {} instead",
+                locations.join(", ")
+            )
+        }
+    }
+}
+
+/// The paths of calls leading to `node`, each ending at a caller without
+/// `#[context]` or without callers. Recursive calls are followed once.
+pub fn traces(graph: &CallGraph, node: usize) -> Vec<Trace> {
+    let mut traces = Vec::new();
+    let mut path = Vec::new();
+    collect_traces(graph, node, node, &mut path, &mut traces);
+    traces
+}
+
+fn collect_traces(
+    graph: &CallGraph,
+    start: usize,
+    node: usize,
+    path: &mut Vec<usize>,
+    traces: &mut Vec<Trace>,
+) {
+    if traces.len() >= MAX_TRACES {
+        return;
+    }
+    let visited = |id: usize| id == start || path.iter().any(|&edge| graph.edges[edge].from == id);
+    let callers: Vec<usize> = match graph.nodes[node].context {
+        // Unannotated callers have no incoming edges anyway.
+        Some(_) => (0..graph.edges.len())
+            .filter(|&edge| graph.edges[edge].to == node && !visited(graph.edges[edge].from))
+            .collect(),
+        None => Vec::new(),
+    };
+    if callers.is_empty() {
+        traces.push(Trace {
+            node: start,
+            edges: path.clone(),
+        });
+        return;
+    }
+    for edge in callers {
+        path.push(edge);
+        collect_traces(graph, start, graph.edges[edge].from, path, traces);
+        path.pop();
+    }
+}
+
+/// Format traces like anyhow prints an error with `{:?}`, outermost context
+/// first, each layer followed by where it's added.
+pub fn format_traces(graph: &CallGraph, traces: &[Trace], prefix: Option<&str>) -> String {
+    let location = |file: &str, line: usize| {
+        let file = prefix
+            .and_then(|prefix| file.strip_prefix(prefix))
+            .unwrap_or(file);
+        format!("{file}:{line}")
+    };
+    let mut out = String::new();
+    for (i, trace) in traces.iter().enumerate() {
+        let failing = &graph.nodes[trace.node];
+        // Innermost layer first, reversed below.
+        let mut layers = vec![(
+            format!("<error in {}>", failing.name),
+            location(&failing.file, failing.line),
+        )];
+        let annotation = |node: &Node| {
+            node.context.as_ref().map(|context| {
+                (
+                    context.clone(),
+                    format!(
+                        "#[context] on {}, {}",
+                        node.name,
+                        location(&node.file, node.line)
+                    ),
+                )
+            })
+        };
+        layers.extend(annotation(failing));
+        let mut path = vec![failing.name.as_str()];
+        for &edge in &trace.edges {
+            let edge = &graph.edges[edge];
+            let caller = &graph.nodes[edge.from];
+            if let Some(method) = &edge.method {
+                let context = edge
+                    .context
+                    .clone()
+                    .unwrap_or_else(|| format!(".{method}(..)"));
+                layers.push((
+                    context,
+                    format!(
+                        ".{method}() in {}, {}",
+                        caller.name,
+                        location(&edge.file, edge.line)
+                    ),
+                ));
+            }
+            layers.extend(annotation(caller));
+            path.push(caller.name.as_str());
+        }
+        layers.reverse();
+
+        if i > 0 {
+            out.push('\n');
+        }
+        path.reverse();
+        let _ = writeln!(out, "Call path: {}\n", path.join(" -> "));
+        let width = layers.iter().map(|(text, _)| text.len()).max().unwrap_or(0);
+        let mut previous: Option<&str> = None;
+        for (depth, (text, source)) in layers.iter().enumerate() {
+            let repeated = previous.is_some_and(|prev| prev.eq_ignore_ascii_case(text));
+            let note = if repeated {
+                " (repeats the layer above)"
+            } else {
+                ""
+            };
+            let label = match depth {
+                0 => "Error: ".to_string(),
+                _ => format!("    {}: ", depth - 1),
+            };
+            if depth == 1 {
+                out.push_str("\nCaused by:\n");
+            }
+            let _ = writeln!(out, "{label}{text:width$}  [{source}]{note}");
+            previous = Some(text);
+        }
+    }
+    out
+}
+
+/// The context an annotated function adds, `..` if unknown (for functions
+/// selected by a `[[rules]]` entry, or attributes without a message).
+fn annotation_context(annotated: &AnnotatedFunction) -> String {
+    annotated
+        .context_string
+        .clone()
+        .unwrap_or_else(|| "..".to_string())
+}
+
+#[derive(Clone)]
+struct Caller {
+    name: String,
+    line: usize,
+    context: Option<String>,
+}
+
+struct GraphBuilder<'a> {
+    file_path: String,
+    index: &'a AnnotatedFunctions,
+    methods: &'a ContextMethodsConfig,
+    /// The type of the enclosing `impl` block.
+    self_type: Option<String>,
+    /// The enclosing function, which becomes a node once it calls an
+    /// annotated function.
+    caller: Option<Caller>,
+    /// Nodes by file and line of the function definition.
+    node_ids: HashMap<(String, usize), usize>,
+    graph: CallGraph,
+}
+
+impl GraphBuilder<'_> {
+    fn node(&mut self, name: String, file: &str, line: usize, context: Option<&str>) -> usize {
+        let key = (file.to_string(), line);
+        if let Some(&id) = self.node_ids.get(&key) {
+            return id;
+        }
+        let id = self.graph.nodes.len();
+        self.graph.nodes.push(Node {
+            id,
+            name,
+            file: file.to_string(),
+            line,
+            context: context.map(str::to_string),
+        });
+        self.node_ids.insert(key, id);
+        id
+    }
+
+    fn annotated_node(&mut self, annotated: &AnnotatedFunction) -> usize {
+        let name = match &annotated.self_type {
+            Some(ty) => format!("{ty}::{}", annotated.name),
+            None => annotated.name.to_string(),
+        };
+        self.node(
+            name,
+            &annotated.file,
+            annotated.line,
+            Some(&annotation_context(annotated)),
+        )
+    }
+
+    /// Make the function with the given signature the caller of the calls
+    /// visited next, returning the previous caller. Annotated functions
+    /// become nodes right away, callers only once they call one.
+    fn enter_fn(&mut self, sig: &Signature) -> Option<Caller> {
+        let line = sig.ident.span().start().line;
+        let name = sig.ident.to_string();
+        let annotated = self.index.definition(&self.file_path, sig);
+        if let Some(annotated) = annotated {
+            self.annotated_node(annotated);
+        }
+        let context = annotated.map(annotation_context);
+        let name = match &self.self_type {
+            Some(ty) => format!("{ty}::{name}"),
+            None => name,
+        };
+        self.caller.replace(Caller {
+            name,
+            line,
+            context,
+        })
+    }
+
+    /// Add edges from the enclosing function to the annotated functions
+    /// `expr` may call. A call already recorded with context on the same
+    /// line is not added again.
+    fn add_calls(&mut self, expr: &Expr, context_call: Option<&ExprMethodCall>) {
+        let callees = annotated_callees(expr, self.index);
+        if callees.is_empty() {
+            return;
+        }
+        let Some(caller) = self.caller.clone() else {
+            return;
+        };
+        let file = self.file_path.clone();
+        let from = self.node(caller.name, &file, caller.line, caller.context.as_deref());
+        let line = syn::spanned::Spanned::span(expr).start().line;
+        for annotated in callees {
+            let to = self.annotated_node(annotated);
+            let known = self.graph.edges.iter().any(|edge| {
+                edge.from == from
+                    && edge.to == to
+                    && edge.file == self.file_path
+                    && edge.line == line
+            });
+            if known {
+                continue;
+            }
+            self.graph.edges.push(Edge {
+                from,
+                to,
+                file: self.file_path.clone(),
+                line,
+                method: context_call.map(|call| call.method.to_string()),
+                context: context_call.and_then(context_arg),
+            });
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for GraphBuilder<'_> {
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        let prev = std::mem::replace(&mut self.self_type, type_name(&node.self_ty));
+        syn::visit::visit_item_impl(self, node);
+        self.self_type = prev;
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        // Nested functions are not methods of the enclosing `impl`.
+        let self_type = self.self_type.take();
+        let prev = self.enter_fn(&node.sig);
+        syn::visit::visit_item_fn(self, node);
+        self.caller = prev;
+        self.self_type = self_type;
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        let prev = self.enter_fn(&node.sig);
+        syn::visit::visit_impl_item_fn(self, node);
+        self.caller = prev;
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        // `load()?.context(..)` adds context to what's inside the `Result`.
+        if self.methods.is_context_method(&node.method.to_string())
+            && !unwraps_result(&node.receiver)
+        {
+            self.add_calls(&node.receiver, Some(node));
+        } else {
+            self.add_calls(&Expr::MethodCall(node.clone()), None);
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        self.add_calls(&Expr::Call(node.clone()), None);
+        syn::visit::visit_expr_call(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(source: &str) -> CallGraph {
+        let file = ParsedFile::from_source(Path::new("test.rs"), source.to_string());
+        let collected = crate::collector::collect_from_file(&file, &[], &[], &[]);
+        let index = crate::collector::build_index(collected.functions);
+        build(&[file], &index, &ContextMethodsConfig::default())
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let graph = graph(
+            r#"
+            #[context("Reading {}", path.display())]
+            fn read_file(path: &Path) -> Result<String> {
+                Ok(std::fs::read_to_string(path)?)
+            }
+
+            #[context("Loading config")]
+            fn load_config() -> Result<Config> {
+                let text = read_file(Path::new("config.toml"))?;
+                parse(&text)
+            }
+
+            struct App;
+
+            impl App {
+                fn start(&self) -> Result<()> {
+                    let config = load_config().context("Starting app")?;
+                let user = find_user()?.context("No user")?;
+                    self.run(config)
+                }
+            }
+
+            #[context("Finding user")]
+            fn find_user() -> Result<Option<User>> {
+                todo!()
+            }
+
+            fn parse(text: &str) -> Result<Config> {
+                todo!()
+            }
+            "#,
+        );
+
+        let names: Vec<&str> = graph.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["read_file", "load_config", "App::start", "find_user"]
+        );
+        assert_eq!(graph.nodes[0].context.as_deref(), Some("Reading {}"));
+        assert_eq!(graph.nodes[2].context, None);
+
+        assert_eq!(graph.edges.len(), 3);
+        let (reading, starting) = (&graph.edges[0], &graph.edges[1]);
+        assert_eq!((reading.from, reading.to, reading.line), (1, 0, 9));
+        assert_eq!(reading.method, None);
+        assert_eq!((starting.from, starting.to, starting.line), (2, 1, 17));
+        assert_eq!(starting.method.as_deref(), Some("context"));
+        assert_eq!(starting.context.as_deref(), Some("Starting app"));
+        // The context goes on the `Option` inside the `Result`.
+        assert_eq!((graph.edges[2].from, graph.edges[2].to), (2, 3));
+        assert_eq!(graph.edges[2].method, None);
+    }
+
+    #[test]
+    fn test_dot() {
+        let graph = graph(
+            r#"
+            #[context("Loading \"config\"")]
+            fn load_config() -> Result<Config> {
+                todo!()
+            }
+
+            fn main() -> Result<()> {
+                load_config().with_context(|| "Starting")?;
+                Ok(())
+            }
+            "#,
+        );
+        let dot = to_dot(&graph);
+        assert!(dot.starts_with("digraph context {\n"));
+        assert!(dot.contains(
+            r#"n0 [shape=box, label="load_config\n#[context(\"Loading \"config\"\")]", tooltip="test.rs:3"];"#
+        ));
+        assert!(dot.contains(r#"n1 [shape=ellipse, label="main", tooltip="test.rs:7"];"#));
+        assert!(dot.contains(
+            r#"n1 -> n0 [tooltip="test.rs:8", color=red, label=".with_context(\"Starting\")"];"#
+        ));
+    }
+
+    #[test]
+    fn test_trace() {
+        let graph = graph(
+            r#"
+            #[context("Reading")]
+            fn read_file(path: &Path) -> Result<String> {
+                todo!()
+            }
+
+            #[context("Loading config")]
+            fn load_config() -> Result<Config> {
+                let text = read_file(path).context("Loading config")?;
+                todo!()
+            }
+
+            #[context("Retrying")]
+            fn retry() -> Result<Config> {
+                load_config().or_else(|_| retry())
+            }
+
+            mod app {
+                fn start() -> Result<()> {
+                    load_config().context("Starting")?;
+                    Ok(())
+                }
+            }
+
+            fn start() {}
+            "#,
+        );
+
+        let read_file = find_node(&graph, "read_file").unwrap();
+        assert_eq!(find_node(&graph, "test.rs:4").unwrap(), read_file);
+        assert!(find_node(&graph, "test.rs:1").is_err());
+        assert!(find_node(&graph, "unknown").is_err());
+
+        // `retry` calling itself is followed once.
+        let traces = traces(&graph, read_file);
+        let paths: Vec<Vec<&str>> = traces
+            .iter()
+            .map(|trace| {
+                trace
+                    .edges
+                    .iter()
+                    .map(|&edge| graph.nodes[graph.edges[edge].from].name.as_str())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [vec!["load_config", "retry"], vec!["load_config", "start"]]
+        );
+
+        let text = format_traces(&graph, &traces[1..], None);
+        assert_eq!(
+            text,
+            "\
+Call path: start -> load_config -> read_file
+
+Error: Starting              [.context() in start, test.rs:20]
+
+Caused by:
+    0: Loading config        [#[context] on load_config, test.rs:8]
+    1: Loading config        [.context() in load_config, test.rs:9] (repeats the layer above)
+    2: Reading               [#[context] on read_file, test.rs:3]
+    3: <error in read_file>  [test.rs:3]
+"
+        );
+    }
+}
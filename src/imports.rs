@@ -0,0 +1,267 @@
+//! Resolution of names brought into scope by `use` declarations.
+//!
+//! Only plain imports are resolved: glob imports can't be without knowing the
+//! contents of the imported module, and are collected separately for callers
+//! that know what to look for. Re-exports
+//! (`pub use`) are collected separately, globs included, so that the index of
+//! annotated functions can follow them.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use syn::{Item, UseTree, Visibility};
+
+/// Names imported by `use` declarations, mapped to the path they refer to.
+pub type Imports = HashMap<String, Vec<String>>;
+
+/// Collect the imports of a list of items (a file or an inline module).
+pub fn collect_imports(items: &[Item]) -> Imports {
+    let mut imports = Imports::new();
+    for item in items {
+        if let Item::Use(use_item) = item {
+            walk(&use_item.tree, &mut Vec::new(), &mut |name, full| {
+                imports.insert(name, full);
+            });
+        }
+    }
+    imports
+}
+
+/// Collect the paths of the glob imports (`use path::*`) of a list of items.
+pub fn collect_globs(items: &[Item]) -> Vec<Vec<String>> {
+    let mut globs = Vec::new();
+    for item in items {
+        if let Item::Use(use_item) = item {
+            walk_globs(&use_item.tree, &mut Vec::new(), &mut globs);
+        }
+    }
+    globs.into_iter().map(|glob| glob.path).collect()
+}
+
+/// A name made visible elsewhere by a `pub use` declaration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReExport {
+    /// The name under which the item is re-exported, or `None` for a glob
+    /// re-export of everything in `path`.
+    pub name: Option<String>,
+    /// The re-exported path, as written.
+    pub path: Vec<String>,
+}
+
+/// Collect the re-exports (`pub use`, including `pub(crate) use`) of a list
+/// of items.
+pub fn collect_reexports(items: &[Item]) -> Vec<ReExport> {
+    let mut reexports = Vec::new();
+    for item in items {
+        if let Item::Use(use_item) = item {
+            if matches!(use_item.vis, Visibility::Inherited) {
+                continue;
+            }
+            walk(&use_item.tree, &mut Vec::new(), &mut |name, path| {
+                reexports.push(ReExport {
+                    name: Some(name),
+                    path,
+                });
+            });
+            walk_globs(&use_item.tree, &mut Vec::new(), &mut reexports);
+        }
+    }
+    reexports
+}
+
+fn walk(tree: &UseTree, prefix: &mut Vec<String>, out: &mut impl FnMut(String, Vec<String>)) {
+    match tree {
+        UseTree::Path(path) => {
+            prefix.push(path.ident.to_string());
+            walk(&path.tree, prefix, out);
+            prefix.pop();
+        }
+        // `use foo::{self}` imports `foo` itself.
+        UseTree::Name(name) if name.ident == "self" => {
+            if let Some(last) = prefix.last() {
+                out(last.clone(), prefix.clone());
+            }
+        }
+        UseTree::Name(name) => {
+            let mut full = prefix.clone();
+            full.push(name.ident.to_string());
+            out(name.ident.to_string(), full);
+        }
+        UseTree::Rename(rename) => {
+            let mut full = prefix.clone();
+            if rename.ident != "self" {
+                full.push(rename.ident.to_string());
+            }
+            out(rename.rename.to_string(), full);
+        }
+        UseTree::Glob(_) => {}
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                walk(tree, prefix, out);
+            }
+        }
+    }
+}
+
+fn walk_globs(tree: &UseTree, prefix: &mut Vec<String>, out: &mut Vec<ReExport>) {
+    match tree {
+        UseTree::Path(path) => {
+            prefix.push(path.ident.to_string());
+            walk_globs(&path.tree, prefix, out);
+            prefix.pop();
+        }
+        UseTree::Glob(_) => out.push(ReExport {
+            name: None,
+            path: prefix.clone(),
+        }),
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                walk_globs(tree, prefix, out);
+            }
+        }
+        UseTree::Name(_) | UseTree::Rename(_) => {}
+    }
+}
+
+/// Resolve a path through the imports: if its first segment was imported,
+/// replace it with the path it refers to.
+pub fn resolve(segments: &[String], imports: &Imports) -> Vec<String> {
+    match segments.split_first() {
+        Some((first, rest)) => match imports.get(first) {
+            Some(full) => full.iter().chain(rest).cloned().collect(),
+            None => segments.to_vec(),
+        },
+        None => Vec::new(),
+    }
+}
+
+/// The qualified paths (as used for index keys) that a path written in
+/// `module` may refer to, most likely first. `self_type` is the type of the
+/// enclosing `impl` block, for `Self::` paths.
+pub fn qualified_candidates(
+    segments: &[String],
+    module: &[String],
+    self_type: Option<&str>,
+) -> Vec<String> {
+    let Some((first, rest)) = segments.split_first() else {
+        return Vec::new();
+    };
+    let join = |base: &[String], rest: &[String]| -> String {
+        base.iter()
+            .chain(rest)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("::")
+    };
+
+    match first.as_str() {
+        "crate" => module
+            .first()
+            .map(|krate| join(std::slice::from_ref(krate), rest))
+            .into_iter()
+            .collect(),
+        "self" if !module.is_empty() => vec![join(module, rest)],
+        "super" => {
+            let supers = segments.iter().take_while(|s| *s == "super").count();
+            if supers >= module.len() {
+                return Vec::new();
+            }
+            vec![join(&module[..module.len() - supers], &segments[supers..])]
+        }
+        "Self" => match self_type {
+            Some(self_type) if !module.is_empty() => {
+                let mut base = module.to_vec();
+                base.push(self_type.to_string());
+                vec![join(&base, rest)]
+            }
+            _ => Vec::new(),
+        },
+        "self" => Vec::new(),
+        _ => {
+            let mut candidates = Vec::new();
+            // A local item, child module or type of the current module...
+            if !module.is_empty() {
+                candidates.push(join(module, segments));
+            }
+            // ...or another workspace crate.
+            if !rest.is_empty() {
+                candidates.push(segments.join("::"));
+            }
+            candidates
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imports(source: &str) -> Imports {
+        collect_imports(&syn::parse_file(source).unwrap().items)
+    }
+
+    fn segments(path: &str) -> Vec<String> {
+        path.split("::").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_collect_imports() {
+        let imports = imports(
+            r#"
+            use crate::pod::storage;
+            use anyhow::{Context, Result as AnyResult};
+            use std::io::{self, Write};
+            use super::*;
+            "#,
+        );
+        assert_eq!(imports["storage"], segments("crate::pod::storage"));
+        assert_eq!(imports["AnyResult"], segments("anyhow::Result"));
+        assert_eq!(imports["Context"], segments("anyhow::Context"));
+        assert_eq!(imports["io"], segments("std::io"));
+        assert_eq!(imports["Write"], segments("std::io::Write"));
+        assert_eq!(imports.len(), 5);
+    }
+
+    #[test]
+    fn test_collect_reexports() {
+        let items = syn::parse_file(
+            r#"
+            use crate::private::hidden;
+            pub use crate::internal::config::load_config;
+            pub(crate) use self::storage::{open as open_storage, Storage};
+            pub use crate::prelude::*;
+            "#,
+        )
+        .unwrap()
+        .items;
+        let named = |name: &str, path: &str| ReExport {
+            name: Some(name.to_string()),
+            path: segments(path),
+        };
+        assert_eq!(
+            collect_reexports(&items),
+            [
+                named("load_config", "crate::internal::config::load_config"),
+                named("open_storage", "self::storage::open"),
+                named("Storage", "self::storage::Storage"),
+                ReExport {
+                    name: None,
+                    path: segments("crate::prelude"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let imports = imports("use crate::pod::storage;");
+        assert_eq!(
+            resolve(&segments("storage::open"), &imports),
+            segments("crate::pod::storage::open")
+        );
+        assert_eq!(
+            resolve(&segments("other::open"), &imports),
+            segments("other::open")
+        );
+    }
+}
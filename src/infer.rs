@@ -0,0 +1,309 @@
+//! Lightweight tracking of local variables: their types, for method-call
+//! receivers, and the calls they were initialized with, for context added to
+//! a result some statements after the call.
+//!
+//! This is not real type checking: it only tracks the types that are evident
+//! from the source of a single function, i.e. typed parameters, `let` bindings
+//! with a type annotation, constructor calls like `Type::new(..)` and
+//! `Type::default()`, struct literals, and `self` inside an `impl` block.
+//! Anything else is unknown.
+
+use std::collections::HashMap;
+
+use syn::{Expr, ExprClosure, FnArg, GenericArgument, Local, Pat, PathArguments, Signature, Type};
+
+/// Smart pointers that are transparent for method calls (via `Deref`).
+const TRANSPARENT_WRAPPERS: &[&str] = &["Box", "Rc", "Arc"];
+
+/// What is known about a local variable.
+#[derive(Clone, Default)]
+struct Binding {
+    /// The variable's type, if evident.
+    ty: Option<String>,
+    /// The call the variable was initialized with, if any.
+    init: Option<Expr>,
+}
+
+/// The local variables in scope, by lexical scope.
+#[derive(Default)]
+pub struct Locals {
+    /// Innermost scope last. A variable that nothing is known about still
+    /// shadows any outer binding of the same name.
+    scopes: Vec<HashMap<String, Binding>>,
+}
+
+impl Locals {
+    /// Enter a new scope (a function body or block).
+    pub fn push(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Leave the innermost scope.
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Bind the parameters of a function in the current scope.
+    pub fn bind_params(&mut self, sig: &Signature, self_type: Option<&str>) {
+        for input in &sig.inputs {
+            if let FnArg::Typed(typed) = input {
+                let ty = type_of(&typed.ty, self_type);
+                self.bind_pattern(&typed.pat, ty, None);
+            }
+        }
+    }
+
+    /// Bind the parameters of a closure in the current scope.
+    pub fn bind_closure(&mut self, closure: &ExprClosure, self_type: Option<&str>) {
+        for input in &closure.inputs {
+            let ty = match input {
+                Pat::Type(typed) => type_of(&typed.ty, self_type),
+                _ => None,
+            };
+            self.bind_pattern(input, ty, None);
+        }
+    }
+
+    /// Bind the variable introduced by a `let` statement.
+    pub fn bind_local(&mut self, local: &Local, self_type: Option<&str>) {
+        let ty = match &local.pat {
+            Pat::Type(typed) => type_of(&typed.ty, self_type),
+            _ => local
+                .init
+                .as_ref()
+                .and_then(|init| self.infer(&init.expr, self_type)),
+        };
+        let init = local.init.as_ref().and_then(|init| call_result(&init.expr));
+        self.bind_pattern(&local.pat, ty, init);
+    }
+
+    /// Bind the variables of a `match` arm or `if let` pattern. The error bound
+    /// by an `Err(e)` pattern stands for the matched call, just like a result
+    /// bound by `let`.
+    pub fn bind_match(&mut self, pat: &Pat, scrutinee: &Expr) {
+        if let Pat::TupleStruct(tuple) = pat {
+            if tuple.path.is_ident("Err") && tuple.elems.len() == 1 {
+                if let Some(error @ Pat::Ident(_)) = tuple.elems.first() {
+                    self.bind_pattern(error, None, call_result(scrutinee));
+                    return;
+                }
+            }
+        }
+        self.bind_pattern(pat, None, None);
+    }
+
+    fn bind_pattern(&mut self, pat: &Pat, ty: Option<String>, init: Option<&Expr>) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        match pat {
+            Pat::Ident(ident) => {
+                let binding = Binding {
+                    ty,
+                    init: init.cloned(),
+                };
+                scope.insert(ident.ident.to_string(), binding);
+            }
+            Pat::Type(typed) => self.bind_pattern(&typed.pat, ty, init),
+            // Variables bound by destructuring have unknown types, but still
+            // shadow outer bindings.
+            Pat::Reference(reference) => self.bind_pattern(&reference.pat, None, None),
+            Pat::Tuple(tuple) => {
+                for elem in &tuple.elems {
+                    self.bind_pattern(elem, None, None);
+                }
+            }
+            Pat::TupleStruct(tuple) => {
+                for elem in &tuple.elems {
+                    self.bind_pattern(elem, None, None);
+                }
+            }
+            Pat::Struct(st) => {
+                for field in &st.fields {
+                    self.bind_pattern(&field.pat, None, None);
+                }
+            }
+            Pat::Slice(slice) => {
+                for elem in &slice.elems {
+                    self.bind_pattern(elem, None, None);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The call a variable was initialized with, e.g. `load_config()` for
+    /// `r` after `let r = load_config();`.
+    pub fn initializer(&self, name: &str) -> Option<&Expr> {
+        self.lookup(name)?.init.as_ref()
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Infer the type of an expression, if evident.
+    pub fn infer(&self, expr: &Expr, self_type: Option<&str>) -> Option<String> {
+        match expr {
+            Expr::Path(path) => {
+                let ident = path.path.get_ident()?;
+                if ident == "self" {
+                    return self_type.map(str::to_string);
+                }
+                self.lookup(&ident.to_string())?.ty.clone()
+            }
+            Expr::Reference(reference) => self.infer(&reference.expr, self_type),
+            Expr::Paren(paren) => self.infer(&paren.expr, self_type),
+            // `Type::new(..)`, `Type::default()` and `Self::..(..)`. Other
+            // associated functions may return anything, e.g. `Result<Type>`.
+            Expr::Call(call) => {
+                let Expr::Path(func) = &*call.func else {
+                    return None;
+                };
+                let segments = &func.path.segments;
+                if segments.len() < 2 {
+                    return None;
+                }
+                let ty = segments[segments.len() - 2].ident.to_string();
+                if ty == "Self" {
+                    return self_type.map(str::to_string);
+                }
+                let function = &segments[segments.len() - 1].ident;
+                if !(function == "new" || function == "default")
+                    || !ty.starts_with(|c: char| c.is_ascii_uppercase())
+                    || ty == "Default"
+                    || TRANSPARENT_WRAPPERS.contains(&ty.as_str())
+                {
+                    return None;
+                }
+                Some(ty)
+            }
+            Expr::Struct(st) => {
+                let ty = st.path.segments.last()?.ident.to_string();
+                if ty == "Self" {
+                    return self_type.map(str::to_string);
+                }
+                Some(ty)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An expression that is the result of a call, if it is one. Only results that
+/// still may hold an error are of interest: after `?`, a variable holds the
+/// success value.
+fn call_result(expr: &Expr) -> Option<&Expr> {
+    matches!(expr, Expr::Call(_) | Expr::MethodCall(_) | Expr::Await(_)).then_some(expr)
+}
+
+/// The name of a declared type, looking through references and smart pointers.
+fn type_of(ty: &Type, self_type: Option<&str>) -> Option<String> {
+    match ty {
+        Type::Reference(reference) => type_of(&reference.elem, self_type),
+        Type::Paren(paren) => type_of(&paren.elem, self_type),
+        Type::Path(type_path) => {
+            let last = type_path.path.segments.last()?;
+            let name = last.ident.to_string();
+            if name == "Self" {
+                return self_type.map(str::to_string);
+            }
+            if TRANSPARENT_WRAPPERS.contains(&name.as_str()) {
+                let PathArguments::AngleBracketed(args) = &last.arguments else {
+                    return None;
+                };
+                return args.args.iter().find_map(|arg| match arg {
+                    GenericArgument::Type(inner) => type_of(inner, self_type),
+                    _ => None,
+                });
+            }
+            Some(name)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer_in(body: &str, expr: &str, self_type: Option<&str>) -> Option<String> {
+        let sig: Signature =
+            syn::parse_str("fn f(a: &Importer, b: Arc<Exporter>, n: usize)").unwrap();
+        let block: syn::Block = syn::parse_str(body).unwrap();
+        let mut types = Locals::default();
+        types.push();
+        types.bind_params(&sig, self_type);
+        for stmt in &block.stmts {
+            if let syn::Stmt::Local(local) = stmt {
+                types.bind_local(local, self_type);
+            }
+        }
+        types.infer(&syn::parse_str(expr).unwrap(), self_type)
+    }
+
+    #[test]
+    fn test_params() {
+        assert_eq!(infer_in("{}", "a", None).as_deref(), Some("Importer"));
+        assert_eq!(infer_in("{}", "b", None).as_deref(), Some("Exporter"));
+        assert_eq!(infer_in("{}", "n", None).as_deref(), Some("usize"));
+        assert_eq!(infer_in("{}", "unknown", None), None);
+    }
+
+    #[test]
+    fn test_locals() {
+        let body = r#"{
+            let x = Importer::new();
+            let y: Box<Store> = make();
+            let z = Config { path };
+            let s = Self::open();
+            let a = compute();
+        }"#;
+        assert_eq!(infer_in(body, "x", None).as_deref(), Some("Importer"));
+        assert_eq!(infer_in(body, "&y", None).as_deref(), Some("Store"));
+        assert_eq!(infer_in(body, "z", None).as_deref(), Some("Config"));
+        assert_eq!(infer_in(body, "s", Some("Pod")).as_deref(), Some("Pod"));
+        // `a` shadows the parameter with an unknown type.
+        assert_eq!(infer_in(body, "a", None), None);
+        assert_eq!(infer_in(body, "self", Some("Pod")).as_deref(), Some("Pod"));
+    }
+
+    #[test]
+    fn test_constructor_calls() {
+        assert_eq!(
+            infer_in("{}", "Importer::new(path)", None).as_deref(),
+            Some("Importer")
+        );
+        assert_eq!(
+            infer_in("{}", "Importer::default()", None).as_deref(),
+            Some("Importer")
+        );
+        assert_eq!(infer_in("{}", "Config::load_from(path)", None), None);
+        assert_eq!(infer_in("{}", "Default::default()", None), None);
+        assert_eq!(infer_in("{}", "module::helper()", None), None);
+        assert_eq!(infer_in("{}", "Arc::new(x)", None), None);
+    }
+
+    #[test]
+    fn test_initializer() {
+        let block: syn::Block = syn::parse_str(
+            r#"{
+                let r = load_config();
+                let v = load_config()?;
+                let w = fetch(url).await;
+            }"#,
+        )
+        .unwrap();
+        let mut locals = Locals::default();
+        locals.push();
+        for stmt in &block.stmts {
+            if let syn::Stmt::Local(local) = stmt {
+                locals.bind_local(local, None);
+            }
+        }
+        assert!(matches!(locals.initializer("r"), Some(Expr::Call(_))));
+        assert!(locals.initializer("v").is_none());
+        assert!(matches!(locals.initializer("w"), Some(Expr::Await(_))));
+        assert!(locals.initializer("unknown").is_none());
+    }
+}
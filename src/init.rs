@@ -0,0 +1,347 @@
+//! `cargo context-lint init`: a commented starter `context-lint.toml`,
+//! adapted to what the workspace uses.
+//!
+//! The error handling crate (anyhow or eyre) is detected from the paths the
+//! sources mention, `Result` aliases like the unattributed check finds them,
+//! and vendored code from `cargo vendor`'s checksum files, unless it is in a
+//! directory skipped anyway (see [`DEFAULT_VENDORED_DIRS`]).
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use syn::visit::Visit;
+use walkdir::WalkDir;
+
+use crate::config::{
+    Config, DEFAULT_MAX_FILE_SIZE, DEFAULT_VENDORED_DIRS, SUGGESTED_MAX_MESSAGE_LENGTH,
+    SUGGESTED_SENSITIVE_WORDS,
+};
+use crate::modules::ModulePaths;
+use crate::parse::ParsedFile;
+use crate::unattributed;
+use crate::Report;
+
+/// What the starter configuration is based on.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Inspection {
+    /// Whether the sources use `anyhow`.
+    pub anyhow: bool,
+    /// Whether the sources use `eyre` (or `color_eyre`).
+    pub eyre: bool,
+    /// Qualified paths of the aliases of `anyhow::Result` defined or
+    /// re-exported in the workspace, sorted.
+    pub result_aliases: Vec<String>,
+    /// Exclude patterns for vendored directories not skipped by default,
+    /// relative to the package directories.
+    pub vendored: Vec<String>,
+}
+
+/// Inspect the files and source directories of a lint run.
+pub fn inspect(report: &Report) -> Result<Inspection> {
+    let files: Vec<ParsedFile> = report
+        .files
+        .iter()
+        .filter(|file| !report.skipped.iter().any(|s| &s.path == *file))
+        .map(|file| ParsedFile::read(file))
+        .collect::<Result<_>>()?;
+
+    let mut usage = ErrorCrates::default();
+    for syntax in files.iter().filter_map(|file| file.syntax.as_ref()) {
+        usage.visit_file(syntax);
+    }
+
+    let modules = ModulePaths::resolve(&report.packages, &files);
+    let module_paths: Vec<Vec<String>> = files.iter().map(|f| modules.get(&f.path)).collect();
+    let mut result_aliases: Vec<String> = unattributed::result_aliases(
+        files.iter().zip(module_paths.iter().map(Vec::as_slice)),
+        &Config::default(),
+    )
+    .into_iter()
+    .collect();
+    result_aliases.sort();
+
+    let mut vendored = Vec::new();
+    for dir in &report.source_dirs {
+        for found in find_vendored(dir) {
+            let relative = found.strip_prefix(dir).unwrap_or(&found);
+            let pattern = format!("{}/**", relative.to_string_lossy());
+            if !vendored.contains(&pattern) {
+                vendored.push(pattern);
+            }
+        }
+    }
+
+    Ok(Inspection {
+        anyhow: usage.anyhow,
+        eyre: usage.eyre,
+        result_aliases,
+        vendored,
+    })
+}
+
+/// Directories below `dir` holding vendored code, as parents of crates with
+/// a `.cargo-checksum.json` file, except in directories skipped by default.
+fn find_vendored(dir: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut walker = WalkDir::new(dir).min_depth(1).into_iter();
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy();
+        if name == "target" || name.starts_with('.') || DEFAULT_VENDORED_DIRS.contains(&&*name) {
+            walker.skip_current_dir();
+            continue;
+        }
+        if entry.path().join(".cargo-checksum.json").is_file() {
+            if let Some(vendored) = entry.path().parent() {
+                if !found.iter().any(|f| f == vendored) {
+                    found.push(vendored.to_path_buf());
+                }
+            }
+            walker.skip_current_dir();
+        }
+    }
+    found
+}
+
+/// Which error handling crates paths and imports refer to.
+#[derive(Debug, Default)]
+struct ErrorCrates {
+    anyhow: bool,
+    eyre: bool,
+}
+
+impl ErrorCrates {
+    fn mention(&mut self, ident: &syn::Ident) {
+        if ident == "anyhow" {
+            self.anyhow = true;
+        } else if ident == "eyre" || ident == "color_eyre" {
+            self.eyre = true;
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ErrorCrates {
+    fn visit_path(&mut self, node: &'ast syn::Path) {
+        if let Some(first) = node.segments.first() {
+            self.mention(&first.ident);
+        }
+        syn::visit::visit_path(self, node);
+    }
+
+    fn visit_use_path(&mut self, node: &'ast syn::UsePath) {
+        self.mention(&node.ident);
+        syn::visit::visit_use_path(self, node);
+    }
+}
+
+/// The starter configuration: the detected settings, and every other one
+/// commented out with its default value.
+pub fn starter_config(inspection: &Inspection) -> String {
+    let mut out = String::from(
+        "# Configuration of cargo context-lint, written by `cargo context-lint init`.\n\
+         # Every setting is optional; those commented out show the default.\n",
+    );
+    let library = match (inspection.anyhow, inspection.eyre) {
+        (true, true) => Some("anyhow and eyre"),
+        (true, false) => Some("anyhow"),
+        (false, true) => Some("eyre"),
+        (false, false) => None,
+    };
+    match library {
+        Some(library) => out.push_str(&format!("#\n# Detected error handling: {library}.\n")),
+        None => out.push_str("#\n# Neither anyhow nor eyre was found in the sources.\n"),
+    }
+
+    out.push_str(
+        "\n[paths]\n\
+         # Only check files matching one of these, relative to each package\n\
+         # include = [\"src/**\"]\n",
+    );
+    if inspection.vendored.is_empty() {
+        out.push_str(
+            "# Never check files matching one of these, e.g. generated code\n\
+             # exclude = []\n",
+        );
+    } else {
+        out.push_str(&format!(
+            "# Never check files matching one of these; vendored code was found here\n\
+             exclude = {}\n",
+            toml_list(&inspection.vendored)
+        ));
+    }
+    let vendored: Vec<String> = DEFAULT_VENDORED_DIRS
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    out.push_str(&format!(
+        "# Directories of vendored code, skipped wherever they are\n\
+         # vendored = {}\n\
+         # Skip files larger than this many bytes; 0 disables the limit\n\
+         # max-file-size = {DEFAULT_MAX_FILE_SIZE}\n",
+        toml_list(&vendored)
+    ));
+
+    out.push_str(
+        "\n[double-context]\n\
+         # Drop findings whose confidence (from 0 to 1) is below this\n\
+         # min-confidence = 0.0\n",
+    );
+
+    out.push_str("\n[unattributed]\n");
+    if inspection.result_aliases.is_empty() {
+        out.push_str("# Result types to treat like `anyhow::Result`\n# result-aliases = []\n");
+    } else {
+        out.push_str(&format!(
+            "# Result types to treat like `anyhow::Result`. These aliases were found in\n\
+             # the workspace; listing them also covers checks of single files.\n\
+             result-aliases = {}\n",
+            toml_list(&inspection.result_aliases)
+        ));
+    }
+    out.push_str(
+        "# Skip functions that only delegate to another function\n\
+         # skip-delegation = true\n\
+         # Only flag `pub` functions\n\
+         # only-pub = false\n\
+         # Only flag functions with at least this many statements\n\
+         # min-statements = 0\n\
+         # Attributes standing in for #[context]\n\
+         # error-attributes = [\"tracing::instrument(err)\"]\n\
+         # Count functions adding context to every error they return as covered\n\
+         # skip-covered = false\n",
+    );
+
+    out.push_str(
+        "\n[bare-propagation]\n\
+         # Only flag functions with at least this many statements\n\
+         # min-statements = 5\n",
+    );
+
+    out.push_str(
+        "\n[style]\n\
+         # Flag #[context] functions that only delegate to another one\n\
+         # delegating-wrapper = false\n\
+         # Flag context strings interpolating values named with one of these\n\
+         # words; empty (the default) disables the check\n",
+    );
+    let words: Vec<String> = SUGGESTED_SENSITIVE_WORDS
+        .iter()
+        .map(|word| format!("\"{word}\""))
+        .collect();
+    out.push_str(&format!("sensitive-words = [{}]\n", words.join(", ")));
+    out.push_str(&format!(
+        "# Flag context messages longer than this; 0 (the default) disables the check\n\
+         max-message-length = {SUGGESTED_MAX_MESSAGE_LENGTH}\n",
+    ));
+
+    out.push_str(
+        "\n[banned-context]\n\
+         # Regular expressions context strings must not match, e.g. \"(?i)\\\\btodo\\\\b\"\n\
+         # patterns = []\n",
+    );
+
+    out.push_str(
+        "\n[spelling]\n\
+         # Word lists (one word per line) to spell check context strings against\n\
+         # dictionaries = [\"/usr/share/dict/words\"]\n\
+         # The project's own words\n\
+         # words = []\n",
+    );
+
+    out.push_str("\n[context-methods]\n");
+    if inspection.eyre {
+        out.push_str("# `wrap_err` and `wrap_err_with` are recognized besides anyhow's methods\n");
+    }
+    out.push_str(
+        "# Additional methods adding context, e.g. snafu's `whatever_context`\n\
+         # extra = []\n\
+         # Also treat error-stack's `change_context` and `attach_printable` as adding context\n\
+         # error-stack = false\n",
+    );
+    out
+}
+
+/// A TOML array of strings.
+fn toml_list(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| format!("{item:?}")).collect();
+    format!("[{}]", items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_crates() {
+        let syntax = syn::parse_file(
+            r#"
+            use color_eyre::eyre::WrapErr;
+            fn main() -> eyre::Result<()> { Ok(()) }
+            "#,
+        )
+        .unwrap();
+        let mut usage = ErrorCrates::default();
+        usage.visit_file(&syntax);
+        assert!(usage.eyre);
+        assert!(!usage.anyhow);
+    }
+
+    #[test]
+    fn test_starter_config() {
+        let inspection = Inspection {
+            anyhow: true,
+            eyre: false,
+            result_aliases: vec!["my_crate::Result".to_string()],
+            vendored: vec!["vendor/**".to_string()],
+        };
+        let text = starter_config(&inspection);
+        assert!(text.contains("# Detected error handling: anyhow."));
+        assert!(text.contains("\nexclude = [\"vendor/**\"]\n"));
+        assert!(text.contains("\nresult-aliases = [\"my_crate::Result\"]\n"));
+        assert!(text.contains("\n[banned-context]\n# "));
+
+        let config = crate::config::parse(&text).unwrap();
+        assert_eq!(config.paths.exclude, ["vendor/**"]);
+        assert_eq!(config.unattributed.result_aliases, ["my_crate::Result"]);
+        assert!(config.unattributed.skip_delegation);
+        assert!(config
+            .style
+            .sensitive_words
+            .contains(&"password".to_string()));
+        assert_eq!(config.style.max_message_length, 120);
+
+        // Nothing detected: only defaults.
+        let text = starter_config(&Inspection::default());
+        let config = crate::config::parse(&text).unwrap();
+        assert!(config.paths.exclude.is_empty());
+        assert!(config.unattributed.result_aliases.is_empty());
+    }
+
+    #[test]
+    fn test_find_vendored() {
+        let dir = crate::fixtures::TempDir::new("init");
+        std::fs::create_dir_all(dir.join("deps/serde")).unwrap();
+        std::fs::write(dir.join("deps/serde/.cargo-checksum.json"), "{}").unwrap();
+        std::fs::create_dir_all(dir.join("src/third_party/zlib")).unwrap();
+        std::fs::write(dir.join("src/third_party/zlib/.cargo-checksum.json"), "{}").unwrap();
+        std::fs::create_dir_all(dir.join("target/vendor")).unwrap();
+
+        let mut found: Vec<String> = find_vendored(&dir)
+            .iter()
+            .map(|path| {
+                path.strip_prefix(&dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        found.sort();
+        // `third_party` is skipped anyway.
+        assert_eq!(found, ["deps"]);
+    }
+}
@@ -0,0 +1,92 @@
+//! `cargo-context-lint` — Detect double error context from `fn_error_context` + `anyhow`.
+//!
+//! When a function is annotated with `#[context("...")]` from the `fn_error_context` crate,
+//! the function body is automatically wrapped to add context to any error it returns.
+//! If the caller *also* adds `.context()` or `.with_context()` from `anyhow::Context`,
+//! the error will carry two context layers, which is redundant.
+//!
+//! This tool detects such "double context" patterns via syntactic analysis.
+//!
+//! Additionally, it can check that all functions returning `anyhow::Result` have a
+//! `#[context]` annotation (the `--unattributed` check).
+//!
+//! The analysis behind `cargo context-lint` is available as a library:
+//!
+//! ```no_run
+//! use cargo_context_lint::{lint_workspace, LintOptions};
+//!
+//! let report = lint_workspace("path/to/workspace".as_ref(), LintOptions::default())?;
+//! for finding in report.iter() {
+//!     println!("{}:{}: {}", finding.file(), finding.line(), finding.lint().code());
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+// The library's API is the facade re-exported below. The modules the
+// command line needs beyond it are public but hidden, and not covered by
+// semver.
+mod banned;
+#[doc(hidden)]
+pub mod baseline;
+mod cfg;
+#[doc(hidden)]
+pub mod checker;
+mod collector;
+#[doc(hidden)]
+pub mod compare;
+#[doc(hidden)]
+pub mod config;
+mod distance;
+mod doctest;
+mod expand;
+mod fingerprint;
+#[doc(hidden)]
+pub mod fix;
+#[doc(hidden)]
+pub mod git;
+mod glob;
+#[doc(hidden)]
+pub mod graph;
+mod imports;
+mod infer;
+#[doc(hidden)]
+pub mod init;
+mod linter;
+mod lints;
+mod modules;
+#[doc(hidden)]
+pub mod package;
+mod parse;
+mod plugin;
+mod propagation;
+#[doc(hidden)]
+pub mod report;
+mod rules;
+mod spelling;
+mod stats;
+mod style;
+mod unattributed;
+
+#[cfg(test)]
+mod fixtures;
+
+pub use crate::banned::BannedContext;
+pub use crate::baseline::Entry as BaselineEntry;
+pub use crate::checker::{Definition, DoubleContext, MatchKind};
+pub use crate::collector::{AnnotatedFunction, AnnotationSource, PlaceholderFix};
+pub use crate::fix::{Fix, FixPlan, TextEdit};
+pub use crate::graph::CallGraph;
+pub use crate::linter::{
+    lint_workspace, BaseComparison, Finding, Heuristics, LintLevel, LintLevels, LintOptions,
+    Linter, Report, SkippedFile,
+};
+pub use crate::lints::{Lint, Severity};
+pub use crate::modules::FileModule;
+pub use crate::package::{CleanReport, MissingDependency, Package};
+pub use crate::parse::{SkipReason, Span};
+pub use crate::plugin::PluginFinding;
+pub use crate::propagation::BarePropagation;
+pub use crate::report::{format_combined_json, format_combined_text, Findings, TextLayout};
+pub use crate::stats::Stats;
+pub use crate::style::{StyleIssue, StyleKind};
+pub use crate::unattributed::{Coverage, UnattributedFunction};
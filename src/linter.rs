@@ -0,0 +1,1785 @@
+//! Running the checks on a workspace: discovering its packages and files,
+//! collecting the annotated functions and checking the selected files.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use walkdir::WalkDir;
+
+use crate::banned::{self, BannedContext};
+use crate::checker::MatchKind;
+use crate::checker::{self, DoubleContext};
+use crate::collector::{self, AnnotatedFunction};
+use crate::fingerprint;
+use crate::fix::{self, FixPlan};
+use crate::glob::{Glob, PathFilter};
+use crate::graph::{self, CallGraph};
+use crate::lints::{Lint, Severity};
+use crate::modules::{FileModule, ModulePaths};
+use crate::package::{self, CleanReport, MissingDependency, Package};
+use crate::parse::{ParsedFile, SkipReason, Span};
+use crate::plugin::{self, InputFinding, Plugin, PluginFinding};
+use crate::propagation::{self, BarePropagation};
+use crate::report::{strip_path, Findings};
+use crate::rules;
+use crate::spelling;
+use crate::stats::{self, Stats};
+use crate::style::{self, StyleIssue};
+use crate::unattributed::{self, Coverage, UnattributedFunction};
+use crate::{baseline, cfg, config, doctest, expand, git};
+
+/// Lint level of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LintLevel {
+    /// Allow (skip the check).
+    Allow,
+    /// Warn (report findings, but don't exit non-zero for them).
+    Warn,
+    /// Deny (flag as a warning, exit non-zero).
+    Deny,
+}
+
+impl std::fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintLevel::Allow => write!(f, "allow"),
+            LintLevel::Warn => write!(f, "warn"),
+            LintLevel::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// How far the double-context check trusts calls matched by name only.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Heuristics {
+    /// Report findings with at least the configured `min-confidence`.
+    #[default]
+    Default,
+    /// Also drop calls matched by name only: only report those resolved by
+    /// path or receiver type, for CI.
+    Strict,
+    /// Report every match regardless of its confidence, for audits.
+    Lenient,
+}
+
+impl Heuristics {
+    /// Whether a double-context finding is reported, given the configured
+    /// minimum confidence.
+    pub fn reports(self, issue: &DoubleContext, min_confidence: f64) -> bool {
+        match self {
+            Heuristics::Default => issue.confidence >= min_confidence,
+            Heuristics::Strict => {
+                issue.match_kind != MatchKind::Name && issue.confidence >= min_confidence
+            }
+            Heuristics::Lenient => true,
+        }
+    }
+}
+
+/// The level of each check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LintLevels {
+    pub double_context: LintLevel,
+    pub unattributed: LintLevel,
+    pub style: LintLevel,
+    pub bare_propagation: LintLevel,
+    pub plugin: LintLevel,
+    pub banned_context: LintLevel,
+}
+
+impl Default for LintLevels {
+    fn default() -> Self {
+        LintLevels {
+            double_context: LintLevel::Deny,
+            unattributed: LintLevel::Deny,
+            style: LintLevel::Deny,
+            bare_propagation: LintLevel::Allow,
+            plugin: LintLevel::Warn,
+            banned_context: LintLevel::Deny,
+        }
+    }
+}
+
+impl LintLevels {
+    pub fn get_mut(&mut self, lint: Lint) -> &mut LintLevel {
+        match lint {
+            Lint::DoubleContext => &mut self.double_context,
+            Lint::Unattributed => &mut self.unattributed,
+            Lint::Style => &mut self.style,
+            Lint::BarePropagation => &mut self.bare_propagation,
+            Lint::Plugin => &mut self.plugin,
+            Lint::BannedContext => &mut self.banned_context,
+        }
+    }
+
+    pub fn get(&self, lint: Lint) -> LintLevel {
+        match lint {
+            Lint::DoubleContext => self.double_context,
+            Lint::Unattributed => self.unattributed,
+            Lint::Style => self.style,
+            Lint::BarePropagation => self.bare_propagation,
+            Lint::Plugin => self.plugin,
+            Lint::BannedContext => self.banned_context,
+        }
+    }
+}
+
+/// What to check and how. The fields correspond to the command-line options
+/// of the same names.
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+    /// Paths to the Cargo.toml of each workspace to check (defaults to the
+    /// current directory's). Several workspaces are checked together, with
+    /// one report and one index of annotated functions.
+    pub manifest_paths: Vec<PathBuf>,
+    /// Check all Rust files below this directory without running `cargo
+    /// metadata`, e.g. for other build systems. Replaces `manifest_paths`.
+    pub path: Option<PathBuf>,
+    /// Check only these files instead of the whole workspace.
+    pub files: Vec<PathBuf>,
+    /// Source code that isn't read from disk (e.g. an editor buffer), and the
+    /// path it is reported under. Like `files`, replaces the workspace walk.
+    pub buffer: Option<(PathBuf, String)>,
+    /// Only check these workspace members.
+    pub packages: Vec<String>,
+    /// Skip workspace members whose name matches one of these globs.
+    pub exclude: Vec<String>,
+    pub include_tests: bool,
+    pub include_benches: bool,
+    pub include_examples: bool,
+    /// Only check files matching one of these globs (in addition to the
+    /// configuration file's `[paths] include`).
+    pub include_paths: Vec<String>,
+    /// Skip files matching one of these globs (in addition to the
+    /// configuration file's `[paths] exclude`).
+    pub exclude_paths: Vec<String>,
+    /// Also check the directories of vendored code (the configuration
+    /// file's `[paths] vendored`).
+    pub include_vendored: bool,
+    /// Follow symlinks to files and directories when looking for sources.
+    pub follow_symlinks: bool,
+    /// Enabled features, `NAME` or `PACKAGE/NAME`, comma or space separated.
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// The target triple whose `cfg` values (`target_os`, `windows`, ..)
+    /// decide which code is compiled, instead of the host's.
+    pub target: Option<String>,
+    /// Leave `cfg` predicates on the target platform undecided, checking the
+    /// code of every platform.
+    pub any_target: bool,
+    /// Skip files larger than this many bytes instead of the configured
+    /// `max-file-size`; 0 disables the limit.
+    pub max_file_size: Option<u64>,
+    /// Path to the configuration file (defaults to `context-lint.toml` in the
+    /// workspace root, if present).
+    pub config: Option<PathBuf>,
+    /// Look for double context in the macro-expanded source.
+    pub expand: bool,
+    /// Also look for double context in the code examples of doc comments.
+    pub doc_tests: bool,
+    /// Also collect annotated functions from dependencies.
+    pub include_deps: bool,
+    /// Write the annotated functions found in the workspace to this file.
+    pub emit_index: Option<PathBuf>,
+    /// Write the call graph of annotated functions to this file.
+    pub emit_graph: Option<PathBuf>,
+    /// Build the call graph of annotated functions (see [`Report::graph`]),
+    /// even without `emit_graph`.
+    pub call_graph: bool,
+    /// Merge annotated functions from these index files.
+    pub load_index: Vec<PathBuf>,
+    /// Only check files changed relative to this git ref.
+    pub changed_since: Option<String>,
+    /// Only check files staged in git's index (their working tree version).
+    pub staged: bool,
+    /// Only report findings that are not present at the merge base of `HEAD`
+    /// and this git ref (see [`Report::base`]).
+    pub deny_new: Option<String>,
+    /// Don't report the findings listed in this baseline file (see
+    /// [`Report::baselined`]).
+    pub baseline: Option<PathBuf>,
+    /// Drop double-context findings with a lower confidence (overrides the
+    /// configuration file's `[double-context] min-confidence`).
+    pub min_confidence: Option<f64>,
+    /// Which double-context matches to report (`--strict`, `--lenient`).
+    pub heuristics: Heuristics,
+    /// Only report `pub` functions in the unattributed check (in addition to
+    /// the configuration file's `[unattributed] only-pub`).
+    pub only_pub: bool,
+    /// Also check the methods of trait impls in the unattributed check, if
+    /// the trait is defined in the workspace.
+    pub unattributed_trait_impls: bool,
+    /// The level of each check.
+    pub levels: LintLevels,
+    /// Only fail for findings of these checks (all if empty).
+    pub error_on: Vec<Lint>,
+    /// Never fail for findings.
+    pub warn_only: bool,
+}
+
+impl LintOptions {
+    /// Whether the findings of a report make the run fail, given the lint
+    /// levels, `error_on` and `warn_only`.
+    pub fn fails(&self, report: &Report) -> bool {
+        !self.warn_only
+            && [
+                (Lint::DoubleContext, report.double_context.len()),
+                (Lint::Unattributed, report.unattributed.len()),
+                (Lint::Style, report.style.len()),
+                (Lint::BarePropagation, report.bare_propagation.len()),
+                (Lint::Plugin, report.plugin.len()),
+                (Lint::BannedContext, report.banned_context.len()),
+            ]
+            .iter()
+            .any(|(lint, count)| {
+                *count > 0
+                    && self.levels.get(*lint) == LintLevel::Deny
+                    && (self.error_on.is_empty() || self.error_on.contains(lint))
+            })
+    }
+}
+
+/// A single finding of any check.
+#[derive(Debug, Clone, Copy)]
+pub enum Finding<'a> {
+    DoubleContext(&'a DoubleContext),
+    Unattributed(&'a UnattributedFunction),
+    Style(&'a StyleIssue),
+    BarePropagation(&'a BarePropagation),
+    Plugin(&'a PluginFinding),
+    BannedContext(&'a BannedContext),
+}
+
+impl<'a> Finding<'a> {
+    /// The check that reported it.
+    pub fn lint(&self) -> Lint {
+        match self {
+            Finding::DoubleContext(_) => Lint::DoubleContext,
+            Finding::Unattributed(_) => Lint::Unattributed,
+            Finding::Style(_) => Lint::Style,
+            Finding::BarePropagation(_) => Lint::BarePropagation,
+            Finding::Plugin(_) => Lint::Plugin,
+            Finding::BannedContext(_) => Lint::BannedContext,
+        }
+    }
+
+    /// The file it is in.
+    pub fn file(&self) -> &'a str {
+        match self {
+            Finding::DoubleContext(issue) => &issue.call_file,
+            Finding::Unattributed(issue) => &issue.file,
+            Finding::Style(issue) => &issue.file,
+            Finding::BarePropagation(issue) => &issue.file,
+            Finding::Plugin(issue) => &issue.file,
+            Finding::BannedContext(issue) => &issue.file,
+        }
+    }
+
+    /// Its severity, given the level of its check: `error` for denied
+    /// checks and `warning` for others, except that double context with
+    /// identical strings is always an `error`, and double context matched by
+    /// name only is one step less serious.
+    pub fn severity(&self, level: LintLevel) -> Severity {
+        let base = match level {
+            LintLevel::Deny => Severity::Error,
+            LintLevel::Warn | LintLevel::Allow => Severity::Warning,
+        };
+        match self {
+            // Identical contexts, or three layers with an annotated caller
+            Finding::DoubleContext(issue) if issue.is_identical() || issue.caller.is_some() => {
+                Severity::Error
+            }
+            Finding::DoubleContext(issue) if issue.match_kind == MatchKind::Name => base.lower(),
+            _ => base,
+        }
+    }
+
+    /// Where exactly it is, if known.
+    pub fn span(&self) -> Option<Span> {
+        let span = match self {
+            Finding::DoubleContext(issue) => issue.call_span,
+            Finding::Unattributed(issue) => issue.span,
+            Finding::Plugin(issue) => issue.span,
+            Finding::BannedContext(issue) => issue.span,
+            Finding::Style(_) | Finding::BarePropagation(_) => return None,
+        };
+        span.is_known().then_some(span)
+    }
+
+    /// The line it is on (1-based).
+    pub fn line(&self) -> usize {
+        match self {
+            Finding::DoubleContext(issue) => issue.call_line,
+            Finding::Unattributed(issue) => issue.line,
+            Finding::Style(issue) => issue.line,
+            Finding::BarePropagation(issue) => issue.line,
+            Finding::Plugin(issue) => issue.line,
+            Finding::BannedContext(issue) => issue.line,
+        }
+    }
+}
+
+/// A file that was not checked because of its size or content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// The result of a run: the findings of each check, sorted by file and line,
+/// and what was checked.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// The workspace root directory.
+    pub workspace_root: PathBuf,
+    /// The level each check ran with.
+    pub levels: LintLevels,
+    /// The workspace member packages.
+    pub packages: Vec<Package>,
+    /// Indexes into `packages` of the checked members.
+    pub selected: Vec<usize>,
+    /// The package directories that were walked for source files.
+    pub source_dirs: Vec<PathBuf>,
+    /// The workspace files read.
+    pub files: Vec<PathBuf>,
+    /// Dependencies whose annotated functions were collected.
+    pub dependencies: Vec<Package>,
+    /// The dependency files read.
+    pub dependency_files: Vec<PathBuf>,
+    /// The files findings were reported for (e.g. with `changed_since`, the
+    /// changed ones).
+    pub checked_files: Vec<PathBuf>,
+    /// The macro-expanded sources written with `expand`.
+    pub expanded_files: Vec<PathBuf>,
+    /// All annotated functions found.
+    pub annotated: Vec<AnnotatedFunction>,
+    /// How the findings compare to those at the merge base, with `deny_new`.
+    pub base: Option<BaseComparison>,
+    /// Findings not reported because they are in the `baseline` file, as
+    /// baseline entries with their current location.
+    pub baselined: Vec<baseline::Entry>,
+    /// The call graph of annotated functions, if requested with
+    /// `call_graph` or `emit_graph`.
+    pub graph: Option<CallGraph>,
+    /// `#[context]` coverage of each checked file.
+    pub coverage: Vec<(PathBuf, Coverage)>,
+    /// The package and module of each workspace file read, following `mod`
+    /// declarations and `#[path]` attributes from the crate roots.
+    pub modules: HashMap<PathBuf, FileModule>,
+    /// Files that were not read or parsed because of their size or content.
+    pub skipped: Vec<SkippedFile>,
+    /// Time spent reading and checking each file.
+    pub timings: HashMap<PathBuf, Duration>,
+    pub double_context: Vec<DoubleContext>,
+    pub unattributed: Vec<UnattributedFunction>,
+    pub style: Vec<StyleIssue>,
+    pub bare_propagation: Vec<BarePropagation>,
+    pub plugin: Vec<PluginFinding>,
+    pub banned_context: Vec<BannedContext>,
+    /// Call sites not reported because their context goes on an `Option`
+    /// (see [`DoubleContext::option_receiver`]).
+    pub option_context: Vec<DoubleContext>,
+    /// Informational notes; these never affect the exit code.
+    pub missing_dependency: Vec<MissingDependency>,
+}
+
+impl Report {
+    /// All findings, for formatting.
+    pub fn findings(&self) -> Findings<'_> {
+        Findings {
+            double_context: &self.double_context,
+            unattributed: &self.unattributed,
+            style: &self.style,
+            bare_propagation: &self.bare_propagation,
+            plugin: &self.plugin,
+            banned_context: &self.banned_context,
+            missing_dependency: &self.missing_dependency,
+            clean: None,
+            levels: self.levels,
+        }
+    }
+
+    /// All findings, check by check.
+    pub fn iter(&self) -> impl Iterator<Item = Finding<'_>> {
+        self.findings().iter()
+    }
+
+    /// The edits of the fixes of all findings that have one.
+    pub fn fix_plan(&self) -> FixPlan {
+        let fixes = self
+            .double_context
+            .iter()
+            .filter_map(|issue| Some((&*issue.call_file, issue.fix.as_ref()?)))
+            .chain(
+                self.style
+                    .iter()
+                    .filter_map(|issue| Some((issue.file.as_str(), issue.fix.as_ref()?))),
+            );
+        fix::plan(fixes)
+    }
+
+    /// Apply a fix plan in place and drop the findings it resolves. Returns
+    /// the number of edits applied.
+    pub fn apply_fixes(&mut self, plan: &FixPlan) -> Result<usize> {
+        let applied = fix::apply_plan(plan)?;
+        // Fixed call sites no longer need to be reported.
+        self.double_context.retain(|issue| {
+            !issue
+                .fix
+                .as_ref()
+                .is_some_and(|f| fix::is_resolved(plan, &issue.call_file, f))
+        });
+        self.style.retain(|issue| {
+            !issue
+                .fix
+                .as_ref()
+                .is_some_and(|f| fix::is_resolved(plan, &issue.file, f))
+        });
+        Ok(applied)
+    }
+
+    /// The checked packages and files without findings.
+    pub fn clean(&self) -> CleanReport {
+        let flagged: HashSet<&str> = self.iter().map(|finding| finding.file()).collect();
+        let checked_packages: Vec<Package> = self
+            .selected
+            .iter()
+            .map(|&idx| self.packages[idx].clone())
+            .collect();
+        package::check_clean(&checked_packages, &self.checked_files, &flagged)
+    }
+
+    /// Per-crate metrics of the checked packages.
+    pub fn stats(&self) -> Stats {
+        stats::compute(
+            &self.packages,
+            &self.selected,
+            &self.coverage,
+            &self.modules,
+            &self.findings(),
+        )
+    }
+}
+
+/// How the findings of a run compare to those at the merge base.
+#[derive(Debug, Clone, Default)]
+pub struct BaseComparison {
+    /// The merge base commit.
+    pub commit: String,
+    /// Findings also present at the merge base, which were not reported.
+    pub unchanged: usize,
+    /// Findings at the merge base that are gone, in the checked files.
+    pub fixed: usize,
+}
+
+/// The findings at the merge base, by fingerprint: the file they're in,
+/// relative to the workspace root, and how many are left to match.
+type BaseFindings = HashMap<String, (String, usize)>;
+
+/// Match a finding with `fingerprint` against `findings`, returning whether
+/// one was left.
+fn take(findings: &mut BaseFindings, fingerprint: &str) -> bool {
+    match findings.get_mut(fingerprint) {
+        Some((_, count @ 1..)) => {
+            *count -= 1;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Runs the checks configured by [`LintOptions`].
+#[derive(Debug, Clone, Default)]
+pub struct Linter {
+    options: LintOptions,
+}
+
+/// Check the workspace at `path` (a directory or its `Cargo.toml`).
+pub fn lint_workspace(path: &Path, options: LintOptions) -> Result<Report> {
+    let manifest_path = if path.is_dir() {
+        path.join("Cargo.toml")
+    } else {
+        path.to_path_buf()
+    };
+    Linter::new(LintOptions {
+        manifest_paths: vec![manifest_path],
+        ..options
+    })
+    .run()
+}
+
+impl Linter {
+    pub fn new(options: LintOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &LintOptions {
+        &self.options
+    }
+
+    /// Run the enabled checks.
+    pub fn run(&self) -> Result<Report> {
+        let mut double_context = Vec::new();
+        let mut unattributed = Vec::new();
+        let mut style = Vec::new();
+        let mut bare_propagation = Vec::new();
+        let mut plugin = Vec::new();
+        let mut banned_context = Vec::new();
+        let mut report = self.run_with(|finding| match finding {
+            Finding::DoubleContext(issue) => double_context.push(issue.clone()),
+            Finding::Unattributed(issue) => unattributed.push(issue.clone()),
+            Finding::Style(issue) => style.push(issue.clone()),
+            Finding::BarePropagation(issue) => bare_propagation.push(issue.clone()),
+            Finding::Plugin(issue) => plugin.push(issue.clone()),
+            Finding::BannedContext(issue) => banned_context.push(issue.clone()),
+        })?;
+
+        // Sort by file and line for stable output
+        double_context.sort_by(|a, b| {
+            a.call_file
+                .cmp(&b.call_file)
+                .then(a.call_line.cmp(&b.call_line))
+        });
+        unattributed.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+        style.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+        bare_propagation.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+        plugin.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+        banned_context.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+
+        report.double_context = double_context;
+        report.unattributed = unattributed;
+        report.style = style;
+        report.bare_propagation = bare_propagation;
+        report.plugin = plugin;
+        report.banned_context = banned_context;
+        Ok(report)
+    }
+
+    /// Run the enabled checks, passing each finding to `on_finding` as soon
+    /// as the file it is in has been checked, in no particular order. The
+    /// returned report holds everything but the findings.
+    pub fn run_with(&self, mut on_finding: impl FnMut(Finding<'_>)) -> Result<Report> {
+        let options = &self.options;
+        let levels = &options.levels;
+        // Explicit files (or a buffer) replace the workspace walk.
+        let explicit = !options.files.is_empty() || options.buffer.is_some();
+
+        let Workspace {
+            packages,
+            dependencies,
+            root: workspace_root,
+            target_dir,
+        } = match &options.path {
+            Some(dir) => plain_workspace(dir, options.follow_symlinks)?,
+            None => discover_workspaces(&options.manifest_paths, options.include_deps)?,
+        };
+        let mut config = config::load(options.config.as_deref(), &workspace_root)?;
+        let function_rules = rules::compile(&mut config)?;
+
+        // With `deny_new`, findings also present at the merge base are not
+        // reported.
+        let base_commit = match &options.deny_new {
+            Some(base) => Some(git::merge_base(&workspace_root, base)?),
+            None => None,
+        };
+        let mut base_findings = match &base_commit {
+            Some(commit) => self.base_findings(commit, &workspace_root, &target_dir)?,
+            None => BaseFindings::new(),
+        };
+        let mut unchanged = 0;
+        // Findings in the baseline file aren't reported either.
+        let mut baseline_findings = match &options.baseline {
+            Some(path) => baseline::read(path)?.counts(),
+            None => BaseFindings::new(),
+        };
+        let mut baselined = Vec::new();
+        let prefix = format!("{}/", workspace_root.display());
+        let mut on_finding = |finding: Finding<'_>| {
+            if base_commit.is_some() || options.baseline.is_some() {
+                let fingerprint = fingerprint::of_finding(&finding, Some(&prefix));
+                if take(&mut baseline_findings, &fingerprint) {
+                    baselined.push(baseline::Entry::new(&finding, Some(&prefix)));
+                    return;
+                }
+                if take(&mut base_findings, &fingerprint) {
+                    unchanged += 1;
+                    return;
+                }
+            }
+            on_finding(finding);
+        };
+
+        let exclude: Vec<Glob> = options.exclude.iter().map(|p| Glob::new(p)).collect();
+        let selected = package::select(&packages, &options.packages, &exclude)?;
+
+        // Deduplicate in case packages share directories
+        let mut source_dirs: Vec<PathBuf> = packages
+            .iter()
+            .filter(|p| !package::is_excluded(p, &exclude))
+            .map(|p| p.dir.clone())
+            .collect();
+        source_dirs.sort();
+        source_dirs.dedup();
+
+        let mut include = config.paths.include.clone();
+        include.extend(options.include_paths.iter().cloned());
+        let mut exclude_paths = config.paths.exclude.clone();
+        exclude_paths.extend(options.exclude_paths.iter().cloned());
+        let mut path_filter = PathFilter::new(&include, &exclude_paths);
+        if !options.include_vendored {
+            path_filter.skip_dirs = config.paths.vendored.clone();
+        }
+
+        // Collect all Rust files
+        let mut all_files: Vec<PathBuf> = Vec::new();
+        if explicit {
+            for file in &options.files {
+                all_files.push(
+                    std::path::absolute(file)
+                        .with_context(|| format!("Resolving {}", file.display()))?,
+                );
+            }
+        } else {
+            for dir in &source_dirs {
+                all_files.extend(find_rust_files(dir, &path_filter, options.follow_symlinks));
+            }
+        }
+        // Excluded members may be nested in the directory of another one.
+        all_files.retain(|file| {
+            !package::package_index(&packages, file)
+                .is_some_and(|idx| package::is_excluded(&packages[idx], &exclude))
+        });
+        // Tests, benchmarks and examples only if asked for.
+        if !explicit {
+            all_files.retain(|file| {
+                package::file_target(&packages, file).is_none_or(|target| {
+                    (options.include_tests || !target.is_kind("test"))
+                        && (options.include_benches || !target.is_kind("bench"))
+                        && (options.include_examples || !target.is_kind("example"))
+                })
+            });
+        }
+
+        // Dependencies only contribute annotated functions, from their `src/`.
+        let mut dependency_files: Vec<PathBuf> = Vec::new();
+        for dependency in &dependencies {
+            dependency_files.extend(find_rust_files(
+                &dependency.dir.join("src"),
+                &PathFilter::default(),
+                false,
+            ));
+        }
+
+        // Time spent on each file, for `-vv`.
+        let mut timings: HashMap<PathBuf, Duration> = HashMap::new();
+
+        // Each file is read and parsed once, and the syntax tree shared by all
+        // passes. Files too large or not looking like source text are skipped.
+        let max_file_size = match options.max_file_size {
+            Some(0) => None,
+            Some(size) => Some(size),
+            None => config.paths.max_file_size(),
+        };
+        let mut skipped = Vec::new();
+        let mut workspace_sources: Vec<ParsedFile> = Vec::new();
+        for file in &all_files {
+            let parsed = timed(&mut timings, file, || {
+                read_source(file, max_file_size, &mut skipped)
+            })?;
+            workspace_sources.extend(parsed);
+        }
+        if let Some((path, source)) = &options.buffer {
+            workspace_sources.push(ParsedFile::from_source(path, source.clone()));
+        }
+
+        // Files that `#[path]` attributes point to outside of the package
+        // directories are checked too, unless the path filter rejects them.
+        let mut workspace_modules = ModulePaths::resolve(&packages, &workspace_sources);
+        loop {
+            let declared: Vec<PathBuf> = workspace_modules
+                .undiscovered()
+                .iter()
+                .filter(|file| file.is_file() && !all_files.contains(file))
+                .filter(|file| {
+                    let dir = package::package_index(&packages, file)
+                        .map_or(&workspace_root, |idx| &packages[idx].dir);
+                    let relative = file.strip_prefix(dir).unwrap_or(file);
+                    let in_skipped_dir = relative.parent().is_some_and(|parent| {
+                        parent
+                            .components()
+                            .any(|c| path_filter.skips_dir(&c.as_os_str().to_string_lossy()))
+                    });
+                    !in_skipped_dir && path_filter.accepts(&relative.to_string_lossy())
+                })
+                .cloned()
+                .collect();
+            if explicit || declared.is_empty() {
+                break;
+            }
+            for file in declared {
+                let parsed = timed(&mut timings, &file, || {
+                    read_source(&file, max_file_size, &mut skipped)
+                })?;
+                workspace_sources.extend(parsed);
+                all_files.push(file);
+            }
+            workspace_modules = ModulePaths::resolve(&packages, &workspace_sources);
+        }
+
+        // Code that isn't compiled for the target platform, or with the
+        // feature flags if any, is removed before any check sees it.
+        let target = match &options.target {
+            _ if options.any_target => None,
+            Some(triple) => Some(cfg::Target::from_triple(triple)?),
+            None => Some(cfg::Target::host()),
+        };
+        let feature_flags =
+            !options.features.is_empty() || options.all_features || options.no_default_features;
+        let requested: Vec<String> = options
+            .features
+            .iter()
+            .flat_map(|features| features.split([',', ' ']))
+            .filter(|feature| !feature.is_empty())
+            .map(str::to_string)
+            .collect();
+        let package_cfgs: Vec<cfg::Cfg> = packages
+            .iter()
+            .map(|p| cfg::Cfg {
+                features: if feature_flags {
+                    cfg::Features::resolve(
+                        &p.name,
+                        &p.features,
+                        &requested,
+                        options.all_features,
+                        options.no_default_features,
+                    )
+                } else {
+                    cfg::Features::Undecided
+                },
+                target: target.clone(),
+            })
+            .collect();
+        let other_cfg = cfg::Cfg {
+            features: cfg::Features::Undecided,
+            target,
+        };
+        for file in &mut workspace_sources {
+            let cfg = match workspace_modules.package(&file.path) {
+                Some(idx) => &package_cfgs[idx],
+                None => &other_cfg,
+            };
+            if let Some(syntax) = &mut file.syntax {
+                cfg::strip(syntax, cfg);
+            }
+        }
+        // Files only declared by modules that were removed aren't compiled
+        // either.
+        let compiled_modules = ModulePaths::resolve(&packages, &workspace_sources);
+        workspace_sources.retain(|file| {
+            !workspace_modules.contains(&file.path) || compiled_modules.contains(&file.path)
+        });
+        let workspace_modules = compiled_modules;
+        let mut dependency_sources: Vec<ParsedFile> = Vec::new();
+        for file in &dependency_files {
+            dependency_sources.extend(read_source(file, max_file_size, &mut skipped)?);
+        }
+
+        let dependency_modules = ModulePaths::resolve(&dependencies, &dependency_sources);
+
+        // Pass 1: Collect all #[context]-annotated functions
+        let mut all_annotated = Vec::new();
+        let mut all_reexports = Vec::new();
+        for file in &workspace_sources {
+            let collected = collector::collect_from_file(
+                file,
+                &workspace_modules.get(&file.path),
+                &config.context_attributes,
+                &function_rules,
+            );
+            all_annotated.extend(collected.functions);
+            all_reexports.extend(collected.reexports);
+        }
+        if let Some(path) = &options.emit_index {
+            let collected = collector::Collected {
+                functions: all_annotated.clone(),
+                reexports: all_reexports.clone(),
+            };
+            collector::write_index(path, &collected)?;
+        }
+        for path in &options.load_index {
+            let collected = collector::read_index(path)?;
+            all_annotated.extend(collected.functions);
+            all_reexports.extend(collected.reexports);
+        }
+        for file in &dependency_sources {
+            let collected = collector::collect_from_file(
+                file,
+                &dependency_modules.get(&file.path),
+                &config.context_attributes,
+                &function_rules,
+            );
+            all_annotated.extend(collected.functions);
+            all_reexports.extend(collected.reexports);
+        }
+
+        let mut index = collector::build_index(all_annotated.clone());
+        index.add_reexports(&all_reexports);
+        let call_graph = (options.call_graph || options.emit_graph.is_some())
+            .then(|| graph::build(&workspace_sources, &index, &config.context_methods));
+        if let (Some(path), Some(call_graph)) = (&options.emit_graph, &call_graph) {
+            graph::write(path, call_graph)?;
+        }
+
+        // With `changed_since` or `staged`, only the changed files are
+        // checked.
+        let changed = match &options.changed_since {
+            Some(base) => Some(git::changed_files(&workspace_root, base)?),
+            None if options.staged => Some(git::staged_files(&workspace_root)?),
+            None => None,
+        };
+        let is_checked = |path: &Path| {
+            // Explicitly given files may lie outside of all packages.
+            workspace_modules
+                .package(path)
+                .is_none_or(|idx| selected.contains(&idx))
+                && changed.as_ref().is_none_or(|changed| {
+                    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+                    changed.contains(&path)
+                })
+        };
+        let checked_sources: Vec<&ParsedFile> = workspace_sources
+            .iter()
+            .filter(|file| is_checked(&file.path))
+            .collect();
+
+        // With `expand`, call sites are checked in the expanded source instead
+        // of the `src/` files it was expanded from.
+        let mut expanded_sources: Vec<ParsedFile> = Vec::new();
+        let mut expanded_module_paths: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        let mut expanded_packages: HashSet<usize> = HashSet::new();
+        if options.expand {
+            for crate_ in expand::expand_packages(&packages, &selected, &target_dir)? {
+                expanded_sources.push(ParsedFile::read(&crate_.file)?);
+                expanded_packages.insert(crate_.package);
+                expanded_module_paths.insert(crate_.file, crate_.module_path);
+            }
+        }
+        // Call sites in `src/` of expanded packages are checked in the
+        // expanded source.
+        let is_expanded = |file: &ParsedFile| {
+            package::package_index(&packages, &file.path).is_some_and(|idx| {
+                expanded_packages.contains(&idx)
+                    && file.path.starts_with(packages[idx].dir.join("src"))
+            })
+        };
+        let check_double_context = levels.double_context != LintLevel::Allow;
+        let min_confidence = options
+            .min_confidence
+            .unwrap_or(config.double_context.min_confidence);
+        let confident = |issue: &&DoubleContext| options.heuristics.reports(issue, min_confidence);
+        let only_pub = options.only_pub || config.unattributed.only_pub;
+        let module_paths: HashMap<&Path, Vec<String>> = workspace_sources
+            .iter()
+            .map(|file| (file.path.as_path(), workspace_modules.get(&file.path)))
+            .collect();
+        let workspace_items = unattributed::WorkspaceItems {
+            local_traits: options
+                .unattributed_trait_impls
+                .then(|| unattributed::local_traits(&workspace_sources)),
+            result_aliases: unattributed::result_aliases(
+                workspace_sources
+                    .iter()
+                    .map(|file| (file, module_paths[file.path.as_path()].as_slice())),
+                &config,
+            ),
+            annotated: {
+                let mut annotated: HashMap<Arc<str>, HashSet<usize>> = HashMap::new();
+                for function in &all_annotated {
+                    annotated
+                        .entry(function.file.clone())
+                        .or_default()
+                        .insert(function.line);
+                }
+                annotated
+            },
+        };
+        let mut unattributed_counts = vec![0usize; packages.len()];
+        let mut option_context = Vec::new();
+        // Plugins are loaded once and run on each checked file.
+        let mut plugins = Vec::new();
+        if levels.plugin != LintLevel::Allow {
+            for config in &config.plugins {
+                plugins.push(Plugin::load(config, &workspace_root)?);
+            }
+        }
+
+        if levels.style != LintLevel::Allow {
+            for issue in style::check_annotated(&index, &config.style) {
+                if is_checked(Path::new(&issue.file)) {
+                    on_finding(Finding::Style(&issue));
+                }
+            }
+        }
+
+        let dictionary = if levels.style != LintLevel::Allow {
+            spelling::Dictionary::load(&config.spelling, &workspace_root)?
+        } else {
+            None
+        };
+        if let Some(dictionary) = &dictionary {
+            for issue in spelling::check_annotated(&index, dictionary) {
+                if is_checked(Path::new(&issue.file)) {
+                    on_finding(Finding::Style(&issue));
+                }
+            }
+        }
+
+        let denylist = banned::Denylist::new(&config.banned_context.patterns)?;
+        let check_banned = levels.banned_context != LintLevel::Allow && !denylist.is_empty();
+        if check_banned {
+            for issue in banned::check_annotated(&index, &denylist) {
+                if is_checked(Path::new(&issue.file)) {
+                    on_finding(Finding::BannedContext(&issue));
+                }
+            }
+        }
+
+        for file in checked_sources.iter().copied() {
+            // Plugins see the findings of the built-in checks in the file.
+            let mut builtin: Vec<InputFinding> = Vec::new();
+            let mut emit = |finding: Finding<'_>| {
+                if !plugins.is_empty() {
+                    builtin.push(InputFinding::new(&finding));
+                }
+                on_finding(finding);
+            };
+            let module_path = &module_paths[file.path.as_path()];
+            if check_double_context && !is_expanded(file) {
+                let issues = timed(&mut timings, &file.path, || {
+                    checker::check_file(file, module_path, &index, &config.context_methods)
+                });
+                let issues = split_option_context(issues, &mut option_context);
+                issues
+                    .iter()
+                    .filter(confident)
+                    .for_each(|issue| emit(Finding::DoubleContext(issue)));
+            }
+
+            if options.doc_tests && check_double_context {
+                let issues = timed(&mut timings, &file.path, || {
+                    doctest::check_file(file, &index, &config.context_methods)
+                });
+                let issues = split_option_context(issues, &mut option_context);
+                issues
+                    .iter()
+                    .filter(confident)
+                    .for_each(|issue| emit(Finding::DoubleContext(issue)));
+            }
+
+            if levels.unattributed != LintLevel::Allow {
+                let mut issues = timed(&mut timings, &file.path, || {
+                    unattributed::check_file(file, &config, module_path, &workspace_items)
+                });
+                if only_pub {
+                    issues.retain(|issue| issue.is_pub);
+                }
+                if let Some(idx) = workspace_modules.package(&file.path) {
+                    unattributed_counts[idx] += issues.len();
+                }
+                issues
+                    .iter()
+                    .for_each(|issue| emit(Finding::Unattributed(issue)));
+            }
+
+            if levels.style != LintLevel::Allow {
+                let issues = timed(&mut timings, &file.path, || {
+                    let mut issues = style::check_file(file);
+                    issues.extend(style::check_messages(file, &index));
+                    issues.extend(style::check_message_lengths(
+                        file,
+                        &config.style,
+                        &config.context_methods,
+                    ));
+                    issues.extend(style::check_sensitive(
+                        file,
+                        &index,
+                        &config.style,
+                        &config.context_methods,
+                    ));
+                    if config.style.delegating_wrapper {
+                        issues.extend(style::check_delegation(file, &index));
+                    }
+                    if let Some(dictionary) = &dictionary {
+                        issues.extend(spelling::check_file(
+                            file,
+                            dictionary,
+                            &config.context_methods,
+                        ));
+                    }
+                    issues
+                });
+                issues.iter().for_each(|issue| emit(Finding::Style(issue)));
+            }
+
+            if check_banned {
+                let issues = timed(&mut timings, &file.path, || {
+                    banned::check_file(file, &denylist, &config.context_methods)
+                });
+                issues
+                    .iter()
+                    .for_each(|issue| emit(Finding::BannedContext(issue)));
+            }
+
+            if levels.bare_propagation != LintLevel::Allow {
+                let issues = timed(&mut timings, &file.path, || {
+                    propagation::check_file(file, &config, module_path, &workspace_items)
+                });
+                issues
+                    .iter()
+                    .for_each(|issue| emit(Finding::BarePropagation(issue)));
+            }
+
+            if !plugins.is_empty() {
+                let relative = file
+                    .path
+                    .strip_prefix(&workspace_root)
+                    .unwrap_or(&file.path);
+                let relative = relative.to_string_lossy();
+                let display_path = file.display_path();
+                let tokens = timed(&mut timings, &file.path, || plugin::tokens(&file.source));
+                for plugin in &mut plugins {
+                    let issues = timed(&mut timings, &file.path, || {
+                        plugin.check(&display_path, &relative, &file.source, &tokens, &builtin)
+                    })?;
+                    issues
+                        .iter()
+                        .for_each(|issue| on_finding(Finding::Plugin(issue)));
+                }
+            }
+        }
+
+        if check_double_context {
+            for file in &expanded_sources {
+                let module_path = &expanded_module_paths[&file.path];
+                let issues = timed(&mut timings, &file.path, || {
+                    checker::check_file(file, module_path, &index, &config.context_methods)
+                });
+                let mut issues = split_option_context(issues, &mut option_context);
+                issues.retain(|issue| confident(&issue));
+                for issue in &mut issues {
+                    // Positions in and edits to the expanded source would
+                    // be meaningless.
+                    issue.call_span = Default::default();
+                    issue.fix = None;
+                    on_finding(Finding::DoubleContext(issue));
+                }
+            }
+        }
+
+        let missing_dependency = package::missing_dependency(&packages, &unattributed_counts);
+        let coverage = checked_sources
+            .iter()
+            .map(|file| {
+                (
+                    file.path.clone(),
+                    unattributed::coverage(
+                        file,
+                        &config,
+                        &module_paths[file.path.as_path()],
+                        &workspace_items,
+                    ),
+                )
+            })
+            .collect();
+        let modules = workspace_sources
+            .iter()
+            .map(|file| (file.path.clone(), workspace_modules.file_module(&file.path)))
+            .collect();
+        let checked_files = checked_sources
+            .iter()
+            .map(|file| file.path.clone())
+            .collect();
+        let base = base_commit.map(|commit| BaseComparison {
+            commit,
+            unchanged,
+            fixed: base_findings
+                .values()
+                .filter(|(file, _)| is_checked(&workspace_root.join(file)))
+                .map(|(_, count)| count)
+                .sum(),
+        });
+
+        Ok(Report {
+            workspace_root,
+            levels: *levels,
+            packages,
+            selected,
+            source_dirs,
+            files: all_files,
+            dependencies,
+            dependency_files,
+            checked_files,
+            expanded_files: expanded_module_paths.into_keys().collect(),
+            annotated: all_annotated,
+            base,
+            baselined,
+            graph: call_graph,
+            coverage,
+            modules,
+            skipped,
+            timings,
+            option_context,
+            missing_dependency,
+            ..Default::default()
+        })
+    }
+
+    /// Run the checks with the same options on the files of `commit`,
+    /// exported to `target_dir`.
+    fn base_findings(
+        &self,
+        commit: &str,
+        workspace_root: &Path,
+        target_dir: &Path,
+    ) -> Result<BaseFindings> {
+        let toplevel = std::fs::canonicalize(git::toplevel(workspace_root)?)?;
+        let in_tree = |path: &Path| -> Result<PathBuf> {
+            let path = std::fs::canonicalize(path)?;
+            Ok(path.strip_prefix(&toplevel).unwrap_or(&path).to_path_buf())
+        };
+        let tree = target_dir.join("context-lint").join("base").join(commit);
+        git::export_tree(workspace_root, commit, &tree)?;
+
+        let base_root = tree.join(in_tree(workspace_root)?);
+        let mut manifest_paths = Vec::new();
+        if self.options.manifest_paths.is_empty() {
+            manifest_paths.push(base_root.join("Cargo.toml"));
+        }
+        for path in &self.options.manifest_paths {
+            manifest_paths.push(tree.join(in_tree(path)?));
+        }
+        // A workspace that didn't exist yet has no findings.
+        manifest_paths.retain(|path| path.exists());
+        let path = self.options.path.as_ref().map(|_| base_root.clone());
+        let mut findings = BaseFindings::new();
+        match &path {
+            Some(path) if !path.exists() => return Ok(findings),
+            None if manifest_paths.is_empty() => return Ok(findings),
+            _ => {}
+        }
+        let several = manifest_paths.len() > 1;
+        let options = LintOptions {
+            manifest_paths,
+            path,
+            files: Vec::new(),
+            buffer: None,
+            emit_index: None,
+            emit_graph: None,
+            call_graph: false,
+            changed_since: None,
+            staged: false,
+            deny_new: None,
+            baseline: None,
+            ..self.options.clone()
+        };
+        let report = Linter::new(options)
+            .run()
+            .with_context(|| format!("Checking the merge base {commit}"))?;
+        // Relative to the same directory as the current findings, even if
+        // some of the workspaces didn't exist yet.
+        let root = if several {
+            &base_root
+        } else {
+            &report.workspace_root
+        };
+        let prefix = format!("{}/", root.display());
+        for finding in report.iter() {
+            let file = strip_path(finding.file(), Some(&prefix)).to_string();
+            findings
+                .entry(fingerprint::of_finding(&finding, Some(&prefix)))
+                .or_insert((file, 0))
+                .1 += 1;
+        }
+        Ok(findings)
+    }
+}
+
+/// Move the call sites whose context goes on an `Option` to `skipped`,
+/// returning the others.
+fn split_option_context(
+    issues: Vec<DoubleContext>,
+    skipped: &mut Vec<DoubleContext>,
+) -> Vec<DoubleContext> {
+    let (option, result) = issues
+        .into_iter()
+        .partition(|issue| issue.option_receiver.is_some());
+    skipped.extend::<Vec<_>>(option);
+    result
+}
+
+/// Read and parse a file, or record in `skipped` why it wasn't.
+fn read_source(
+    path: &Path,
+    max_size: Option<u64>,
+    skipped: &mut Vec<SkippedFile>,
+) -> Result<Option<ParsedFile>> {
+    match ParsedFile::read_guarded(path, max_size)? {
+        Ok(file) => Ok(Some(file)),
+        Err(reason) => {
+            skipped.push(SkippedFile {
+                path: path.to_path_buf(),
+                reason,
+            });
+            Ok(None)
+        }
+    }
+}
+
+/// Find the Rust files below `dir` whose path relative to `dir` passes `filter`.
+///
+/// With `follow_symlinks`, symlinked files and directories are included.
+/// Symlink cycles end the walk of their branch, and a file reached through
+/// several paths is kept once, under the path through the fewest symlinked
+/// directories, preferring one that isn't a link itself: the path a `mod`
+/// declaration resolves to, rather than a linked copy of its directory.
+fn find_rust_files(dir: &Path, filter: &PathFilter, follow_symlinks: bool) -> Vec<PathBuf> {
+    // Each file, with how many symlinked directories lead to it and whether
+    // it is a link itself, lower being preferred.
+    let mut files: Vec<(PathBuf, (usize, bool))> = Vec::new();
+    // How many symlinked directories lead to the directories being walked,
+    // by depth.
+    let mut links: Vec<usize> = Vec::new();
+    let mut walker = WalkDir::new(dir)
+        .follow_links(follow_symlinks)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        // Errors, including the loops walkdir detects itself, are skipped.
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy();
+        let parent_links = entry.depth().checked_sub(1).map_or(0, |depth| links[depth]);
+        if entry.file_type().is_dir() {
+            // Skip hidden directories, target directories, and common
+            // non-source dirs. The directory itself is checked even if it is
+            // named like vendored code.
+            let skipped = name == "target"
+                || name == ".git"
+                || name == ".hg"
+                || (entry.depth() > 0 && filter.skips_dir(&name));
+            if skipped {
+                walker.skip_current_dir();
+            } else {
+                links.truncate(entry.depth());
+                links.push(parent_links + usize::from(entry.path_is_symlink()));
+            }
+            continue;
+        }
+        if !entry.file_type().is_file() || entry.path().extension().is_none_or(|ext| ext != "rs") {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        if filter.accepts(&relative.to_string_lossy()) {
+            let rank = (parent_links, entry.path_is_symlink());
+            files.push((entry.into_path(), rank));
+        }
+    }
+    if !follow_symlinks {
+        return files.into_iter().map(|(path, _)| path).collect();
+    }
+
+    // The preferred path of each file, by canonical path.
+    let mut preferred: HashMap<PathBuf, usize> = HashMap::new();
+    for (i, (path, rank)) in files.iter().enumerate() {
+        let Ok(canonical) = std::fs::canonicalize(path) else {
+            continue;
+        };
+        let best = preferred.entry(canonical).or_insert(i);
+        if *rank < files[*best].1 {
+            *best = i;
+        }
+    }
+    let kept: HashSet<usize> = preferred.into_values().collect();
+    files
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| kept.contains(i))
+        .map(|(_, (path, _))| path)
+        .collect()
+}
+
+/// The packages of a cargo workspace and where they live.
+struct Workspace {
+    /// The workspace member packages.
+    packages: Vec<Package>,
+    /// Dependencies scanned for annotated functions (`include_deps`).
+    dependencies: Vec<Package>,
+    /// The workspace root directory.
+    root: PathBuf,
+    /// The target directory.
+    target_dir: PathBuf,
+}
+
+/// A workspace of all Rust files below `dir`, without `cargo metadata`: a
+/// single package named after the directory, with a library target for each
+/// `lib.rs` and a binary target for each `main.rs`, named after the
+/// directory containing them (or its parent, for `src`).
+fn plain_workspace(dir: &Path, follow_symlinks: bool) -> Result<Workspace> {
+    let dir = std::path::absolute(dir).with_context(|| format!("Resolving {}", dir.display()))?;
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    let dir_name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let mut targets = Vec::new();
+    for file in find_rust_files(&dir, &PathFilter::default(), follow_symlinks) {
+        let kind = match file.file_name().and_then(|name| name.to_str()) {
+            Some("lib.rs") => "lib",
+            Some("main.rs") => "bin",
+            _ => continue,
+        };
+        let Some(mut parent) = file.parent() else {
+            continue;
+        };
+        if parent.ends_with("src") && parent != dir {
+            parent = parent.parent().unwrap_or(parent);
+        }
+        targets.push(package::Target {
+            name: dir_name(parent),
+            kinds: vec![kind.to_string()],
+            src_path: file,
+        });
+    }
+    let package = Package {
+        name: dir_name(&dir),
+        manifest_path: dir.join("Cargo.toml"),
+        dir: dir.clone(),
+        // Unknown, so no note suggests adding it.
+        has_fn_error_context: true,
+        targets,
+        features: Default::default(),
+    };
+    Ok(Workspace {
+        packages: vec![package],
+        dependencies: Vec::new(),
+        target_dir: dir.join("target"),
+        root: dir,
+    })
+}
+
+/// Discover the packages of several workspaces, merged into one whose root
+/// is the closest directory containing all of them. Members of one
+/// workspace aren't also dependencies. Workspaces sharing no directory but
+/// the filesystem root are rejected: their configs and report paths would
+/// all be relative to `/`.
+fn discover_workspaces(manifest_paths: &[PathBuf], include_deps: bool) -> Result<Workspace> {
+    let [first, rest @ ..] = manifest_paths else {
+        return discover_workspace(None, include_deps);
+    };
+    let mut merged = discover_workspace(Some(first), include_deps)?;
+    let mut roots = vec![merged.root.clone()];
+    for path in rest {
+        let workspace = discover_workspace(Some(path), include_deps)
+            .with_context(|| format!("Loading the workspace of {}", path.display()))?;
+        roots.push(workspace.root);
+        for package in workspace.packages {
+            if !merged
+                .packages
+                .iter()
+                .any(|p| p.manifest_path == package.manifest_path)
+            {
+                merged.packages.push(package);
+            }
+        }
+        merged.dependencies.extend(workspace.dependencies);
+    }
+    let mut seen = HashSet::new();
+    merged.dependencies.retain(|dependency| {
+        !merged
+            .packages
+            .iter()
+            .any(|p| p.manifest_path == dependency.manifest_path)
+            && seen.insert(dependency.manifest_path.clone())
+    });
+    merged.root = common_ancestor(&roots)?;
+    Ok(merged)
+}
+
+/// The deepest directory containing all of `paths`, other than the
+/// filesystem root.
+fn common_ancestor(paths: &[PathBuf]) -> Result<PathBuf> {
+    let mut common = paths.first().cloned().unwrap_or_default();
+    for path in paths {
+        while !path.starts_with(&common) && common.pop() {}
+    }
+    if common.parent().is_none() {
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        bail!(
+            "The workspaces {} share no directory but {}; check them separately",
+            paths.join(", "),
+            common.display()
+        );
+    }
+    Ok(common)
+}
+
+/// Discover the workspace member packages using `cargo_metadata`. With
+/// `include_deps`, also the resolved dependencies that depend on
+/// `fn-error-context` themselves.
+fn discover_workspace(manifest_path: Option<&Path>, include_deps: bool) -> Result<Workspace> {
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    if !include_deps {
+        cmd.no_deps();
+    }
+    if let Some(path) = manifest_path {
+        cmd.manifest_path(path);
+    }
+    let metadata = cmd.exec().context("Running cargo metadata")?;
+
+    let mut packages = Vec::new();
+    let mut dependencies = Vec::new();
+    for package in &metadata.packages {
+        let package_info = to_package(package);
+        if metadata.workspace_members.contains(&package.id) {
+            packages.push(package_info);
+        } else if package_info.has_fn_error_context {
+            // Without `#[context]` there is nothing to collect.
+            dependencies.push(package_info);
+        }
+    }
+
+    Ok(Workspace {
+        packages,
+        dependencies,
+        root: PathBuf::from(&metadata.workspace_root),
+        target_dir: PathBuf::from(&metadata.target_directory),
+    })
+}
+
+fn to_package(package: &cargo_metadata::Package) -> Package {
+    let manifest_path = PathBuf::from(&package.manifest_path);
+    let dir = manifest_path
+        .parent()
+        .expect("manifest path should have parent")
+        .to_path_buf();
+    let has_fn_error_context = package
+        .dependencies
+        .iter()
+        .any(|dep| dep.name.replace('-', "_") == "fn_error_context");
+    let targets = package
+        .targets
+        .iter()
+        .map(|target| package::Target {
+            name: target.name.clone(),
+            kinds: target.kind.iter().map(|kind| kind.to_string()).collect(),
+            src_path: PathBuf::from(&target.src_path),
+        })
+        .collect();
+    Package {
+        name: package.name.to_string(),
+        manifest_path,
+        dir,
+        has_fn_error_context,
+        targets,
+        features: package.features.clone(),
+    }
+}
+
+/// Run `f`, adding the time it takes to the time spent on `path`.
+fn timed<T>(timings: &mut HashMap<PathBuf, Duration>, path: &Path, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    *timings.entry(path.to_path_buf()).or_default() += start.elapsed();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::Definition;
+    use crate::fixtures::{unattributed, TempDir};
+
+    #[test]
+    fn test_common_ancestor() {
+        let paths = [
+            PathBuf::from("/repo/services/api"),
+            PathBuf::from("/repo/services/worker"),
+            PathBuf::from("/repo/tools"),
+        ];
+        assert_eq!(common_ancestor(&paths).unwrap(), PathBuf::from("/repo"));
+        assert_eq!(
+            common_ancestor(&paths[..2]).unwrap(),
+            PathBuf::from("/repo/services")
+        );
+        assert_eq!(
+            common_ancestor(&paths[..1]).unwrap(),
+            PathBuf::from("/repo/services/api")
+        );
+
+        let disjoint = [PathBuf::from("/repo"), PathBuf::from("/opt/tools")];
+        let err = common_ancestor(&disjoint).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The workspaces /repo, /opt/tools share no directory but /; check them separately"
+        );
+    }
+
+    #[test]
+    fn test_find_rust_files_vendored() {
+        let dir = TempDir::new("vendor");
+        for file in [
+            "src/lib.rs",
+            "vendor/dep/src/lib.rs",
+            "src/third_party/x.rs",
+        ] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let files = |filter: &PathFilter| {
+            let mut files: Vec<String> = find_rust_files(&dir, filter, false)
+                .iter()
+                .map(|f| f.strip_prefix(&dir).unwrap().display().to_string())
+                .collect();
+            files.sort();
+            files
+        };
+
+        let mut filter = PathFilter::default();
+        assert_eq!(files(&filter).len(), 3);
+        filter.skip_dirs = crate::config::PathsConfig::default().vendored;
+        assert_eq!(files(&filter), ["src/lib.rs"]);
+        // A package living in a vendored directory is still checked.
+        let vendored = find_rust_files(&dir.join("vendor/dep"), &filter, false);
+        assert_eq!(vendored.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_rust_files_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = TempDir::new("links");
+        let pkg = root.join("pkg");
+        std::fs::create_dir_all(pkg.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("shared")).unwrap();
+        std::fs::write(pkg.join("src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("shared/util.rs"), "").unwrap();
+        std::fs::create_dir_all(pkg.join("src/real")).unwrap();
+        std::fs::write(pkg.join("src/real/mod.rs"), "").unwrap();
+        // Shared sources linked twice, as a directory and as a file, and a
+        // cycle back to the package.
+        symlink("../../shared", pkg.join("src/again")).unwrap();
+        symlink("../../shared", pkg.join("src/shared")).unwrap();
+        symlink("../../shared/util.rs", pkg.join("src/util.rs")).unwrap();
+        symlink("..", pkg.join("src/cycle")).unwrap();
+        // An alias of a directory of the package, sorting before it.
+        symlink("real", pkg.join("src/alias")).unwrap();
+
+        let files = |follow_symlinks| {
+            find_rust_files(&pkg, &PathFilter::default(), follow_symlinks)
+                .iter()
+                .map(|f| f.strip_prefix(&pkg).unwrap().display().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(files(false), ["src/lib.rs", "src/real/mod.rs"]);
+        // Files are kept under the paths `mod util;` and `mod real;` declare.
+        assert_eq!(
+            files(true),
+            ["src/lib.rs", "src/real/mod.rs", "src/util.rs"]
+        );
+    }
+
+    #[test]
+    fn test_plain_workspace() {
+        let dir = TempDir::new("plain");
+        for file in [
+            "libs/store/src/lib.rs",
+            "tools/cli/main.rs",
+            "main.rs",
+            "target/x/lib.rs",
+        ] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let workspace = plain_workspace(&dir, false).unwrap();
+        assert_eq!(workspace.root, *dir);
+        let [package] = workspace.packages.as_slice() else {
+            panic!("expected one package");
+        };
+        let mut targets: Vec<(String, bool, String)> = package
+            .targets
+            .iter()
+            .map(|t| {
+                let file = t.src_path.strip_prefix(&dir).unwrap();
+                (t.name.clone(), t.is_lib(), file.display().to_string())
+            })
+            .collect();
+        targets.sort();
+        let dir_name = dir.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(
+            targets,
+            [
+                ("cli".to_string(), false, "tools/cli/main.rs".to_string()),
+                (dir_name, false, "main.rs".to_string()),
+                (
+                    "store".to_string(),
+                    true,
+                    "libs/store/src/lib.rs".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fails() {
+        let report = Report {
+            unattributed: vec![unattributed("a.rs")],
+            ..Default::default()
+        };
+        let mut options = LintOptions::default();
+        assert!(options.fails(&report));
+        options.error_on = vec![Lint::DoubleContext];
+        assert!(!options.fails(&report));
+        options.error_on.clear();
+        options.levels.unattributed = LintLevel::Warn;
+        assert!(!options.fails(&report));
+        options.levels.unattributed = LintLevel::Deny;
+        options.warn_only = true;
+        assert!(!options.fails(&report));
+    }
+
+    fn double_context() -> DoubleContext {
+        DoubleContext {
+            call_file: "a.rs".into(),
+            call_line: 1,
+            call_span: Default::default(),
+            function_name: "load".into(),
+            inner_context: Some("Loading".to_string()),
+            inner_source: Default::default(),
+            inner_args: Vec::new(),
+            outer_context: Some("Reading".to_string()),
+            def_file: "b.rs".into(),
+            def_line: 1,
+            def_span: Default::default(),
+            outer_method: "context".to_string(),
+            fix: None,
+            match_kind: MatchKind::Name,
+            confidence: 0.5,
+            other_definitions: Vec::new(),
+            option_receiver: None,
+            caller: None,
+        }
+    }
+
+    #[test]
+    fn test_severity() {
+        let mut issue = double_context();
+        let severity = |issue: &DoubleContext, level| Finding::DoubleContext(issue).severity(level);
+        assert_eq!(severity(&issue, LintLevel::Deny), Severity::Warning);
+        assert_eq!(severity(&issue, LintLevel::Warn), Severity::Info);
+        issue.match_kind = MatchKind::Path;
+        assert_eq!(severity(&issue, LintLevel::Deny), Severity::Error);
+        assert_eq!(severity(&issue, LintLevel::Warn), Severity::Warning);
+        issue.outer_context = Some("loading".to_string());
+        assert_eq!(severity(&issue, LintLevel::Warn), Severity::Error);
+        issue.outer_context = Some("Reading".to_string());
+        issue.match_kind = MatchKind::Name;
+        issue.caller = Some(Definition {
+            file: "a.rs".into(),
+            line: 1,
+            span: Default::default(),
+            inner_context: Some("Starting".to_string()),
+            source: Default::default(),
+        });
+        assert_eq!(severity(&issue, LintLevel::Warn), Severity::Error);
+
+        let issue = unattributed("a.rs");
+        let finding = Finding::Unattributed(&issue);
+        assert_eq!(finding.severity(LintLevel::Warn), Severity::Warning);
+    }
+
+    #[test]
+    fn test_heuristics() {
+        let mut issue = double_context();
+        assert!(Heuristics::Default.reports(&issue, 0.5));
+        assert!(!Heuristics::Default.reports(&issue, 0.6));
+        assert!(!Heuristics::Strict.reports(&issue, 0.0));
+        assert!(Heuristics::Lenient.reports(&issue, 0.6));
+        issue.match_kind = MatchKind::Type;
+        issue.confidence = 0.7;
+        assert!(Heuristics::Strict.reports(&issue, 0.6));
+        assert!(!Heuristics::Strict.reports(&issue, 0.9));
+    }
+
+    #[test]
+    fn test_iter() {
+        let report = Report {
+            unattributed: vec![unattributed("a.rs"), unattributed("b.rs")],
+            ..Default::default()
+        };
+        let files: Vec<(Lint, &str)> = report.iter().map(|f| (f.lint(), f.file())).collect();
+        assert_eq!(
+            files,
+            [(Lint::Unattributed, "a.rs"), (Lint::Unattributed, "b.rs")]
+        );
+    }
+
+    #[test]
+    fn test_run_with() {
+        let dir = TempDir::new("run-with");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("src/lib.rs"),
+            r#"use anyhow::{Context, Result};
+use fn_error_context::context;
+
+#[context("Loading config")]
+fn load_config() -> Result<String> {
+    Ok(String::new())
+}
+
+pub fn run() -> Result<()> {
+    let config = load_config().context("Reading config")?;
+    println!("{config}");
+    Ok(())
+}
+"#,
+        )
+        .unwrap();
+        let linter = Linter::new(LintOptions {
+            path: Some(dir.to_path_buf()),
+            ..Default::default()
+        });
+        let mut found = Vec::new();
+        let report = linter
+            .run_with(|finding| {
+                let file = Path::new(finding.file()).strip_prefix(&dir).unwrap();
+                found.push((finding.lint(), file.display().to_string(), finding.line()));
+            })
+            .unwrap();
+        found.sort_by_key(|(lint, _, line)| (lint.code(), *line));
+        assert_eq!(
+            found,
+            [
+                (Lint::DoubleContext, "src/lib.rs".to_string(), 10),
+                (Lint::Unattributed, "src/lib.rs".to_string(), 9),
+            ]
+        );
+        assert_eq!(report.iter().count(), 0);
+        assert_eq!(report.checked_files, [dir.join("src/lib.rs")]);
+    }
+}
@@ -0,0 +1,283 @@
+//! The checks, their stable codes and extended explanations (`explain`).
+
+/// A check, i.e. a category of findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Lint {
+    /// Double context at call sites of annotated functions.
+    DoubleContext,
+    /// Functions returning `anyhow::Result` without `#[context]`.
+    Unattributed,
+    /// Wasteful uses of `.context()` / `.with_context()`.
+    Style,
+    /// Functions propagating errors with bare `?`.
+    BarePropagation,
+    /// Findings of WebAssembly plugins.
+    Plugin,
+    /// Context strings matching a banned pattern.
+    BannedContext,
+}
+
+impl Lint {
+    /// All checks, in code order.
+    pub const ALL: [Lint; 6] = [
+        Lint::DoubleContext,
+        Lint::Unattributed,
+        Lint::Style,
+        Lint::BarePropagation,
+        Lint::Plugin,
+        Lint::BannedContext,
+    ];
+
+    /// The stable code shown with findings, e.g. `CL0001`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Lint::DoubleContext => "CL0001",
+            Lint::Unattributed => "CL0002",
+            Lint::Style => "CL0003",
+            Lint::BarePropagation => "CL0004",
+            Lint::Plugin => "CL0005",
+            Lint::BannedContext => "CL0006",
+        }
+    }
+
+    /// The name used on the command line, e.g. `double-context`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::DoubleContext => "double-context",
+            Lint::Unattributed => "unattributed",
+            Lint::Style => "style",
+            Lint::BarePropagation => "bare-propagation",
+            Lint::Plugin => "plugin",
+            Lint::BannedContext => "banned-context",
+        }
+    }
+
+    /// Look up a check by code (case-insensitive) or name.
+    pub fn find(code_or_name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| {
+            lint.code().eq_ignore_ascii_case(code_or_name) || lint.name() == code_or_name
+        })
+    }
+
+    /// The extended description printed by `explain`.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Lint::DoubleContext => DOUBLE_CONTEXT,
+            Lint::Unattributed => UNATTRIBUTED,
+            Lint::Style => STYLE,
+            Lint::BarePropagation => BARE_PROPAGATION,
+            Lint::Plugin => PLUGIN,
+            Lint::BannedContext => BANNED_CONTEXT,
+        }
+    }
+}
+
+/// How serious a finding is, derived from the level of its check and the
+/// finding itself.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// The next less serious severity.
+    pub fn lower(self) -> Severity {
+        match self {
+            Severity::Error => Severity::Warning,
+            Severity::Warning | Severity::Info => Severity::Info,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+const DOUBLE_CONTEXT: &str = r#"A function annotated with `#[context("...")]` is called and the caller adds
+context to its error again.
+
+`#[context]` already wraps every error the function returns with its
+message. Adding `.context()`, `.with_context()` (or eyre's `.wrap_err()`,
+or an `anyhow!` in `.map_err()`) at the call site stacks a second layer
+that usually says the same thing, which makes error chains noisy and
+repetitive.
+
+Example:
+
+    #[context("Loading config")]
+    fn load_config() -> anyhow::Result<Config> { ... }
+
+    let config = load_config().context("Loading config")?;  // CL0001
+
+Fix: remove the outer call (`--fix` does this), or drop the attribute if
+the call sites know better what went wrong.
+
+To suppress: `--allow double-context` (or `-A double-context`) skips the
+check, `--warn double-context` reports without failing.
+"#;
+
+const UNATTRIBUTED: &str = r#"A function returns `anyhow::Result` (or `eyre::Result`) but has no
+`#[context]` attribute.
+
+Errors leaving such a function carry no information about what the
+function was doing, unless every caller adds context. Annotating the
+function once covers all call sites.
+
+Example:
+
+    fn read_manifest(path: &Path) -> anyhow::Result<Manifest> { ... }  // CL0002
+
+Fix:
+
+    #[context("Reading manifest {}", path.display())]
+    fn read_manifest(path: &Path) -> anyhow::Result<Manifest> { ... }
+
+Tests, `main`, functions in `#[cfg(test)]` modules and implementations of
+traits defined outside the workspace are never flagged. Implementations of
+workspace traits are flagged with `--unattributed-trait-impls`.
+
+Also skipped, as configured in the `[unattributed]` section of
+`context-lint.toml`:
+
+- functions that only delegate to another function (`skip-delegation`,
+  on by default), whose callee's context would be repeated;
+- functions with an attribute reporting their errors, such as
+  `#[tracing::instrument(err)]` (`error-attributes`);
+- with `skip-covered = true`, functions adding context to every error they
+  return in their body.
+
+Custom result types are declared with `result-aliases`.
+
+To suppress: `--unattributed allow` or `-A unattributed` skips the check,
+`-W unattributed` reports without failing.
+"#;
+
+const STYLE: &str = r#"A context method or `#[context]` attribute is used in a valid but wasteful
+or broken way.
+
+- constant-closure: `.with_context(|| "static")` has nothing to defer; use
+  `.context("static")`.
+- eager-format: `.context(format!(...))` formats even when there is no
+  error; use `.with_context(|| format!(...))`.
+- context-without-result: `#[context]` on a function that doesn't return a
+  `Result` has no effect.
+- format-placeholder: a placeholder in `#[context("...")]` matches no
+  parameter or argument.
+- repeated-message: a `bail!` / `anyhow!` message in a `#[context]` function
+  says the same as its context, so the error chain repeats itself.
+- sensitive-data (opt-in): a context string interpolates a variable whose
+  name (or label, as in `"token={}"`) suggests a credential; see
+  `sensitive-words` under `[style]` in `context-lint.toml`.
+- long-message (opt-in): a context message is longer than
+  `max-message-length` under `[style]` in `context-lint.toml`.
+- misspelling (opt-in): a word of a context string is in none of the
+  dictionaries under `[spelling]` in `context-lint.toml`, nor in its
+  `words`.
+- delegating-wrapper (opt-in): a `#[context]` function only calls another
+  `#[context]` function, so both layers are always added.
+
+Example:
+
+    file.read_to_string(&mut s).context(format!("Reading {name}"))?;  // CL0003
+
+Fix: `--fix` rewrites the call.
+
+To suppress: `--style allow` or `-A style` skips the check, `-W style`
+reports without failing.
+"#;
+
+const BARE_PROPAGATION: &str = r#"A function propagates errors with bare `?` and adds no context at all: no
+`#[context]` attribute and no context method call in its body. Only
+functions returning `anyhow::Result` (or an alias of it) are checked.
+
+Errors pass through such functions unchanged, so the resulting error chain
+doesn't say which step failed. Short functions are not flagged; the
+threshold is `min-statements` in the `[bare-propagation]` section of
+`context-lint.toml`.
+
+Example:
+
+    fn sync_all(&self) -> anyhow::Result<()> {
+        self.flush()?;
+        self.fsync()?;
+        ...
+    }  // CL0004
+
+Fix: add `#[context("...")]`, or `.context()` on the calls that need it.
+
+This check is off by default; enable it with `--bare-propagation deny` or
+`-D bare-propagation`.
+"#;
+
+const PLUGIN: &str = r#"A WebAssembly plugin declared in `context-lint.toml` reported a finding.
+
+Plugins add project-specific rules, e.g. that context messages follow a
+naming convention. Each plugin sees the source, the tokens and the
+built-in findings of every checked file and reports its own findings with
+a rule name, shown next to the message.
+
+Example configuration:
+
+    [[plugins]]
+    path = "tools/context-rules.wasm"
+    options = { prefix = "Failed to" }
+
+Fix: see the rule's message, or the plugin's documentation.
+
+To suppress: `-A plugin` skips all plugins, `-W plugin` reports without
+failing.
+"#;
+
+const BANNED_CONTEXT: &str = r#"A context string matches one of the patterns under `[banned-context]` in
+`context-lint.toml`.
+
+Context messages end up in logs and in front of users, so projects ban
+things that don't belong there: leftover TODOs, ticket numbers, internal
+hostnames. Both `#[context]` strings and the messages of context method
+calls are checked; for `format!` messages, the format string.
+
+Example configuration:
+
+    [banned-context]
+    patterns = ["(?i)\\btodo\\b", "JIRA-[0-9]+"]
+
+    load().context("Loading config, see JIRA-123")?;  // CL0006
+
+Fix: reword the message.
+
+To suppress: `-A banned-context` skips the check, `-W banned-context`
+reports without failing.
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        assert_eq!(Lint::find("CL0001"), Some(Lint::DoubleContext));
+        assert_eq!(Lint::find("cl0004"), Some(Lint::BarePropagation));
+        assert_eq!(Lint::find("unattributed"), Some(Lint::Unattributed));
+        assert_eq!(Lint::find("CL9999"), None);
+    }
+
+    #[test]
+    fn test_codes_unique() {
+        for (i, a) in Lint::ALL.iter().enumerate() {
+            for b in &Lint::ALL[i + 1..] {
+                assert_ne!(a.code(), b.code());
+            }
+        }
+    }
+}
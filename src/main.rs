@@ -1,45 +1,112 @@
-//! `cargo-context-lint` — Detect double error context from `fn_error_context` + `anyhow`.
-//!
-//! When a function is annotated with `#[context("...")]` from the `fn_error_context` crate,
-//! the function body is automatically wrapped to add context to any error it returns.
-//! If the caller *also* adds `.context()` or `.with_context()` from `anyhow::Context`,
-//! the error will carry two context layers, which is redundant.
-//!
-//! This tool detects such "double context" patterns via syntactic analysis.
-//!
-//! Additionally, it can check that all functions returning `anyhow::Result` have a
-//! `#[context]` annotation (the `--unattributed` check).
-
-mod checker;
-mod collector;
-mod report;
-mod unattributed;
+//! The `cargo context-lint` command line: parses the options, runs the
+//! [`Linter`] and prints its report.
 
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use walkdir::WalkDir;
-
-/// Lint level for optional checks.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
-enum LintLevel {
-    /// Allow (skip the check).
-    Allow,
-    /// Deny (flag as a warning, exit non-zero).
-    Deny,
+use cargo_context_lint::{
+    baseline, checker, compare, config, fix, git, graph, init, package, report, Heuristics, Lint,
+    LintLevel, LintLevels, LintOptions, Linter, Report,
+};
+use clap::{CommandFactory, FromArgMatches, Parser};
+
+/// Lint levels from the per-check options, overridden by `--allow`, `--warn`
+/// and `--deny` in the order they are given.
+fn levels_from_cli(cli: &Cli, matches: &clap::ArgMatches) -> LintLevels {
+    let mut levels = LintLevels {
+        double_context: LintLevel::Deny,
+        unattributed: cli.unattributed,
+        style: cli.style,
+        bare_propagation: cli.bare_propagation,
+        plugin: LintLevel::Warn,
+        banned_context: LintLevel::Deny,
+    };
+    let mut overrides: Vec<(usize, Lint, LintLevel)> = Vec::new();
+    for (id, lints, level) in [
+        ("allow", &cli.allow, LintLevel::Allow),
+        ("warn", &cli.warn, LintLevel::Warn),
+        ("deny", &cli.deny, LintLevel::Deny),
+    ] {
+        let indices = matches.indices_of(id).into_iter().flatten();
+        overrides.extend(indices.zip(lints).map(|(idx, lint)| (idx, *lint, level)));
+    }
+    overrides.sort_by_key(|(idx, _, _)| *idx);
+    for (_, lint, level) in overrides {
+        *levels.get_mut(lint) = level;
+    }
+    levels
 }
 
-impl std::fmt::Display for LintLevel {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            LintLevel::Allow => write!(f, "allow"),
-            LintLevel::Deny => write!(f, "deny"),
-        }
-    }
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Print the extended description of a check: what it finds, why, an
+    /// example and how to suppress it.
+    Explain {
+        /// The check's code (e.g. `CL0001`) or name (e.g. `double-context`).
+        code: String,
+    },
+    /// Check the files changed in git, with one line per finding, for use
+    /// as a pre-commit hook. Annotated functions are still collected from
+    /// the whole workspace.
+    Hook {
+        /// Only check the files staged for commit (default: all files with
+        /// uncommitted changes).
+        #[arg(long)]
+        staged: bool,
+    },
+    /// Compare two reports written with `--format json`: list the findings
+    /// that are new in NEW and those fixed since OLD, and fail if there are
+    /// new ones. Findings are matched regardless of their line.
+    Compare {
+        /// The earlier report, e.g. of the target branch.
+        old: PathBuf,
+        /// The later report, e.g. of the pull request.
+        new: PathBuf,
+    },
+    /// List every `#[context]`-annotated function, by module, with its
+    /// signature and context string, e.g. to document or audit a crate's
+    /// error contexts.
+    List {
+        /// Output format (default: the top-level `--format`, or text).
+        #[arg(long, value_parser = ["text", "json"])]
+        format: Option<String>,
+    },
+    /// Print, per workspace member, how many functions returning
+    /// `anyhow::Result` have `#[context]`.
+    Coverage {
+        /// Fail if the coverage of a member is below this percentage.
+        #[arg(long, value_name = "PCT", value_parser = parse_percentage)]
+        fail_under: Option<f64>,
+    },
+    /// Write a commented starter `context-lint.toml` to the workspace root
+    /// (or the `--config` path), with the error handling crate, `Result`
+    /// aliases and vendored directories detected.
+    Init {
+        /// Replace an existing configuration file.
+        #[arg(long)]
+        force: bool,
+        /// Also record the current findings in this baseline file, for use
+        /// with `--baseline`.
+        #[arg(long, value_name = "PATH")]
+        baseline: Option<PathBuf>,
+    },
+    /// Install a git pre-commit hook that runs `cargo context-lint hook
+    /// --staged`.
+    InstallHook {
+        /// Replace an existing pre-commit hook.
+        #[arg(long)]
+        force: bool,
+    },
 }
 
+/// The pre-commit hook written by `install-hook`.
+const PRE_COMMIT_HOOK: &str = "#!/bin/sh
+# Installed by `cargo context-lint install-hook`.
+exec cargo context-lint hook --staged
+";
+
 /// Detect double error context from `fn_error_context` + `anyhow`.
 ///
 /// Finds call sites where a function annotated with `#[context("...")]` is called
@@ -55,172 +122,845 @@ impl std::fmt::Display for LintLevel {
     about
 )]
 struct Cli {
-    // When invoked as `cargo context-lint`, cargo passes "context-lint" as the
-    // first argument. We accept and ignore it.
-    #[arg(hide = true, default_value = "context-lint")]
-    _subcommand: String,
-
-    /// Path to Cargo.toml (defaults to current directory).
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Check only these files instead of the whole workspace. Annotated
+    /// functions are then only collected from these files and from indexes
+    /// given with `--load-index`.
+    #[arg(value_name = "FILE", conflicts_with = "stdin")]
+    files: Vec<PathBuf>,
+
+    /// Check source code read from standard input.
+    #[arg(long, conflicts_with = "fix")]
+    stdin: bool,
+
+    /// The path of the file whose source is read with `--stdin`, used in
+    /// findings and to determine its module path.
+    #[arg(long, value_name = "NAME", requires = "stdin")]
+    stdin_path: Option<PathBuf>,
+
+    /// Path to Cargo.toml (defaults to current directory). May be given
+    /// multiple times to check several workspaces in one run, with one
+    /// report and one index of annotated functions.
     #[arg(long, value_name = "PATH")]
-    manifest_path: Option<PathBuf>,
+    manifest_path: Vec<PathBuf>,
+
+    /// Also check the workspaces listed in this file: one Cargo.toml or
+    /// workspace directory per line, relative to the file. Empty lines and
+    /// lines starting with `#` are ignored.
+    #[arg(long, value_name = "FILE")]
+    manifest_list: Option<PathBuf>,
+
+    /// Check all Rust files below DIR without running `cargo metadata`, for
+    /// other build systems (Bazel, Buck) or partial checkouts. Each `lib.rs`
+    /// and `main.rs` is taken as a crate root.
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["manifest_path", "manifest_list", "expand", "include_deps", "package", "features", "all_features", "no_default_features"]
+    )]
+    path: Option<PathBuf>,
+
+    /// Only check this workspace member. May be given multiple times.
+    #[arg(short, long = "package", value_name = "NAME")]
+    package: Vec<String>,
+
+    /// Skip workspace members whose name matches this glob pattern (e.g.
+    /// `*-proto`). May be given multiple times.
+    #[arg(long, value_name = "NAME")]
+    exclude: Vec<String>,
+
+    /// Also check integration tests (`tests/`).
+    #[arg(long)]
+    include_tests: bool,
+
+    /// Also check benchmarks (`benches/`).
+    #[arg(long)]
+    include_benches: bool,
 
-    /// Output format.
-    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    /// Also check examples (`examples/`).
+    #[arg(long)]
+    include_examples: bool,
+
+    /// Only check files whose path relative to the package directory matches
+    /// this glob pattern (e.g. `src/**`). May be given multiple times.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Skip files whose path relative to the package directory matches this
+    /// glob pattern (e.g. `src/generated/**`). May be given multiple times.
+    #[arg(long, value_name = "GLOB")]
+    exclude_path: Vec<String>,
+
+    /// Also check directories of vendored code: `vendor`, `third_party` and
+    /// `third-party`, or those configured in `[paths] vendored`.
+    #[arg(long)]
+    include_vendored: bool,
+
+    /// Skip files larger than this many bytes (default: `[paths]
+    /// max-file-size`, 1 MiB); 0 checks files of any size. Files with binary
+    /// or non-UTF-8 content are always skipped. `-v` lists skipped files.
+    #[arg(long, value_name = "BYTES")]
+    max_file_size: Option<u64>,
+
+    /// Follow symlinks when looking for source files, e.g. to shared
+    /// sources in a monorepo. Files reached through several paths are
+    /// checked once, and symlink cycles are detected.
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Skip code gated behind `#[cfg(feature = "..")]` unless these features
+    /// are enabled (comma or space separated, `NAME` or `PACKAGE/NAME`).
+    /// Default features are enabled too.
+    #[arg(short = 'F', long, value_name = "FEATURES")]
+    features: Vec<String>,
+
+    /// Only skip code whose `#[cfg]` is false for the target platform: all
+    /// features count as enabled.
+    #[arg(long)]
+    all_features: bool,
+
+    /// Skip code gated behind default features, unless given with
+    /// `--features`.
+    #[arg(long)]
+    no_default_features: bool,
+
+    /// Skip code gated on other platforms (`#[cfg(windows)]`,
+    /// `#[cfg(target_os = "..")]`) than this target triple, as described by
+    /// `rustc --print cfg`, instead of the host.
+    #[arg(long, value_name = "TRIPLE", conflicts_with = "any_target")]
+    target: Option<String>,
+
+    /// Check the code of every platform: don't skip code gated on the target
+    /// platform.
+    #[arg(long)]
+    any_target: bool,
+
+    /// Path to the configuration file (defaults to `context-lint.toml` in the
+    /// workspace root, if present).
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Drop double-context findings whose confidence (from 0 to 1) is below
+    /// this. Overrides `min-confidence` in the configuration file.
+    #[arg(long, value_name = "N", value_parser = parse_confidence)]
+    min_confidence: Option<f64>,
+
+    /// Only report double-context calls resolved by path or receiver type,
+    /// not those matched by name only. Minimizes false positives, e.g. in CI.
+    #[arg(long, conflicts_with = "lenient")]
+    strict: bool,
+
+    /// Report every double-context match, ignoring `--min-confidence` and
+    /// the configuration file's `min-confidence`. Maximizes recall, e.g. for
+    /// audits.
+    #[arg(long, conflicts_with = "min_confidence")]
+    lenient: bool,
+
+    /// Output format. `short` prints one line per finding, like gcc does;
+    /// `lsp` emits the available fixes as an LSP `WorkspaceEdit`.
+    #[arg(long, default_value = "text", value_parser = ["text", "short", "json", "lsp"])]
     format: String,
 
-    /// Show verbose output including all annotated functions found.
+    /// Group the text output by file: one header per file with its findings
+    /// beneath, instead of one section per check.
+    #[arg(long, value_name = "KEY", value_parser = ["file"])]
+    group_by: Option<String>,
+
+    /// Order of the double-context findings: by location, or most confident
+    /// first.
+    #[arg(long, default_value = "location", value_parser = ["location", "confidence"])]
+    sort: String,
+
+    /// Apply suggested fixes in place.
+    #[arg(long)]
+    fix: bool,
+
+    /// Wrap text output at this many columns (0 disables wrapping). Defaults to
+    /// the terminal width when writing to a terminal.
+    #[arg(long, value_name = "COLUMNS")]
+    max_width: Option<usize>,
+
+    /// Truncate context strings in text output to this many characters.
+    #[arg(long, value_name = "N")]
+    truncate_context: Option<usize>,
+
+    /// Also list the packages and files that were checked and have no findings.
+    #[arg(long)]
+    report_clean: bool,
+
+    /// Look for double context in the macro-expanded source of library and
+    /// binary targets, so call sites generated by macros are covered.
+    /// Requires the targets to compile.
+    #[arg(long)]
+    expand: bool,
+
+    /// Also look for double context in the code examples of doc comments.
+    #[arg(long)]
+    doc_tests: bool,
+
+    /// Also collect annotated functions from the sources of dependencies that
+    /// use `fn-error-context`, so calls into them are checked too.
+    #[arg(long)]
+    include_deps: bool,
+
+    /// Write the annotated functions found in the workspace to an index file,
+    /// for consumers of its crates to lint against.
+    #[arg(long, value_name = "PATH")]
+    emit_index: Option<PathBuf>,
+
+    /// Write the call graph of annotated functions and the calls into them to
+    /// a `.dot` (Graphviz) or `.json` file, to see how context layers stack up
+    /// along call paths.
+    #[arg(long, value_name = "PATH")]
+    emit_graph: Option<PathBuf>,
+
+    /// Merge annotated functions from an index file written by
+    /// `--emit-index`. May be given multiple times.
+    #[arg(long, value_name = "PATH")]
+    load_index: Vec<PathBuf>,
+
+    /// Only report findings in files changed relative to this git ref (e.g.
+    /// `origin/main`), including uncommitted and untracked files. Annotated
+    /// functions are still collected from the whole workspace.
+    #[arg(long, value_name = "GIT_REF")]
+    changed_since: Option<String>,
+
+    /// Only report (and fail on) findings not present at the merge base of
+    /// HEAD and `--base`. The merge base is checked with the same options,
+    /// from a copy of its files in the target directory.
+    #[arg(long, requires = "base")]
+    deny_new: bool,
+
+    /// The git ref (e.g. `origin/main`) whose merge base with HEAD
+    /// `--deny-new` compares against.
+    #[arg(long, value_name = "GIT_REF", requires = "deny_new")]
+    base: Option<String>,
+
+    /// Don't report the findings listed in this baseline file. Entries are
+    /// matched by fingerprint, so findings that only moved stay suppressed.
+    #[arg(long, value_name = "PATH")]
+    baseline: Option<PathBuf>,
+
+    /// Rewrite the `--baseline` file: update the locations of entries that
+    /// moved and drop those that were fixed. New findings are still
+    /// reported, not added. Creates the file from all findings if it doesn't
+    /// exist.
+    #[arg(long, requires = "baseline")]
+    update_baseline: bool,
+
+    /// Only exit non-zero for findings of these checks (comma separated);
+    /// the others are reported without failing. Defaults to all checks.
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "CHECKS")]
+    error_on: Vec<Lint>,
+
+    /// Report findings without failing: always exit with 0 unless the tool
+    /// itself fails.
+    #[arg(long, conflicts_with = "error_on")]
+    warn_only: bool,
+
+    /// Print the JSON Schema of the `--format json` output (of `--stats`
+    /// output, if given too) and exit.
+    #[arg(long)]
+    print_schema: bool,
+
+    /// Print per-crate metrics instead of the findings: functions returning
+    /// `anyhow::Result`, how many have `#[context]`, warning counts, and the
+    /// files with the most warnings.
     #[arg(long)]
-    verbose: bool,
+    stats: bool,
+
+    /// Write `#[context]` coverage per crate and per module to a JSON file,
+    /// and the overall coverage as a shields.io endpoint badge next to it
+    /// (`metrics.json` gets `metrics.badge.json`).
+    #[arg(long, value_name = "PATH")]
+    metrics: Option<PathBuf>,
+
+    /// Print the error chains a failure in a function would produce instead of
+    /// the findings, one per path of annotated callers leading to it. TARGET is
+    /// a `file:line` within the function or its name.
+    #[arg(long, value_name = "TARGET", conflicts_with = "stats")]
+    trace_context: Option<String>,
+
+    /// Show verbose output including all annotated functions found. Given
+    /// twice, also list the files scanned, the time spent on each file and
+    /// how findings were matched.
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Print nothing; only the exit status tells whether issues were found.
+    #[arg(short, long)]
+    quiet: bool,
 
     /// Check for functions returning anyhow::Result without #[context].
     #[arg(long, default_value_t = LintLevel::Deny, value_enum)]
     unattributed: LintLevel,
+
+    /// Only flag `pub` functions in the unattributed check, to enforce context
+    /// at API boundaries first.
+    #[arg(long)]
+    only_pub: bool,
+
+    /// Also flag methods of trait impls in the unattributed check, if the
+    /// trait is defined in the workspace (so `#[context]` can be added).
+    #[arg(long)]
+    unattributed_trait_impls: bool,
+
+    /// Check for wasteful uses of `.context()` / `.with_context()`.
+    #[arg(long, default_value_t = LintLevel::Deny, value_enum)]
+    style: LintLevel,
+
+    /// Check for functions that propagate errors with bare `?` and add no
+    /// context at all.
+    #[arg(long, default_value_t = LintLevel::Allow, value_enum)]
+    bare_propagation: LintLevel,
+
+    /// Skip a check. May be given multiple times; overrides the per-check
+    /// options, and later `--allow`, `--warn` and `--deny` win.
+    #[arg(short = 'A', long, value_enum, value_name = "CHECK")]
+    allow: Vec<Lint>,
+
+    /// Report the findings of a check without failing on them. May be given
+    /// multiple times.
+    #[arg(short = 'W', long, value_enum, value_name = "CHECK")]
+    warn: Vec<Lint>,
+
+    /// Report the findings of a check and fail on them. May be given
+    /// multiple times.
+    #[arg(short = 'D', long, value_enum, value_name = "CHECK")]
+    deny: Vec<Lint>,
 }
 
-fn find_rust_files(dir: &Path) -> Vec<PathBuf> {
-    WalkDir::new(dir)
-        .into_iter()
-        .filter_entry(|e| {
-            let name = e.file_name().to_string_lossy();
-            // Skip hidden directories, target directories, and common non-source dirs
-            if e.file_type().is_dir() {
-                return name != "target" && name != ".git" && name != ".hg";
-            }
-            true
-        })
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|ext| ext == "rs"))
-        .map(|e| e.into_path())
-        .collect()
+/// Parse a confidence threshold between 0 and 1.
+fn parse_confidence(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(n) if (0.0..=1.0).contains(&n) => Ok(n),
+        _ => Err(format!("`{s}` is not a number between 0 and 1")),
+    }
 }
 
-/// Discover source directories for the workspace using `cargo_metadata`.
-fn discover_source_dirs(manifest_path: Option<&Path>) -> Result<(Vec<PathBuf>, PathBuf)> {
-    let mut cmd = cargo_metadata::MetadataCommand::new();
-    cmd.no_deps();
-    if let Some(path) = manifest_path {
-        cmd.manifest_path(path);
+/// Parse a percentage between 0 and 100.
+fn parse_percentage(s: &str) -> Result<f64, String> {
+    match s.trim_end_matches('%').parse::<f64>() {
+        Ok(n) if (0.0..=100.0).contains(&n) => Ok(n),
+        _ => Err(format!("`{s}` is not a percentage between 0 and 100")),
     }
-    let metadata = cmd.exec().context("Running cargo metadata")?;
+}
 
-    let workspace_root = PathBuf::from(&metadata.workspace_root);
+/// Determine the wrap width for text output: an explicit `--max-width` (0 means
+/// no wrapping), else the terminal width if stdout is a terminal.
+fn text_width(max_width: Option<usize>) -> Option<usize> {
+    match max_width {
+        Some(0) => None,
+        Some(width) => Some(width),
+        None => terminal_size::terminal_size_of(std::io::stdout())
+            .map(|(terminal_size::Width(w), _)| w as usize),
+    }
+}
 
-    let mut dirs = Vec::new();
-    for package in &metadata.packages {
-        // Only include packages that are workspace members
-        if !metadata.workspace_members.contains(&package.id) {
-            continue;
+fn run() -> Result<bool> {
+    // When invoked as `cargo context-lint`, cargo passes "context-lint" as the
+    // first argument. We accept and ignore it.
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    if args.get(1).is_some_and(|arg| arg == "context-lint") {
+        args.remove(1);
+    }
+    let matches = Cli::command().get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let levels = levels_from_cli(&cli, &matches);
+
+    if let Some(Command::Explain { code }) = &cli.command {
+        let Some(lint) = Lint::find(code) else {
+            anyhow::bail!("unknown check `{code}`");
+        };
+        println!("{} ({})\n", lint.code(), lint.name());
+        print!("{}", lint.explanation());
+        return Ok(false);
+    }
+    if cli.print_schema {
+        println!("{}", report::format_json_schema(cli.stats));
+        return Ok(false);
+    }
+    if let Some(Command::Compare { old, new }) = &cli.command {
+        let comparison = compare::compare(&compare::read_report(old)?, &compare::read_report(new)?);
+        if !cli.quiet {
+            match cli.format.as_str() {
+                "json" => println!("{}", compare::format_json(&comparison)),
+                _ => print!("{}", compare::format_text(&comparison, cli.verbose > 0)),
+            }
         }
-        let pkg_dir = PathBuf::from(&package.manifest_path)
-            .parent()
-            .expect("manifest path should have parent")
-            .to_path_buf();
-        dirs.push(pkg_dir);
+        return Ok(!comparison.new.is_empty());
+    }
+    if let Some(Command::InstallHook { force }) = &cli.command {
+        install_hook(*force)?;
+        return Ok(false);
     }
+    let hook = match &cli.command {
+        Some(Command::Hook { staged }) => Some(*staged),
+        _ => None,
+    };
+    // Explicit files (or stdin) replace the workspace walk.
+    let explicit = !cli.files.is_empty() || cli.stdin;
+
+    let buffer = if cli.stdin {
+        let mut source = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut source)
+            .context("Reading standard input")?;
+        let path = match &cli.stdin_path {
+            Some(path) => std::path::absolute(path)
+                .with_context(|| format!("Resolving {}", path.display()))?,
+            None => PathBuf::from("<stdin>"),
+        };
+        Some((path, source))
+    } else {
+        None
+    };
 
-    // Deduplicate in case packages share directories
-    dirs.sort();
-    dirs.dedup();
+    let mut manifest_paths = cli.manifest_path.clone();
+    if let Some(list) = &cli.manifest_list {
+        manifest_paths.extend(package::read_manifest_list(list)?);
+    }
 
-    Ok((dirs, workspace_root))
-}
+    let options = LintOptions {
+        manifest_paths,
+        path: cli.path.clone(),
+        files: cli.files.clone(),
+        buffer,
+        packages: cli.package.clone(),
+        exclude: cli.exclude.clone(),
+        include_tests: cli.include_tests,
+        include_benches: cli.include_benches,
+        include_examples: cli.include_examples,
+        include_paths: cli.include.clone(),
+        exclude_paths: cli.exclude_path.clone(),
+        include_vendored: cli.include_vendored,
+        max_file_size: cli.max_file_size,
+        follow_symlinks: cli.follow_symlinks,
+        features: cli.features.clone(),
+        all_features: cli.all_features,
+        no_default_features: cli.no_default_features,
+        target: cli.target.clone(),
+        any_target: cli.any_target,
+        config: cli.config.clone(),
+        expand: cli.expand,
+        doc_tests: cli.doc_tests,
+        include_deps: cli.include_deps,
+        emit_index: cli.emit_index.clone(),
+        emit_graph: cli.emit_graph.clone(),
+        call_graph: cli.trace_context.is_some(),
+        load_index: cli.load_index.clone(),
+        changed_since: match hook {
+            Some(false) => Some("HEAD".to_string()),
+            _ => cli.changed_since.clone(),
+        },
+        staged: hook == Some(true),
+        deny_new: cli.base.clone().filter(|_| cli.deny_new),
+        // A baseline being created doesn't suppress anything yet.
+        baseline: cli
+            .baseline
+            .clone()
+            .filter(|path| !cli.update_baseline || path.exists()),
+        levels,
+        error_on: cli.error_on.clone(),
+        warn_only: cli.warn_only,
+        min_confidence: cli.min_confidence,
+        heuristics: if cli.strict {
+            Heuristics::Strict
+        } else if cli.lenient {
+            Heuristics::Lenient
+        } else {
+            Heuristics::Default
+        },
+        only_pub: cli.only_pub,
+        unattributed_trait_impls: cli.unattributed_trait_impls,
+    };
+    let mut report = Linter::new(options.clone()).run()?;
+    if let Some(Command::List { format }) = &cli.command {
+        let prefix = format!("{}/", report.workspace_root.display());
+        match format.as_deref().unwrap_or(&cli.format) {
+            "json" => println!(
+                "{}",
+                report::format_function_list_json(&report.annotated, Some(&prefix))
+            ),
+            _ => print!(
+                "{}",
+                report::format_function_list_text(&report.annotated, Some(&prefix))
+            ),
+        }
+        return Ok(false);
+    }
+    if let Some(Command::Init { force, baseline }) = &cli.command {
+        init(&cli, &options, &report, *force, baseline.as_deref())?;
+        return Ok(false);
+    }
+    if let Some(Command::Coverage { fail_under }) = &cli.command {
+        let stats = report.stats();
+        if !cli.quiet {
+            match cli.format.as_str() {
+                "json" => println!("{}", report::format_coverage_json(&stats, *fail_under)),
+                _ => print!("{}", report::format_coverage_text(&stats, *fail_under)),
+            }
+        }
+        let under = fail_under.map(|min| stats.under(min)).unwrap_or_default();
+        if !cli.quiet {
+            for c in &under {
+                eprintln!(
+                    "error: #[context] coverage of `{}` is {:.1}%, below {}%",
+                    c.name,
+                    c.coverage_percent().unwrap_or_default(),
+                    fail_under.unwrap_or_default()
+                );
+            }
+        }
+        return Ok(!under.is_empty());
+    }
+    if cli.sort == "confidence" {
+        report
+            .double_context
+            .sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    }
 
-fn run() -> Result<bool> {
-    let cli = Cli::parse();
+    // Trailing slash so strip_prefix works cleanly
+    let prefix = format!("{}/", report.workspace_root.display());
+
+    if cli.verbose > 0 {
+        print_verbose(&cli, &options, &report, explicit, &prefix);
+    }
 
-    let (source_dirs, workspace_root) = discover_source_dirs(cli.manifest_path.as_deref())?;
+    let fix_plan = report.fix_plan();
 
-    // Trailing slash so strip_prefix works cleanly
-    let prefix = format!("{}/", workspace_root.display());
+    if cli.format == "lsp" {
+        let found_issues = options.fails(&report);
+        let edit = fix::workspace_edit(&fix_plan)?;
+        if !cli.quiet {
+            println!("{}", serde_json::to_string_pretty(&edit)?);
+        }
+        return Ok(found_issues);
+    }
+
+    if cli.fix {
+        let applied = report.apply_fixes(&fix_plan)?;
+        if !cli.quiet {
+            eprintln!("Applied {applied} fixes across {} files", fix_plan.len());
+        }
+    }
 
-    // Collect all Rust files
-    let mut all_files: Vec<PathBuf> = Vec::new();
-    for dir in &source_dirs {
-        all_files.extend(find_rust_files(dir));
+    if let (Some(path), true) = (&cli.baseline, cli.update_baseline) {
+        update_baseline(&cli, &mut report, path, &prefix)?;
     }
 
-    if cli.verbose {
+    let found_issues = options.fails(&report);
+
+    if let (Some(base), false) = (&report.base, cli.quiet) {
         eprintln!(
-            "Scanning {} Rust files across {} package directories",
-            all_files.len(),
-            source_dirs.len()
+            "Compared to the merge base {}: {} finding{} not new, {} fixed",
+            &base.commit[..base.commit.len().min(12)],
+            base.unchanged,
+            if base.unchanged == 1 { "" } else { "s" },
+            base.fixed
         );
     }
 
-    // Pass 1: Collect all #[context]-annotated functions
-    let mut all_annotated = Vec::new();
-    for file in &all_files {
-        let entries = collector::collect_from_file(file)
-            .with_context(|| format!("Collecting from {}", file.display()))?;
-        all_annotated.extend(entries);
+    if let Some(path) = &cli.metrics {
+        write_metrics(&report, path, &prefix)?;
+    }
+
+    // Output results
+    let clean = cli.report_clean.then(|| report.clean());
+    let findings = report::Findings {
+        clean: clean.as_ref(),
+        ..report.findings()
+    };
+    let stats = cli.stats.then(|| report.stats());
+    let output = match (cli.format.as_str(), &stats) {
+        _ if hook.is_some() => report::format_concise_text(&findings, Some(&prefix)),
+        _ if cli.trace_context.is_some() => format_trace(&cli, &report, &prefix)?,
+        ("json", Some(stats)) => report::format_stats_json(stats, Some(&prefix)),
+        (_, Some(stats)) => report::format_stats_text(stats, Some(&prefix)),
+        ("json", None) => report::format_combined_json(&findings, Some(&prefix)),
+        ("short", None) => report::format_short_text(&findings, Some(&prefix)),
+        _ => {
+            let layout = report::TextLayout {
+                max_width: text_width(cli.max_width),
+                truncate_context: cli.truncate_context,
+            };
+            if cli.group_by.is_some() {
+                report::format_grouped_text(&findings, Some(&prefix), &layout)
+            } else {
+                report::format_combined_text(&findings, Some(&prefix), &layout)
+            }
+        }
+    };
+
+    if cli.quiet {
+        // Only the exit status.
+    } else if !output.is_empty() {
+        print!("{output}");
+    } else if cli.verbose > 0 {
+        eprintln!("No issues found.");
     }
 
-    if cli.verbose {
-        eprintln!("Found {} annotated functions", all_annotated.len());
-        for entry in &all_annotated {
-            let file = entry.file.strip_prefix(&prefix).unwrap_or(&entry.file);
-            let kind = if entry.is_method { "method" } else { "fn" };
+    Ok(found_issues)
+}
+
+/// Rewrite the baseline file at `path` with the baselined findings at their
+/// current location, or create it with all findings (which are then not
+/// reported).
+fn update_baseline(cli: &Cli, report: &mut Report, path: &Path, prefix: &str) -> Result<()> {
+    if !path.exists() {
+        let entries = report
+            .iter()
+            .map(|finding| baseline::Entry::new(&finding, Some(prefix)))
+            .collect();
+        let baseline = baseline::Baseline::new(entries);
+        baseline::write(path, &baseline)?;
+        if !cli.quiet {
             eprintln!(
-                "  {}:{} — {} {}() #[context(\"{}\")]",
-                file, entry.line, kind, entry.name, entry.context_string
+                "Created {} with {} findings",
+                path.display(),
+                baseline.findings.len()
             );
         }
+        report.baselined = baseline.findings;
+        report.double_context.clear();
+        report.unattributed.clear();
+        report.style.clear();
+        report.bare_propagation.clear();
+        report.plugin.clear();
+        report.banned_context.clear();
+        return Ok(());
     }
+    let old = baseline::read(path)?;
+    let updated = baseline::Baseline::new(report.baselined.clone());
+    let (moved, removed) = old.changes(&updated);
+    if updated != old {
+        baseline::write(path, &updated)?;
+    }
+    if !cli.quiet {
+        eprintln!(
+            "Updated {}: {moved} moved, {removed} fixed and removed",
+            path.display()
+        );
+    }
+    Ok(())
+}
 
-    let index = collector::build_index(all_annotated);
+/// Write the `--metrics` file and its badge.
+fn write_metrics(report: &Report, path: &Path, prefix: &str) -> Result<()> {
+    let stats = report.stats();
+    std::fs::write(path, report::format_metrics_json(&stats, Some(prefix)))
+        .with_context(|| format!("Writing {}", path.display()))?;
+    let badge = path.with_extension("badge.json");
+    std::fs::write(&badge, report::format_badge_json(&stats))
+        .with_context(|| format!("Writing {}", badge.display()))
+}
+
+/// The error chains of `--trace-context`.
+fn format_trace(cli: &Cli, report: &Report, prefix: &str) -> Result<String> {
+    let (Some(target), Some(call_graph)) = (&cli.trace_context, &report.graph) else {
+        return Ok(String::new());
+    };
+    let node = graph::find_node(call_graph, target)?;
+    let traces = graph::traces(call_graph, node);
+    Ok(graph::format_traces(call_graph, &traces, Some(prefix)))
+}
 
-    // Pass 2: Check for double-context call sites
-    let mut all_double_context = Vec::new();
-    for file in &all_files {
-        let issues = checker::check_file(file, &index)
-            .with_context(|| format!("Checking {}", file.display()))?;
-        all_double_context.extend(issues);
+/// Write the pre-commit hook of the repository in the current directory.
+fn install_hook(force: bool) -> Result<()> {
+    let dir = std::env::current_dir().context("Getting the current directory")?;
+    let hooks_dir = git::hooks_dir(&dir)?;
+    let path = hooks_dir.join("pre-commit");
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists; use --force to replace it",
+            path.display()
+        );
+    }
+    std::fs::create_dir_all(&hooks_dir)
+        .with_context(|| format!("Creating {}", hooks_dir.display()))?;
+    std::fs::write(&path, PRE_COMMIT_HOOK)
+        .with_context(|| format!("Writing {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Making {} executable", path.display()))?;
     }
+    eprintln!("Installed {}", path.display());
+    Ok(())
+}
 
-    // Sort by file and line for stable output
-    all_double_context.sort_by(|a, b| {
-        a.call_file
-            .cmp(&b.call_file)
-            .then(a.call_line.cmp(&b.call_line))
-    });
+/// Write the starter configuration of `init` and, if asked for, a baseline
+/// of the findings with it.
+fn init(
+    cli: &Cli,
+    options: &LintOptions,
+    report: &Report,
+    force: bool,
+    baseline: Option<&Path>,
+) -> Result<()> {
+    let path = match &cli.config {
+        Some(path) => path.clone(),
+        None => report.workspace_root.join(config::CONFIG_FILE_NAME),
+    };
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists; use --force to replace it",
+            path.display()
+        );
+    }
+    let inspection = init::inspect(report)?;
+    std::fs::write(&path, init::starter_config(&inspection))
+        .with_context(|| format!("Writing {}", path.display()))?;
+    if !cli.quiet {
+        eprintln!("Wrote {}", path.display());
+    }
 
-    // Pass 3 (optional): Check for unattributed functions
-    let mut all_unattributed = Vec::new();
-    if cli.unattributed == LintLevel::Deny {
-        for file in &all_files {
-            let issues = unattributed::check_file(file)
-                .with_context(|| format!("Checking unattributed in {}", file.display()))?;
-            all_unattributed.extend(issues);
-        }
+    let Some(baseline) = baseline else {
+        return Ok(());
+    };
+    // The findings with the new configuration.
+    let options = LintOptions {
+        config: Some(path),
+        baseline: None,
+        ..options.clone()
+    };
+    let report = Linter::new(options).run()?;
+    let prefix = format!("{}/", report.workspace_root.display());
+    let entries = report
+        .iter()
+        .map(|finding| baseline::Entry::new(&finding, Some(&prefix)))
+        .collect();
+    let written = baseline::Baseline::new(entries);
+    baseline::write(baseline, &written)?;
+    if !cli.quiet {
+        eprintln!(
+            "Wrote {} with {} findings; pass `--baseline {}` to suppress them",
+            baseline.display(),
+            written.findings.len(),
+            baseline.display()
+        );
+    }
+    Ok(())
+}
 
-        // Sort by file and line for stable output
-        all_unattributed.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+/// Print what was scanned and found for `-v`, and with `-vv` also the files,
+/// how call sites were matched and the time spent per file.
+fn print_verbose(cli: &Cli, options: &LintOptions, report: &Report, explicit: bool, prefix: &str) {
+    let levels = &options.levels;
+    if explicit {
+        eprintln!(
+            "Checking {} explicitly given files",
+            report.files.len() + cli.stdin as usize
+        );
+    } else {
+        eprintln!(
+            "Scanning {} Rust files across {} package directories",
+            report.files.len(),
+            report.source_dirs.len()
+        );
+    }
+    for skipped in &report.skipped {
+        let file = skipped.path.to_string_lossy();
+        eprintln!(
+            "Skipped {}: {}",
+            file.strip_prefix(prefix).unwrap_or(&file),
+            skipped.reason
+        );
+    }
+    if cli.include_deps {
+        eprintln!(
+            "Scanning {} Rust files across {} dependencies using fn-error-context",
+            report.dependency_files.len(),
+            report.dependencies.len()
+        );
+    }
 
-        if cli.verbose {
-            eprintln!(
-                "Found {} unattributed functions returning anyhow::Result",
-                all_unattributed.len()
-            );
+    if cli.verbose > 1 {
+        for file in report.files.iter().chain(&report.dependency_files) {
+            let file = file.to_string_lossy();
+            eprintln!("  {}", file.strip_prefix(prefix).unwrap_or(&file));
         }
     }
 
-    let found_issues = !all_double_context.is_empty() || !all_unattributed.is_empty();
-
-    // Output results
-    let output = match cli.format.as_str() {
-        "json" => {
-            report::format_combined_json(&all_double_context, &all_unattributed, Some(&prefix))
-        }
-        _ => report::format_combined_text(&all_double_context, &all_unattributed, Some(&prefix)),
-    };
+    eprintln!("Found {} annotated functions", report.annotated.len());
+    for entry in &report.annotated {
+        let file = entry.file.strip_prefix(prefix).unwrap_or(&entry.file);
+        let condition = match &entry.cfg {
+            Some(cfg) => format!(" if {cfg}"),
+            None => String::new(),
+        };
+        eprintln!(
+            "  {}:{} — {} {}{}",
+            file,
+            entry.line,
+            entry.signature(),
+            entry.annotation(),
+            condition
+        );
+    }
 
-    if !output.is_empty() {
-        print!("{output}");
-    } else if cli.verbose {
-        eprintln!("No issues found.");
+    if let Some(base) = &cli.changed_since {
+        eprintln!(
+            "Checking {} of {} Rust files changed since {}",
+            report.checked_files.len(),
+            report.files.len() + cli.stdin as usize,
+            base
+        );
+    }
+    for file in &report.expanded_files {
+        eprintln!("Expanded source written to {}", file.display());
+    }
+    if levels.unattributed != LintLevel::Allow {
+        eprintln!(
+            "Found {} unattributed functions returning anyhow::Result",
+            report.unattributed.len()
+        );
     }
 
-    Ok(found_issues)
+    if cli.verbose > 1 {
+        for issue in &report.double_context {
+            let file = issue
+                .call_file
+                .strip_prefix(prefix)
+                .unwrap_or(&issue.call_file);
+            let how = match issue.match_kind {
+                checker::MatchKind::Path => "by its resolved path",
+                checker::MatchKind::Type => "by the inferred receiver type",
+                checker::MatchKind::Name => "by name only",
+            };
+            eprintln!(
+                "  {}:{}: call to {}() matched {}",
+                file, issue.call_line, issue.function_name, how
+            );
+        }
+        for issue in &report.option_context {
+            let file = issue
+                .call_file
+                .strip_prefix(prefix)
+                .unwrap_or(&issue.call_file);
+            eprintln!(
+                "  {}:{}: call to {}() skipped: .{}() goes on an Option after {}",
+                file,
+                issue.call_line,
+                issue.function_name,
+                issue.outer_method,
+                issue.option_receiver.as_deref().unwrap_or_default()
+            );
+        }
+        let mut timings: Vec<(&PathBuf, &Duration)> = report.timings.iter().collect();
+        timings.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        eprintln!("Time spent per file:");
+        for (file, elapsed) in &timings {
+            let file = file.to_string_lossy();
+            eprintln!(
+                "  {:>8.2?}  {}",
+                elapsed,
+                file.strip_prefix(prefix).unwrap_or(&file)
+            );
+        }
+    }
 }
 
 fn main() -> ExitCode {
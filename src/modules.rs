@@ -0,0 +1,340 @@
+//! Module paths of source files, resolved from the crate roots.
+//!
+//! Starting at each library and binary target's root file, `mod foo;`
+//! declarations are followed the way rustc does: to `foo.rs` or
+//! `foo/mod.rs` next to a crate root or `mod.rs` file, below a directory
+//! named after any other file (`bar.rs` declares `bar/foo.rs`), through
+//! inline `mod` blocks, and to the file named by a `#[path]` attribute.
+//! Files that aren't reached fall back to [`crate::package::module_path`].
+//!
+//! Files belong to the package whose crate declares them, even if a `#[path]`
+//! attribute puts them in another package's directory or outside of all of
+//! them.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use syn::ext::IdentExt;
+use syn::{Expr, ExprLit, Item, Lit, Meta};
+
+use crate::package::{self, Package};
+use crate::parse::ParsedFile;
+
+/// The package and module of a workspace file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileModule {
+    /// Index of the package the file belongs to, if any.
+    pub package: Option<usize>,
+    /// The module path, starting with the crate name, or empty if the file
+    /// isn't part of a library or binary.
+    pub path: Vec<String>,
+}
+
+/// The module path of each file, starting with the crate name.
+#[derive(Debug)]
+pub struct ModulePaths<'p> {
+    paths: HashMap<PathBuf, Vec<String>>,
+    /// The package whose crate declares each file.
+    owners: HashMap<PathBuf, usize>,
+    /// Files named by `#[path]` attributes that aren't among the resolved
+    /// files.
+    undiscovered: Vec<PathBuf>,
+    packages: &'p [Package],
+}
+
+impl<'p> ModulePaths<'p> {
+    /// Resolve the module paths of `files`, the sources of `packages`.
+    pub fn resolve(packages: &'p [Package], files: &[ParsedFile]) -> Self {
+        let files: HashMap<&Path, &ParsedFile> = files
+            .iter()
+            .map(|file| (file.path.as_path(), file))
+            .collect();
+        let mut resolver = Resolver {
+            files,
+            paths: HashMap::new(),
+            owners: HashMap::new(),
+            undiscovered: Vec::new(),
+            package: 0,
+        };
+        for (idx, package) in packages.iter().enumerate() {
+            resolver.package = idx;
+            // Libraries first, for files a binary declares as well.
+            let mut roots: Vec<_> = package
+                .targets
+                .iter()
+                .filter(|t| t.is_lib() || t.is_bin())
+                .collect();
+            roots.sort_by_key(|t| !t.is_lib());
+            for target in roots {
+                let crate_name = target.name.replace('-', "_");
+                resolver.file(&target.src_path, vec![crate_name], true);
+            }
+        }
+        Self {
+            paths: resolver.paths,
+            owners: resolver.owners,
+            undiscovered: resolver.undiscovered,
+            packages,
+        }
+    }
+
+    /// The package a file belongs to: the one whose crate declares it, or
+    /// else the one whose directory contains it.
+    pub fn package(&self, file: &Path) -> Option<usize> {
+        match self.owners.get(file) {
+            Some(&idx) => Some(idx),
+            None => package::package_index(self.packages, file),
+        }
+    }
+
+    /// The package and module path of a file.
+    pub fn file_module(&self, file: &Path) -> FileModule {
+        FileModule {
+            package: self.package(file),
+            path: self.get(file),
+        }
+    }
+
+    /// Whether a file is declared as a module of a crate.
+    pub fn contains(&self, file: &Path) -> bool {
+        self.paths.contains_key(file)
+    }
+
+    /// Files named by `#[path]` attributes that weren't given to
+    /// [`ModulePaths::resolve`], e.g. because they are outside of the
+    /// package directories. They may not exist.
+    pub fn undiscovered(&self) -> &[PathBuf] {
+        &self.undiscovered
+    }
+
+    /// The module path of a file, or an empty path if it isn't part of a
+    /// library or binary.
+    pub fn get(&self, file: &Path) -> Vec<String> {
+        match self.paths.get(file) {
+            Some(path) => path.clone(),
+            None => package::module_path(self.packages, file),
+        }
+    }
+}
+
+struct Resolver<'a> {
+    files: HashMap<&'a Path, &'a ParsedFile>,
+    paths: HashMap<PathBuf, Vec<String>>,
+    owners: HashMap<PathBuf, usize>,
+    undiscovered: Vec<PathBuf>,
+    /// The package whose targets are being resolved.
+    package: usize,
+}
+
+impl Resolver<'_> {
+    /// Record the module path of `file` and follow its `mod` declarations.
+    /// `mod_rs` is whether it owns its directory, like a crate root or
+    /// `mod.rs` file does.
+    fn file(&mut self, file: &Path, module_path: Vec<String>, mod_rs: bool) {
+        // Also stops at files declared twice, and at `#[path]` cycles.
+        if self.paths.contains_key(file) {
+            return;
+        }
+        self.paths.insert(file.to_path_buf(), module_path.clone());
+        self.owners.insert(file.to_path_buf(), self.package);
+        let Some(syntax) = self.files.get(file).and_then(|f| f.syntax.as_ref()) else {
+            return;
+        };
+        let dir = file.parent().unwrap_or(Path::new(""));
+        let children = match (mod_rs, file.file_stem()) {
+            (false, Some(stem)) => dir.join(stem),
+            _ => dir.to_path_buf(),
+        };
+        self.items(&syntax.items, dir, &children, &module_path);
+    }
+
+    /// Follow the `mod` declarations among `items`. Declared files are looked
+    /// for in `children`, and `#[path]` attributes are relative to
+    /// `path_dir`.
+    fn items(&mut self, items: &[Item], path_dir: &Path, children: &Path, module_path: &[String]) {
+        for item in items {
+            let Item::Mod(item) = item else {
+                continue;
+            };
+            let name = item.ident.unraw().to_string();
+            let mut child_path = module_path.to_vec();
+            child_path.push(name.clone());
+            match &item.content {
+                Some((_, items)) => {
+                    // Inside inline modules, `#[path]` is relative to their
+                    // directory too.
+                    let dir = children.join(&name);
+                    self.items(items, &dir, &dir, &child_path);
+                }
+                None => match path_attr(&item.attrs) {
+                    Some(path) => {
+                        let file = normalize(&path_dir.join(path));
+                        if !self.files.contains_key(file.as_path()) {
+                            self.undiscovered.push(file.clone());
+                        }
+                        self.file(&file, child_path, true);
+                    }
+                    None => {
+                        let file = children.join(format!("{name}.rs"));
+                        if self.files.contains_key(file.as_path()) {
+                            self.file(&file, child_path, false);
+                        } else {
+                            self.file(&children.join(&name).join("mod.rs"), child_path, true);
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// The value of a `#[path = "..."]` attribute.
+fn path_attr(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(path),
+                ..
+            }) => Some(path.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// `path` without `.` components, and with `..` components removing the
+/// previous one, like rustc does with `#[path]` attributes.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::package::Target;
+
+    fn package(targets: &[(&str, &str, &str)]) -> Package {
+        Package {
+            targets: targets
+                .iter()
+                .map(|(name, kind, src_path)| Target {
+                    name: name.to_string(),
+                    kinds: vec![kind.to_string()],
+                    src_path: PathBuf::from(src_path),
+                })
+                .collect(),
+            ..fixtures::package("my-crate", "/ws")
+        }
+    }
+
+    fn file(path: &str, source: &str) -> ParsedFile {
+        ParsedFile::from_source(Path::new(path), source.to_string())
+    }
+
+    #[test]
+    fn test_resolve() {
+        let packages = vec![package(&[
+            ("my-crate", "lib", "/ws/src/lib.rs"),
+            ("tool", "bin", "/ws/src/bin/tool.rs"),
+        ])];
+        let files = vec![
+            file(
+                "/ws/src/lib.rs",
+                r#"
+                mod pod;
+                mod r#async;
+                #[path = "generated/bindings.rs"]
+                mod ffi;
+                mod inline {
+                    mod nested;
+                    #[path = "other.rs"]
+                    mod renamed;
+                }
+                "#,
+            ),
+            file("/ws/src/pod/mod.rs", "mod storage;"),
+            file("/ws/src/pod/storage.rs", "mod disk;"),
+            file("/ws/src/pod/storage/disk.rs", ""),
+            file("/ws/src/async.rs", ""),
+            file("/ws/src/generated/bindings.rs", "mod types;"),
+            file("/ws/src/generated/types.rs", ""),
+            file("/ws/src/inline/nested.rs", ""),
+            file("/ws/src/inline/other.rs", ""),
+            file("/ws/src/bin/tool.rs", "mod cli;"),
+            file("/ws/src/bin/cli.rs", ""),
+            file("/ws/src/orphan.rs", ""),
+        ];
+        let paths = ModulePaths::resolve(&packages, &files);
+        let path = |file: &str| paths.get(Path::new(file)).join("::");
+        assert_eq!(path("/ws/src/lib.rs"), "my_crate");
+        assert_eq!(path("/ws/src/pod/mod.rs"), "my_crate::pod");
+        assert_eq!(path("/ws/src/pod/storage.rs"), "my_crate::pod::storage");
+        assert_eq!(
+            path("/ws/src/pod/storage/disk.rs"),
+            "my_crate::pod::storage::disk"
+        );
+        assert_eq!(path("/ws/src/async.rs"), "my_crate::async");
+        assert_eq!(path("/ws/src/generated/bindings.rs"), "my_crate::ffi");
+        assert_eq!(path("/ws/src/generated/types.rs"), "my_crate::ffi::types");
+        assert_eq!(path("/ws/src/inline/nested.rs"), "my_crate::inline::nested");
+        assert_eq!(path("/ws/src/inline/other.rs"), "my_crate::inline::renamed");
+        assert_eq!(path("/ws/src/bin/tool.rs"), "tool");
+        assert_eq!(path("/ws/src/bin/cli.rs"), "tool::cli");
+        // Not declared anywhere: named after the file.
+        assert_eq!(path("/ws/src/orphan.rs"), "my_crate::orphan");
+    }
+
+    #[test]
+    fn test_path_attribute_packages() {
+        let mut a = package(&[("a", "lib", "/ws/a/src/lib.rs")]);
+        a.name = "a".to_string();
+        a.dir = PathBuf::from("/ws/a");
+        let mut b = package(&[("b", "lib", "/ws/b/src/lib.rs")]);
+        b.name = "b".to_string();
+        b.dir = PathBuf::from("/ws/b");
+        let packages = vec![a, b];
+        let files = vec![
+            file(
+                "/ws/a/src/lib.rs",
+                r#"
+                #[path = "../../shared/util.rs"]
+                mod util;
+                #[path = "./../../missing.rs"]
+                mod missing;
+                "#,
+            ),
+            file(
+                "/ws/b/src/lib.rs",
+                r#"#[path = "../../a/src/gen.rs"] mod gen;"#,
+            ),
+            file("/ws/a/src/gen.rs", ""),
+        ];
+        let paths = ModulePaths::resolve(&packages, &files);
+        let module = |file: &str| {
+            let module = paths.file_module(Path::new(file));
+            (module.package, module.path.join("::"))
+        };
+        assert_eq!(
+            module("/ws/shared/util.rs"),
+            (Some(0), "a::util".to_string())
+        );
+        // In the directory of `a`, but declared by `b`.
+        assert_eq!(module("/ws/a/src/gen.rs"), (Some(1), "b::gen".to_string()));
+        assert_eq!(module("/ws/other.rs"), (None, String::new()));
+        assert_eq!(
+            paths.undiscovered(),
+            [Path::new("/ws/shared/util.rs"), Path::new("/ws/missing.rs")]
+        );
+    }
+}
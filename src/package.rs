@@ -0,0 +1,460 @@
+//! Package-level checks based on `cargo metadata`.
+//!
+//! These don't look at source code directly; they combine the per-function
+//! findings with what each workspace member's manifest declares.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::glob::Glob;
+use crate::unattributed::UnattributedFunction;
+
+/// Packages with at least this many unattributed functions and no
+/// `fn_error_context` dependency get a note suggesting to add it.
+pub const MISSING_DEPENDENCY_THRESHOLD: usize = 5;
+
+/// A workspace member package.
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    /// The package name.
+    pub name: String,
+    /// Path to the package's `Cargo.toml`.
+    pub manifest_path: PathBuf,
+    /// Directory containing the package's `Cargo.toml`.
+    pub dir: PathBuf,
+    /// Whether the package depends on `fn-error-context`.
+    pub has_fn_error_context: bool,
+    /// The package's build targets.
+    pub targets: Vec<Target>,
+    /// The package's `[features]` table.
+    pub features: BTreeMap<String, Vec<String>>,
+}
+
+/// A build target (library, binary, test, ...) of a package.
+#[derive(Debug, Clone)]
+pub struct Target {
+    /// The target name.
+    pub name: String,
+    /// The target kinds as reported by cargo, e.g. `lib`, `bin`, `proc-macro`.
+    pub kinds: Vec<String>,
+    /// Path of the target's root source file.
+    pub src_path: PathBuf,
+}
+
+impl Target {
+    /// Whether the target is a library of any kind.
+    pub fn is_lib(&self) -> bool {
+        self.kinds.iter().any(|kind| {
+            matches!(
+                kind.as_str(),
+                "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro"
+            )
+        })
+    }
+
+    /// Whether the target is a binary.
+    pub fn is_bin(&self) -> bool {
+        self.kinds.iter().any(|kind| kind == "bin")
+    }
+
+    /// Whether the target is of the given kind, e.g. `test` or `example`.
+    pub fn is_kind(&self, kind: &str) -> bool {
+        self.kinds.iter().any(|k| k == kind)
+    }
+}
+
+/// Find the target a file belongs to: the target whose root file it is, else
+/// the one with the deepest directory containing its root file. Build scripts
+/// only own their root file, since it usually sits in the package directory.
+pub fn file_target<'p>(packages: &'p [Package], file: &Path) -> Option<&'p Target> {
+    let package = &packages[package_index(packages, file)?];
+    if let Some(target) = package.targets.iter().find(|t| t.src_path == file) {
+        return Some(target);
+    }
+    package
+        .targets
+        .iter()
+        .filter(|t| !t.is_kind("custom-build"))
+        .filter_map(|t| Some((t, t.src_path.parent()?)))
+        .filter(|(_, dir)| file.starts_with(dir))
+        .max_by_key(|(_, dir)| dir.components().count())
+        .map(|(t, _)| t)
+}
+
+/// An informational note: a package would benefit from `fn_error_context`.
+#[derive(Debug, Clone)]
+pub struct MissingDependency {
+    /// The package name.
+    pub package: String,
+    /// Path to the package's `Cargo.toml`.
+    pub manifest_path: String,
+    /// Number of unattributed functions found in the package.
+    pub unattributed: usize,
+}
+
+/// A package in which none of the checked files had a finding.
+#[derive(Debug, Clone)]
+pub struct CleanPackage {
+    /// The package name.
+    pub name: String,
+    /// Path to the package's `Cargo.toml`.
+    pub manifest_path: String,
+    /// Number of files checked in the package.
+    pub files: usize,
+}
+
+/// Positive evidence of coverage: what was checked and came out clean.
+#[derive(Debug, Clone, Default)]
+pub struct CleanReport {
+    /// Packages with no findings in any of their files.
+    pub packages: Vec<CleanPackage>,
+    /// Files with no findings.
+    pub files: Vec<String>,
+    /// Total number of files checked.
+    pub files_checked: usize,
+    /// Total number of packages checked.
+    pub packages_checked: usize,
+}
+
+/// Whether a package matches one of the `--exclude` patterns.
+pub fn is_excluded(package: &Package, exclude: &[Glob]) -> bool {
+    exclude.iter().any(|glob| glob.is_match(&package.name))
+}
+
+/// Read a list of manifest paths, for `--manifest-list`.
+pub fn read_manifest_list(path: &Path) -> Result<Vec<PathBuf>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    Ok(parse_manifest_list(
+        &contents,
+        path.parent().unwrap_or(Path::new("")),
+    ))
+}
+
+/// The manifest paths listed one per line, relative to `dir`. Empty lines
+/// and `#` comments are skipped, and a workspace directory stands for its
+/// `Cargo.toml`.
+pub fn parse_manifest_list(contents: &str, dir: &Path) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let path = dir.join(line);
+            match path.extension() {
+                Some(ext) if ext == "toml" => path,
+                _ => path.join("Cargo.toml"),
+            }
+        })
+        .collect()
+}
+
+/// The indices of the packages selected with `-p/--package` (all packages if
+/// no names are given), minus those excluded with `--exclude`.
+pub fn select(packages: &[Package], names: &[String], exclude: &[Glob]) -> Result<Vec<usize>> {
+    let mut selected = Vec::new();
+    if names.is_empty() {
+        selected.extend(0..packages.len());
+    }
+    for name in names {
+        let Some(idx) = packages.iter().position(|p| &p.name == name) else {
+            bail!("package `{name}` is not a member of the workspace");
+        };
+        if !selected.contains(&idx) {
+            selected.push(idx);
+        }
+    }
+    selected.retain(|&idx| !is_excluded(&packages[idx], exclude));
+    selected.sort();
+    Ok(selected)
+}
+
+/// Find the package a file belongs to: the one with the deepest directory
+/// containing it (packages may be nested inside the workspace root package).
+pub fn package_index(packages: &[Package], file: &Path) -> Option<usize> {
+    packages
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| file.starts_with(&p.dir))
+        .max_by_key(|(_, p)| p.dir.components().count())
+        .map(|(idx, _)| idx)
+}
+
+/// The module path a file defines, starting with the crate name: for package
+/// `my-crate`, `src/lib.rs` is `my_crate` and both `src/pod/storage.rs` and
+/// `src/pod/storage/mod.rs` are `my_crate::pod::storage`.
+///
+/// Returns an empty path for files outside `src/` and for extra binaries in
+/// `src/bin/`, which are separate crates.
+pub fn module_path(packages: &[Package], file: &Path) -> Vec<String> {
+    let Some(idx) = package_index(packages, file) else {
+        return Vec::new();
+    };
+    let package = &packages[idx];
+    let Ok(relative) = file.strip_prefix(package.dir.join("src")) else {
+        return Vec::new();
+    };
+
+    let mut segments: Vec<String> = relative
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if segments.first().is_some_and(|s| s == "bin") {
+        return Vec::new();
+    }
+    match segments.as_slice() {
+        [root] if root == "lib" || root == "main" => segments.clear(),
+        [.., last] if last == "mod" => {
+            segments.pop();
+        }
+        _ => {}
+    }
+
+    let mut path = vec![package.name.replace('-', "_")];
+    path.extend(segments);
+    path
+}
+
+/// Note packages that have many unattributed functions but can't annotate
+/// them because they don't depend on `fn_error_context`.
+pub fn check_missing_dependency(
+    packages: &[Package],
+    unattributed: &[UnattributedFunction],
+) -> Vec<MissingDependency> {
+    let mut counts = vec![0usize; packages.len()];
+    for issue in unattributed {
+        if let Some(idx) = package_index(packages, Path::new(&*issue.file)) {
+            counts[idx] += 1;
+        }
+    }
+    missing_dependency(packages, &counts)
+}
+
+/// Like [`check_missing_dependency`], given the number of unattributed
+/// functions in each package.
+pub fn missing_dependency(packages: &[Package], counts: &[usize]) -> Vec<MissingDependency> {
+    packages
+        .iter()
+        .zip(counts.iter().copied())
+        .filter(|(p, count)| !p.has_fn_error_context && *count >= MISSING_DEPENDENCY_THRESHOLD)
+        .map(|(p, count)| MissingDependency {
+            package: p.name.clone(),
+            manifest_path: p.manifest_path.to_string_lossy().to_string(),
+            unattributed: count,
+        })
+        .collect()
+}
+
+/// List the packages and files that were checked and have no findings.
+/// `flagged` holds the files with at least one finding.
+pub fn check_clean(
+    packages: &[Package],
+    files: &[PathBuf],
+    flagged: &HashSet<&str>,
+) -> CleanReport {
+    let mut counts = vec![0usize; packages.len()];
+    let mut dirty = vec![false; packages.len()];
+    let mut clean_files = Vec::new();
+    for file in files {
+        let name = file.to_string_lossy();
+        let is_flagged = flagged.contains(name.as_ref());
+        if !is_flagged {
+            clean_files.push(name.to_string());
+        }
+        if let Some(idx) = package_index(packages, file) {
+            counts[idx] += 1;
+            dirty[idx] |= is_flagged;
+        }
+    }
+    clean_files.sort();
+
+    let clean_packages = packages
+        .iter()
+        .zip(counts.into_iter().zip(dirty))
+        .filter(|(_, (_, dirty))| !dirty)
+        .map(|(p, (count, _))| CleanPackage {
+            name: p.name.clone(),
+            manifest_path: p.manifest_path.to_string_lossy().to_string(),
+            files: count,
+        })
+        .collect();
+
+    CleanReport {
+        packages: clean_packages,
+        files: clean_files,
+        files_checked: files.len(),
+        packages_checked: packages.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn package(name: &str, dir: &str, has_fn_error_context: bool) -> Package {
+        Package {
+            has_fn_error_context,
+            ..fixtures::package(name, dir)
+        }
+    }
+
+    fn unattributed(file: &str, n: usize) -> Vec<UnattributedFunction> {
+        (0..n)
+            .map(|i| UnattributedFunction {
+                line: i + 1,
+                name: format!("f{i}").into(),
+                ..fixtures::unattributed(file)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_package_index_prefers_nested() {
+        let packages = vec![
+            package("root", "/ws", false),
+            package("sub", "/ws/sub", true),
+        ];
+        let file = Path::new("/ws/sub/src/lib.rs");
+        assert_eq!(package_index(&packages, file), Some(1));
+        let file = Path::new("/ws/src/main.rs");
+        assert_eq!(package_index(&packages, file), Some(0));
+        let file = Path::new("/elsewhere/src/main.rs");
+        assert_eq!(package_index(&packages, file), None);
+    }
+
+    #[test]
+    fn test_select() {
+        let packages = vec![
+            package("root", "/ws", false),
+            package("a", "/ws/crates/a", false),
+            package("b", "/ws/crates/b", false),
+        ];
+        assert_eq!(select(&packages, &[], &[]).unwrap(), vec![0, 1, 2]);
+        let names = ["b".to_string(), "a".to_string(), "b".to_string()];
+        assert_eq!(select(&packages, &names, &[]).unwrap(), vec![1, 2]);
+        assert!(select(&packages, &["c".to_string()], &[]).is_err());
+        let exclude = [Glob::new("r*")];
+        assert_eq!(select(&packages, &[], &exclude).unwrap(), vec![1, 2]);
+        assert_eq!(
+            select(&packages, &names, &[Glob::new("a")]).unwrap(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_target_kinds() {
+        let target = |kinds: &[&str]| Target {
+            name: "t".to_string(),
+            kinds: kinds.iter().map(|k| k.to_string()).collect(),
+            src_path: PathBuf::new(),
+        };
+        assert!(target(&["lib"]).is_lib());
+        assert!(target(&["rlib", "cdylib"]).is_lib());
+        assert!(target(&["proc-macro"]).is_lib());
+        assert!(!target(&["bin"]).is_lib());
+        assert!(target(&["bin"]).is_bin());
+        assert!(!target(&["test"]).is_bin());
+    }
+
+    #[test]
+    fn test_file_target() {
+        let target = |kind: &str, path: &str| Target {
+            name: kind.to_string(),
+            kinds: vec![kind.to_string()],
+            src_path: PathBuf::from(path),
+        };
+        let mut pkg = package("a", "/ws/a", false);
+        pkg.targets = vec![
+            target("lib", "/ws/a/src/lib.rs"),
+            target("bin", "/ws/a/src/bin/tool.rs"),
+            target("test", "/ws/a/tests/it.rs"),
+            target("example", "/ws/a/examples/demo.rs"),
+            target("custom-build", "/ws/a/build.rs"),
+        ];
+        let packages = vec![pkg];
+        let name_of = |file: &str| file_target(&packages, Path::new(file)).map(|t| t.name.as_str());
+        assert_eq!(name_of("/ws/a/src/pod/storage.rs"), Some("lib"));
+        assert_eq!(name_of("/ws/a/src/bin/tool.rs"), Some("bin"));
+        assert_eq!(name_of("/ws/a/tests/common/mod.rs"), Some("test"));
+        assert_eq!(name_of("/ws/a/examples/demo.rs"), Some("example"));
+        assert_eq!(name_of("/ws/a/build.rs"), Some("custom-build"));
+        assert_eq!(name_of("/ws/a/scripts/gen.rs"), None);
+    }
+
+    #[test]
+    fn test_module_path() {
+        let packages = vec![package("my-crate", "/ws", false)];
+        let path = |file: &str| module_path(&packages, Path::new(file)).join("::");
+        assert_eq!(path("/ws/src/lib.rs"), "my_crate");
+        assert_eq!(path("/ws/src/main.rs"), "my_crate");
+        assert_eq!(path("/ws/src/pod/storage.rs"), "my_crate::pod::storage");
+        assert_eq!(path("/ws/src/pod/mod.rs"), "my_crate::pod");
+        assert_eq!(path("/ws/src/bin/tool.rs"), "");
+        assert_eq!(path("/ws/tests/it.rs"), "");
+        assert_eq!(path("/elsewhere/src/lib.rs"), "");
+    }
+
+    #[test]
+    fn test_missing_dependency() {
+        let packages = vec![
+            package("without", "/ws/without", false),
+            package("with", "/ws/with", true),
+            package("few", "/ws/few", false),
+        ];
+        let mut issues = unattributed("/ws/without/src/lib.rs", 6);
+        issues.extend(unattributed("/ws/with/src/lib.rs", 6));
+        issues.extend(unattributed("/ws/few/src/lib.rs", 2));
+        let notes = check_missing_dependency(&packages, &issues);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].package, "without");
+        assert_eq!(notes[0].unattributed, 6);
+        assert_eq!(notes[0].manifest_path, "/ws/without/Cargo.toml");
+    }
+
+    #[test]
+    fn test_check_clean() {
+        let packages = vec![
+            package("dirty", "/ws/dirty", true),
+            package("clean", "/ws/clean", true),
+        ];
+        let files = vec![
+            PathBuf::from("/ws/dirty/src/lib.rs"),
+            PathBuf::from("/ws/dirty/src/ok.rs"),
+            PathBuf::from("/ws/clean/src/lib.rs"),
+        ];
+        let flagged: HashSet<&str> = ["/ws/dirty/src/lib.rs"].into_iter().collect();
+        let report = check_clean(&packages, &files, &flagged);
+        assert_eq!(report.packages.len(), 1);
+        assert_eq!(report.packages[0].name, "clean");
+        assert_eq!(report.packages[0].files, 1);
+        assert_eq!(
+            report.files,
+            vec!["/ws/clean/src/lib.rs", "/ws/dirty/src/ok.rs"]
+        );
+        assert_eq!(report.files_checked, 3);
+        assert_eq!(report.packages_checked, 2);
+    }
+
+    #[test]
+    fn test_parse_manifest_list() {
+        let list = "
+            # Services
+            services/api/Cargo.toml
+            services/worker
+
+            /opt/tools/Cargo.toml
+        ";
+        assert_eq!(
+            parse_manifest_list(list, Path::new("/repo")),
+            [
+                PathBuf::from("/repo/services/api/Cargo.toml"),
+                PathBuf::from("/repo/services/worker/Cargo.toml"),
+                PathBuf::from("/opt/tools/Cargo.toml"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Source files, read and parsed once and shared by all passes.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use proc_macro2::LineColumn;
+use serde::{Deserialize, Serialize};
+use syn::File;
+
+/// A range of source text, for editors to jump to: 1-based lines and
+/// columns (counted in characters) and byte offsets into the file, the end
+/// exclusive. The default span (line 0) stands for an unknown position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    /// Byte offset of the start (missing in index files written by older
+    /// versions).
+    #[serde(default)]
+    pub start: usize,
+    /// Byte offset of the end.
+    #[serde(default)]
+    pub end: usize,
+}
+
+impl Span {
+    /// The span from the start of `first` to the end of `last`, two spans of
+    /// a file parsed by `syn`. `base` is the number of leading bytes `syn`
+    /// skipped (see [`crate::fix::syn_offset`]).
+    pub fn new(first: proc_macro2::Span, last: proc_macro2::Span, base: usize) -> Self {
+        let (start, end): (LineColumn, LineColumn) = (first.start(), last.end());
+        Self {
+            line: start.line,
+            column: start.column + 1,
+            end_line: end.line,
+            end_column: end.column + 1,
+            start: base + first.byte_range().start,
+            end: base + last.byte_range().end,
+        }
+    }
+
+    /// Whether the position is known.
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+
+    /// The byte range, if known.
+    pub fn bytes(&self) -> Option<std::ops::Range<usize>> {
+        (self.end > self.start).then_some(self.start..self.end)
+    }
+}
+
+/// How many leading bytes are looked at for NUL bytes, which source text
+/// doesn't contain.
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Why a file was not parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Larger than the size limit; the size in bytes.
+    TooLarge(u64),
+    /// Contains NUL bytes, so it isn't source text.
+    Binary,
+    /// Not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::TooLarge(size) => write!(f, "too large ({size} bytes)"),
+            SkipReason::Binary => write!(f, "binary content"),
+            SkipReason::InvalidUtf8 => write!(f, "not valid UTF-8"),
+        }
+    }
+}
+
+/// A Rust source file and its syntax tree.
+pub struct ParsedFile {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// The file contents.
+    pub source: String,
+    /// The syntax tree, or `None` if the file doesn't parse (e.g.
+    /// macro-heavy code). Such files are skipped by all passes.
+    pub syntax: Option<File>,
+    /// The path as displayed in findings, shared by all of them.
+    display_path: Arc<str>,
+}
+
+impl ParsedFile {
+    /// Read and parse a file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        Ok(Self::from_source(path, source))
+    }
+
+    /// Read and parse a file, unless it is larger than `max_size` bytes or
+    /// doesn't look like source text, e.g. a huge generated table or a binary
+    /// file with a `.rs` name.
+    pub fn read_guarded(path: &Path, max_size: Option<u64>) -> Result<Result<Self, SkipReason>> {
+        if let Some(max_size) = max_size {
+            let size = std::fs::metadata(path)
+                .with_context(|| format!("Reading {}", path.display()))?
+                .len();
+            if size > max_size {
+                return Ok(Err(SkipReason::TooLarge(size)));
+            }
+        }
+        let bytes = std::fs::read(path).with_context(|| format!("Reading {}", path.display()))?;
+        if bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+            return Ok(Err(SkipReason::Binary));
+        }
+        match String::from_utf8(bytes) {
+            Ok(source) => Ok(Ok(Self::from_source(path, source))),
+            Err(_) => Ok(Err(SkipReason::InvalidUtf8)),
+        }
+    }
+
+    /// Parse source text read from `path`.
+    pub fn from_source(path: &Path, source: String) -> Self {
+        let syntax = syn::parse_file(&source).ok();
+        Self {
+            path: path.to_path_buf(),
+            source,
+            syntax,
+            display_path: path.to_string_lossy().into(),
+        }
+    }
+
+    /// Number of leading bytes `syn` skipped when parsing the file, to add to
+    /// the byte offsets of its spans.
+    pub fn syn_offset(&self) -> usize {
+        crate::fix::syn_offset(&self.source)
+    }
+
+    /// The path as displayed in findings. Findings share it rather than
+    /// holding a copy each.
+    pub fn display_path(&self) -> Arc<str> {
+        self.display_path.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_guarded() {
+        let dir = crate::fixtures::TempDir::new("parse");
+        let write = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        let read = |path: &Path, max_size| ParsedFile::read_guarded(path, max_size).unwrap();
+
+        let source = write("lib.rs", b"fn f() {}\n");
+        assert!(read(&source, Some(100)).unwrap().syntax.is_some());
+        assert_eq!(read(&source, Some(4)).err(), Some(SkipReason::TooLarge(10)));
+        assert!(read(&source, None).is_ok());
+
+        let binary = write("blob.rs", b"\x7fELF\0\0\x01");
+        assert_eq!(read(&binary, None).err(), Some(SkipReason::Binary));
+        let latin1 = write("latin1.rs", b"// caf\xe9\n");
+        assert_eq!(read(&latin1, None).err(), Some(SkipReason::InvalidUtf8));
+    }
+}
@@ -0,0 +1,549 @@
+//! Custom checks loaded from WebAssembly modules.
+//!
+//! A plugin is a `.wasm` module declared under `[[plugins]]` in
+//! `context-lint.toml`. It is run once per checked file, in a sandbox without
+//! any imports (no file system, network or clock) and a fresh instance each
+//! time, so nothing it allocates outlives the file. It is given the file as
+//! JSON: its source, its tokens and the findings of the built-in checks. It
+//! answers with JSON listing its own findings, which are reported under the
+//! `plugin` check.
+//!
+//! The module exports:
+//!
+//! - `memory`, its linear memory;
+//! - `context_lint_alloc(len: i32) -> i32`, returning the address of `len`
+//!   free bytes, where the input is written;
+//! - `context_lint_check(ptr: i32, len: i32) -> i64`, called with the input
+//!   and returning the address of the output in the upper 32 bits and its
+//!   length in the lower 32 bits.
+//!
+//! The input is a [`PluginInput`]; the output an object with a `findings`
+//! array of `{"line", "column", "rule", "message"}` objects, of which `line`
+//! and `message` are required. Fields the plugin doesn't know or report are
+//! ignored, so plugins keep working when fields are added.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use serde::{Deserialize, Serialize};
+
+use crate::config::PluginConfig;
+use crate::linter::Finding;
+use crate::parse::Span;
+use crate::report;
+
+/// Version of the input format, incremented whenever a change could break
+/// plugins. Added fields don't change it.
+pub const PLUGIN_API_VERSION: u32 = 1;
+
+/// How many WebAssembly instructions (roughly) a plugin may execute per file
+/// by default, so that a plugin stuck in a loop fails the run instead of
+/// hanging it.
+pub const DEFAULT_FUEL: u64 = 1_000_000_000;
+
+/// A finding reported by a plugin.
+#[derive(Debug, Clone)]
+pub struct PluginFinding {
+    /// File it is in.
+    pub file: String,
+    /// Line number (1-based).
+    pub line: usize,
+    /// Line and column if the plugin reported a column, otherwise unknown.
+    pub span: Span,
+    /// Name of the plugin.
+    pub plugin: String,
+    /// The plugin's name for the rule, or the plugin's name if it has one
+    /// rule only.
+    pub rule: String,
+    /// One-line description.
+    pub message: String,
+}
+
+/// What a plugin is given for each file.
+#[derive(Debug, Serialize)]
+pub struct PluginInput<'a> {
+    /// [`PLUGIN_API_VERSION`].
+    pub version: u32,
+    /// Path of the file, relative to the workspace root.
+    pub file: &'a str,
+    /// The file contents.
+    pub source: &'a str,
+    /// The tokens of the file, empty if it doesn't lex.
+    pub tokens: &'a [Token],
+    /// Findings of the built-in checks in the file.
+    pub findings: &'a [InputFinding],
+    /// The plugin's `options` from the configuration.
+    pub options: &'a serde_json::Value,
+}
+
+/// A token of the file, as seen by `rustc`'s lexer: comments are left out
+/// and doc comments turned into `#[doc = "..."]` attributes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    /// Line of the first character (1-based).
+    pub line: usize,
+    /// Column of the first character (1-based, in characters).
+    pub column: usize,
+}
+
+/// The kind of a [`Token`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Ident,
+    Punct,
+    Literal,
+    /// An opening delimiter: `(`, `[` or `{`.
+    Open,
+    /// A closing delimiter.
+    Close,
+}
+
+/// A finding of a built-in check, as given to plugins.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputFinding {
+    /// The check's code, e.g. `CL0001`.
+    pub code: &'static str,
+    pub line: usize,
+    pub message: String,
+}
+
+impl InputFinding {
+    pub fn new(finding: &Finding) -> Self {
+        Self {
+            code: finding.lint().code(),
+            line: finding.line(),
+            message: report::message(finding),
+        }
+    }
+}
+
+/// The output of a plugin.
+#[derive(Debug, Deserialize)]
+struct PluginOutput {
+    findings: Vec<OutputFinding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputFinding {
+    line: usize,
+    column: Option<usize>,
+    rule: Option<String>,
+    message: String,
+}
+
+/// The tokens of a source file, or none if it doesn't lex.
+pub fn tokens(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    if let Ok(stream) = source.parse::<TokenStream>() {
+        push_tokens(stream, &mut tokens);
+    }
+    tokens
+}
+
+fn push_tokens(stream: TokenStream, tokens: &mut Vec<Token>) {
+    let token = |kind, text: String, span: proc_macro2::Span| {
+        let start = span.start();
+        Token {
+            kind,
+            text,
+            line: start.line,
+            column: start.column + 1,
+        }
+    };
+    for tree in stream {
+        match tree {
+            TokenTree::Ident(ident) => {
+                tokens.push(token(TokenKind::Ident, ident.to_string(), ident.span()))
+            }
+            TokenTree::Punct(punct) => tokens.push(token(
+                TokenKind::Punct,
+                punct.as_char().to_string(),
+                punct.span(),
+            )),
+            TokenTree::Literal(literal) => tokens.push(token(
+                TokenKind::Literal,
+                literal.to_string(),
+                literal.span(),
+            )),
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                if !open.is_empty() {
+                    tokens.push(token(TokenKind::Open, open.into(), group.span_open()));
+                }
+                push_tokens(group.stream(), tokens);
+                if !close.is_empty() {
+                    tokens.push(token(TokenKind::Close, close.into(), group.span_close()));
+                }
+            }
+        }
+    }
+}
+
+/// A loaded plugin.
+pub struct Plugin {
+    /// The name shown with its findings.
+    pub name: String,
+    path: PathBuf,
+    options: serde_json::Value,
+    #[cfg(feature = "plugins")]
+    runtime: runtime::Runtime,
+}
+
+impl std::fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Plugin")
+            .field("name", &self.name)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Plugin {
+    /// Load the plugin declared by `config`, whose path is relative to
+    /// `root`.
+    pub fn load(config: &PluginConfig, root: &Path) -> Result<Self> {
+        let path = root.join(&config.path);
+        let name = match &config.name {
+            Some(name) => name.clone(),
+            None => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        let options = serde_json::to_value(&config.options)
+            .with_context(|| format!("Converting the options of plugin {name}"))?;
+        let fuel = config.fuel.unwrap_or(DEFAULT_FUEL);
+        #[cfg(feature = "plugins")]
+        {
+            let wasm =
+                std::fs::read(&path).with_context(|| format!("Reading {}", path.display()))?;
+            let runtime = runtime::Runtime::new(&wasm, fuel)
+                .with_context(|| format!("Loading plugin {}", path.display()))?;
+            Ok(Self {
+                name,
+                path,
+                options,
+                runtime,
+            })
+        }
+        #[cfg(not(feature = "plugins"))]
+        {
+            let _ = (options, fuel);
+            anyhow::bail!(
+                "Plugin {name} ({}) can't be loaded: cargo-context-lint was built without the `plugins` feature",
+                path.display()
+            )
+        }
+    }
+
+    /// Run the plugin on one file, given by its path relative to the workspace
+    /// root (`relative`) and as displayed in findings (`file`).
+    pub fn check(
+        &mut self,
+        file: &str,
+        relative: &str,
+        source: &str,
+        tokens: &[Token],
+        findings: &[InputFinding],
+    ) -> Result<Vec<PluginFinding>> {
+        let input = PluginInput {
+            version: PLUGIN_API_VERSION,
+            file: relative,
+            source,
+            tokens,
+            findings,
+            options: &self.options,
+        };
+        let input = serde_json::to_vec(&input)?;
+        let output = self
+            .call(&input)
+            .with_context(|| format!("Running plugin {} on {relative}", self.name))?;
+        let output: PluginOutput = serde_json::from_slice(&output).with_context(|| {
+            format!(
+                "Parsing the output of plugin {} for {relative}: {}",
+                self.name,
+                String::from_utf8_lossy(&output)
+            )
+        })?;
+        Ok(output
+            .findings
+            .into_iter()
+            .map(|finding| PluginFinding {
+                file: file.to_string(),
+                line: finding.line,
+                span: match finding.column {
+                    Some(column) if finding.line > 0 => Span {
+                        line: finding.line,
+                        column,
+                        end_line: finding.line,
+                        end_column: column,
+                        ..Span::default()
+                    },
+                    _ => Span::default(),
+                },
+                plugin: self.name.clone(),
+                rule: finding.rule.unwrap_or_else(|| self.name.clone()),
+                message: finding.message,
+            })
+            .collect())
+    }
+
+    #[cfg(feature = "plugins")]
+    fn call(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+        self.runtime.call(input)
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn call(&mut self, _input: &[u8]) -> Result<Vec<u8>> {
+        unreachable!("plugins can't be loaded without the `plugins` feature")
+    }
+}
+
+#[cfg(feature = "plugins")]
+mod runtime {
+    use anyhow::{anyhow, Context, Result};
+    use wasmi::{Config, Engine, Linker, Memory, Module, Store, TypedFunc};
+
+    /// A compiled plugin module, instantiated anew for every file.
+    pub struct Runtime {
+        engine: Engine,
+        module: Module,
+        fuel: u64,
+    }
+
+    /// An instance of the module and its exports.
+    struct Instance {
+        store: Store<()>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        check: TypedFunc<(i32, i32), i64>,
+    }
+
+    impl Runtime {
+        pub fn new(wasm: &[u8], fuel: u64) -> Result<Self> {
+            let mut config = Config::default();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config);
+            let module = Module::new(&engine, wasm).map_err(|e| anyhow!("{e}"))?;
+            let runtime = Self {
+                engine,
+                module,
+                fuel,
+            };
+            // Missing exports are reported when loading, not on the first file.
+            runtime.instantiate()?;
+            Ok(runtime)
+        }
+
+        fn instantiate(&self) -> Result<Instance> {
+            let mut store = Store::new(&self.engine, ());
+            store.set_fuel(self.fuel).map_err(|e| anyhow!("{e}"))?;
+            // No imports: plugins can't reach anything outside their memory.
+            let instance = Linker::<()>::new(&self.engine)
+                .instantiate(&mut store, &self.module)
+                .and_then(|instance| instance.start(&mut store))
+                .map_err(|e| anyhow!("{e}"))?;
+            let memory = instance
+                .get_memory(&store, "memory")
+                .context("The module doesn't export `memory`")?;
+            let alloc = instance
+                .get_typed_func(&store, "context_lint_alloc")
+                .map_err(|e| anyhow!("{e}"))
+                .context("The module doesn't export `context_lint_alloc(i32) -> i32`")?;
+            let check = instance
+                .get_typed_func(&store, "context_lint_check")
+                .map_err(|e| anyhow!("{e}"))
+                .context("The module doesn't export `context_lint_check(i32, i32) -> i64`")?;
+            Ok(Instance {
+                store,
+                memory,
+                alloc,
+                check,
+            })
+        }
+
+        /// Pass `input` to `context_lint_check` of a new instance and return
+        /// its output.
+        pub fn call(&self, input: &[u8]) -> Result<Vec<u8>> {
+            let Instance {
+                mut store,
+                memory,
+                alloc,
+                check,
+            } = self.instantiate()?;
+            let len = i32::try_from(input.len()).context("Input too large")?;
+            let ptr = alloc.call(&mut store, len).map_err(|e| anyhow!("{e}"))?;
+            memory
+                .write(&mut store, ptr as u32 as usize, input)
+                .map_err(|e| anyhow!("Writing the input: {e}"))?;
+            let result = check
+                .call(&mut store, (ptr, len))
+                .map_err(|e| anyhow!("{e}"))?;
+            let (ptr, len) = ((result as u64 >> 32) as usize, result as u32 as usize);
+            let mut output = vec![0; len];
+            memory
+                .read(&store, ptr, &mut output)
+                .map_err(|e| anyhow!("Reading the output: {e}"))?;
+            Ok(output)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "plugins"))]
+mod tests {
+    use super::*;
+
+    /// Hands its input back, so that the input's `findings` come back as
+    /// findings.
+    const ECHO: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "context_lint_alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (block $done
+              (loop $grow
+                (br_if $done
+                  (i32.le_u (global.get $next) (i32.mul (memory.size) (i32.const 65536))))
+                (drop (memory.grow (i32.const 1)))
+                (br $grow)))
+            (local.get $ptr))
+          (func (export "context_lint_check") (param $ptr i32) (param $len i32) (result i64)
+            (i64.or
+              (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+              (i64.extend_i32_u (local.get $len)))))
+    "#;
+
+    fn constant(output: &str) -> String {
+        format!(
+            r#"
+            (module
+              (memory (export "memory") 1)
+              (data (i32.const 0) "{}")
+              (func (export "context_lint_alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "context_lint_check") (param i32 i32) (result i64)
+                (i64.const {})))
+            "#,
+            output.replace('"', "\\\"").replace('\n', "\\n"),
+            output.len()
+        )
+    }
+
+    fn load(wat: &str, name: Option<&str>) -> Result<Plugin> {
+        let dir = crate::fixtures::TempDir::new("plugin");
+        std::fs::write(dir.join("rules.wasm"), wat::parse_str(wat).unwrap()).unwrap();
+        let config = PluginConfig {
+            path: "rules.wasm".into(),
+            name: name.map(str::to_string),
+            fuel: Some(1_000_000),
+            options: Default::default(),
+        };
+        Plugin::load(&config, &dir)
+    }
+
+    #[test]
+    fn test_plugin_findings() {
+        let output = r#"{"findings": [
+            {"line": 2, "column": 5, "rule": "no-todo", "message": "TODO left"},
+            {"line": 3, "message": "no column"}
+        ]}"#;
+        let mut plugin = load(&constant(output), None).unwrap();
+        assert_eq!(plugin.name, "rules");
+        let findings = plugin
+            .check("/ws/src/lib.rs", "src/lib.rs", "", &[], &[])
+            .unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].file, "/ws/src/lib.rs");
+        assert_eq!(findings[0].rule, "no-todo");
+        assert_eq!((findings[0].span.line, findings[0].span.column), (2, 5));
+        assert_eq!(findings[1].rule, "rules");
+        assert_eq!(findings[1].line, 3);
+        assert!(!findings[1].span.is_known());
+    }
+
+    #[test]
+    fn test_plugin_input() {
+        let mut plugin = load(ECHO, Some("echo")).unwrap();
+        let builtin = [InputFinding {
+            code: "CL0002",
+            line: 7,
+            message: "fn returning Result without #[context]: `f`".into(),
+        }];
+        let source = "fn f() {}\n".repeat(10_000);
+        let tokens = tokens(&source);
+        let findings = plugin
+            .check("src/lib.rs", "src/lib.rs", &source, &tokens, &builtin)
+            .unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 7);
+        assert_eq!(findings[0].plugin, "echo");
+        assert_eq!(findings[0].message, builtin[0].message);
+    }
+
+    #[test]
+    fn test_plugin_instance_per_file() {
+        // Traps when checking a second file in the same instance.
+        let once = r#"
+            (module
+              (memory (export "memory") 1)
+              (global $checked (mut i32) (i32.const 0))
+              (data (i32.const 0) "{\"findings\": []}")
+              (func (export "context_lint_alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "context_lint_check") (param i32 i32) (result i64)
+                (if (global.get $checked) (then unreachable))
+                (global.set $checked (i32.const 1))
+                (i64.const 16)))
+        "#;
+        let mut plugin = load(once, None).unwrap();
+        for file in ["a.rs", "b.rs"] {
+            assert!(plugin.check(file, file, "", &[], &[]).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_plugin_errors() {
+        let err = load("(module (memory (export \"memory\") 1))", None).unwrap_err();
+        assert!(format!("{err:#}").contains("context_lint_alloc"), "{err:#}");
+
+        let looping = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "context_lint_alloc") (param i32) (result i32) (i32.const 0))
+              (func (export "context_lint_check") (param i32 i32) (result i64)
+                (loop $forever (br $forever))
+                (i64.const 0)))
+        "#;
+        let mut plugin = load(looping, None).unwrap();
+        let err = plugin.check("a.rs", "a.rs", "", &[], &[]).unwrap_err();
+        assert!(format!("{err:#}").contains("Running plugin rules on a.rs"));
+
+        let mut plugin = load(&constant("[]"), None).unwrap();
+        let err = plugin.check("a.rs", "a.rs", "", &[], &[]).unwrap_err();
+        assert!(format!("{err:#}").contains("Parsing the output"));
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = tokens("fn f(x: u8) {\n    x + 1\n}");
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["fn", "f", "(", "x", ":", "u8", ")", "{", "x", "+", "1", "}"]
+        );
+        assert_eq!(tokens[2].kind, TokenKind::Open);
+        assert_eq!(tokens[10].kind, TokenKind::Literal);
+        assert_eq!((tokens[8].line, tokens[8].column), (2, 5));
+        assert!(super::tokens("fn f() { \"unterminated }").is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Check for functions that propagate errors with bare `?` and add no context.
+//!
+//! A function annotated with `#[context]`, or one that calls `.context()` /
+//! `.with_context()` (or eyre's `.wrap_err()` / `.wrap_err_with()`) somewhere,
+//! contributes at least one layer to the error chain. Functions doing neither
+//! just pass errors through, and if enough of them stack up the resulting
+//! chains are hard to act on. This opt-in check measures how much of the
+//! codebase does that.
+
+use std::collections::HashSet;
+
+use syn::visit::Visit;
+use syn::{Attribute, Block, ExprClosure, ExprMethodCall, ExprTry, ImplItemFn, Item, ItemFn};
+use syn::{ItemMod, Signature, Stmt};
+
+use crate::config::{Config, ContextAttribute, ContextMethodsConfig};
+use crate::parse::ParsedFile;
+use crate::unattributed::{
+    self, has_cfg_test_attribute, has_context_attribute, has_test_attribute, WorkspaceItems,
+};
+
+/// A function propagating errors with bare `?` and no context at all.
+#[derive(Debug, Clone)]
+pub struct BarePropagation {
+    /// File where the function is defined.
+    pub file: String,
+    /// Line number of the function definition.
+    pub line: usize,
+    /// The function name.
+    pub name: String,
+    /// Number of statements in the function body.
+    pub statements: usize,
+    /// Number of `?` operators in the function body.
+    pub try_count: usize,
+}
+
+/// Check a single Rust source file for functions propagating errors with bare
+/// `?`. Only functions returning `anyhow::Result`, as the unattributed check
+/// recognizes it, are checked: on other errors `.context()` isn't available.
+/// `module_path` is the module of the file.
+pub fn check_file(
+    file: &ParsedFile,
+    config: &Config,
+    module_path: &[String],
+    workspace: &WorkspaceItems,
+) -> Vec<BarePropagation> {
+    let Some(syntax) = &file.syntax else {
+        return Vec::new();
+    };
+
+    let mut visitor = PropagationChecker {
+        file_path: file.display_path().to_string(),
+        anyhow_result_fns: unattributed::anyhow_result_fns(file, config, module_path, workspace),
+        min_statements: config.bare_propagation.min_statements,
+        methods: &config.context_methods,
+        context_attributes: &config.context_attributes,
+        workspace,
+        in_cfg_test: false,
+        results: Vec::new(),
+    };
+    visitor.visit_file(syntax);
+
+    visitor.results
+}
+
+/// Number of statements in a function body, counted the way
+/// `[bare-propagation] min-statements` does: including those of nested blocks,
+/// but not those of closures or nested items.
+pub fn statement_count(block: &Block) -> usize {
+    let methods = ContextMethodsConfig::default();
+    let mut stats = BodyStats {
+        methods: &methods,
+        statements: 0,
+        try_count: 0,
+        has_context_call: false,
+    };
+    stats.visit_block(block);
+    stats.statements
+}
+
+struct PropagationChecker<'a> {
+    file_path: String,
+    /// Lines of the functions returning `anyhow::Result`.
+    anyhow_result_fns: HashSet<usize>,
+    min_statements: usize,
+    /// Which methods count as adding context.
+    methods: &'a ContextMethodsConfig,
+    /// Additional attributes to treat like `#[context]`.
+    context_attributes: &'a [ContextAttribute],
+    /// Functions found to add context in pass 1.
+    workspace: &'a WorkspaceItems,
+    /// Whether we are inside a `#[cfg(test)]` module.
+    in_cfg_test: bool,
+    results: Vec<BarePropagation>,
+}
+
+impl PropagationChecker<'_> {
+    fn check_fn(&mut self, attrs: &[Attribute], sig: &Signature, block: &Block) {
+        if self.in_cfg_test
+            || !self
+                .anyhow_result_fns
+                .contains(&sig.ident.span().start().line)
+            || has_test_attribute(attrs)
+            || has_context_attribute(attrs, self.context_attributes)
+            || self
+                .workspace
+                .is_annotated(&self.file_path, sig.ident.span().start().line)
+        {
+            return;
+        }
+
+        let mut stats = BodyStats {
+            methods: self.methods,
+            statements: 0,
+            try_count: 0,
+            has_context_call: false,
+        };
+        stats.visit_block(block);
+
+        if stats.has_context_call || stats.try_count == 0 || stats.statements < self.min_statements
+        {
+            return;
+        }
+
+        self.results.push(BarePropagation {
+            file: self.file_path.clone(),
+            line: sig.ident.span().start().line,
+            name: sig.ident.to_string(),
+            statements: stats.statements,
+            try_count: stats.try_count,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for PropagationChecker<'_> {
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.check_fn(&node.attrs, &node.sig, &node.block);
+        syn::visit::visit_item_fn(self, node);
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.check_fn(&node.attrs, &node.sig, &node.block);
+        syn::visit::visit_impl_item_fn(self, node);
+    }
+
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        let prev_in_cfg_test = self.in_cfg_test;
+
+        if has_cfg_test_attribute(&node.attrs) {
+            self.in_cfg_test = true;
+        }
+
+        syn::visit::visit_item_mod(self, node);
+
+        self.in_cfg_test = prev_in_cfg_test;
+    }
+}
+
+/// Statistics about a single function body. Closures and nested items are
+/// skipped: their `?` operators belong to them, not to the function.
+struct BodyStats<'a> {
+    methods: &'a ContextMethodsConfig,
+    statements: usize,
+    try_count: usize,
+    has_context_call: bool,
+}
+
+impl<'ast> Visit<'ast> for BodyStats<'_> {
+    fn visit_stmt(&mut self, node: &'ast Stmt) {
+        self.statements += 1;
+        syn::visit::visit_stmt(self, node);
+    }
+
+    fn visit_expr_try(&mut self, node: &'ast ExprTry) {
+        self.try_count += 1;
+        syn::visit::visit_expr_try(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        if self.methods.is_context_method(&node.method.to_string()) {
+            self.has_context_call = true;
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_closure(&mut self, _node: &'ast ExprClosure) {}
+
+    fn visit_item(&mut self, _node: &'ast Item) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn check_source(source: &str, min_statements: usize) -> Vec<BarePropagation> {
+        let mut config = Config::default();
+        config.bare_propagation.min_statements = min_statements;
+        check_source_with(source, &config)
+    }
+
+    fn check_source_with(source: &str, config: &Config) -> Vec<BarePropagation> {
+        let file = ParsedFile::from_source(Path::new("test.rs"), source.to_string());
+        check_file(&file, config, &[], &WorkspaceItems::default())
+    }
+
+    #[test]
+    fn test_flagged_bare_propagation() {
+        let results = check_source(
+            r#"
+            use anyhow::Result;
+
+            fn sync_all(paths: &[PathBuf]) -> Result<()> {
+                let root = open_root()?;
+                for p in paths {
+                    root.sync(p)?;
+                }
+                Ok(())
+            }
+            "#,
+            3,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "sync_all");
+        assert_eq!(results[0].try_count, 2);
+        assert_eq!(results[0].statements, 4);
+    }
+
+    #[test]
+    fn test_not_flagged_below_threshold() {
+        let results = check_source(
+            r#"
+            fn helper() -> Result<()> {
+                open_root()?;
+                Ok(())
+            }
+            "#,
+            5,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_not_flagged_with_context_call() {
+        let results = check_source(
+            r#"
+            fn sync_all() -> Result<()> {
+                let root = open_root().context("Opening root")?;
+                root.sync()?;
+                Ok(())
+            }
+            "#,
+            1,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_not_flagged_with_context_attribute() {
+        let results = check_source(
+            r#"
+            #[context("Syncing")]
+            fn sync_all() -> Result<()> {
+                let root = open_root()?;
+                root.sync()?;
+                Ok(())
+            }
+            "#,
+            1,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_closure_try_not_counted() {
+        let results = check_source(
+            r#"
+            fn collect(items: &[Item]) -> Vec<Result<u8>> {
+                let a = 1;
+                let b = 2;
+                items.iter().map(|i| { let x = parse(i)?; Ok(x) }).collect()
+            }
+            "#,
+            1,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_only_anyhow_results() {
+        let source = r#"
+            use std::io;
+
+            fn first_line(paths: &[PathBuf]) -> Option<String> {
+                let path = paths.first()?;
+                let line = read_line(path)?;
+                Some(line)
+            }
+
+            fn copy_all(paths: &[PathBuf]) -> io::Result<()> {
+                let root = open_root()?;
+                root.copy(paths)?;
+                Ok(())
+            }
+
+            fn load(path: &Path) -> crate::Result<Config> {
+                let text = read(path)?;
+                parse(&text)
+            }
+        "#;
+        let mut config = Config::default();
+        config.bare_propagation.min_statements = 1;
+        assert!(check_source_with(source, &config).is_empty());
+
+        config.unattributed.result_aliases = vec!["crate::Result".to_string()];
+        let results = check_source_with(source, &config);
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["load"]);
+    }
+}
@@ -1,203 +1,1431 @@
 //! Output formatting for lint results.
 
-use crate::checker::DoubleContext;
-use crate::unattributed::UnattributedFunction;
+use crate::banned::BannedContext;
+use crate::checker::{DoubleContext, MatchKind};
+use crate::collector::{AnnotatedFunction, AnnotationSource};
+use crate::fingerprint;
+use crate::fix::Fix;
+use crate::linter::{Finding, LintLevel, LintLevels};
+use crate::lints::{Lint, Severity};
+use crate::package::{CleanReport, MissingDependency};
+use crate::parse::Span;
+use crate::plugin::PluginFinding;
+use crate::propagation::BarePropagation;
+use crate::stats::{CrateStats, Stats};
+use crate::style::{StyleIssue, StyleKind};
+use crate::unattributed::{Coverage, UnattributedFunction};
+use schemars::JsonSchema;
 use serde::Serialize;
 
-/// JSON-serializable report combining both check types.
-#[derive(Debug, Serialize)]
+/// All findings of a run, borrowed for formatting.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Findings<'a> {
+    pub double_context: &'a [DoubleContext],
+    pub unattributed: &'a [UnattributedFunction],
+    pub style: &'a [StyleIssue],
+    pub bare_propagation: &'a [BarePropagation],
+    pub plugin: &'a [PluginFinding],
+    pub banned_context: &'a [BannedContext],
+    /// Informational notes; these never affect the exit code.
+    pub missing_dependency: &'a [MissingDependency],
+    /// Packages and files without findings, with `--report-clean`.
+    pub clean: Option<&'a CleanReport>,
+    /// The level of each check, which the severity of findings depends on.
+    pub levels: LintLevels,
+}
+
+/// Version of the JSON output, incremented whenever a change could break
+/// consumers (fields removed, renamed or changing meaning). Added fields don't
+/// change it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+impl<'a> Findings<'a> {
+    /// All findings, check by check.
+    pub fn iter(&self) -> impl Iterator<Item = Finding<'a>> {
+        let double_context = self.double_context.iter().map(Finding::DoubleContext);
+        let unattributed = self.unattributed.iter().map(Finding::Unattributed);
+        let style = self.style.iter().map(Finding::Style);
+        let bare_propagation = self.bare_propagation.iter().map(Finding::BarePropagation);
+        let plugin = self.plugin.iter().map(Finding::Plugin);
+        let banned_context = self.banned_context.iter().map(Finding::BannedContext);
+        double_context
+            .chain(unattributed)
+            .chain(style)
+            .chain(bare_propagation)
+            .chain(plugin)
+            .chain(banned_context)
+    }
+
+    /// The severity of a finding, given the level of its check.
+    pub fn severity(&self, finding: &Finding) -> Severity {
+        finding.severity(self.levels.get(finding.lint()))
+    }
+}
+
+/// JSON-serializable report combining all check types.
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonReport {
+    /// Version of the output format (`SCHEMA_VERSION`).
+    pub schema_version: u32,
     pub double_context: JsonDoubleContextSection,
     pub unattributed: JsonUnattributedSection,
+    pub style: JsonStyleSection,
+    pub bare_propagation: JsonBarePropagationSection,
+    pub plugin: JsonPluginSection,
+    pub banned_context: JsonBannedContextSection,
+    pub notes: JsonNotesSection,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clean: Option<JsonCleanSection>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonDoubleContextSection {
     pub warnings: Vec<JsonDoubleContextWarning>,
     pub total: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonUnattributedSection {
     pub warnings: Vec<JsonUnattributedWarning>,
     pub total: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonStyleSection {
+    pub warnings: Vec<JsonStyleWarning>,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonBarePropagationSection {
+    pub warnings: Vec<JsonBarePropagationWarning>,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonPluginSection {
+    pub warnings: Vec<JsonPluginWarning>,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonBannedContextSection {
+    pub warnings: Vec<JsonBannedContextWarning>,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonNotesSection {
+    pub missing_dependency: Vec<JsonMissingDependency>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonCleanSection {
+    pub packages: Vec<JsonCleanPackage>,
+    pub files: Vec<String>,
+    pub files_checked: usize,
+    pub packages_checked: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonCleanPackage {
+    pub name: String,
+    pub manifest_path: String,
+    pub files: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonMissingDependency {
+    pub package: String,
+    pub manifest_path: String,
+    pub unattributed: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonDoubleContextWarning {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Identifies the finding across runs, regardless of its line.
+    pub fingerprint: String,
     pub function_name: String,
     pub call_site: JsonLocation,
     pub definition: JsonLocation,
-    pub inner_context: String,
+    pub inner_context: Option<String>,
+    pub inner_context_source: AnnotationSource,
+    /// The format arguments of the `#[context]` attribute, as written.
+    pub inner_context_args: Vec<String>,
+    pub outer_method: String,
     pub outer_context: Option<String>,
     pub identical: bool,
+    pub match_kind: MatchKind,
+    /// How likely the call refers to the definition, from 0 to 1.
+    pub confidence: f64,
+    /// Further definitions the call may refer to.
+    pub other_definitions: Vec<JsonDefinition>,
+    /// The enclosing function's `#[context]`, if it has one.
+    pub caller: Option<JsonDefinition>,
+    pub suggestion: Option<JsonSuggestion>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonDefinition {
+    pub location: JsonLocation,
+    pub inner_context: Option<String>,
+    pub inner_context_source: AnnotationSource,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonUnattributedWarning {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Identifies the finding across runs, regardless of its line.
+    pub fingerprint: String,
     pub function_name: String,
     pub location: JsonLocation,
     pub is_method: bool,
     pub is_pub: bool,
 }
 
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonStyleWarning {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Identifies the finding across runs, regardless of its line.
+    pub fingerprint: String,
+    pub kind: StyleKind,
+    pub message: String,
+    pub location: JsonLocation,
+    pub suggestion: Option<JsonSuggestion>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonBarePropagationWarning {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Identifies the finding across runs, regardless of its line.
+    pub fingerprint: String,
+    pub function_name: String,
+    pub location: JsonLocation,
+    pub statements: usize,
+    pub try_count: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonPluginWarning {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Identifies the finding across runs, regardless of its line.
+    pub fingerprint: String,
+    /// Name of the plugin that reported it.
+    pub plugin: String,
+    pub rule: String,
+    pub message: String,
+    pub location: JsonLocation,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonBannedContextWarning {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Identifies the finding across runs, regardless of its line.
+    pub fingerprint: String,
+    pub message: String,
+    /// Where the string is, e.g. `` `.context()` ``.
+    pub site: String,
+    pub context: String,
+    /// The pattern it matches, as configured.
+    pub pattern: String,
+    /// The part of the context matching the pattern.
+    pub matched: String,
+    pub location: JsonLocation,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonSuggestion {
+    pub description: String,
+    pub edits: Vec<JsonEdit>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonEdit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonStats {
+    /// Version of the output format (`SCHEMA_VERSION`).
+    pub schema_version: u32,
+    pub crates: Vec<JsonCrateStats>,
+    pub top_files: Vec<JsonFileWarnings>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonCrateStats {
+    pub name: String,
+    pub functions: usize,
+    pub annotated: usize,
+    pub coverage: Option<f64>,
+    pub double_context: usize,
+    pub warnings: usize,
+}
+
+/// The output of `coverage --format json`.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonCoverage {
+    /// Version of the output format (`SCHEMA_VERSION`).
+    pub schema_version: u32,
+    /// Functions returning `anyhow::Result` in all checked crates.
+    pub functions: usize,
+    /// Those of them with `#[context]`.
+    pub annotated: usize,
+    /// Percentage of them with `#[context]`, `null` if there are none.
+    pub coverage: Option<f64>,
+    /// The minimum coverage of each crate given with `--fail-under`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_under: Option<f64>,
+    pub crates: Vec<JsonCrateCoverage>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonCrateCoverage {
+    pub name: String,
+    pub functions: usize,
+    pub annotated: usize,
+    pub coverage: Option<f64>,
+    /// Whether the coverage is below `--fail-under`.
+    pub under: bool,
+}
+
+/// The `--metrics` file.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonMetrics {
+    /// Version of the output format (`SCHEMA_VERSION`).
+    pub schema_version: u32,
+    /// Functions returning `anyhow::Result` in all checked crates.
+    pub functions: usize,
+    /// Those of them with `#[context]`.
+    pub annotated: usize,
+    /// Percentage of them with `#[context]`, `null` if there are none.
+    pub coverage: Option<f64>,
+    pub crates: Vec<JsonCrateStats>,
+    pub modules: Vec<JsonModuleStats>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonModuleStats {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    /// `null` for tests, examples and extra binaries.
+    pub module: Option<String>,
+    pub file: String,
+    pub functions: usize,
+    pub annotated: usize,
+    pub coverage: Option<f64>,
+}
+
+/// A badge for shields.io's endpoint badges
+/// (<https://shields.io/badges/endpoint-badge>).
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonBadge {
+    pub schema_version: u32,
+    pub label: String,
+    pub message: String,
+    pub color: String,
+}
+
+/// The output of `list --format json`.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonFunctionList {
+    /// Version of the output format (`SCHEMA_VERSION`).
+    pub schema_version: u32,
+    pub functions: Vec<JsonAnnotatedFunction>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonAnnotatedFunction {
+    pub name: String,
+    /// Path of the defining module, e.g. `my_crate::pod`, if known.
+    pub module: Option<String>,
+    /// The type or trait of the enclosing `impl` or `trait` block.
+    pub self_type: Option<String>,
+    /// Visibility, asyncness, name, generics and parameter names, e.g.
+    /// `pub async fn load<P: AsRef<Path>>(path)`.
+    pub signature: String,
+    /// The context string, if the annotation or rule has one.
+    pub context: Option<String>,
+    pub source: AnnotationSource,
+    /// The format arguments following the context string, as written.
+    pub context_args: Vec<String>,
+    /// The `cfg_attr` predicate the attribute depends on, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cfg: Option<String>,
+    pub location: JsonLocation,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct JsonFileWarnings {
+    pub file: String,
+    pub warnings: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct JsonLocation {
     pub file: String,
     pub line: usize,
+    /// 1-based column (in characters), where known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// End of the flagged code (exclusive), where known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<usize>,
+    /// Byte offset of the start of the flagged code, where known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_byte: Option<usize>,
+    /// Byte offset of the end of the flagged code (exclusive), where known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_byte: Option<usize>,
+}
+
+impl JsonLocation {
+    /// A known `span` takes precedence over `line`, so that the line and
+    /// column always point at the same token.
+    fn new(file: &str, line: usize, span: Option<Span>, strip_prefix: Option<&str>) -> Self {
+        let span = span.filter(Span::is_known);
+        Self {
+            file: strip_path(file, strip_prefix).to_string(),
+            line: span.map_or(line, |span| span.line),
+            column: span.map(|span| span.column),
+            end_line: span.map(|span| span.end_line),
+            end_column: span.map(|span| span.end_column),
+            start_byte: span.and_then(|span| span.bytes()).map(|bytes| bytes.start),
+            end_byte: span.and_then(|span| span.bytes()).map(|bytes| bytes.end),
+        }
+    }
 }
 
 // ── Text formatting ─────────────────────────────────────────────────────
 
+/// Layout options for the text format.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TextLayout {
+    /// Wrap lines longer than this many characters.
+    pub max_width: Option<usize>,
+    /// Truncate displayed context strings to this many characters.
+    pub truncate_context: Option<usize>,
+}
+
+/// Narrowest width we wrap to; anything smaller makes the output unreadable.
+const MIN_WRAP_WIDTH: usize = 40;
+
 /// Format combined results as human-readable text with separate sections.
 pub fn format_combined_text(
-    double_context: &[DoubleContext],
-    unattributed: &[UnattributedFunction],
+    findings: &Findings,
     strip_prefix: Option<&str>,
+    layout: &TextLayout,
 ) -> String {
+    let Findings {
+        double_context,
+        unattributed,
+        style,
+        bare_propagation,
+        plugin,
+        banned_context,
+        missing_dependency,
+        clean,
+        levels,
+    } = *findings;
     let mut output = String::new();
 
     if !double_context.is_empty() {
-        output.push_str(&format_double_context_text(double_context, strip_prefix));
+        output.push_str(&format_section_text(
+            double_context.iter().map(Finding::DoubleContext),
+            levels.double_context,
+            strip_prefix,
+            layout.truncate_context,
+            ("double-context warning", "double-context warnings"),
+        ));
     }
 
     if !unattributed.is_empty() {
         if !output.is_empty() {
             output.push('\n');
         }
-        output.push_str(&format_unattributed_text(unattributed, strip_prefix));
+        output.push_str(&format_section_text(
+            unattributed.iter().map(Finding::Unattributed),
+            levels.unattributed,
+            strip_prefix,
+            layout.truncate_context,
+            (
+                "unattributed function returning anyhow::Result",
+                "unattributed functions returning anyhow::Result",
+            ),
+        ));
+    }
+
+    if !style.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format_section_text(
+            style.iter().map(Finding::Style),
+            levels.style,
+            strip_prefix,
+            layout.truncate_context,
+            ("style warning", "style warnings"),
+        ));
+    }
+
+    if !bare_propagation.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format_section_text(
+            bare_propagation.iter().map(Finding::BarePropagation),
+            levels.bare_propagation,
+            strip_prefix,
+            layout.truncate_context,
+            (
+                "function propagating errors without context",
+                "functions propagating errors without context",
+            ),
+        ));
+    }
+
+    if !plugin.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format_section_text(
+            plugin.iter().map(Finding::Plugin),
+            levels.plugin,
+            strip_prefix,
+            layout.truncate_context,
+            ("plugin finding", "plugin findings"),
+        ));
+    }
+
+    if !banned_context.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format_section_text(
+            banned_context.iter().map(Finding::BannedContext),
+            levels.banned_context,
+            strip_prefix,
+            layout.truncate_context,
+            ("banned context string", "banned context strings"),
+        ));
+    }
+
+    if !missing_dependency.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format_missing_dependency_text(
+            missing_dependency,
+            strip_prefix,
+        ));
+    }
+
+    if let Some(clean) = clean {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format_clean_text(clean, strip_prefix));
+    }
+
+    match layout.max_width {
+        Some(width) => wrap_text(&output, width.max(MIN_WRAP_WIDTH)),
+        None => output,
+    }
+}
+
+/// Format findings one line each, like [`format_short_text`], followed by
+/// their number, for pre-commit hooks.
+pub fn format_concise_text(findings: &Findings, strip_prefix: Option<&str>) -> String {
+    let mut output = format_short_text(findings, strip_prefix);
+    let count = output.lines().count();
+    if count > 0 {
+        output.push_str(&format!(
+            "Found {} warning{}\n",
+            count,
+            if count == 1 { "" } else { "s" }
+        ));
+    }
+    output
+}
+
+/// Format findings one line each, like gcc does
+/// (`file:line:column: severity[code]: message [fingerprint]`), for
+/// `--format short`: editors' quickfix lists and grep.
+pub fn format_short_text(findings: &Findings, strip_prefix: Option<&str>) -> String {
+    let mut sorted: Vec<Finding> = findings.iter().collect();
+    sorted.sort_by(|a, b| a.file().cmp(b.file()).then(a.line().cmp(&b.line())));
+
+    let mut output = String::new();
+    for finding in &sorted {
+        output.push_str(&format!(
+            "{}: {}[{}]: {} [{}]\n",
+            position(finding, strip_prefix),
+            findings.severity(finding),
+            finding.lint().code(),
+            message(finding),
+            fingerprint::of_finding(finding, strip_prefix)
+        ));
+    }
+    output
+}
+
+/// Format findings as human-readable text with one section per file, for
+/// `--group-by file`.
+pub fn format_grouped_text(
+    findings: &Findings,
+    strip_prefix: Option<&str>,
+    layout: &TextLayout,
+) -> String {
+    let mut sorted: Vec<Finding> = findings.iter().collect();
+    sorted.sort_by(|a, b| a.file().cmp(b.file()).then(a.line().cmp(&b.line())));
+
+    let mut output = String::new();
+    let mut files = 0;
+    for (i, finding) in sorted.iter().enumerate() {
+        if i == 0 || sorted[i - 1].file() != finding.file() {
+            if i > 0 {
+                output.push('\n');
+            }
+            output.push_str(&format!("{}:\n", strip_path(finding.file(), strip_prefix)));
+            files += 1;
+        }
+        output.push_str(&format!(
+            "  {}: {}[{}]: {}\n",
+            line_column(finding),
+            findings.severity(finding),
+            finding.lint().code(),
+            message(finding)
+        ));
+        for line in details(finding, strip_prefix, layout.truncate_context).lines() {
+            output.push_str(&format!("  {line}\n"));
+        }
+    }
+    if !sorted.is_empty() {
+        output.push_str(&format!(
+            "\nFound {} warning{} in {} file{}\n",
+            sorted.len(),
+            if sorted.len() == 1 { "" } else { "s" },
+            files,
+            if files == 1 { "" } else { "s" }
+        ));
+    }
+
+    let notes = Findings {
+        double_context: &[],
+        unattributed: &[],
+        style: &[],
+        bare_propagation: &[],
+        plugin: &[],
+        banned_context: &[],
+        ..*findings
+    };
+    let notes = format_combined_text(&notes, strip_prefix, &TextLayout::default());
+    if !notes.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&notes);
+    }
+
+    match layout.max_width {
+        Some(width) => wrap_text(&output, width.max(MIN_WRAP_WIDTH)),
+        None => output,
+    }
+}
+
+/// `file:line:column` of a finding, or `file:line` if the column is unknown.
+fn position(finding: &Finding, strip_prefix: Option<&str>) -> String {
+    format!(
+        "{}:{}",
+        strip_path(finding.file(), strip_prefix),
+        line_column(finding)
+    )
+}
+
+/// `line:col` for a finding, or just `line` when its span is unknown.
+fn line_column(finding: &Finding) -> String {
+    match finding.span() {
+        Some(span) => format!("{}:{}", span.line, span.column),
+        None => finding.line().to_string(),
+    }
+}
+
+/// The one-line description of a finding.
+pub fn message(finding: &Finding) -> String {
+    match finding {
+        Finding::DoubleContext(issue) => format!("double context on `{}`", issue.function_name),
+        Finding::Unattributed(issue) => {
+            let kind = if issue.is_method { "method" } else { "fn" };
+            format!(
+                "{kind} returning Result without #[context]: `{}`",
+                issue.name
+            )
+        }
+        Finding::Style(issue) => issue.message.clone(),
+        Finding::BarePropagation(issue) => format!(
+            "`{}` propagates errors with bare `?` and adds no context",
+            issue.name
+        ),
+        Finding::Plugin(issue) => issue.message.clone(),
+        Finding::BannedContext(issue) => {
+            format!("{} matches banned pattern `{}`", issue.site, issue.pattern)
+        }
+    }
+}
+
+/// `file:line:column` of a definition's `#[context]` attribute, or
+/// `file:line` of the function if the attribute's position is unknown.
+fn definition_location(file: &str, line: usize, span: Span, strip_prefix: Option<&str>) -> String {
+    let file = strip_path(file, strip_prefix);
+    if span.is_known() {
+        format!("{file}:{}:{}", span.line, span.column)
+    } else {
+        format!("{file}:{line}")
+    }
+}
+
+/// The lines shown below the location of a finding (`   | ...` and
+/// `   = ...`).
+fn details(
+    finding: &Finding,
+    strip_prefix: Option<&str>,
+    truncate_context: Option<usize>,
+) -> String {
+    let mut output = String::new();
+    match finding {
+        Finding::DoubleContext(issue) => {
+            let outer_display = issue
+                .outer_context
+                .as_deref()
+                .unwrap_or("<complex expression>");
+            let method = format!(".{}()", issue.outer_method);
+
+            output.push_str(&format!(
+                "   | inner context (from {}): {}\n",
+                context_origin(issue.inner_source),
+                quoted_context(issue.inner_context.as_deref(), truncate_context)
+            ));
+            if !issue.inner_args.is_empty() {
+                output.push_str(&format!(
+                    "   |   with arguments: {}\n",
+                    issue.inner_args.join(", ")
+                ));
+            }
+            output.push_str(&format!(
+                "   |   defined at: {}\n",
+                definition_location(
+                    &issue.def_file,
+                    issue.def_line,
+                    issue.def_span,
+                    strip_prefix
+                )
+            ));
+            for other in &issue.other_definitions {
+                output.push_str(&format!(
+                    "   | also matches: {}\n",
+                    quoted_context(other.inner_context.as_deref(), truncate_context)
+                ));
+                output.push_str(&format!(
+                    "   |   defined at: {}\n",
+                    definition_location(&other.file, other.line, other.span, strip_prefix)
+                ));
+            }
+            output.push_str(&format!(
+                "   | outer context (from {method}): \"{}\"\n",
+                truncate(outer_display, truncate_context).escape_debug()
+            ));
+            if let Some(caller) = &issue.caller {
+                output.push_str(&format!(
+                    "   | caller context (from {}): {}\n",
+                    context_origin(caller.source),
+                    quoted_context(caller.inner_context.as_deref(), truncate_context)
+                ));
+                output.push_str(&format!(
+                    "   |   defined at: {}\n",
+                    definition_location(&caller.file, caller.line, caller.span, strip_prefix)
+                ));
+            }
+            output.push_str(&format!("   | confidence: {:.2}\n", issue.confidence));
+            if issue.is_identical() {
+                output.push_str("   |\n");
+                output.push_str("   = note: these context strings are identical\n");
+            }
+            if issue.caller.is_some() {
+                output.push_str(
+                    "   = note: the caller has #[context] too, so three context layers stack\n",
+                );
+            }
+            if issue.match_kind == MatchKind::Name {
+                output.push_str(
+                    "   = note: matched by name only; the call may refer to another function\n",
+                );
+            }
+            if let Some(fix) = &issue.fix {
+                output.push_str(&format!("   = help: {}\n", fix.description));
+            }
+        }
+        Finding::Unattributed(issue) => {
+            let vis = if issue.is_pub { "pub " } else { "" };
+            let kind = if issue.is_method { "method" } else { "fn" };
+            output.push_str(&format!("   | {vis}{kind} {}\n", issue.name));
+        }
+        Finding::Style(issue) => {
+            if let Some(fix) = &issue.fix {
+                output.push_str(&format!("   = help: {}\n", fix.description));
+            }
+        }
+        Finding::BarePropagation(issue) => {
+            output.push_str(&format!(
+                "   | {} statement{}, {} `?` operator{}\n",
+                issue.statements,
+                if issue.statements == 1 { "" } else { "s" },
+                issue.try_count,
+                if issue.try_count == 1 { "" } else { "s" }
+            ));
+            output.push_str("   = help: add #[context] or `.context()` on the fallible calls\n");
+        }
+        Finding::Plugin(issue) => {
+            output.push_str(&format!(
+                "   = note: rule `{}` of plugin `{}`\n",
+                issue.rule, issue.plugin
+            ));
+        }
+        Finding::BannedContext(issue) => {
+            output.push_str(&format!("   | context: {:?}\n", issue.context));
+            output.push_str(&format!("   | matched: {:?}\n", issue.matched));
+        }
+    }
+    output.push_str(&format!(
+        "   = fingerprint: {}\n",
+        fingerprint::of_finding(finding, strip_prefix)
+    ));
+    output
+}
+
+/// Format the findings of one check as human-readable text, followed by
+/// `Found {count} {summary}`.
+fn format_section_text<'a>(
+    findings: impl ExactSizeIterator<Item = Finding<'a>>,
+    level: LintLevel,
+    strip_prefix: Option<&str>,
+    truncate_context: Option<usize>,
+    summary: (&str, &str),
+) -> String {
+    let mut output = String::new();
+    let count = findings.len();
+
+    for finding in findings {
+        output.push_str(&format!(
+            "{}[{}]: {}\n",
+            finding.severity(level),
+            finding.lint().code(),
+            message(&finding)
+        ));
+        output.push_str(&format!("  --> {}\n", position(&finding, strip_prefix)));
+        output.push_str(&details(&finding, strip_prefix, truncate_context));
+        output.push('\n');
+    }
+
+    let (singular, plural) = summary;
+    output.push_str(&format!(
+        "Found {count} {}\n",
+        if count == 1 { singular } else { plural }
+    ));
+
+    output
+}
+
+/// Format missing-dependency notes as human-readable text.
+fn format_missing_dependency_text(
+    notes: &[MissingDependency],
+    strip_prefix: Option<&str>,
+) -> String {
+    let mut output = String::new();
+
+    for (i, note) in notes.iter().enumerate() {
+        let manifest = strip_path(&note.manifest_path, strip_prefix);
+
+        if i > 0 {
+            output.push('\n');
+        }
+
+        output.push_str(&format!(
+            "note: package `{}` has {} unattributed function{} but does not depend on fn_error_context\n",
+            note.package,
+            note.unattributed,
+            if note.unattributed == 1 { "" } else { "s" }
+        ));
+        output.push_str(&format!("  --> {manifest}\n"));
+        output.push_str(
+            "   = help: add `fn-error-context` to [dependencies] to annotate them with #[context]\n",
+        );
+    }
+
+    output
+}
+
+/// Format the clean packages and files as human-readable text.
+fn format_clean_text(clean: &CleanReport, strip_prefix: Option<&str>) -> String {
+    let mut output = String::new();
+
+    for package in &clean.packages {
+        let manifest = strip_path(&package.manifest_path, strip_prefix);
+        output.push_str(&format!(
+            "ok: package `{}` is clean ({} file{} checked)\n",
+            package.name,
+            package.files,
+            if package.files == 1 { "" } else { "s" }
+        ));
+        output.push_str(&format!("  --> {manifest}\n"));
+    }
+    if !clean.packages.is_empty() {
+        output.push('\n');
+    }
+
+    for file in &clean.files {
+        output.push_str(&format!("ok: {}\n", strip_path(file, strip_prefix)));
+    }
+    if !clean.files.is_empty() {
+        output.push('\n');
+    }
+
+    output.push_str(&format!(
+        "{} of {} package{} and {} of {} file{} clean\n",
+        clean.packages.len(),
+        clean.packages_checked,
+        if clean.packages_checked == 1 { "" } else { "s" },
+        clean.files.len(),
+        clean.files_checked,
+        if clean.files_checked == 1 { "" } else { "s" }
+    ));
+
+    output
+}
+
+/// Where the context of a definition comes from, as shown in findings.
+fn context_origin(source: AnnotationSource) -> &'static str {
+    match source {
+        AnnotationSource::Attribute => "#[context]",
+        AnnotationSource::Rule => "[[rules]]",
+    }
+}
+
+/// A definition's context in quotes, or `<unknown>` if it has none.
+fn quoted_context(context: Option<&str>, truncate_context: Option<usize>) -> String {
+    match context {
+        Some(context) => format!("\"{}\"", truncate(context, truncate_context).escape_debug()),
+        None => "<unknown>".to_string(),
+    }
+}
+
+/// Truncate a string to at most `max` characters, marking the cut with `…`.
+fn truncate(s: &str, max: Option<usize>) -> std::borrow::Cow<'_, str> {
+    match max {
+        Some(max) if s.chars().count() > max => {
+            let kept: String = s.chars().take(max.saturating_sub(1)).collect();
+            format!("{kept}…").into()
+        }
+        _ => s.into(),
+    }
+}
+
+/// Wrap every line of `text` that is wider than `width`.
+fn wrap_text(text: &str, width: usize) -> String {
+    let mut output = String::with_capacity(text.len());
+    for line in text.lines() {
+        for wrapped in wrap_line(line, width) {
+            output.push_str(&wrapped);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Wrap a single line, breaking after spaces and path separators where possible.
+///
+/// Continuation lines are indented to stay inside the line's gutter: lines of a
+/// `   |` / `   =` block continue as `   |     `, `  -->` locations are
+/// indented past the arrow, and headlines get a plain indent.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    if line.chars().count() <= width {
+        return vec![line.to_string()];
+    }
+
+    let indent = if line.starts_with("   |") || line.starts_with("   =") {
+        "   |     "
+    } else if line.starts_with("  --> ") {
+        "      "
+    } else {
+        "    "
+    };
+
+    // Split into chunks that each end at a break opportunity.
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    for c in line.chars() {
+        chunk.push(c);
+        if c == ' ' || c == '/' {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for chunk in chunks {
+        let current_len = current.chars().count();
+        let chunk_len = chunk.trim_end().chars().count();
+        if current_len + chunk_len > width && current.trim() != indent.trim() {
+            lines.push(current.trim_end().to_string());
+            current = indent.to_string();
+        }
+        // Hard-break chunks that can't fit even on a fresh line.
+        for c in chunk.chars() {
+            if current.chars().count() >= width {
+                lines.push(current.trim_end().to_string());
+                current = indent.to_string();
+            }
+            current.push(c);
+        }
+    }
+    if !current.trim().is_empty() {
+        lines.push(current.trim_end().to_string());
+    }
+    lines
+}
+
+/// Format `--stats` metrics as a human-readable table.
+pub fn format_stats_text(stats: &Stats, strip_prefix: Option<&str>) -> String {
+    let name_width = stats
+        .crates
+        .iter()
+        .map(|c| c.name.len())
+        .chain(["crate".len()])
+        .max()
+        .unwrap_or_default();
+    let mut output = format!(
+        "{:<name_width$}  {:>9}  {:>9}  {:>8}  {:>14}  {:>8}\n",
+        "crate", "functions", "annotated", "coverage", "double-context", "warnings"
+    );
+    for c in &stats.crates {
+        let coverage = match c.coverage_percent() {
+            Some(percent) => format!("{percent:.1}%"),
+            None => "-".to_string(),
+        };
+        output.push_str(&format!(
+            "{:<name_width$}  {:>9}  {:>9}  {:>8}  {:>14}  {:>8}\n",
+            c.name,
+            c.coverage.functions,
+            c.coverage.annotated,
+            coverage,
+            c.double_context,
+            c.warnings
+        ));
+    }
+
+    if !stats.top_files.is_empty() {
+        output.push_str("\nTop files by warnings:\n");
+        for (file, count) in &stats.top_files {
+            output.push_str(&format!(
+                "  {count:>5}  {}\n",
+                strip_path(file, strip_prefix)
+            ));
+        }
+    }
+
+    output
+}
+
+/// Format the annotated functions for `list`, grouped by module (and
+/// `impl` block) in path order.
+pub fn format_function_list_text(
+    functions: &[AnnotatedFunction],
+    strip_prefix: Option<&str>,
+) -> String {
+    let mut output = String::new();
+    let mut current = None;
+    for af in sorted_functions(functions) {
+        let mut scope = af.module_path.clone();
+        scope.extend(af.self_type.clone());
+        if current.as_ref() != Some(&scope) {
+            if current.is_some() {
+                output.push('\n');
+            }
+            if scope.is_empty() {
+                output.push_str("(unknown module)\n");
+            } else {
+                output.push_str(&format!("{}\n", scope.join("::")));
+            }
+            current = Some(scope);
+        }
+        let condition = match &af.cfg {
+            Some(cfg) => format!(" if {cfg}"),
+            None => String::new(),
+        };
+        output.push_str(&format!(
+            "  {}\n      {}{condition}\n      at {}:{}\n",
+            af.signature(),
+            af.annotation(),
+            strip_path(&af.file, strip_prefix),
+            af.line,
+        ));
+    }
+    output
+}
+
+/// Annotated functions ordered by module path, then location.
+fn sorted_functions(functions: &[AnnotatedFunction]) -> Vec<&AnnotatedFunction> {
+    let mut sorted: Vec<_> = functions.iter().collect();
+    sorted.sort_by(|a, b| {
+        (&a.module_path, &a.self_type, &a.file, a.line).cmp(&(
+            &b.module_path,
+            &b.self_type,
+            &b.file,
+            b.line,
+        ))
+    });
+    sorted
+}
+
+/// Format the `coverage` table: per crate and in total, with crates below
+/// `fail_under` marked.
+pub fn format_coverage_text(stats: &Stats, fail_under: Option<f64>) -> String {
+    let name_width = stats
+        .crates
+        .iter()
+        .map(|c| c.name.len())
+        .chain(["crate".len()])
+        .max()
+        .unwrap_or_default();
+    let under = fail_under.map(|min| stats.under(min)).unwrap_or_default();
+    let row = |name: &str, coverage: &Coverage| {
+        let percent = match coverage.percent() {
+            Some(percent) => format!("{percent:.1}%"),
+            None => "-".to_string(),
+        };
+        format!(
+            "{name:<name_width$}  {:>9}  {:>9}  {percent:>8}",
+            coverage.functions, coverage.annotated
+        )
+    };
+    let mut output = format!(
+        "{:<name_width$}  {:>9}  {:>9}  {:>8}\n",
+        "crate", "functions", "annotated", "coverage"
+    );
+    for c in &stats.crates {
+        output.push_str(&row(&c.name, &c.coverage));
+        if let (Some(min), true) = (fail_under, under.contains(&c)) {
+            output.push_str(&format!("  (under {min}%)"));
+        }
+        output.push('\n');
+    }
+    if stats.crates.len() > 1 {
+        output.push_str(&row("total", &stats.total_coverage()));
+        output.push('\n');
     }
-
     output
 }
 
-/// Format double-context results as human-readable text.
-fn format_double_context_text(issues: &[DoubleContext], strip_prefix: Option<&str>) -> String {
-    let mut output = String::new();
+// ── JSON formatting ─────────────────────────────────────────────────────
 
-    for issue in issues {
-        let call_file = strip_path(&issue.call_file, strip_prefix);
-        let def_file = strip_path(&issue.def_file, strip_prefix);
+/// Format the `coverage` metrics as JSON.
+pub fn format_coverage_json(stats: &Stats, fail_under: Option<f64>) -> String {
+    let total = stats.total_coverage();
+    let under = fail_under.map(|min| stats.under(min)).unwrap_or_default();
+    let coverage = JsonCoverage {
+        schema_version: SCHEMA_VERSION,
+        functions: total.functions,
+        annotated: total.annotated,
+        coverage: total.percent(),
+        fail_under,
+        crates: stats
+            .crates
+            .iter()
+            .map(|c| JsonCrateCoverage {
+                name: c.name.clone(),
+                functions: c.coverage.functions,
+                annotated: c.coverage.annotated,
+                coverage: c.coverage_percent(),
+                under: under.contains(&c),
+            })
+            .collect(),
+    };
 
-        let outer_display = issue
-            .outer_context
-            .as_deref()
-            .unwrap_or("<complex expression>");
+    serde_json::to_string_pretty(&coverage).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
+}
 
-        let method = if issue.is_with_context {
-            ".with_context()"
-        } else {
-            ".context()"
-        };
+/// Format the annotated functions for `list --format json`.
+pub fn format_function_list_json(
+    functions: &[AnnotatedFunction],
+    strip_prefix: Option<&str>,
+) -> String {
+    let list = JsonFunctionList {
+        schema_version: SCHEMA_VERSION,
+        functions: sorted_functions(functions)
+            .into_iter()
+            .map(|af| JsonAnnotatedFunction {
+                name: af.name.to_string(),
+                module: (!af.module_path.is_empty()).then(|| af.module_path.join("::")),
+                self_type: af.self_type.clone(),
+                signature: af.signature(),
+                context: af.context_string.clone(),
+                source: af.source,
+                context_args: af.context_args.clone(),
+                cfg: af.cfg.clone(),
+                location: JsonLocation::new(&af.file, af.line, None, strip_prefix),
+            })
+            .collect(),
+    };
 
-        let identical = is_context_identical(&issue.inner_context, outer_display);
+    serde_json::to_string_pretty(&list).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
+}
 
-        output.push_str(&format!(
-            "warning: double context on `{}`\n",
-            issue.function_name
-        ));
-        output.push_str(&format!("  --> {}:{}\n", call_file, issue.call_line));
-        output.push_str(&format!(
-            "   | inner context (from #[context]): \"{}\"\n",
-            issue.inner_context
-        ));
-        output.push_str(&format!(
-            "   |   defined at: {}:{}\n",
-            def_file, issue.def_line
-        ));
-        output.push_str(&format!(
-            "   | outer context (from {method}): \"{outer_display}\"\n",
-        ));
-        if identical {
-            output.push_str("   |\n");
-            output.push_str("   = note: these context strings are identical\n");
-        }
-        output.push('\n');
-    }
+/// Format `--stats` metrics as JSON.
+pub fn format_stats_json(stats: &Stats, strip_prefix: Option<&str>) -> String {
+    let report = JsonStats {
+        schema_version: SCHEMA_VERSION,
+        crates: stats.crates.iter().map(json_crate_stats).collect(),
+        top_files: stats
+            .top_files
+            .iter()
+            .map(|(file, warnings)| JsonFileWarnings {
+                file: strip_path(file, strip_prefix).to_string(),
+                warnings: *warnings,
+            })
+            .collect(),
+    };
 
-    output.push_str(&format!(
-        "Found {} double-context warning{}\n",
-        issues.len(),
-        if issues.len() == 1 { "" } else { "s" }
-    ));
+    serde_json::to_string_pretty(&report).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
+}
 
-    output
+fn json_crate_stats(stats: &CrateStats) -> JsonCrateStats {
+    JsonCrateStats {
+        name: stats.name.clone(),
+        functions: stats.coverage.functions,
+        annotated: stats.coverage.annotated,
+        coverage: stats.coverage_percent(),
+        double_context: stats.double_context,
+        warnings: stats.warnings,
+    }
 }
 
-/// Format unattributed function results as human-readable text.
-fn format_unattributed_text(issues: &[UnattributedFunction], strip_prefix: Option<&str>) -> String {
-    let mut output = String::new();
+/// Format the coverage metrics of `--metrics` as JSON: overall, per crate and
+/// per module.
+pub fn format_metrics_json(stats: &Stats, strip_prefix: Option<&str>) -> String {
+    let total = stats.total_coverage();
+    let metrics = JsonMetrics {
+        schema_version: SCHEMA_VERSION,
+        functions: total.functions,
+        annotated: total.annotated,
+        coverage: total.percent(),
+        crates: stats.crates.iter().map(json_crate_stats).collect(),
+        modules: stats
+            .modules
+            .iter()
+            .map(|m| JsonModuleStats {
+                crate_name: m.crate_name.clone(),
+                module: m.module.clone(),
+                file: strip_path(&m.file, strip_prefix).to_string(),
+                functions: m.coverage.functions,
+                annotated: m.coverage.annotated,
+                coverage: m.coverage.percent(),
+            })
+            .collect(),
+    };
 
-    for issue in issues {
-        let file = strip_path(&issue.file, strip_prefix);
+    serde_json::to_string_pretty(&metrics).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
+}
 
-        let vis = if issue.is_pub { "pub " } else { "" };
-        let kind = if issue.is_method { "method" } else { "fn" };
+/// Format the overall `#[context]` coverage as a shields.io endpoint badge.
+pub fn format_badge_json(stats: &Stats) -> String {
+    let (message, color) = match stats.total_coverage().percent() {
+        Some(percent) => (format!("{percent:.0}%"), badge_color(percent)),
+        None => ("n/a".to_string(), "lightgrey"),
+    };
+    let badge = JsonBadge {
+        schema_version: 1,
+        label: "error context".to_string(),
+        message,
+        color: color.to_string(),
+    };
+    serde_json::to_string_pretty(&badge).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
+}
 
-        output.push_str(&format!(
-            "warning: {kind} returning Result without #[context]: `{}`\n",
-            issue.name
-        ));
-        output.push_str(&format!("  --> {}:{}\n", file, issue.line));
-        output.push_str(&format!("   | {vis}{kind} {}\n", issue.name));
-        output.push('\n');
+/// The usual coverage badge colors, from red to bright green.
+fn badge_color(percent: f64) -> &'static str {
+    match percent {
+        p if p >= 90.0 => "brightgreen",
+        p if p >= 75.0 => "green",
+        p if p >= 60.0 => "yellowgreen",
+        p if p >= 40.0 => "yellow",
+        p if p >= 20.0 => "orange",
+        _ => "red",
     }
-
-    output.push_str(&format!(
-        "Found {} unattributed function{} returning anyhow::Result\n",
-        issues.len(),
-        if issues.len() == 1 { "" } else { "s" }
-    ));
-
-    output
 }
 
-// ── JSON formatting ─────────────────────────────────────────────────────
+/// The JSON Schema of the JSON output: of the report, or with `stats` of the
+/// `--stats` metrics.
+pub fn format_json_schema(stats: bool) -> String {
+    let schema = if stats {
+        schemars::schema_for!(JsonStats)
+    } else {
+        schemars::schema_for!(JsonReport)
+    };
+    serde_json::to_string_pretty(&schema).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
+}
 
 /// Format combined results as JSON.
-pub fn format_combined_json(
-    double_context: &[DoubleContext],
-    unattributed: &[UnattributedFunction],
-    strip_prefix: Option<&str>,
-) -> String {
+pub fn format_combined_json(findings: &Findings, strip_prefix: Option<&str>) -> String {
+    let Findings {
+        double_context,
+        unattributed,
+        style,
+        bare_propagation,
+        plugin,
+        banned_context,
+        missing_dependency,
+        clean,
+        levels,
+    } = *findings;
+
     let dc_warnings: Vec<JsonDoubleContextWarning> = double_context
         .iter()
-        .map(|issue| {
-            let outer = issue
-                .outer_context
-                .as_deref()
-                .unwrap_or("<complex expression>");
-            JsonDoubleContextWarning {
-                function_name: issue.function_name.clone(),
-                call_site: JsonLocation {
-                    file: strip_path(&issue.call_file, strip_prefix).to_string(),
-                    line: issue.call_line,
-                },
-                definition: JsonLocation {
-                    file: strip_path(&issue.def_file, strip_prefix).to_string(),
-                    line: issue.def_line,
-                },
-                inner_context: issue.inner_context.clone(),
-                outer_context: issue.outer_context.clone(),
-                identical: is_context_identical(&issue.inner_context, outer),
-            }
+        .map(|issue| JsonDoubleContextWarning {
+            code: Lint::DoubleContext.code(),
+            severity: Finding::DoubleContext(issue).severity(levels.double_context),
+            fingerprint: fingerprint::of_finding(&Finding::DoubleContext(issue), strip_prefix),
+            function_name: issue.function_name.to_string(),
+            call_site: JsonLocation::new(
+                &issue.call_file,
+                issue.call_line,
+                Some(issue.call_span),
+                strip_prefix,
+            ),
+            definition: JsonLocation::new(
+                &issue.def_file,
+                issue.def_line,
+                Some(issue.def_span),
+                strip_prefix,
+            ),
+            inner_context: issue.inner_context.clone(),
+            inner_context_source: issue.inner_source,
+            inner_context_args: issue.inner_args.clone(),
+            outer_method: issue.outer_method.clone(),
+            outer_context: issue.outer_context.clone(),
+            identical: issue.is_identical(),
+            match_kind: issue.match_kind,
+            confidence: issue.confidence,
+            other_definitions: issue
+                .other_definitions
+                .iter()
+                .map(|other| JsonDefinition {
+                    location: JsonLocation::new(
+                        &other.file,
+                        other.line,
+                        Some(other.span),
+                        strip_prefix,
+                    ),
+                    inner_context: other.inner_context.clone(),
+                    inner_context_source: other.source,
+                })
+                .collect(),
+            caller: issue.caller.as_ref().map(|caller| JsonDefinition {
+                location: JsonLocation::new(
+                    &caller.file,
+                    caller.line,
+                    Some(caller.span),
+                    strip_prefix,
+                ),
+                inner_context: caller.inner_context.clone(),
+                inner_context_source: caller.source,
+            }),
+            suggestion: issue.fix.as_ref().map(json_suggestion),
         })
         .collect();
 
     let ua_warnings: Vec<JsonUnattributedWarning> = unattributed
         .iter()
         .map(|issue| JsonUnattributedWarning {
-            function_name: issue.name.clone(),
-            location: JsonLocation {
-                file: strip_path(&issue.file, strip_prefix).to_string(),
-                line: issue.line,
-            },
+            code: Lint::Unattributed.code(),
+            severity: Finding::Unattributed(issue).severity(levels.unattributed),
+            fingerprint: fingerprint::of_finding(&Finding::Unattributed(issue), strip_prefix),
+            function_name: issue.name.to_string(),
+            location: JsonLocation::new(&issue.file, issue.line, Some(issue.span), strip_prefix),
             is_method: issue.is_method,
             is_pub: issue.is_pub,
         })
         .collect();
 
+    let style_warnings: Vec<JsonStyleWarning> = style
+        .iter()
+        .map(|issue| JsonStyleWarning {
+            code: Lint::Style.code(),
+            severity: Finding::Style(issue).severity(levels.style),
+            fingerprint: fingerprint::of_finding(&Finding::Style(issue), strip_prefix),
+            kind: issue.kind,
+            message: issue.message.clone(),
+            location: JsonLocation::new(&issue.file, issue.line, None, strip_prefix),
+            suggestion: issue.fix.as_ref().map(json_suggestion),
+        })
+        .collect();
+
+    let bp_warnings: Vec<JsonBarePropagationWarning> = bare_propagation
+        .iter()
+        .map(|issue| JsonBarePropagationWarning {
+            code: Lint::BarePropagation.code(),
+            severity: Finding::BarePropagation(issue).severity(levels.bare_propagation),
+            fingerprint: fingerprint::of_finding(&Finding::BarePropagation(issue), strip_prefix),
+            function_name: issue.name.clone(),
+            location: JsonLocation::new(&issue.file, issue.line, None, strip_prefix),
+            statements: issue.statements,
+            try_count: issue.try_count,
+        })
+        .collect();
+
+    let plugin_warnings: Vec<JsonPluginWarning> = plugin
+        .iter()
+        .map(|issue| JsonPluginWarning {
+            code: Lint::Plugin.code(),
+            severity: Finding::Plugin(issue).severity(levels.plugin),
+            fingerprint: fingerprint::of_finding(&Finding::Plugin(issue), strip_prefix),
+            plugin: issue.plugin.clone(),
+            rule: issue.rule.clone(),
+            message: issue.message.clone(),
+            location: JsonLocation::new(&issue.file, issue.line, Some(issue.span), strip_prefix),
+        })
+        .collect();
+
+    let banned_context_warnings: Vec<JsonBannedContextWarning> = banned_context
+        .iter()
+        .map(|issue| JsonBannedContextWarning {
+            code: Lint::BannedContext.code(),
+            severity: Finding::BannedContext(issue).severity(levels.banned_context),
+            fingerprint: fingerprint::of_finding(&Finding::BannedContext(issue), strip_prefix),
+            message: message(&Finding::BannedContext(issue)),
+            site: issue.site.clone(),
+            context: issue.context.clone(),
+            pattern: issue.pattern.clone(),
+            matched: issue.matched.clone(),
+            location: JsonLocation::new(&issue.file, issue.line, Some(issue.span), strip_prefix),
+        })
+        .collect();
+
     let report = JsonReport {
+        schema_version: SCHEMA_VERSION,
         double_context: JsonDoubleContextSection {
             total: dc_warnings.len(),
             warnings: dc_warnings,
@@ -206,6 +1434,50 @@ pub fn format_combined_json(
             total: ua_warnings.len(),
             warnings: ua_warnings,
         },
+        style: JsonStyleSection {
+            total: style_warnings.len(),
+            warnings: style_warnings,
+        },
+        bare_propagation: JsonBarePropagationSection {
+            total: bp_warnings.len(),
+            warnings: bp_warnings,
+        },
+        plugin: JsonPluginSection {
+            total: plugin_warnings.len(),
+            warnings: plugin_warnings,
+        },
+        banned_context: JsonBannedContextSection {
+            total: banned_context_warnings.len(),
+            warnings: banned_context_warnings,
+        },
+        notes: JsonNotesSection {
+            missing_dependency: missing_dependency
+                .iter()
+                .map(|note| JsonMissingDependency {
+                    package: note.package.clone(),
+                    manifest_path: strip_path(&note.manifest_path, strip_prefix).to_string(),
+                    unattributed: note.unattributed,
+                })
+                .collect(),
+        },
+        clean: clean.map(|clean| JsonCleanSection {
+            packages: clean
+                .packages
+                .iter()
+                .map(|package| JsonCleanPackage {
+                    name: package.name.clone(),
+                    manifest_path: strip_path(&package.manifest_path, strip_prefix).to_string(),
+                    files: package.files,
+                })
+                .collect(),
+            files: clean
+                .files
+                .iter()
+                .map(|file| strip_path(file, strip_prefix).to_string())
+                .collect(),
+            files_checked: clean.files_checked,
+            packages_checked: clean.packages_checked,
+        }),
     };
 
     serde_json::to_string_pretty(&report).unwrap_or_else(|e| format!("{{\"error\": \"{e}\"}}"))
@@ -213,20 +1485,23 @@ pub fn format_combined_json(
 
 // ── Helpers ─────────────────────────────────────────────────────────────
 
-/// Check if the inner and outer context strings are identical or near-identical.
-fn is_context_identical(inner: &str, outer: &str) -> bool {
-    // Exact match
-    if inner == outer {
-        return true;
-    }
-    // Case-insensitive match
-    if inner.eq_ignore_ascii_case(outer) {
-        return true;
+fn json_suggestion(fix: &Fix) -> JsonSuggestion {
+    JsonSuggestion {
+        description: fix.description.clone(),
+        edits: fix
+            .edits
+            .iter()
+            .map(|edit| JsonEdit {
+                start: edit.start,
+                end: edit.end,
+                replacement: edit.replacement.clone(),
+            })
+            .collect(),
     }
-    false
 }
 
-fn strip_path<'a>(path: &'a str, prefix: Option<&str>) -> &'a str {
+/// `path` relative to `prefix`, if it starts with it.
+pub fn strip_path<'a>(path: &'a str, prefix: Option<&str>) -> &'a str {
     match prefix {
         Some(p) => path.strip_prefix(p).unwrap_or(path),
         None => path,
@@ -236,29 +1511,40 @@ fn strip_path<'a>(path: &'a str, prefix: Option<&str>) -> &'a str {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::checker::DoubleContext;
+    use crate::checker::{Definition, DoubleContext};
+    use crate::fixtures;
     use crate::unattributed::UnattributedFunction;
 
     fn make_double_context_issue(inner: &str, outer: &str) -> DoubleContext {
         DoubleContext {
-            call_file: "/project/src/main.rs".to_string(),
+            call_file: "/project/src/main.rs".into(),
             call_line: 42,
-            function_name: "test_fn".to_string(),
-            inner_context: inner.to_string(),
+            call_span: Default::default(),
+            function_name: "test_fn".into(),
+            inner_context: Some(inner.to_string()),
+            inner_source: Default::default(),
+            inner_args: Vec::new(),
             outer_context: Some(outer.to_string()),
-            def_file: "/project/src/lib.rs".to_string(),
+            def_file: "/project/src/lib.rs".into(),
             def_line: 10,
-            is_with_context: false,
+            def_span: Default::default(),
+            outer_method: "context".to_string(),
+            fix: None,
+            match_kind: MatchKind::Path,
+            confidence: 0.95,
+            other_definitions: Vec::new(),
+            option_receiver: None,
+            caller: None,
         }
     }
 
     fn make_unattributed_issue(name: &str, is_pub: bool) -> UnattributedFunction {
         UnattributedFunction {
-            file: "/project/src/utils.rs".to_string(),
+            file: "/project/src/utils.rs".into(),
             line: 25,
-            name: name.to_string(),
-            is_method: false,
+            name: name.into(),
             is_pub,
+            ..Default::default()
         }
     }
 
@@ -268,29 +1554,123 @@ mod tests {
             "Computing boot digest",
             "Computing boot digest",
         )];
-        let output = format_combined_text(&issues, &[], Some("/project/"));
-        assert!(output.contains("warning: double context on `test_fn`"));
+        let output = format_combined_text(
+            &Findings {
+                double_context: &issues,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
+        assert!(output.contains("error[CL0001]: double context on `test_fn`"));
         assert!(output.contains("src/main.rs:42"));
         assert!(output.contains("these context strings are identical"));
         assert!(output.contains("Found 1 double-context warning"));
     }
 
+    #[test]
+    fn test_double_context_name_match_note() {
+        let mut issue = make_double_context_issue("Loading", "loading");
+        let output = format_combined_text(
+            &Findings {
+                double_context: std::slice::from_ref(&issue),
+                ..Default::default()
+            },
+            None,
+            &TextLayout::default(),
+        );
+        assert!(!output.contains("matched by name only"));
+
+        issue.match_kind = MatchKind::Name;
+        let findings = Findings {
+            double_context: std::slice::from_ref(&issue),
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, None, &TextLayout::default());
+        assert!(output.contains("= note: matched by name only"));
+        let output = format_combined_json(&findings, None);
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            parsed["double_context"]["warnings"][0]["match_kind"],
+            "name"
+        );
+        // Identical strings outweigh the uncertain match.
+        assert_eq!(parsed["double_context"]["warnings"][0]["severity"], "error");
+    }
+
     #[test]
     fn test_double_context_different_strings() {
         let issues = vec![make_double_context_issue(
             "Loading config",
             "querying config",
         )];
-        let output = format_combined_text(&issues, &[], Some("/project/"));
-        assert!(output.contains("warning: double context on `test_fn`"));
+        let output = format_combined_text(
+            &Findings {
+                double_context: &issues,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
+        assert!(output.contains("error[CL0001]: double context on `test_fn`"));
         assert!(!output.contains("identical"));
+        assert!(!output.contains("with arguments"));
+    }
+
+    #[test]
+    fn test_double_context_args() {
+        let mut issue = make_double_context_issue("Loading {} as {user}", "Loading");
+        issue.inner_args = vec!["path.display()".to_string(), "user = self.user".to_string()];
+        let findings = Findings {
+            double_context: std::slice::from_ref(&issue),
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, Some("/project/"), &TextLayout::default());
+        assert!(output.contains(
+            "   | inner context (from #[context]): \"Loading {} as {user}\"\n   |   with arguments: path.display(), user = self.user\n"
+        ));
+        let parsed: serde_json::Value =
+            serde_json::from_str(&format_combined_json(&findings, Some("/project/"))).unwrap();
+        assert_eq!(
+            parsed["double_context"]["warnings"][0]["inner_context_args"],
+            serde_json::json!(["path.display()", "user = self.user"])
+        );
+    }
+
+    #[test]
+    fn test_double_context_text_escapes() {
+        let issues = vec![make_double_context_issue(
+            "Parsing \"quoted\"\nnext line",
+            "café",
+        )];
+        let output = format_combined_text(
+            &Findings {
+                double_context: &issues,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
+        assert!(
+            output.contains(r#"inner context (from #[context]): "Parsing \"quoted\"\nnext line""#)
+        );
+        assert!(output.contains(r#"outer context (from .context()): "café""#));
     }
 
     #[test]
     fn test_unattributed_text() {
         let issues = vec![make_unattributed_issue("find_kernel", false)];
-        let output = format_combined_text(&[], &issues, Some("/project/"));
-        assert!(output.contains("warning: fn returning Result without #[context]: `find_kernel`"));
+        let output = format_combined_text(
+            &Findings {
+                unattributed: &issues,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
+        assert!(
+            output.contains("error[CL0002]: fn returning Result without #[context]: `find_kernel`")
+        );
         assert!(output.contains("src/utils.rs:25"));
         assert!(output.contains("Found 1 unattributed function"));
     }
@@ -298,7 +1678,14 @@ mod tests {
     #[test]
     fn test_unattributed_pub() {
         let issues = vec![make_unattributed_issue("public_fn", true)];
-        let output = format_combined_text(&[], &issues, Some("/project/"));
+        let output = format_combined_text(
+            &Findings {
+                unattributed: &issues,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
         assert!(output.contains("pub fn public_fn"));
     }
 
@@ -306,29 +1693,505 @@ mod tests {
     fn test_combined_text() {
         let dc = vec![make_double_context_issue("Loading", "Loading")];
         let ua = vec![make_unattributed_issue("helper", false)];
-        let output = format_combined_text(&dc, &ua, Some("/project/"));
+        let output = format_combined_text(
+            &Findings {
+                double_context: &dc,
+                unattributed: &ua,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
         assert!(output.contains("double context"));
         assert!(output.contains("unattributed"));
     }
 
+    #[test]
+    fn test_concise_text() {
+        let dc = vec![make_double_context_issue("Loading", "Loading")];
+        let ua = vec![make_unattributed_issue("helper", false)];
+        let output = format_concise_text(
+            &Findings {
+                double_context: &dc,
+                unattributed: &ua,
+                ..Default::default()
+            },
+            Some("/project/"),
+        );
+        assert_eq!(
+            output,
+            "src/main.rs:42: error[CL0001]: double context on `test_fn` [c30b13fba724f5b4]\n\
+             src/utils.rs:25: error[CL0002]: fn returning Result without #[context]: `helper` \
+             [80cea56897ddcd7a]\n\
+             Found 2 warnings\n"
+        );
+        assert_eq!(format_concise_text(&Findings::default(), None), "");
+
+        let output = format_short_text(
+            &Findings {
+                double_context: &dc,
+                unattributed: &ua,
+                ..Default::default()
+            },
+            Some("/project/"),
+        );
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.ends_with("`helper` [80cea56897ddcd7a]\n"));
+    }
+
+    #[test]
+    fn test_other_definitions() {
+        let mut dc = make_double_context_issue("Loading", "Reading");
+        dc.other_definitions.push(Definition {
+            file: "/project/src/other.rs".into(),
+            line: 7,
+            span: Default::default(),
+            inner_context: Some("Loading other".to_string()),
+            source: Default::default(),
+        });
+        let findings = Findings {
+            double_context: std::slice::from_ref(&dc),
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, Some("/project/"), &TextLayout::default());
+        assert!(output.contains(
+            "   |   defined at: src/lib.rs:10\n\
+             \x20  | also matches: \"Loading other\"\n\
+             \x20  |   defined at: src/other.rs:7\n"
+        ));
+        assert_eq!(output.matches("double context on").count(), 1);
+        let output = format_combined_json(&findings, Some("/project/"));
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let other = &parsed["double_context"]["warnings"][0]["other_definitions"][0];
+        assert_eq!(other["location"]["file"], "src/other.rs");
+        assert_eq!(other["inner_context"], "Loading other");
+    }
+
+    #[test]
+    fn test_rule_definition() {
+        let mut dc = make_double_context_issue("Loading", "Reading");
+        dc.inner_context = None;
+        dc.inner_source = AnnotationSource::Rule;
+        let findings = Findings {
+            double_context: std::slice::from_ref(&dc),
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, None, &TextLayout::default());
+        assert!(output.contains("   | inner context (from [[rules]]): <unknown>\n"));
+        let output = format_combined_json(&findings, None);
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let warning = &parsed["double_context"]["warnings"][0];
+        assert_eq!(warning["inner_context"], serde_json::Value::Null);
+        assert_eq!(warning["inner_context_source"], "rule");
+    }
+
+    #[test]
+    fn test_span_columns() {
+        let mut dc = make_double_context_issue("Loading", "Loading");
+        dc.call_span = Span {
+            line: 42,
+            column: 19,
+            end_line: 42,
+            end_column: 31,
+            start: 700,
+            end: 712,
+        };
+        let findings = Findings {
+            double_context: std::slice::from_ref(&dc),
+            ..Default::default()
+        };
+        let output = format_concise_text(&findings, Some("/project/"));
+        assert!(output.starts_with("src/main.rs:42:19: error[CL0001]"));
+        let output = format_combined_json(&findings, Some("/project/"));
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let call_site = &parsed["double_context"]["warnings"][0]["call_site"];
+        assert_eq!(call_site["column"], 19);
+        assert_eq!(call_site["end_column"], 31);
+        assert_eq!(call_site["start_byte"], 700);
+        assert_eq!(call_site["end_byte"], 712);
+        // Unknown spans are omitted rather than reported as zero.
+        assert!(parsed["double_context"]["warnings"][0]["definition"]
+            .get("column")
+            .is_none());
+    }
+
+    #[test]
+    fn test_grouped_text() {
+        let dc = vec![make_double_context_issue("Loading", "Reading")];
+        let mut ua = vec![
+            make_unattributed_issue("helper", false),
+            make_unattributed_issue("other", false),
+        ];
+        ua[1].file = "/project/src/main.rs".into();
+        ua[1].line = 3;
+        let output = format_grouped_text(
+            &Findings {
+                double_context: &dc,
+                unattributed: &ua,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
+        assert_eq!(
+            output,
+            "src/main.rs:
+  3: error[CL0002]: fn returning Result without #[context]: `other`
+     | fn other
+     = fingerprint: 527a2f5363505ff6
+  42: error[CL0001]: double context on `test_fn`
+     | inner context (from #[context]): \"Loading\"
+     |   defined at: src/lib.rs:10
+     | outer context (from .context()): \"Reading\"
+     | confidence: 0.95
+     = fingerprint: 3927b4098527f3a0
+
+src/utils.rs:
+  25: error[CL0002]: fn returning Result without #[context]: `helper`
+     | fn helper
+     = fingerprint: 80cea56897ddcd7a
+
+Found 3 warnings in 2 files
+"
+        );
+    }
+
     #[test]
     fn test_combined_json() {
         let dc = vec![make_double_context_issue("Loading", "Loading")];
         let ua = vec![make_unattributed_issue("helper", false)];
-        let output = format_combined_json(&dc, &ua, Some("/project/"));
+        let output = format_combined_json(
+            &Findings {
+                double_context: &dc,
+                unattributed: &ua,
+                ..Default::default()
+            },
+            Some("/project/"),
+        );
         let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
         assert_eq!(parsed["double_context"]["total"], 1);
         assert_eq!(parsed["unattributed"]["total"], 1);
         assert_eq!(parsed["double_context"]["warnings"][0]["identical"], true);
+        assert_eq!(
+            parsed["double_context"]["warnings"][0]["fingerprint"],
+            "c30b13fba724f5b4"
+        );
         assert_eq!(
             parsed["unattributed"]["warnings"][0]["function_name"],
             "helper"
         );
     }
 
+    #[test]
+    fn test_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&format_json_schema(false)).unwrap();
+        assert_eq!(schema["title"], "JsonReport");
+        assert!(schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&"schema_version".into()));
+
+        let output = format_combined_json(&Findings::default(), None);
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed["schema_version"], SCHEMA_VERSION);
+
+        let schema: serde_json::Value = serde_json::from_str(&format_json_schema(true)).unwrap();
+        assert_eq!(schema["title"], "JsonStats");
+    }
+
+    #[test]
+    fn test_coverage() {
+        let coverage = |functions, annotated| Coverage {
+            functions,
+            annotated,
+        };
+        let stats = Stats {
+            crates: vec![
+                CrateStats {
+                    name: "core".to_string(),
+                    coverage: coverage(4, 3),
+                    ..Default::default()
+                },
+                CrateStats {
+                    name: "cli".to_string(),
+                    coverage: coverage(4, 2),
+                    ..Default::default()
+                },
+                CrateStats {
+                    name: "macros".to_string(),
+                    coverage: coverage(0, 0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            format_coverage_text(&stats, Some(60.0)),
+            "crate   functions  annotated  coverage
+core            4          3     75.0%
+cli             4          2     50.0%  (under 60%)
+macros          0          0         -
+total           8          5     62.5%
+"
+        );
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&format_coverage_json(&stats, Some(60.0))).unwrap();
+        assert_eq!(parsed["coverage"], 62.5);
+        assert_eq!(parsed["fail_under"], 60.0);
+        assert_eq!(parsed["crates"][0]["under"], false);
+        assert_eq!(parsed["crates"][1]["under"], true);
+        assert_eq!(parsed["crates"][2]["coverage"], serde_json::Value::Null);
+        assert_eq!(parsed["crates"][2]["under"], false);
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&format_coverage_json(&stats, None)).unwrap();
+        assert!(parsed.get("fail_under").is_none());
+    }
+
+    #[test]
+    fn test_metrics_and_badge() {
+        use crate::stats::ModuleStats;
+
+        let coverage = |functions, annotated| Coverage {
+            functions,
+            annotated,
+        };
+        let stats = Stats {
+            crates: vec![
+                CrateStats {
+                    name: "a".to_string(),
+                    coverage: coverage(4, 3),
+                    ..Default::default()
+                },
+                CrateStats {
+                    name: "b".to_string(),
+                    coverage: coverage(4, 2),
+                    ..Default::default()
+                },
+            ],
+            modules: vec![ModuleStats {
+                crate_name: "a".to_string(),
+                module: Some("a::pod".to_string()),
+                file: "/ws/a/src/pod.rs".to_string(),
+                coverage: coverage(4, 3),
+            }],
+            ..Default::default()
+        };
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&format_metrics_json(&stats, Some("/ws/"))).unwrap();
+        assert_eq!(parsed["coverage"], 62.5);
+        assert_eq!(parsed["crates"][1]["coverage"], 50.0);
+        assert_eq!(parsed["modules"][0]["crate"], "a");
+        assert_eq!(parsed["modules"][0]["module"], "a::pod");
+        assert_eq!(parsed["modules"][0]["file"], "a/src/pod.rs");
+
+        let badge: serde_json::Value = serde_json::from_str(&format_badge_json(&stats)).unwrap();
+        assert_eq!(
+            badge,
+            serde_json::json!({
+                "schemaVersion": 1,
+                "label": "error context",
+                "message": "62%",
+                "color": "yellowgreen",
+            })
+        );
+
+        let badge: serde_json::Value =
+            serde_json::from_str(&format_badge_json(&Stats::default())).unwrap();
+        assert_eq!(badge["message"], "n/a");
+        assert_eq!(badge["color"], "lightgrey");
+    }
+
+    fn annotated(
+        name: &str,
+        module: &str,
+        self_type: Option<&str>,
+        line: usize,
+    ) -> AnnotatedFunction {
+        AnnotatedFunction {
+            is_method: self_type.is_some(),
+            visibility: "pub".to_string(),
+            module_path: module.split("::").map(str::to_string).collect(),
+            self_type: self_type.map(str::to_string),
+            ..fixtures::annotated(name, "/ws/src/lib.rs", line, &format!("Running {name}"))
+        }
+    }
+
+    #[test]
+    fn test_function_list() {
+        let mut open = annotated("open", "my_crate::pod", Some("Pod"), 20);
+        open.is_async = true;
+        open.params = vec!["self".to_string(), "path".to_string()];
+        open.context_string = Some("Opening {}".to_string());
+        open.context_args = vec!["path.display()".to_string()];
+        let functions = vec![
+            annotated("sync", "my_crate::pod", None, 30),
+            open,
+            annotated("load", "my_crate", None, 5),
+            annotated("init", "my_crate::pod", None, 10),
+        ];
+
+        let text = format_function_list_text(&functions, Some("/ws/"));
+        assert_eq!(
+            text,
+            "my_crate
+  pub fn load()
+      #[context(\"Running load\")]
+      at src/lib.rs:5
+
+my_crate::pod
+  pub fn init()
+      #[context(\"Running init\")]
+      at src/lib.rs:10
+  pub fn sync()
+      #[context(\"Running sync\")]
+      at src/lib.rs:30
+
+my_crate::pod::Pod
+  pub async fn open(self, path)
+      #[context(\"Opening {}\", path.display())]
+      at src/lib.rs:20
+"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_function_list_json(&functions, Some("/ws/"))).unwrap();
+        let open = &json["functions"][3];
+        assert_eq!(open["name"], "open");
+        assert_eq!(open["module"], "my_crate::pod");
+        assert_eq!(open["self_type"], "Pod");
+        assert_eq!(open["signature"], "pub async fn open(self, path)");
+        assert_eq!(open["context_args"][0], "path.display()");
+        assert_eq!(open["location"]["file"], "src/lib.rs");
+    }
+
+    #[test]
+    fn test_style_text_and_json() {
+        let style = vec![StyleIssue {
+            file: "/project/src/main.rs".to_string(),
+            line: 7,
+            kind: StyleKind::ConstantClosure,
+            message: "`.with_context()` closure returns a constant string".to_string(),
+            fix: Some(Fix {
+                description: "use `.context(\"x\")`".to_string(),
+                edits: vec![],
+            }),
+        }];
+        let output = format_combined_text(
+            &Findings {
+                style: &style,
+                ..Default::default()
+            },
+            Some("/project/"),
+            &TextLayout::default(),
+        );
+        assert!(output.contains("src/main.rs:7"));
+        assert!(output.contains("help: use `.context(\"x\")`"));
+        assert!(output.contains("Found 1 style warning"));
+
+        let output = format_combined_json(
+            &Findings {
+                style: &style,
+                ..Default::default()
+            },
+            Some("/project/"),
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed["style"]["total"], 1);
+        assert_eq!(parsed["style"]["warnings"][0]["kind"], "constant-closure");
+    }
+
+    #[test]
+    fn test_missing_dependency_note() {
+        let notes = vec![MissingDependency {
+            package: "foo".to_string(),
+            manifest_path: "/project/foo/Cargo.toml".to_string(),
+            unattributed: 7,
+        }];
+        let findings = Findings {
+            missing_dependency: &notes,
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, Some("/project/"), &TextLayout::default());
+        assert!(output.contains(
+            "note: package `foo` has 7 unattributed functions but does not depend on fn_error_context"
+        ));
+        assert!(output.contains("--> foo/Cargo.toml"));
+
+        let output = format_combined_json(&findings, Some("/project/"));
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            parsed["notes"]["missing_dependency"][0]["manifest_path"],
+            "foo/Cargo.toml"
+        );
+    }
+
+    #[test]
+    fn test_bare_propagation_text_and_json() {
+        let issues = vec![BarePropagation {
+            file: "/project/src/lib.rs".to_string(),
+            line: 12,
+            name: "sync_all".to_string(),
+            statements: 6,
+            try_count: 3,
+        }];
+        let findings = Findings {
+            bare_propagation: &issues,
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, Some("/project/"), &TextLayout::default());
+        assert!(output.contains("warning[CL0004]: `sync_all` propagates errors with bare `?`"));
+        assert!(output.contains("--> src/lib.rs:12"));
+        assert!(output.contains("6 statements, 3 `?` operators"));
+        assert!(output.contains("Found 1 function propagating errors without context"));
+
+        let output = format_combined_json(&findings, Some("/project/"));
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed["bare_propagation"]["total"], 1);
+        assert_eq!(parsed["bare_propagation"]["warnings"][0]["try_count"], 3);
+    }
+
+    #[test]
+    fn test_clean_report() {
+        let clean = CleanReport {
+            packages: vec![crate::package::CleanPackage {
+                name: "foo".to_string(),
+                manifest_path: "/project/foo/Cargo.toml".to_string(),
+                files: 2,
+            }],
+            files: vec![
+                "/project/foo/src/lib.rs".to_string(),
+                "/project/foo/src/main.rs".to_string(),
+            ],
+            files_checked: 3,
+            packages_checked: 2,
+        };
+        let findings = Findings {
+            clean: Some(&clean),
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, Some("/project/"), &TextLayout::default());
+        assert!(output.contains("ok: package `foo` is clean (2 files checked)"));
+        assert!(output.contains("ok: foo/src/main.rs"));
+        assert!(output.contains("1 of 2 packages and 2 of 3 files clean"));
+
+        let output = format_combined_json(&findings, Some("/project/"));
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed["clean"]["packages"][0]["name"], "foo");
+        assert_eq!(parsed["clean"]["files"][0], "foo/src/lib.rs");
+        assert_eq!(parsed["clean"]["files_checked"], 3);
+
+        let output = format_combined_json(&Findings::default(), None);
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(parsed.get("clean").is_none());
+    }
+
     #[test]
     fn test_empty_results() {
-        let output = format_combined_text(&[], &[], None);
+        let output = format_combined_text(&Findings::default(), None, &TextLayout::default());
         assert!(output.is_empty());
     }
 
@@ -337,4 +2200,54 @@ mod tests {
         assert_eq!(strip_path("/foo/bar/baz.rs", Some("/foo/")), "bar/baz.rs");
         assert_eq!(strip_path("/foo/bar/baz.rs", None), "/foo/bar/baz.rs");
     }
+
+    #[test]
+    fn test_truncate_context() {
+        let issues = vec![make_double_context_issue(
+            "Loading the configuration file",
+            "short",
+        )];
+        let layout = TextLayout {
+            truncate_context: Some(10),
+            ..Default::default()
+        };
+        let findings = Findings {
+            double_context: &issues,
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, Some("/project/"), &layout);
+        assert!(output.contains(r#"inner context (from #[context]): "Loading t…""#));
+        assert!(output.contains(r#"outer context (from .context()): "short""#));
+    }
+
+    #[test]
+    fn test_wrap_long_lines() {
+        let long = "Loading the configuration file from the system directory ".repeat(3);
+        let issues = vec![make_double_context_issue(&long, "short")];
+        let layout = TextLayout {
+            max_width: Some(60),
+            ..Default::default()
+        };
+        let findings = Findings {
+            double_context: &issues,
+            ..Default::default()
+        };
+        let output = format_combined_text(&findings, Some("/project/"), &layout);
+        assert!(output.lines().all(|l| l.chars().count() <= 60));
+        assert!(output.contains("\n   |     "));
+    }
+
+    #[test]
+    fn test_wrap_line_paths() {
+        let line = "  --> some/very/long/directory/structure/that/keeps/going/file.rs:42";
+        let wrapped = wrap_line(line, 40);
+        assert_eq!(
+            wrapped,
+            [
+                "  --> some/very/long/directory/",
+                "      structure/that/keeps/going/",
+                "      file.rs:42"
+            ]
+        );
+    }
 }
@@ -0,0 +1,137 @@
+//! Declarative rules from the `[[rules]]` entries of `context-lint.toml`.
+//!
+//! A rule selects functions that add context to their errors the way
+//! `#[context]` does, either by path (`functions = "crate::db::*"`) or by
+//! attribute (`attribute = "my::wrap"`), and optionally methods that add a
+//! second layer on their results (`methods = ["ctx"]`). Rules are compiled
+//! once at startup: attribute rules into [`ContextAttribute`]s, which all
+//! checks know about, and path rules into [`FunctionRule`]s, which the
+//! collector matches every function returning a `Result` against.
+
+use anyhow::{bail, Result};
+
+use crate::config::{Config, ContextAttribute, RuleConfig};
+use crate::glob::Glob;
+
+/// A rule selecting functions by path.
+#[derive(Debug, Clone)]
+pub struct FunctionRule {
+    /// The pattern with `/` separators, as globs expect.
+    pattern: Glob,
+    /// The context the functions add, as shown in findings, if configured.
+    pub context: Option<String>,
+    /// Methods adding another layer of context on their results.
+    pub methods: Vec<String>,
+}
+
+impl FunctionRule {
+    /// Whether the function `name`, defined in the module `module_path`
+    /// (starting with the crate name) and in the `impl` or trait `self_type`,
+    /// is selected. Patterns may start with `crate` or the crate name.
+    pub fn matches(&self, module_path: &[String], self_type: Option<&str>, name: &str) -> bool {
+        let Some((crate_name, modules)) = module_path.split_first() else {
+            return false;
+        };
+        let rest: Vec<&str> = modules
+            .iter()
+            .map(String::as_str)
+            .chain(self_type)
+            .chain([name])
+            .collect();
+        let rest = rest.join("/");
+        self.pattern.is_match(&format!("crate/{rest}"))
+            || self.pattern.is_match(&format!("{crate_name}/{rest}"))
+    }
+}
+
+/// Compile the rules of `config`: attribute rules are added to its
+/// `context_attributes`, and the function rules returned.
+pub fn compile(config: &mut Config) -> Result<Vec<FunctionRule>> {
+    let mut functions = Vec::new();
+    for (idx, rule) in config.rules.iter().enumerate() {
+        let RuleConfig {
+            functions: pattern,
+            attribute,
+            methods,
+            context,
+        } = rule.clone();
+        match (pattern, attribute) {
+            (Some(pattern), None) => functions.push(FunctionRule {
+                pattern: Glob::new(&pattern.replace("::", "/")),
+                context,
+                methods,
+            }),
+            (None, Some(path)) => config.context_attributes.push(ContextAttribute {
+                context,
+                path,
+                message_index: 0,
+                message_key: None,
+                methods,
+                rule: true,
+            }),
+            _ => bail!(
+                "Rule {} must have either `functions` or `attribute`",
+                idx + 1
+            ),
+        }
+    }
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_str(toml: &str) -> Result<(Config, Vec<FunctionRule>)> {
+        let mut config = crate::config::parse(toml)?;
+        let rules = compile(&mut config)?;
+        Ok((config, rules))
+    }
+
+    #[test]
+    fn test_compile() {
+        let (config, rules) = compile_str(
+            r#"
+            [[rules]]
+            functions = "crate::db::*"
+            methods = ["ctx"]
+            context = "Querying"
+
+            [[rules]]
+            attribute = "my::wrap"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].context.as_deref(), Some("Querying"));
+        assert_eq!(rules[0].methods, ["ctx"]);
+        let attribute = &config.context_attributes[0];
+        assert_eq!(attribute.path, "my::wrap");
+        assert_eq!(attribute.context, None);
+        assert!(attribute.rule);
+
+        let err = compile_str("[[rules]]\nmethods = [\"ctx\"]").unwrap_err();
+        assert!(err.to_string().contains("Rule 1"));
+    }
+
+    #[test]
+    fn test_function_rule_matches() {
+        let (_, rules) = compile_str(
+            r#"
+            [[rules]]
+            functions = "crate::db::*"
+
+            [[rules]]
+            functions = "app::store::**"
+            "#,
+        )
+        .unwrap();
+        let path = |path: &str| -> Vec<String> { path.split("::").map(str::to_string).collect() };
+        assert!(rules[0].matches(&path("app::db"), None, "query"));
+        assert!(!rules[0].matches(&path("app::db"), Some("Pool"), "query"));
+        assert!(!rules[0].matches(&path("app::dbx"), None, "query"));
+        assert!(!rules[0].matches(&[], None, "query"));
+        assert!(rules[1].matches(&path("app::store"), Some("Store"), "get"));
+        assert!(!rules[1].matches(&path("other::store"), None, "get"));
+    }
+}
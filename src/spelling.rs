@@ -0,0 +1,316 @@
+//! Spell checking of context strings.
+//!
+//! Typos in error messages are otherwise only noticed in production logs.
+//! With dictionaries configured under `[spelling]` in `context-lint.toml`,
+//! every word of the strings of context attributes and the messages of
+//! context method calls must be in one of them or in the project's own word
+//! list. Placeholders, code in backticks and tokens that aren't plain words
+//! (paths, identifiers, numbers, acronyms) are skipped.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::collector::AnnotatedFunctions;
+use crate::config::{ContextMethodsConfig, SpellingConfig};
+use crate::distance::edit_distance;
+use crate::parse::ParsedFile;
+use crate::style::{for_each_context_message, StyleIssue, StyleKind};
+
+/// How many suggestions are shown for an unknown word.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The known words, lowercased.
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Load the dictionaries of `config`, whose paths are relative to `root`,
+    /// and add the project's words. `None` if no dictionary is configured,
+    /// which disables the check.
+    pub fn load(config: &SpellingConfig, root: &Path) -> Result<Option<Self>> {
+        if config.dictionaries.is_empty() {
+            return Ok(None);
+        }
+        let mut dictionary = Self::default();
+        for path in &config.dictionaries {
+            let path = root.join(path);
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Reading dictionary {}", path.display()))?;
+            dictionary.extend(contents.lines());
+        }
+        dictionary.extend(config.words.iter().map(String::as_str));
+        Ok(Some(dictionary))
+    }
+
+    /// Add words, one per item. Blank items and `#` comments are ignored.
+    pub fn extend<'a>(&mut self, words: impl IntoIterator<Item = &'a str>) {
+        for word in words {
+            let word = word.trim();
+            if !word.is_empty() && !word.starts_with('#') {
+                self.words.insert(word.to_lowercase());
+            }
+        }
+    }
+
+    /// Whether `word` is known, ignoring case and a possessive `'s`.
+    pub fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words.contains(&word)
+            || ["'s", "\u{2019}s"].iter().any(|suffix| {
+                word.strip_suffix(suffix)
+                    .is_some_and(|stem| self.words.contains(stem))
+            })
+    }
+
+    /// The known words closest to `word` (at most two edits away), with its
+    /// capitalization.
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let length = lower.chars().count();
+        let max = if length < 5 { 1 } else { 2 };
+        let mut candidates: Vec<(usize, &str)> = self
+            .words
+            .iter()
+            .filter(|known| known.chars().count().abs_diff(length) <= max)
+            .map(|known| (edit_distance(&lower, known), known.as_str()))
+            .filter(|(distance, _)| *distance <= max)
+            .collect();
+        let Some(best) = candidates.iter().map(|(distance, _)| *distance).min() else {
+            return Vec::new();
+        };
+        candidates.retain(|(distance, _)| *distance == best);
+        candidates.sort();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, known)| {
+                if capitalized {
+                    capitalize(known)
+                } else {
+                    known.to_string()
+                }
+            })
+            .collect()
+    }
+
+    /// The descriptions of the unknown words of `text`, e.g. ``unknown word
+    /// `Lodaing`; did you mean `Loading`?``.
+    fn check(&self, text: &str) -> Vec<String> {
+        let mut seen = HashSet::new();
+        words(text)
+            .into_iter()
+            .filter(|word| !self.contains(word) && seen.insert(*word))
+            .map(|word| {
+                let suggestions: Vec<String> = self
+                    .suggestions(word)
+                    .iter()
+                    .map(|suggestion| format!("`{suggestion}`"))
+                    .collect();
+                match suggestions.as_slice() {
+                    [] => format!("unknown word `{word}`"),
+                    [one] => format!("unknown word `{word}`; did you mean {one}?"),
+                    [init @ .., last] => format!(
+                        "unknown word `{word}`; did you mean {} or {last}?",
+                        init.join(", ")
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The words of a context string worth checking. Placeholders (`{name}`) and
+/// code in backticks are removed; of the remaining whitespace-separated
+/// tokens, only those made of letters, apostrophes and hyphens are split into
+/// words. Single letters and words with uppercase letters past the first
+/// (acronyms, `CamelCase` names) are skipped.
+pub fn words(text: &str) -> Vec<&str> {
+    let mut prose = String::with_capacity(text.len());
+    let mut closing = None;
+    for c in text.chars() {
+        match closing {
+            Some(end) if c == end => closing = None,
+            Some(_) => {}
+            None if c == '{' => closing = Some('}'),
+            None if c == '`' => closing = Some('`'),
+            None => {
+                prose.push(c);
+                continue;
+            }
+        }
+        // Keep byte offsets, so words can be borrowed from `text`.
+        prose.extend(std::iter::repeat_n(' ', c.len_utf8()));
+    }
+
+    let mut result = Vec::new();
+    for token in prose.split_whitespace() {
+        let token = token.trim_matches(|c: char| !c.is_alphabetic());
+        if !token
+            .chars()
+            .all(|c| c.is_alphabetic() || matches!(c, '\'' | '\u{2019}' | '-'))
+        {
+            continue;
+        }
+        for word in token.split('-') {
+            if word.chars().count() > 1 && !word.chars().skip(1).any(char::is_uppercase) {
+                let start = word.as_ptr() as usize - prose.as_ptr() as usize;
+                result.push(&text[start..start + word.len()]);
+            }
+        }
+    }
+    result
+}
+
+/// Check the context strings of the collected functions.
+pub fn check_annotated(index: &AnnotatedFunctions, dictionary: &Dictionary) -> Vec<StyleIssue> {
+    index
+        .iter()
+        .flat_map(|af| {
+            dictionary
+                .check(af.written_context().unwrap_or_default())
+                .into_iter()
+                .map(|problem| StyleIssue {
+                    file: af.file.to_string(),
+                    line: af.line,
+                    kind: StyleKind::Misspelling,
+                    message: format!("#[context] on `{}`: {problem}", af.name),
+                    fix: None,
+                })
+        })
+        .collect()
+}
+
+/// Check the messages of the context method calls in a file.
+pub fn check_file(
+    file: &ParsedFile,
+    dictionary: &Dictionary,
+    methods: &ContextMethodsConfig,
+) -> Vec<StyleIssue> {
+    let mut results = Vec::new();
+    for_each_context_message(file, methods, |call, message| {
+        for problem in dictionary.check(&message.literal.value()) {
+            results.push(StyleIssue {
+                file: file.display_path().to_string(),
+                line: call.method.span().start().line,
+                kind: StyleKind::Misspelling,
+                message: format!("`.{}()`: {problem}", call.method),
+                fix: None,
+            });
+        }
+    });
+    results
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary() -> Dictionary {
+        let mut dictionary = Dictionary::default();
+        dictionary.extend(
+            "# test words\nloading\nlading\nthe\nconfig\nfrom\nfor\nfile\nreading\nuser\nnon\nempty\ndon't"
+                .lines(),
+        );
+        dictionary
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            words("Loading {name} from `cfg.toml`, e.g. config.toml (UTF-8) for the HTTP user's non-empty file"),
+            ["Loading", "from", "for", "the", "user's", "non", "empty", "file"]
+        );
+        assert_eq!(words("Reading {}: don't"), ["Reading", "don't"]);
+        assert_eq!(words("Façade"), ["Façade"]);
+    }
+
+    #[test]
+    fn test_check() {
+        let dictionary = dictionary();
+        assert!(dictionary.check("Loading the config for {user}").is_empty());
+        assert!(dictionary.contains("User's"));
+        assert_eq!(
+            dictionary.check("Lodaing the confg, Lodaing"),
+            [
+                "unknown word `Lodaing`; did you mean `Loading`?",
+                "unknown word `confg`; did you mean `config`?",
+            ]
+        );
+        assert_eq!(dictionary.check("Fetching"), ["unknown word `Fetching`"]);
+        assert_eq!(dictionary.suggestions("loding"), ["lading", "loading"],);
+    }
+
+    #[test]
+    fn test_check_file() {
+        let source = r#"
+            fn main() -> anyhow::Result<()> {
+                load().context("Reading the fiel")?;
+                load().with_context(|| format!("Loading {path}"))?;
+                load().map(|x| "Unchecked wrods")?;
+                Ok(())
+            }
+        "#;
+        let file = ParsedFile::from_source(Path::new("test.rs"), source.to_string());
+        let results = check_file(&file, &dictionary(), &ContextMethodsConfig::default());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line, 3);
+        assert_eq!(results[0].kind, StyleKind::Misspelling);
+        assert_eq!(
+            results[0].message,
+            "`.context()`: unknown word `fiel`; did you mean `file`?"
+        );
+    }
+
+    #[test]
+    fn test_check_annotated() {
+        let source = r#"
+            #[context("Lodaing {path}")]
+            fn load(path: &str) -> anyhow::Result<()> {
+                Ok(())
+            }
+        "#;
+        let file = ParsedFile::from_source(Path::new("test.rs"), source.to_string());
+        let collected = crate::collector::collect_from_file(&file, &[], &[], &[]);
+        let index = crate::collector::build_index(collected.functions);
+        let results = check_annotated(&index, &dictionary());
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].message,
+            "#[context] on `load`: unknown word `Lodaing`; did you mean `Loading`?"
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = crate::fixtures::TempDir::new("spelling");
+        std::fs::write(dir.join("words.txt"), "loading\n").unwrap();
+        let config = SpellingConfig {
+            dictionaries: vec!["words.txt".into()],
+            words: vec!["Tokio".to_string()],
+        };
+        let dictionary = Dictionary::load(&config, &dir).unwrap().unwrap();
+        assert!(dictionary.contains("Loading") && dictionary.contains("tokio"));
+        let missing = SpellingConfig {
+            dictionaries: vec!["missing.txt".into()],
+            words: Vec::new(),
+        };
+        let err = Dictionary::load(&missing, &dir).unwrap_err();
+        assert!(err.to_string().contains("missing.txt"));
+        assert!(Dictionary::load(&SpellingConfig::default(), &dir)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,256 @@
+//! Error-context hygiene metrics per crate, for `--stats` and `--metrics`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::modules::FileModule;
+use crate::package::{module_path, package_index, Package};
+use crate::report::Findings;
+use crate::unattributed::Coverage;
+
+/// Number of files listed as having the most warnings.
+pub const TOP_FILES: usize = 10;
+
+/// Metrics of one workspace member.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrateStats {
+    /// The package name.
+    pub name: String,
+    /// Functions returning `anyhow::Result` and how many have `#[context]`.
+    pub coverage: Coverage,
+    /// Number of double-context warnings.
+    pub double_context: usize,
+    /// Number of warnings of all checks.
+    pub warnings: usize,
+}
+
+impl CrateStats {
+    /// Percentage of functions returning `anyhow::Result` with `#[context]`,
+    /// or `None` if there are none.
+    pub fn coverage_percent(&self) -> Option<f64> {
+        self.coverage.percent()
+    }
+}
+
+/// Coverage of one module of a checked package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleStats {
+    /// The package name.
+    pub crate_name: String,
+    /// The module path (e.g. `my_crate::pod::storage`), or `None` for files
+    /// outside of the library and main binary (tests, examples, extra
+    /// binaries).
+    pub module: Option<String>,
+    /// The file defining the module.
+    pub file: String,
+    /// Functions returning `anyhow::Result` and how many have `#[context]`.
+    pub coverage: Coverage,
+}
+
+/// Metrics of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// One entry per checked package, in workspace order.
+    pub crates: Vec<CrateStats>,
+    /// The files with the most warnings, most first.
+    pub top_files: Vec<(String, usize)>,
+    /// The modules of the checked packages having functions returning
+    /// `anyhow::Result`, in the order they were checked.
+    pub modules: Vec<ModuleStats>,
+}
+
+impl Stats {
+    /// The coverage of all checked packages together.
+    pub fn total_coverage(&self) -> Coverage {
+        let mut total = Coverage::default();
+        for stats in &self.crates {
+            total.add(&stats.coverage);
+        }
+        total
+    }
+
+    /// The crates whose coverage is below `min_percent`. Crates without
+    /// functions returning `anyhow::Result` are never below.
+    pub fn under(&self, min_percent: f64) -> Vec<&CrateStats> {
+        self.crates
+            .iter()
+            .filter(|c| c.coverage_percent().is_some_and(|p| p < min_percent))
+            .collect()
+    }
+}
+
+/// Compute the metrics of the `selected` packages from the coverage of each
+/// checked file and the findings. Files are attributed to crates and modules
+/// by `modules`, or else by their location.
+pub fn compute(
+    packages: &[Package],
+    selected: &[usize],
+    coverage: &[(PathBuf, Coverage)],
+    modules: &HashMap<PathBuf, FileModule>,
+    findings: &Findings,
+) -> Stats {
+    let package_of = |file: &Path| match modules.get(file) {
+        Some(module) => module.package,
+        None => package_index(packages, file),
+    };
+    let mut crates: Vec<CrateStats> = packages
+        .iter()
+        .map(|p| CrateStats {
+            name: p.name.clone(),
+            ..Default::default()
+        })
+        .collect();
+
+    let mut module_stats = Vec::new();
+    for (file, file_coverage) in coverage {
+        let Some(idx) = package_of(file) else {
+            continue;
+        };
+        crates[idx].coverage.add(file_coverage);
+        if file_coverage.functions > 0 && selected.contains(&idx) {
+            let module = match modules.get(file) {
+                Some(module) => module.path.clone(),
+                None => module_path(packages, file),
+            };
+            module_stats.push(ModuleStats {
+                crate_name: packages[idx].name.clone(),
+                module: (!module.is_empty()).then(|| module.join("::")),
+                file: file.to_string_lossy().to_string(),
+                coverage: *file_coverage,
+            });
+        }
+    }
+
+    let mut per_file: HashMap<&str, usize> = HashMap::new();
+    let double_context = findings
+        .double_context
+        .iter()
+        .map(|issue| &*issue.call_file);
+    for file in double_context.clone() {
+        if let Some(idx) = package_of(Path::new(file)) {
+            crates[idx].double_context += 1;
+        }
+    }
+    let all_files = double_context
+        .chain(findings.unattributed.iter().map(|issue| &*issue.file))
+        .chain(findings.style.iter().map(|issue| issue.file.as_str()))
+        .chain(
+            findings
+                .bare_propagation
+                .iter()
+                .map(|issue| issue.file.as_str()),
+        )
+        .chain(findings.plugin.iter().map(|issue| issue.file.as_str()))
+        .chain(
+            findings
+                .banned_context
+                .iter()
+                .map(|issue| issue.file.as_str()),
+        );
+    for file in all_files {
+        *per_file.entry(file).or_default() += 1;
+        if let Some(idx) = package_of(Path::new(file)) {
+            crates[idx].warnings += 1;
+        }
+    }
+
+    let mut top_files: Vec<(String, usize)> = per_file
+        .into_iter()
+        .map(|(file, count)| (file.to_string(), count))
+        .collect();
+    top_files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_files.truncate(TOP_FILES);
+
+    let crates = crates
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| selected.contains(idx))
+        .map(|(_, stats)| stats)
+        .collect();
+    Stats {
+        crates,
+        top_files,
+        modules: module_stats,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{package, unattributed};
+
+    #[test]
+    fn test_compute() {
+        let packages = vec![package("a", "/ws/a"), package("b", "/ws/b")];
+        let coverage = vec![
+            (
+                PathBuf::from("/ws/a/src/lib.rs"),
+                Coverage {
+                    functions: 4,
+                    annotated: 3,
+                },
+            ),
+            (
+                PathBuf::from("/ws/b/src/lib.rs"),
+                Coverage {
+                    functions: 0,
+                    annotated: 0,
+                },
+            ),
+        ];
+        let unattributed = vec![
+            unattributed("/ws/a/src/lib.rs"),
+            unattributed("/ws/a/src/x.rs"),
+            unattributed("/ws/a/src/x.rs"),
+        ];
+        let findings = Findings {
+            unattributed: &unattributed,
+            ..Default::default()
+        };
+        let stats = compute(&packages, &[0, 1], &coverage, &HashMap::new(), &findings);
+
+        assert_eq!(stats.crates.len(), 2);
+        assert_eq!(stats.crates[0].coverage_percent(), Some(75.0));
+        assert_eq!(stats.crates[0].warnings, 3);
+        assert_eq!(stats.crates[1].coverage_percent(), None);
+        assert_eq!(
+            stats.top_files,
+            [
+                ("/ws/a/src/x.rs".to_string(), 2),
+                ("/ws/a/src/lib.rs".to_string(), 1)
+            ]
+        );
+
+        assert_eq!(stats.modules.len(), 1);
+        assert_eq!(stats.modules[0].module.as_deref(), Some("a"));
+        assert_eq!(stats.modules[0].crate_name, "a");
+        assert_eq!(stats.total_coverage().percent(), Some(75.0));
+
+        let stats = compute(&packages, &[1], &coverage, &HashMap::new(), &findings);
+        assert_eq!(stats.crates.len(), 1);
+        assert_eq!(stats.crates[0].name, "b");
+        assert!(stats.modules.is_empty());
+
+        // A file in the directory of `a` that `b` declares with `#[path]`.
+        let modules = HashMap::from([(
+            PathBuf::from("/ws/a/src/x.rs"),
+            FileModule {
+                package: Some(1),
+                path: vec!["b".to_string(), "x".to_string()],
+            },
+        )]);
+        let coverage = vec![(
+            PathBuf::from("/ws/a/src/x.rs"),
+            Coverage {
+                functions: 2,
+                annotated: 0,
+            },
+        )];
+        let stats = compute(&packages, &[0, 1], &coverage, &modules, &findings);
+        assert_eq!(stats.crates[0].warnings, 1);
+        assert_eq!(stats.crates[1].warnings, 2);
+        assert_eq!(stats.crates[1].coverage_percent(), Some(0.0));
+        assert_eq!(stats.modules[0].crate_name, "b");
+        assert_eq!(stats.modules[0].module.as_deref(), Some("b::x"));
+    }
+}
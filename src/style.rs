@@ -0,0 +1,1180 @@
+//! Style lints for context usage.
+//!
+//! Most of these checks look at `.context()` / `.with_context()` calls on their
+//! own, independently of whether the callee carries a `#[context]` attribute, and
+//! flag usages that are valid but wasteful; those come with a fix. Misplaced
+//! `#[context]` attributes found by the collector are reported here as well.
+
+use serde::Serialize;
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::visit::Visit;
+use syn::{
+    Attribute, Block, Expr, ExprCall, ExprMethodCall, ImplItemFn, ItemFn, Macro, Signature, Stmt,
+    Token,
+};
+
+use crate::collector::{AnnotatedFunction, AnnotatedFunctions};
+use crate::config::{ContextMethodsConfig, StyleConfig};
+use crate::fix::{Fix, TextEdit};
+use crate::parse::{ParsedFile, Span};
+
+/// The kind of style issue found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum StyleKind {
+    /// `.with_context(|| "static string")` — the closure buys nothing.
+    ConstantClosure,
+    /// `.context(format!(...))` — formats even on the success path.
+    EagerFormat,
+    /// `#[context]` on a function that doesn't return a `Result`.
+    ContextWithoutResult,
+    /// `#[context]` format string with placeholders matching no argument.
+    FormatPlaceholder,
+    /// `#[context]` on a function that only calls another `#[context]` function.
+    DelegatingWrapper,
+    /// `bail!` / `anyhow!` message repeating the function's `#[context]`.
+    RepeatedMessage,
+    /// Context string interpolating what looks like a credential.
+    SensitiveData,
+    /// Context message longer than the configured limit.
+    LongMessage,
+    /// Context string with a word missing from the configured dictionaries.
+    Misspelling,
+}
+
+impl StyleKind {
+    /// The kind's name, as in the JSON output (e.g. `constant-closure`).
+    pub fn name(self) -> &'static str {
+        match self {
+            StyleKind::ConstantClosure => "constant-closure",
+            StyleKind::EagerFormat => "eager-format",
+            StyleKind::ContextWithoutResult => "context-without-result",
+            StyleKind::FormatPlaceholder => "format-placeholder",
+            StyleKind::DelegatingWrapper => "delegating-wrapper",
+            StyleKind::RepeatedMessage => "repeated-message",
+            StyleKind::SensitiveData => "sensitive-data",
+            StyleKind::LongMessage => "long-message",
+            StyleKind::Misspelling => "misspelling",
+        }
+    }
+}
+
+/// A detected style issue.
+#[derive(Debug, Clone)]
+pub struct StyleIssue {
+    /// File where the issue is located.
+    pub file: String,
+    /// Line number of the offending method call.
+    pub line: usize,
+    /// What kind of issue this is.
+    pub kind: StyleKind,
+    /// Human-readable description of the issue.
+    pub message: String,
+    /// Suggested fix.
+    pub fix: Option<Fix>,
+}
+
+/// Check a single Rust source file for style issues.
+pub fn check_file(file: &ParsedFile) -> Vec<StyleIssue> {
+    let Some(syntax) = &file.syntax else {
+        return Vec::new();
+    };
+
+    let mut visitor = StyleChecker {
+        file_path: file.display_path().to_string(),
+        source: &file.source,
+        results: Vec::new(),
+    };
+    visitor.visit_file(syntax);
+
+    visitor.results
+}
+
+/// Check collected `#[context]` functions for misplaced attributes and
+/// overly long messages.
+pub fn check_annotated(index: &AnnotatedFunctions, config: &StyleConfig) -> Vec<StyleIssue> {
+    let mut results = Vec::new();
+    for af in index.iter() {
+        if let Some(message) = af
+            .written_context()
+            .and_then(|context| long_message(context, config.max_message_length))
+        {
+            results.push(StyleIssue {
+                file: af.file.to_string(),
+                line: af.line,
+                kind: StyleKind::LongMessage,
+                message: format!("#[context] on `{}`: {message}", af.name),
+                fix: None,
+            });
+        }
+        if !af.returns_result {
+            results.push(StyleIssue {
+                file: af.file.to_string(),
+                line: af.line,
+                kind: StyleKind::ContextWithoutResult,
+                message: format!(
+                    "#[context] on `{}`, which does not return a Result",
+                    af.name
+                ),
+                fix: None,
+            });
+        }
+        for (i, error) in af.format_errors.iter().enumerate() {
+            // A misspelled parameter is renamed in the format string.
+            let fix = af
+                .placeholder_fixes
+                .iter()
+                .find(|pf| pf.error == i && !pf.edits.is_empty())
+                .map(|pf| Fix {
+                    description: format!(
+                        "rename `{{{}}}` to `{{{}}}`",
+                        pf.placeholder, pf.parameter
+                    ),
+                    edits: pf.edits.clone(),
+                });
+            results.push(StyleIssue {
+                file: af.file.to_string(),
+                line: af.line,
+                kind: StyleKind::FormatPlaceholder,
+                message: format!("#[context] on `{}`: {error}", af.name),
+                fix,
+            });
+        }
+    }
+    results
+}
+
+/// Check a single Rust source file for `#[context]` functions that only
+/// delegate to another `#[context]` function.
+pub fn check_delegation(file: &ParsedFile, index: &AnnotatedFunctions) -> Vec<StyleIssue> {
+    let Some(syntax) = &file.syntax else {
+        return Vec::new();
+    };
+
+    let mut visitor = DelegationChecker {
+        file_path: file.display_path().to_string(),
+        index,
+        results: Vec::new(),
+    };
+    visitor.visit_file(syntax);
+
+    visitor.results
+}
+
+/// Check a single Rust source file for error messages in `#[context]`
+/// functions that repeat the function's own context.
+pub fn check_messages(file: &ParsedFile, index: &AnnotatedFunctions) -> Vec<StyleIssue> {
+    let Some(syntax) = &file.syntax else {
+        return Vec::new();
+    };
+
+    let mut visitor = MessageChecker {
+        file_path: file.display_path().to_string(),
+        index,
+        annotated: None,
+        results: Vec::new(),
+    };
+    visitor.visit_file(syntax);
+
+    visitor.results
+}
+
+/// Check a single Rust source file for context strings interpolating what
+/// looks like a credential, in `#[context]` attributes and context method
+/// calls.
+pub fn check_sensitive(
+    file: &ParsedFile,
+    index: &AnnotatedFunctions,
+    config: &StyleConfig,
+    methods: &ContextMethodsConfig,
+) -> Vec<StyleIssue> {
+    let Some(syntax) = &file.syntax else {
+        return Vec::new();
+    };
+    if config.sensitive_words.is_empty() {
+        return Vec::new();
+    }
+
+    let mut visitor = SensitiveChecker {
+        file_path: file.display_path().to_string(),
+        base: file.syn_offset(),
+        index,
+        words: config.sensitive_words.iter().map(|w| words(w)).collect(),
+        methods,
+        results: Vec::new(),
+    };
+    visitor.visit_file(syntax);
+
+    visitor.results
+}
+
+/// Check a single Rust source file for context method calls with a message
+/// longer than the configured limit.
+pub fn check_message_lengths(
+    file: &ParsedFile,
+    config: &StyleConfig,
+    methods: &ContextMethodsConfig,
+) -> Vec<StyleIssue> {
+    let mut results = Vec::new();
+    if config.max_message_length == 0 {
+        return results;
+    }
+
+    for_each_context_message(file, methods, |call, message| {
+        if let Some(message) = long_message(&message.literal.value(), config.max_message_length) {
+            results.push(StyleIssue {
+                file: file.display_path().to_string(),
+                line: call.method.span().start().line,
+                kind: StyleKind::LongMessage,
+                message: format!("`.{}()` {message}", call.method),
+                fix: None,
+            });
+        }
+    });
+    results
+}
+
+/// Describe a message longer than `max` characters (0 for no limit).
+fn long_message(message: &str, max: usize) -> Option<String> {
+    let length = message.chars().count();
+    (max > 0 && length > max).then(|| {
+        format!(
+            "message is {length} characters long (limit {max}); details may belong in structured fields or logs"
+        )
+    })
+}
+
+struct StyleChecker<'a> {
+    file_path: String,
+    source: &'a str,
+    results: Vec<StyleIssue>,
+}
+
+impl StyleChecker<'_> {
+    /// Flag `.with_context(|| "literal")`, suggesting `.context("literal")`.
+    fn check_constant_closure(&mut self, method_call: &ExprMethodCall) {
+        if method_call.method != "with_context" || method_call.args.len() != 1 {
+            return;
+        }
+        let Some(Expr::Closure(closure)) = method_call.args.first() else {
+            return;
+        };
+        if !closure.inputs.is_empty() {
+            return;
+        }
+        let Some(lit) = constant_string(&closure.body) else {
+            return;
+        };
+
+        let base = crate::fix::syn_offset(self.source);
+        let lit_range = lit.span().byte_range();
+        let start = base + method_call.method.span().byte_range().start;
+        let end = base + method_call.paren_token.span.close().byte_range().end;
+        let lit_text = self
+            .source
+            .get(base + lit_range.start..base + lit_range.end);
+
+        let fix = lit_text.map(|lit_text| Fix {
+            description: format!("use `.context({lit_text})`"),
+            edits: vec![TextEdit {
+                start,
+                end,
+                replacement: format!("context({lit_text})"),
+            }],
+        });
+
+        self.results.push(StyleIssue {
+            file: self.file_path.clone(),
+            line: method_call.method.span().start().line,
+            kind: StyleKind::ConstantClosure,
+            message: "`.with_context()` closure returns a constant string".to_string(),
+            fix,
+        });
+    }
+
+    /// Flag `.context(format!(...))`, suggesting `.with_context(|| format!(...))`.
+    fn check_eager_format(&mut self, method_call: &ExprMethodCall) {
+        if method_call.method != "context" || method_call.args.len() != 1 {
+            return;
+        }
+        let Some(Expr::Macro(mac)) = method_call.args.first() else {
+            return;
+        };
+        if !mac.mac.path.is_ident("format") {
+            return;
+        }
+
+        let base = crate::fix::syn_offset(self.source);
+        let start = base + method_call.method.span().byte_range().start;
+        let end = base + method_call.paren_token.span.close().byte_range().end;
+        let arg_start = base + mac.mac.path.segments[0].ident.span().byte_range().start;
+        let arg_end = base + mac.mac.delimiter.span().close().byte_range().end;
+        let arg_text = self.source.get(arg_start..arg_end);
+
+        let fix = arg_text.map(|arg_text| Fix {
+            description: "use `.with_context(|| format!(...))`".to_string(),
+            edits: vec![TextEdit {
+                start,
+                end,
+                replacement: format!("with_context(|| {arg_text})"),
+            }],
+        });
+
+        self.results.push(StyleIssue {
+            file: self.file_path.clone(),
+            line: method_call.method.span().start().line,
+            kind: StyleKind::EagerFormat,
+            message: "`format!` passed to `.context()` is evaluated even on success".to_string(),
+            fix,
+        });
+    }
+}
+
+/// If an expression always evaluates to the same string, return its literal.
+///
+/// Recognizes `"..."`, `{ "..." }`, `"...".to_string()` (and `to_owned`/`into`),
+/// `String::from("...")`, and `format!("...")` without any braces.
+fn constant_string(expr: &Expr) -> Option<syn::LitStr> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            syn::Lit::Str(s) => Some(s.clone()),
+            _ => None,
+        },
+        Expr::Paren(paren) => constant_string(&paren.expr),
+        Expr::Block(block) if block.block.stmts.len() == 1 => match &block.block.stmts[0] {
+            syn::Stmt::Expr(expr, None) => constant_string(expr),
+            _ => None,
+        },
+        Expr::MethodCall(call)
+            if call.args.is_empty()
+                && matches!(
+                    call.method.to_string().as_str(),
+                    "to_string" | "to_owned" | "into"
+                ) =>
+        {
+            constant_string(&call.receiver)
+        }
+        Expr::Call(call) if call.args.len() == 1 => match &*call.func {
+            Expr::Path(path)
+                if path.path.segments.len() == 2
+                    && path.path.segments[0].ident == "String"
+                    && path.path.segments[1].ident == "from" =>
+            {
+                constant_string(&call.args[0])
+            }
+            _ => None,
+        },
+        Expr::Macro(mac) if mac.mac.path.is_ident("format") => {
+            // Braces are either placeholders or escapes; both change the text.
+            let lit: syn::LitStr = mac.mac.parse_body().ok()?;
+            (!lit.value().contains(['{', '}'])).then_some(lit)
+        }
+        _ => None,
+    }
+}
+
+impl<'ast> Visit<'ast> for StyleChecker<'_> {
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        self.check_constant_closure(node);
+        self.check_eager_format(node);
+        syn::visit::visit_expr_method_call(self, node);
+    }
+}
+
+struct DelegationChecker<'a> {
+    file_path: String,
+    index: &'a AnnotatedFunctions,
+    results: Vec<StyleIssue>,
+}
+
+impl DelegationChecker<'_> {
+    fn check_fn(&mut self, sig: &Signature, block: &Block) {
+        if self.index.definition(&self.file_path, sig).is_none() {
+            return;
+        }
+        let line = sig.ident.span().start().line;
+        let Some(call) = delegated_call(block) else {
+            return;
+        };
+        // A recursive call is not delegation.
+        let Some(callee) = crate::checker::annotated_callees(call, self.index)
+            .into_iter()
+            .find(|af| *af.file != *self.file_path || af.line != line)
+        else {
+            return;
+        };
+
+        self.results.push(StyleIssue {
+            file: self.file_path.clone(),
+            line,
+            kind: StyleKind::DelegatingWrapper,
+            message: format!(
+                "#[context] on `{}`, which only delegates to `{}` (also #[context]); both contexts will always be added",
+                sig.ident, callee.name
+            ),
+            fix: None,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for DelegationChecker<'_> {
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.check_fn(&node.sig, &node.block);
+        syn::visit::visit_item_fn(self, node);
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.check_fn(&node.sig, &node.block);
+        syn::visit::visit_impl_item_fn(self, node);
+    }
+}
+
+/// Macros creating an error from a message, with the position of the message
+/// among their arguments.
+const ERROR_MACROS: &[(&str, usize)] = &[
+    ("bail", 0),
+    ("anyhow", 0),
+    ("format_err", 0),
+    ("eyre", 0),
+    ("ensure", 1),
+];
+
+struct MessageChecker<'a> {
+    file_path: String,
+    index: &'a AnnotatedFunctions,
+    /// The `#[context]` function being visited, if any.
+    annotated: Option<&'a AnnotatedFunction>,
+    results: Vec<StyleIssue>,
+}
+
+impl<'a> MessageChecker<'a> {
+    fn visit_fn(&mut self, sig: &Signature, visit: impl FnOnce(&mut Self)) {
+        let annotated = self.index.definition(&self.file_path, sig);
+        let outer = std::mem::replace(&mut self.annotated, annotated);
+        visit(self);
+        self.annotated = outer;
+    }
+
+    /// Flag `bail!("...")` and the like whose message is the same as the
+    /// enclosing function's context, ignoring case, punctuation and
+    /// placeholders.
+    fn check_macro(&mut self, mac: &Macro) {
+        let Some(annotated) = self.annotated else {
+            return;
+        };
+        let Some(name) = mac.path.segments.last().map(|seg| seg.ident.to_string()) else {
+            return;
+        };
+        let Some(&(_, position)) = ERROR_MACROS.iter().find(|(m, _)| *m == name) else {
+            return;
+        };
+        let Ok(args) = mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) else {
+            return;
+        };
+        let Some(Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(message),
+            ..
+        })) = args.iter().nth(position)
+        else {
+            return;
+        };
+        let normalized = normalize_message(&message.value());
+        if normalized.is_empty()
+            || Some(normalized) != annotated.written_context().map(normalize_message)
+        {
+            return;
+        }
+
+        self.results.push(StyleIssue {
+            file: self.file_path.clone(),
+            line: mac.path.segments[0].ident.span().start().line,
+            kind: StyleKind::RepeatedMessage,
+            message: format!(
+                "`{name}!` message repeats the #[context] of `{}`; the error chain will say it twice",
+                annotated.name
+            ),
+            fix: None,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for MessageChecker<'_> {
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.visit_fn(&node.sig, |this| syn::visit::visit_item_fn(this, node));
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.visit_fn(&node.sig, |this| syn::visit::visit_impl_item_fn(this, node));
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        self.check_macro(node);
+        syn::visit::visit_macro(self, node);
+    }
+}
+
+/// A message reduced to its lowercase words, without placeholders and
+/// punctuation: `"Loading config: {e}"` becomes `"loading config"`.
+fn normalize_message(message: &str) -> String {
+    let mut words = String::new();
+    let mut depth = 0usize;
+    for c in message.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            c if c.is_alphanumeric() => words.extend(c.to_lowercase()),
+            _ => words.push(' '),
+        }
+    }
+    words.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+struct SensitiveChecker<'a> {
+    file_path: String,
+    /// Leading bytes skipped by `syn`, see [`ParsedFile::syn_offset`].
+    base: usize,
+    index: &'a AnnotatedFunctions,
+    /// The configured sensitive words, each split into words.
+    words: Vec<Vec<String>>,
+    methods: &'a ContextMethodsConfig,
+    results: Vec<StyleIssue>,
+}
+
+impl SensitiveChecker<'_> {
+    /// Check the `#[context]` attribute of a function, if it has one.
+    fn check_fn(&mut self, attrs: &[Attribute], sig: &Signature) {
+        let Some(annotated) = self.index.definition(&self.file_path, sig) else {
+            return;
+        };
+        let Some(attr) = attrs.iter().find(|attr| {
+            Span::new(
+                attr.pound_token.span,
+                attr.bracket_token.span.close(),
+                self.base,
+            ) == annotated.attr_span
+        }) else {
+            return;
+        };
+        // The message literal (or `key = "message"`) is the format string;
+        // everything else is interpolated.
+        let args = attr
+            .parse_args_with(|input: ParseStream| {
+                if input.peek(Token![move]) {
+                    input.parse::<Token![move]>()?;
+                    input.parse::<Token![,]>()?;
+                }
+                Punctuated::<Expr, Token![,]>::parse_terminated(input)
+            })
+            .unwrap_or_default();
+        let args: Vec<&Expr> = args
+            .iter()
+            .filter(|arg| match arg {
+                Expr::Assign(assign) => !is_string_literal(&assign.right),
+                arg => !is_string_literal(arg),
+            })
+            .collect();
+        let Some(context) = annotated.written_context() else {
+            return;
+        };
+        if let Some(name) = self.sensitive_interpolation(context, &args) {
+            self.push(annotated.attr_span.line, &name);
+        }
+    }
+
+    /// Check the message of a context method call: `.context(format!(..))`
+    /// or `.with_context(|| format!(..))`.
+    fn check_method_call(&mut self, method_call: &ExprMethodCall) {
+        let Some(Message {
+            literal,
+            format_args: Some(args),
+        }) = context_message(method_call, self.methods)
+        else {
+            return;
+        };
+        let args: Vec<&Expr> = args.iter().collect();
+        if let Some(name) = self.sensitive_interpolation(&literal.value(), &args) {
+            self.push(method_call.method.span().start().line, &name);
+        }
+    }
+
+    /// The first interpolated binding, or label before a placeholder, that
+    /// contains a sensitive word. Arguments other than a plain variable, such
+    /// as `token.kind` or `tokens.len()`, are left alone: their value isn't
+    /// the one the name describes.
+    fn sensitive_interpolation(&self, format: &str, args: &[&Expr]) -> Option<String> {
+        let mut names = placeholder_names(format);
+        names.extend(args.iter().filter_map(|arg| binding(arg)));
+        names.into_iter().find(|name| {
+            let name = words(name);
+            self.words
+                .iter()
+                .any(|word| !word.is_empty() && name.windows(word.len()).any(|w| w == word))
+        })
+    }
+
+    fn push(&mut self, line: usize, name: &str) {
+        self.results.push(StyleIssue {
+            file: self.file_path.clone(),
+            line,
+            kind: StyleKind::SensitiveData,
+            message: format!("context string interpolates `{name}`, which may hold a secret"),
+            fix: None,
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for SensitiveChecker<'_> {
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.check_fn(&node.attrs, &node.sig);
+        syn::visit::visit_item_fn(self, node);
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.check_fn(&node.attrs, &node.sig);
+        syn::visit::visit_impl_item_fn(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        self.check_method_call(node);
+        syn::visit::visit_expr_method_call(self, node);
+    }
+}
+
+/// The message given to a context method.
+pub struct Message {
+    /// The string literal, or the format string of a `format!`.
+    pub literal: syn::LitStr,
+    /// The arguments of a `format!`; `None` for a plain literal.
+    pub format_args: Option<Vec<Expr>>,
+}
+
+/// The message of a context method call, if it's a literal or `format!`,
+/// possibly returned by a closure: `.context("..")`,
+/// `.with_context(|| format!(..))`.
+pub fn context_message(
+    method_call: &ExprMethodCall,
+    methods: &ContextMethodsConfig,
+) -> Option<Message> {
+    if !methods.is_context_method(&method_call.method.to_string()) {
+        return None;
+    }
+    let mut arg = method_call.args.first()?;
+    if let Expr::Closure(closure) = arg {
+        arg = &closure.body;
+    }
+    match crate::checker::single_expr(arg)? {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(literal),
+            ..
+        }) => Some(Message {
+            literal: literal.clone(),
+            format_args: None,
+        }),
+        Expr::Macro(mac) if mac.mac.path.is_ident("format") => {
+            let args = mac
+                .mac
+                .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                .ok()?;
+            let mut args = args.into_iter();
+            let Some(Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(literal),
+                ..
+            })) = args.next()
+            else {
+                return None;
+            };
+            Some(Message {
+                literal,
+                format_args: Some(args.collect()),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Call `f` with every context method call in a file whose message is a
+/// literal or `format!` (see [`context_message`]), and that message.
+pub fn for_each_context_message(
+    file: &ParsedFile,
+    methods: &ContextMethodsConfig,
+    f: impl FnMut(&ExprMethodCall, Message),
+) {
+    struct MessageVisitor<'a, F> {
+        methods: &'a ContextMethodsConfig,
+        f: F,
+    }
+
+    impl<'ast, F: FnMut(&ExprMethodCall, Message)> Visit<'ast> for MessageVisitor<'_, F> {
+        fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+            if let Some(message) = context_message(node, self.methods) {
+                (self.f)(node, message);
+            }
+            syn::visit::visit_expr_method_call(self, node);
+        }
+    }
+
+    if let Some(syntax) = &file.syntax {
+        MessageVisitor { methods, f }.visit_file(syntax);
+    }
+}
+
+/// The variable an argument interpolates, looking through references and
+/// parentheses, and the value of a `name = value` argument.
+fn binding(arg: &Expr) -> Option<String> {
+    match arg {
+        Expr::Assign(assign) => binding(&assign.right),
+        Expr::Reference(reference) => binding(&reference.expr),
+        Expr::Paren(paren) => binding(&paren.expr),
+        Expr::Path(path) if path.qself.is_none() => {
+            path.path.get_ident().map(|ident| ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn is_string_literal(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(_),
+            ..
+        })
+    )
+}
+
+/// The names of a format string's named placeholders, and the label
+/// preceding each placeholder: `"user {name}, token={}"` gives `user`,
+/// `name` and `token`.
+fn placeholder_names(format: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '{' => {
+                let spec: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let label: String = text
+                    .trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_')
+                    .chars()
+                    .rev()
+                    .take_while(|&c| c.is_alphanumeric() || c == '_')
+                    .collect();
+                names.push(label.chars().rev().collect());
+                let arg = spec.split(':').next().unwrap_or_default().trim();
+                if !arg.is_empty() && arg.parse::<usize>().is_err() {
+                    names.push(arg.to_string());
+                }
+                text.clear();
+            }
+            c => text.push(c),
+        }
+    }
+    names.retain(|name| !name.is_empty());
+    names
+}
+
+/// The lowercase words of an identifier or phrase, split at underscores,
+/// other punctuation and camelCase boundaries.
+fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            prev_lower = false;
+            words.push(String::new());
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            words.push(String::new());
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        match words.last_mut() {
+            Some(word) => word.extend(c.to_lowercase()),
+            None => words.push(c.to_lowercase().collect()),
+        }
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// If a function body does nothing but call another function, return that call.
+///
+/// Recognizes `inner(..)`, `Ok(inner(..)?)` and `inner(..)?; Ok(())`, with or
+/// without `.await`.
+pub fn delegated_call(block: &Block) -> Option<&Expr> {
+    match block.stmts.as_slice() {
+        [Stmt::Expr(Expr::Call(ok), None)] if is_ok_call(ok) => match &ok.args[0] {
+            Expr::Try(inner) => Some(&inner.expr),
+            _ => None,
+        },
+        [Stmt::Expr(expr @ (Expr::Call(_) | Expr::MethodCall(_) | Expr::Await(_)), None)] => {
+            Some(expr)
+        }
+        [Stmt::Expr(Expr::Try(inner), Some(_)), Stmt::Expr(Expr::Call(ok), None)]
+            if is_ok_call(ok) && matches!(&ok.args[0], Expr::Tuple(t) if t.elems.is_empty()) =>
+        {
+            Some(&inner.expr)
+        }
+        _ => None,
+    }
+}
+
+/// Whether a call is `Ok(value)`.
+fn is_ok_call(call: &ExprCall) -> bool {
+    call.args.len() == 1 && matches!(&*call.func, Expr::Path(p) if p.path.is_ident("Ok"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn check_source(source: &str) -> Vec<StyleIssue> {
+        let syntax: syn::File = syn::parse_file(source).unwrap();
+        let mut visitor = StyleChecker {
+            file_path: "test.rs".to_string(),
+            source,
+            results: Vec::new(),
+        };
+        visitor.visit_file(&syntax);
+        visitor.results
+    }
+
+    fn check_delegation_source(source: &str, index: &AnnotatedFunctions) -> Vec<StyleIssue> {
+        let syntax: syn::File = syn::parse_file(source).unwrap();
+        let mut visitor = DelegationChecker {
+            file_path: "test.rs".to_string(),
+            index,
+            results: Vec::new(),
+        };
+        visitor.visit_file(&syntax);
+        visitor.results
+    }
+
+    fn fixed(source: &str, issue: &StyleIssue) -> String {
+        crate::fix::apply_edits(source, &issue.fix.as_ref().unwrap().edits).unwrap()
+    }
+
+    #[test]
+    fn test_constant_closure() {
+        let source = r#"
+            fn main() {
+                load().with_context(|| "Loading config").unwrap();
+            }
+            "#;
+        let results = check_source(source);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind, StyleKind::ConstantClosure);
+        assert!(fixed(source, &results[0]).contains(r#"load().context("Loading config")"#));
+    }
+
+    #[test]
+    fn test_constant_closure_to_string() {
+        let source = r#"
+            fn main() {
+                load().with_context(|| "Loading config".to_string()).unwrap();
+            }
+            "#;
+        let results = check_source(source);
+        assert_eq!(results.len(), 1);
+        assert!(fixed(source, &results[0]).contains(r#"load().context("Loading config")"#));
+    }
+
+    #[test]
+    fn test_dynamic_closure_not_flagged() {
+        let results = check_source(
+            r#"
+            fn main() {
+                load(p).with_context(|| format!("Loading {}", p.display())).unwrap();
+            }
+            "#,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_eager_format() {
+        let source = r#"
+            fn main() {
+                load(p).context(format!("Loading {}", p.display())).unwrap();
+            }
+            "#;
+        let results = check_source(source);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind, StyleKind::EagerFormat);
+        assert!(fixed(source, &results[0])
+            .contains(r#"load(p).with_context(|| format!("Loading {}", p.display()))"#));
+    }
+
+    #[test]
+    fn test_context_literal_not_flagged() {
+        let results = check_source(
+            r#"
+            fn main() {
+                load(p).context("Loading").unwrap();
+                load(p).with_context(|| format!("Loading {}", p.display())).unwrap();
+            }
+            "#,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_context_without_result() {
+        let index = crate::collector::build_index(vec![crate::collector::AnnotatedFunction {
+            returns_result: false,
+            ..fixtures::annotated("count", "test.rs", 3, "Counting")
+        }]);
+        let results = check_annotated(&index, &StyleConfig::default());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind, StyleKind::ContextWithoutResult);
+        assert!(results[0].fix.is_none());
+    }
+
+    #[test]
+    fn test_format_placeholder() {
+        let index = crate::collector::build_index(vec![crate::collector::AnnotatedFunction {
+            format_errors: vec![
+                "placeholder `{taget}` matches no parameter; did you mean `target`?".to_string(),
+                "format string needs 1 positional argument but 0 were given".to_string(),
+            ],
+            params: vec!["target".to_string()],
+            placeholder_fixes: vec![crate::collector::PlaceholderFix {
+                error: 0,
+                placeholder: "taget".to_string(),
+                parameter: "target".to_string(),
+                edits: vec![TextEdit {
+                    start: 20,
+                    end: 25,
+                    replacement: "target".to_string(),
+                }],
+            }],
+            ..fixtures::annotated("open", "test.rs", 3, "Opening {taget} {}")
+        }]);
+        let results = check_annotated(&index, &StyleConfig::default());
+        assert_eq!(results.len(), 2);
+        assert!(results[1].fix.is_none());
+        assert_eq!(results[0].kind, StyleKind::FormatPlaceholder);
+        assert_eq!(
+            results[0].message,
+            "#[context] on `open`: placeholder `{taget}` matches no parameter; did you mean `target`?"
+        );
+        let fix = results[0].fix.as_ref().unwrap();
+        assert_eq!(fix.description, "rename `{taget}` to `{target}`");
+        assert_eq!(fix.edits.len(), 1);
+    }
+
+    fn annotated(name: &str, line: usize, is_method: bool) -> crate::collector::AnnotatedFunction {
+        crate::collector::AnnotatedFunction {
+            is_method,
+            ..fixtures::annotated(name, "test.rs", line, &format!("Running {name}"))
+        }
+    }
+
+    #[test]
+    fn test_delegating_wrapper() {
+        let source = r#"
+            #[context("Loading")]
+            fn load_config() -> Result<Config> {
+                read_config_file()
+            }
+
+            #[context("Reading")]
+            fn read_config_file() -> Result<Config> {
+                todo!()
+            }
+
+            impl Store {
+                #[context("Syncing")]
+                async fn sync(&self) -> Result<()> {
+                    self.sync_all().await?;
+                    Ok(())
+                }
+
+                #[context("Flushing")]
+                fn flush(&self) -> Result<()> {
+                    Ok(read_config_file()?)
+                }
+            }
+            "#;
+        let index = crate::collector::build_index(vec![
+            annotated("load_config", 3, false),
+            annotated("read_config_file", 8, false),
+            annotated("sync", 14, true),
+            annotated("flush", 20, true),
+            annotated("sync_all", 30, true),
+        ]);
+        let results = check_delegation_source(source, &index);
+        assert_eq!(results.len(), 3);
+        assert!(results
+            .iter()
+            .all(|r| r.kind == StyleKind::DelegatingWrapper));
+        assert!(results[0].message.starts_with(
+            "#[context] on `load_config`, which only delegates to `read_config_file`"
+        ));
+        assert!(results[1].message.contains("`sync_all`"));
+    }
+
+    #[test]
+    fn test_repeated_message() {
+        let source = r#"
+            #[context("Loading config {}", path.display())]
+            fn load_config(path: &Path) -> Result<Config> {
+                if !path.exists() {
+                    bail!("Loading config: {}", path.display());
+                }
+                ensure!(path.is_file(), "loading CONFIG");
+                let text = read(path).map_err(|e| anyhow::anyhow!("Loading config: {e}"))?;
+                if text.is_empty() {
+                    bail!("Config {path:?} is empty");
+                }
+                fn helper() -> Result<()> {
+                    bail!("Loading config")
+                }
+                parse(&text)
+            }
+
+            fn unannotated() -> Result<()> {
+                bail!("Loading config")
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let collected = crate::collector::collect_from_file(&file, &[], &[], &[]);
+        let index = crate::collector::build_index(collected.functions);
+        let results = check_messages(&file, &index);
+        let lines: Vec<usize> = results.iter().map(|r| r.line).collect();
+        assert_eq!(lines, [5, 7, 8]);
+        assert!(results.iter().all(|r| r.kind == StyleKind::RepeatedMessage));
+        assert_eq!(
+            results[0].message,
+            "`bail!` message repeats the #[context] of `load_config`; the error chain will say it twice"
+        );
+    }
+
+    #[test]
+    fn test_sensitive_data() {
+        let source = r#"
+            #[context("Logging in as {user} with {}", db_password)]
+            fn login(user: &str, db_password: &str) -> Result<()> {
+                connect(url).with_context(|| format!("Connecting with token={}", self.current()))?;
+                connect(url).context(format!("Connecting with {authToken}"))?;
+                connect(url).context(format!("Connecting to {url}"))?;
+                let key = lookup(&cache).context("Reading the password file")?;
+                lookup(&cache).with_context(|| format!("Looking up {key}"))?;
+                lookup(&cache).with_context(|| format!("Using {}", &api_key))?;
+                lookup(&cache).with_context(|| format!("Parsing {}", token.kind))?;
+                lookup(&cache).with_context(|| format!("Parsing {} of {}", tokens.len(), self.secret))?;
+                Ok(())
+            }
+
+            #[context("Rotating keys for {name}")]
+            fn rotate(name: &str) -> Result<()> {
+                todo!()
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let collected = crate::collector::collect_from_file(&file, &[], &[], &[]);
+        let index = crate::collector::build_index(collected.functions);
+        let config = StyleConfig {
+            sensitive_words: crate::config::SUGGESTED_SENSITIVE_WORDS
+                .iter()
+                .map(|word| word.to_string())
+                .collect(),
+            ..Default::default()
+        };
+        let results = check_sensitive(&file, &index, &config, &Default::default());
+        let lines: Vec<usize> = results.iter().map(|r| r.line).collect();
+        assert_eq!(lines, [2, 4, 5, 9]);
+        assert_eq!(
+            results[0].message,
+            "context string interpolates `db_password`, which may hold a secret"
+        );
+        let names: Vec<&str> = results
+            .iter()
+            .filter_map(|r| r.message.split('`').nth(1))
+            .collect();
+        assert_eq!(names, ["db_password", "token", "authToken", "api_key"]);
+        assert!(results.iter().all(|r| r.kind == StyleKind::SensitiveData));
+
+        // Off by default.
+        let config = StyleConfig::default();
+        assert!(check_sensitive(&file, &index, &config, &Default::default()).is_empty());
+    }
+
+    #[test]
+    fn test_long_message() {
+        let source = r#"
+            #[context("Loading the configuration files")]
+            fn load_config() -> Result<Config> {
+                read(path).context("Reading the configuration file from the usual place")?;
+                read(path).with_context(|| format!("Reading {}", path.display()))?;
+                read(path).context("Reading")?;
+                todo!()
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let config = StyleConfig {
+            max_message_length: 30,
+            ..Default::default()
+        };
+        let results = check_message_lengths(&file, &config, &Default::default());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind, StyleKind::LongMessage);
+        assert_eq!(results[0].line, 4);
+        assert!(results[0]
+            .message
+            .starts_with("`.context()` message is 51 characters long (limit 30)"));
+
+        let collected = crate::collector::collect_from_file(&file, &[], &[], &[]);
+        let index = crate::collector::build_index(collected.functions);
+        let results = check_annotated(&index, &config);
+        assert_eq!(results.len(), 1);
+        assert!(results[0]
+            .message
+            .starts_with("#[context] on `load_config`: message is 31 characters long"));
+
+        // Off by default.
+        let config = StyleConfig::default();
+        assert!(check_message_lengths(&file, &config, &Default::default()).is_empty());
+        assert!(check_annotated(&index, &config).is_empty());
+    }
+
+    #[test]
+    fn test_delegation_not_flagged() {
+        let source = r#"
+            #[context("Loading")]
+            fn load_config(path: &Path) -> Result<Config> {
+                let config = read_config_file(path)?;
+                config.validate()?;
+                Ok(config)
+            }
+
+            fn unannotated() -> Result<Config> {
+                read_config_file()
+            }
+
+            #[context("Retrying")]
+            fn retry(n: u32) -> Result<()> {
+                retry(n - 1)
+            }
+
+            #[context("Parsing")]
+            fn parse_it() -> Result<()> {
+                parse_unannotated()
+            }
+            "#;
+        let index = crate::collector::build_index(vec![
+            annotated("load_config", 3, false),
+            annotated("read_config_file", 30, false),
+            annotated("retry", 14, false),
+            annotated("parse_it", 19, false),
+        ]);
+        assert!(check_delegation_source(source, &index).is_empty());
+    }
+}
@@ -2,64 +2,267 @@
 //!
 //! Functions that return `anyhow::Result` should generally have a `#[context("...")]`
 //! annotation from the `fn_error_context` crate to provide meaningful error context.
-//! This module detects functions that are missing this annotation.
+//! This module detects functions that are missing this annotation. `eyre::Result`
+//! (including `color_eyre`'s re-exports) is treated the same way.
 
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use proc_macro2::TokenTree;
+use syn::spanned::Spanned;
 use syn::visit::Visit;
 use syn::{
-    Attribute, File, GenericArgument, ImplItemFn, ItemFn, ItemImpl, ItemMod, PathArguments,
-    ReturnType, Signature, Type, Visibility,
+    Attribute, Block, Expr, ExprClosure, ExprReturn, ExprTry, GenericArgument, ImplItemFn, Item,
+    ItemFn, ItemImpl, ItemMod, ItemTrait, ItemType, PathArguments, ReturnType, Signature, Stmt,
+    Type, TypePath, Visibility,
 };
 
+use crate::cfg;
+use crate::config::{Config, ContextAttribute, ContextMethodsConfig};
+use crate::imports::{
+    collect_globs, collect_imports, collect_reexports, qualified_candidates, resolve, Imports,
+};
+use crate::parse::{ParsedFile, Span};
+use crate::propagation::statement_count;
+use crate::style::delegated_call;
+
 /// A function returning `anyhow::Result` without `#[context]`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct UnattributedFunction {
     /// File where the function is defined.
-    pub file: String,
+    pub file: Arc<str>,
     /// Line number of the function definition.
     pub line: usize,
+    /// The span of the signature, from `fn` to the return type.
+    pub span: Span,
     /// The function name.
-    pub name: String,
+    pub name: Arc<str>,
     /// Whether this is a method (has a `self` receiver).
     pub is_method: bool,
     /// Whether this function has `pub` visibility.
     pub is_pub: bool,
 }
 
+/// How many of the functions returning `anyhow::Result` have `#[context]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// Functions returning `anyhow::Result` that are subject to the check.
+    pub functions: usize,
+    /// Those of them with `#[context]`.
+    pub annotated: usize,
+}
+
+impl Coverage {
+    /// Percentage of the functions with `#[context]`, or `None` if there are
+    /// none.
+    pub fn percent(&self) -> Option<f64> {
+        (self.functions > 0).then(|| 100.0 * self.annotated as f64 / self.functions as f64)
+    }
+
+    /// Add the counts of `other`.
+    pub fn add(&mut self, other: &Coverage) {
+        self.functions += other.functions;
+        self.annotated += other.annotated;
+    }
+}
+
+/// What the check knows about the rest of the workspace.
+#[derive(Debug, Default)]
+pub struct WorkspaceItems {
+    /// Traits whose impls are checked (those defined in the workspace, see
+    /// [`local_traits`]); all trait impls are skipped if `None`.
+    pub local_traits: Option<HashSet<String>>,
+    /// Qualified paths of the aliases of `anyhow::Result` defined or
+    /// re-exported in the workspace, see [`result_aliases`].
+    pub result_aliases: HashSet<String>,
+    /// Lines of the functions found to add context in pass 1, by file: those
+    /// with a context attribute, and those selected by a `[[rules]]` entry.
+    pub annotated: HashMap<Arc<str>, HashSet<usize>>,
+}
+
+impl WorkspaceItems {
+    /// Whether pass 1 found the function defined on `line` of `file` to add
+    /// context.
+    pub fn is_annotated(&self, file: &str, line: usize) -> bool {
+        self.annotated
+            .get(file)
+            .is_some_and(|lines| lines.contains(&line))
+    }
+}
+
 /// Check a single Rust source file for functions returning `anyhow::Result`
-/// without a `#[context]` attribute.
-pub fn check_file(path: &Path) -> Result<Vec<UnattributedFunction>> {
-    let source =
-        std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
-
-    let syntax: File = match syn::parse_file(&source) {
-        Ok(f) => f,
-        Err(_) => return Ok(Vec::new()),
+/// without a `#[context]` attribute. `module_path` is the module of the file,
+/// as given by [`crate::package::module_path`].
+pub fn check_file(
+    file: &ParsedFile,
+    config: &Config,
+    module_path: &[String],
+    workspace: &WorkspaceItems,
+) -> Vec<UnattributedFunction> {
+    visit(file, config, module_path, workspace).results
+}
+
+/// Count the functions returning `anyhow::Result` in a file, and how many of
+/// them have `#[context]`.
+pub fn coverage(
+    file: &ParsedFile,
+    config: &Config,
+    module_path: &[String],
+    workspace: &WorkspaceItems,
+) -> Coverage {
+    visit(file, config, module_path, workspace).coverage
+}
+
+/// The lines of the functions returning `anyhow::Result` (or an alias of it)
+/// in a file, the functions the check is about.
+pub fn anyhow_result_fns(
+    file: &ParsedFile,
+    config: &Config,
+    module_path: &[String],
+    workspace: &WorkspaceItems,
+) -> HashSet<usize> {
+    visit(file, config, module_path, workspace).anyhow_result_fns
+}
+
+/// The qualified paths (`my_crate::error::Result`) of the type aliases for
+/// `anyhow::Result` defined in some files, given with their module paths, and
+/// of their re-exports, so that functions returning them under an imported
+/// name are checked too.
+pub fn result_aliases<'a>(
+    files: impl IntoIterator<Item = (&'a ParsedFile, &'a [String])>,
+    config: &Config,
+) -> HashSet<String> {
+    let files: Vec<_> = files
+        .into_iter()
+        .filter(|(_, module_path)| !module_path.is_empty())
+        .collect();
+    let mut aliases = HashSet::new();
+    for (file, module_path) in &files {
+        let workspace = WorkspaceItems::default();
+        aliases.extend(visit(file, config, module_path, &workspace).defined_aliases);
+    }
+
+    // Follow re-exports, such as those of a prelude module, until no new
+    // path turns up
+    loop {
+        let mut found = Vec::new();
+        for (file, module_path) in &files {
+            let Some(syntax) = &file.syntax else {
+                continue;
+            };
+            for reexport in collect_reexports(&syntax.items) {
+                let candidates = qualified_candidates(&reexport.path, module_path, None);
+                match reexport.name {
+                    Some(name) => {
+                        if candidates.iter().any(|path| aliases.contains(path)) {
+                            found.push(format!("{}::{name}", module_path.join("::")));
+                        }
+                    }
+                    None => {
+                        for alias in &aliases {
+                            let Some((parent, name)) = alias.rsplit_once("::") else {
+                                continue;
+                            };
+                            if candidates.iter().any(|path| path == parent) {
+                                found.push(format!("{}::{name}", module_path.join("::")));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let before = aliases.len();
+        aliases.extend(found);
+        if aliases.len() == before {
+            return aliases;
+        }
+    }
+}
+
+/// The names of the traits defined in some files, including those in inline
+/// modules.
+pub fn local_traits<'a>(files: impl IntoIterator<Item = &'a ParsedFile>) -> HashSet<String> {
+    struct TraitCollector(HashSet<String>);
+
+    impl<'ast> Visit<'ast> for TraitCollector {
+        fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+            self.0.insert(node.ident.to_string());
+        }
+    }
+
+    let mut collector = TraitCollector(HashSet::new());
+    for syntax in files.into_iter().filter_map(|file| file.syntax.as_ref()) {
+        collector.visit_file(syntax);
+    }
+    collector.0
+}
+
+/// The outcome of visiting a file.
+#[derive(Default)]
+struct Visited {
+    results: Vec<UnattributedFunction>,
+    coverage: Coverage,
+    defined_aliases: Vec<String>,
+    anyhow_result_fns: HashSet<usize>,
+}
+
+fn visit(
+    file: &ParsedFile,
+    config: &Config,
+    module_path: &[String],
+    workspace: &WorkspaceItems,
+) -> Visited {
+    let Some(syntax) = &file.syntax else {
+        return Visited::default();
     };
 
     // Determine if `anyhow::Result` is in scope at the file level.
-    let has_anyhow_result_import = has_anyhow_result_in_scope(&syntax);
+    let has_anyhow_result_import = has_anyhow_result_in_scope(&syntax.items);
+
+    // Collect type aliases, which may shadow the import or wrap `anyhow::Result`.
+    let type_aliases = collect_type_aliases(&syntax.items);
 
-    // Check for non-anyhow `type Result` aliases that shadow the import.
-    let has_non_anyhow_result_alias = has_non_anyhow_result_alias(&syntax);
+    let result_aliases = parse_result_aliases(&config.unattributed.result_aliases);
 
     let mut visitor = UnattributedChecker {
-        file_path: path.to_string_lossy().to_string(),
-        anyhow_result_imported: has_anyhow_result_import && !has_non_anyhow_result_alias,
+        file_path: file.display_path(),
+        base: file.syn_offset(),
+        anyhow_result_imported: has_anyhow_result_import,
+        type_aliases,
+        imports: collect_imports(&syntax.items),
+        globs: collect_globs(&syntax.items),
+        module_path: module_path.to_vec(),
+        result_aliases,
+        context_attributes: &config.context_attributes,
+        error_attributes: parse_error_attributes(&config.unattributed.error_attributes),
+        covering_methods: config
+            .unattributed
+            .skip_covered
+            .then_some(&config.context_methods),
+        skip_delegation: config.unattributed.skip_delegation,
+        min_statements: config.unattributed.min_statements,
+        workspace,
         in_cfg_test: false,
         in_trait_impl: false,
+        coverage: Coverage::default(),
         results: Vec::new(),
+        defined_aliases: Vec::new(),
+        anyhow_result_fns: HashSet::new(),
     };
-    visitor.visit_file(&syntax);
+    visitor.visit_file(syntax);
 
-    Ok(visitor.results)
+    Visited {
+        results: visitor.results,
+        coverage: visitor.coverage,
+        defined_aliases: visitor.defined_aliases,
+        anyhow_result_fns: visitor.anyhow_result_fns,
+    }
 }
 
-/// Check if the file has `use anyhow::Result` or equivalent in scope.
-fn has_anyhow_result_in_scope(file: &File) -> bool {
-    for item in &file.items {
+/// Check if the items of a file or module have `use anyhow::Result` or
+/// equivalent.
+fn has_anyhow_result_in_scope(items: &[Item]) -> bool {
+    for item in items {
         if let syn::Item::Use(use_item) = item {
             if use_tree_imports_anyhow_result(&use_item.tree) {
                 return true;
@@ -69,14 +272,19 @@ fn has_anyhow_result_in_scope(file: &File) -> bool {
     false
 }
 
-/// Recursively check a use tree for `anyhow::Result`.
+/// Recursively check a use tree for `anyhow::Result` (or `eyre::Result`).
 fn use_tree_imports_anyhow_result(tree: &syn::UseTree) -> bool {
     match tree {
         // `use anyhow::Result;`
         syn::UseTree::Path(path) => {
-            if path.ident == "anyhow" {
+            if path.ident == "anyhow" || path.ident == "eyre" {
                 return use_subtree_imports_result(&path.tree);
             }
+            // `use color_eyre::Result;` or `use color_eyre::eyre::Result;`
+            if path.ident == "color_eyre" {
+                return use_subtree_imports_result(&path.tree)
+                    || use_tree_imports_anyhow_result(&path.tree);
+            }
             false
         }
         // `use anyhow::*;` at top level won't have "anyhow" as the ident here,
@@ -101,53 +309,274 @@ fn use_subtree_imports_result(tree: &syn::UseTree) -> bool {
     }
 }
 
-/// Check if the file has a `type Result<T> = ...;` alias that is NOT `anyhow::Result`.
-fn has_non_anyhow_result_alias(file: &File) -> bool {
-    for item in &file.items {
-        if let syn::Item::Type(type_alias) = item {
-            if type_alias.ident == "Result" {
-                // Check if the RHS is `anyhow::Result<T>` — if so, it's fine.
-                // If it's something else, it shadows the import.
-                if !is_anyhow_result_type(&type_alias.ty) {
-                    return true;
+/// Names a module imports from its parent with `use super::..`.
+enum SuperImports {
+    /// `use super::*;`
+    All,
+    Names(HashSet<String>),
+}
+
+impl SuperImports {
+    fn contains(&self, name: &str) -> bool {
+        match self {
+            SuperImports::All => true,
+            SuperImports::Names(names) => names.contains(name),
+        }
+    }
+}
+
+/// The names the items of a module import from its parent, as in
+/// `use super::Result;`, `use super::{Result, Context};` or `use super::*;`.
+fn names_from_super(items: &[Item]) -> SuperImports {
+    fn collect(tree: &syn::UseTree, names: &mut HashSet<String>) -> bool {
+        match tree {
+            syn::UseTree::Name(name) => {
+                names.insert(name.ident.to_string());
+                false
+            }
+            syn::UseTree::Glob(_) => true,
+            // A glob imports everything, so the other names don't matter.
+            syn::UseTree::Group(group) => group.items.iter().any(|tree| collect(tree, names)),
+            // Renamed imports and items of submodules aren't type aliases of
+            // the parent under their own name.
+            syn::UseTree::Rename(_) | syn::UseTree::Path(_) => false,
+        }
+    }
+
+    let mut names = HashSet::new();
+    for item in items {
+        if let Item::Use(use_item) = item {
+            if let syn::UseTree::Path(path) = &use_item.tree {
+                if path.ident == "super" && collect(&path.tree, &mut names) {
+                    return SuperImports::All;
                 }
             }
         }
     }
-    false
+    SuperImports::Names(names)
 }
 
-/// Check if a type is `anyhow::Result<T>`.
-fn is_anyhow_result_type(ty: &Type) -> bool {
-    if let Type::Path(type_path) = ty {
-        let segments: Vec<String> = type_path
-            .path
+/// A `type Name<Params> = Target;` alias.
+#[derive(Clone)]
+struct TypeAlias {
+    /// Generic type parameters with their defaults, in declaration order.
+    params: Vec<(String, Option<Type>)>,
+    /// The aliased type.
+    target: Type,
+}
+
+/// Collect the type aliases among the items of a file or module, keyed by
+/// name.
+fn collect_type_aliases(items: &[Item]) -> HashMap<String, TypeAlias> {
+    let mut aliases = HashMap::new();
+    for item in items {
+        if let syn::Item::Type(type_alias) = item {
+            let params = type_alias
+                .generics
+                .type_params()
+                .map(|p| (p.ident.to_string(), p.default.clone()))
+                .collect();
+            aliases.insert(
+                type_alias.ident.to_string(),
+                TypeAlias {
+                    params,
+                    target: (*type_alias.ty).clone(),
+                },
+            );
+        }
+    }
+    aliases
+}
+
+/// An attribute standing in for `#[context]`, from `[unattributed]
+/// error-attributes`.
+struct ErrorAttribute {
+    path: Vec<String>,
+    /// An argument the attribute needs to be given, like `err`.
+    arg: Option<String>,
+}
+
+impl ErrorAttribute {
+    /// Whether an attribute is this one, either as written in the
+    /// configuration or imported by its last segment, with the argument if
+    /// required.
+    fn matches(&self, attr: &Attribute) -> bool {
+        let segments: Vec<String> = attr
+            .path()
             .segments
             .iter()
             .map(|s| s.ident.to_string())
             .collect();
-        // `anyhow::Result` or just `Result` (which would be the imported one)
-        if segments == ["anyhow", "Result"] || segments == ["Result"] {
-            return true;
+        let path_matches = segments == self.path
+            || (segments.len() == 1 && self.path.last() == Some(&segments[0]));
+        path_matches && self.arg.as_ref().is_none_or(|arg| has_argument(attr, arg))
+    }
+}
+
+/// Whether `arg` is one of the arguments of an attribute, as `err` is in
+/// `#[instrument(skip(db), err)]` and `#[instrument(err(Debug))]`.
+fn has_argument(attr: &Attribute, arg: &str) -> bool {
+    let Ok(list) = attr.meta.require_list() else {
+        return false;
+    };
+    let mut at_start = true;
+    for token in list.tokens.clone() {
+        match &token {
+            TokenTree::Ident(ident) if at_start && ident == arg => return true,
+            TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                at_start = true;
+                continue;
+            }
+            _ => {}
         }
+        at_start = false;
     }
     false
 }
 
-struct UnattributedChecker {
-    file_path: String,
-    /// Whether `anyhow::Result` is imported at the file level.
+/// Parse configured error attributes like `tracing::instrument(err)`.
+fn parse_error_attributes(attributes: &[String]) -> Vec<ErrorAttribute> {
+    attributes
+        .iter()
+        .map(|attr| {
+            let (path, arg) = match attr.split_once('(') {
+                Some((path, rest)) => (path, Some(rest.trim_end_matches(')').trim().to_string())),
+                None => (attr.as_str(), None),
+            };
+            ErrorAttribute {
+                path: path.split("::").map(|s| s.trim().to_string()).collect(),
+                arg,
+            }
+        })
+        .collect()
+}
+
+/// Split configured result aliases like `crate::Result` into path segments.
+fn parse_result_aliases(aliases: &[String]) -> Vec<Vec<String>> {
+    aliases
+        .iter()
+        .map(|alias| alias.split("::").map(|s| s.trim().to_string()).collect())
+        .collect()
+}
+
+/// Maximum depth of alias-to-alias expansion, guarding against cycles.
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Type arguments of the last path segment (`Result<T, E>` -> `[T, E]`).
+fn type_arguments(path: &syn::Path) -> Vec<&Type> {
+    match path.segments.last().map(|seg| &seg.arguments) {
+        Some(PathArguments::AngleBracketed(angle)) => angle
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// If `ty` is a bare generic parameter bound in `subst`, return its binding.
+fn substitute<'a>(ty: &'a Type, subst: &'a HashMap<String, Type>) -> &'a Type {
+    if let Type::Path(type_path) = ty {
+        if let Some(ident) = type_path.path.get_ident() {
+            if let Some(bound) = subst.get(&ident.to_string()) {
+                return bound;
+            }
+        }
+    }
+    ty
+}
+
+/// Check if path segments name an item of anyhow or eyre: `anyhow::Result`,
+/// `eyre::Result`, `color_eyre::Result` or `color_eyre::eyre::Result`.
+fn is_error_crate_item(segments: &[String], item: &str) -> bool {
+    match segments {
+        [krate, name] => {
+            (krate == "anyhow" || krate == "eyre" || krate == "color_eyre") && name == item
+        }
+        [krate, eyre, name] => krate == "color_eyre" && eyre == "eyre" && name == item,
+        _ => false,
+    }
+}
+
+/// Check if an error type is `anyhow::Error` (or `eyre::Report`).
+fn is_anyhow_error(ty: &Type, subst: &HashMap<String, Type>) -> bool {
+    match substitute(ty, subst) {
+        Type::Path(type_path) => {
+            let segments: Vec<String> = type_path
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            segments == ["anyhow", "Error"] || is_error_crate_item(&segments, "Report")
+        }
+        _ => false,
+    }
+}
+
+struct UnattributedChecker<'a> {
+    file_path: Arc<str>,
+    /// Leading bytes skipped by `syn`, see [`ParsedFile::syn_offset`].
+    base: usize,
+    /// Whether `anyhow::Result` is imported in the current module.
     anyhow_result_imported: bool,
+    /// Type aliases of the current module, used to resolve custom `Result`
+    /// types.
+    type_aliases: HashMap<String, TypeAlias>,
+    /// Imports of the current module, used to resolve configured result
+    /// aliases.
+    imports: Imports,
+    /// Glob imports of the current module, which may bring in aliases of the
+    /// workspace.
+    globs: Vec<Vec<String>>,
+    /// The path of the current module, to qualify imported aliases.
+    module_path: Vec<String>,
+    /// Configured result types to treat like `anyhow::Result`.
+    result_aliases: Vec<Vec<String>>,
+    /// Additional attributes to treat like `#[context]`.
+    context_attributes: &'a [ContextAttribute],
+    /// Attributes standing in for `#[context]` in this check.
+    error_attributes: Vec<ErrorAttribute>,
+    /// Methods adding context, to recognize bodies adding context to all
+    /// their errors; `None` flags such functions too.
+    covering_methods: Option<&'a ContextMethodsConfig>,
+    /// Skip functions that only delegate to another function.
+    skip_delegation: bool,
+    /// Skip functions with fewer statements.
+    min_statements: usize,
+    /// Traits and result aliases of the workspace.
+    workspace: &'a WorkspaceItems,
     /// Whether we are inside a `#[cfg(test)]` module.
     in_cfg_test: bool,
-    /// Whether we are inside a trait impl block (`impl Trait for Type`).
+    /// Whether we are inside a trait impl block (`impl Trait for Type`) whose
+    /// methods are skipped.
     in_trait_impl: bool,
+    coverage: Coverage,
     results: Vec<UnattributedFunction>,
+    /// Qualified paths of the aliases of `anyhow::Result` in the file.
+    defined_aliases: Vec<String>,
+    /// Lines of the functions returning `anyhow::Result` in the file.
+    anyhow_result_fns: HashSet<usize>,
 }
 
-impl UnattributedChecker {
+impl UnattributedChecker<'_> {
     /// Check a function signature and attributes to decide if it should be flagged.
-    fn check_fn(&mut self, attrs: &[Attribute], sig: &Signature, vis: Option<&Visibility>) {
+    fn check_fn(
+        &mut self,
+        attrs: &[Attribute],
+        sig: &Signature,
+        vis: Option<&Visibility>,
+        block: &Block,
+    ) {
+        // Check if the return type looks like `anyhow::Result<T>`
+        if !self.returns_anyhow_result(sig) {
+            return;
+        }
+        self.anyhow_result_fns.insert(sig.ident.span().start().line);
+
         // Skip if inside a #[cfg(test)] module
         if self.in_cfg_test {
             return;
@@ -168,13 +597,30 @@ impl UnattributedChecker {
             return;
         }
 
-        // Skip if already has #[context] attribute
-        if has_context_attribute(attrs) {
+        let annotated = has_context_attribute(attrs, self.context_attributes)
+            || self
+                .workspace
+                .is_annotated(&self.file_path, sig.ident.span().start().line)
+            || attrs
+                .iter()
+                .any(|attr| self.error_attributes.iter().any(|e| e.matches(attr)))
+            || self
+                .covering_methods
+                .is_some_and(|methods| adds_context_everywhere(block, methods));
+
+        // Skip trivial wrappers like `fn f() -> Result<()> { g() }` and
+        // helpers below the configured size, unless annotated anyway
+        if !annotated
+            && ((self.skip_delegation && delegated_call(block).is_some())
+                || statement_count(block) < self.min_statements)
+        {
             return;
         }
+        self.coverage.functions += 1;
 
-        // Check if the return type looks like `anyhow::Result<T>`
-        if !self.returns_anyhow_result(sig) {
+        // Skip if already has #[context] attribute
+        if annotated {
+            self.coverage.annotated += 1;
             return;
         }
 
@@ -183,7 +629,8 @@ impl UnattributedChecker {
         self.results.push(UnattributedFunction {
             file: self.file_path.clone(),
             line: sig.ident.span().start().line,
-            name: sig.ident.to_string(),
+            span: signature_span(sig, self.base),
+            name: sig.ident.to_string().into(),
             is_method: sig.receiver().is_some(),
             is_pub,
         });
@@ -191,42 +638,132 @@ impl UnattributedChecker {
 
     /// Check if a function signature returns what looks like `anyhow::Result<T>`.
     fn returns_anyhow_result(&self, sig: &Signature) -> bool {
-        let return_type = match &sig.output {
-            ReturnType::Default => return false,
-            ReturnType::Type(_, ty) => ty.as_ref(),
-        };
+        match &sig.output {
+            ReturnType::Default => false,
+            ReturnType::Type(_, ty) => self.is_anyhow_result(ty, &HashMap::new(), 0),
+        }
+    }
 
-        match return_type {
-            Type::Path(type_path) => {
-                let segments: Vec<String> = type_path
-                    .path
-                    .segments
-                    .iter()
-                    .map(|s| s.ident.to_string())
-                    .collect();
-
-                // Explicitly qualified: `anyhow::Result<T>`
-                if segments == ["anyhow", "Result"] {
-                    return true;
+    /// Check if a type path names one of the configured result aliases. Single
+    /// names match under any path; qualified ones must match the path as
+    /// written or as resolved through the file's imports.
+    fn is_result_alias(&self, segments: &[String]) -> bool {
+        if self.result_aliases.is_empty() {
+            return false;
+        }
+        let resolved = resolve(segments, &self.imports);
+        self.result_aliases
+            .iter()
+            .any(|alias| match alias.as_slice() {
+                [name] => segments.last() == Some(name),
+                _ => alias == segments || *alias == resolved,
+            })
+    }
+
+    /// Check if a type path names an alias of `anyhow::Result` defined
+    /// elsewhere in the workspace, imported by name or through a glob.
+    fn is_workspace_alias(&self, segments: &[String]) -> bool {
+        let aliases = &self.workspace.result_aliases;
+        if aliases.is_empty() {
+            return false;
+        }
+        let resolved = resolve(segments, &self.imports);
+        let mut candidates = qualified_candidates(&resolved, &self.module_path, None);
+        if let [name] = segments {
+            if !self.imports.contains_key(name) {
+                for glob in &self.globs {
+                    let mut path = glob.clone();
+                    path.push(name.clone());
+                    candidates.extend(qualified_candidates(&path, &self.module_path, None));
                 }
+            }
+        }
+        candidates.iter().any(|path| aliases.contains(path))
+    }
 
-                // Bare `Result<T>` — only if anyhow::Result is imported
-                if segments == ["Result"] && self.anyhow_result_imported {
-                    // Make sure it has exactly one type argument (not `Result<T, E>`)
-                    if let Some(last_seg) = type_path.path.segments.last() {
-                        return has_single_type_argument(&last_seg.arguments);
-                    }
+    /// Check if a type resolves to `anyhow::Result<T>`, expanding (possibly
+    /// generic) type aliases. `subst` binds the generic parameters of the alias
+    /// currently being expanded.
+    fn is_anyhow_result(&self, ty: &Type, subst: &HashMap<String, Type>, depth: usize) -> bool {
+        let Type::Path(type_path) = substitute(ty, subst) else {
+            return false;
+        };
+        let path = &type_path.path;
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let args = type_arguments(path);
+
+        // Explicitly qualified: `anyhow::Result<T>` or `eyre::Result<T>`
+        if is_error_crate_item(&segments, "Result") {
+            return true;
+        }
+
+        // A configured result alias: `crate::Result<T>`, possibly imported.
+        if self.is_result_alias(&segments) {
+            return true;
+        }
+
+        // An alias from elsewhere in the workspace, unless shadowed by a local
+        // one: `use crate::error::Result;`
+        let local_alias =
+            matches!(segments.as_slice(), [name] if self.type_aliases.contains_key(name));
+        if !local_alias && self.is_workspace_alias(&segments) {
+            return true;
+        }
+
+        if let [name] = segments.as_slice() {
+            // A type alias, possibly generic: `type MyResult<T> = ...;`
+            if let Some(alias) = self.type_aliases.get(name) {
+                if depth >= MAX_ALIAS_DEPTH {
+                    return false;
                 }
+                let mut inner = HashMap::new();
+                for (i, (param, default)) in alias.params.iter().enumerate() {
+                    let bound = match args.get(i) {
+                        Some(arg) => substitute(arg, subst).clone(),
+                        None => match default {
+                            Some(default) => default.clone(),
+                            None => continue,
+                        },
+                    };
+                    inner.insert(param.clone(), bound);
+                }
+                return self.is_anyhow_result(&alias.target, &inner, depth + 1);
+            }
 
-                false
+            if name == "Result" {
+                return match args.as_slice() {
+                    // Bare `Result<T>` — only if anyhow::Result is imported
+                    [_] => self.anyhow_result_imported,
+                    // `Result<T, anyhow::Error>`
+                    [_, err] => is_anyhow_error(err, subst),
+                    _ => false,
+                };
             }
-            _ => false,
         }
+
+        // `std::result::Result<T, anyhow::Error>`
+        if segments.len() >= 2 && segments.ends_with(&["result".to_string(), "Result".to_string()])
+        {
+            if let [_, err] = args.as_slice() {
+                return is_anyhow_error(err, subst);
+            }
+        }
+
+        false
     }
 }
 
+/// The span of a signature, from `fn` to the end of the return type.
+fn signature_span(sig: &Signature, base: usize) -> Span {
+    let end = match &sig.output {
+        ReturnType::Default => sig.paren_token.span.close(),
+        ReturnType::Type(_, ty) => ty.span(),
+    };
+    Span::new(sig.fn_token.span, end, base)
+}
+
 /// Check if a `#[cfg(test)]` attribute is present.
-fn has_cfg_test_attribute(attrs: &[Attribute]) -> bool {
+pub fn has_cfg_test_attribute(attrs: &[Attribute]) -> bool {
     for attr in attrs {
         if !attr.path().is_ident("cfg") {
             continue;
@@ -243,7 +780,7 @@ fn has_cfg_test_attribute(attrs: &[Attribute]) -> bool {
 }
 
 /// Check if a `#[test]` attribute is present.
-fn has_test_attribute(attrs: &[Attribute]) -> bool {
+pub fn has_test_attribute(attrs: &[Attribute]) -> bool {
     attrs.iter().any(|attr| {
         let path = attr.path();
         // #[test]
@@ -253,10 +790,14 @@ fn has_test_attribute(attrs: &[Attribute]) -> bool {
     })
 }
 
-/// Check if a `#[context]` or `#[fn_error_context::context]` attribute is present.
-fn has_context_attribute(attrs: &[Attribute]) -> bool {
-    attrs.iter().any(|attr| {
-        let path = attr.path();
+/// Check if a `#[context]` or `#[fn_error_context::context]` attribute, or one
+/// of the configured `custom` attributes, is present, including inside a
+/// `#[cfg_attr(..)]`.
+pub fn has_context_attribute(attrs: &[Attribute], custom: &[ContextAttribute]) -> bool {
+    let is_context = |path: &syn::Path| {
+        if custom.iter().any(|c| c.matches(path)) {
+            return true;
+        }
         match path.segments.len() {
             1 => path.segments[0].ident == "context",
             2 => {
@@ -264,42 +805,141 @@ fn has_context_attribute(attrs: &[Attribute]) -> bool {
             }
             _ => false,
         }
+    };
+    attrs.iter().any(|attr| match cfg::cfg_attr(attr) {
+        Some((_, metas)) => metas.iter().any(|meta| is_context(meta.path())),
+        None => is_context(attr.path()),
     })
 }
 
-/// Check if path arguments contain exactly one type argument.
-/// This distinguishes `Result<T>` (anyhow) from `Result<T, E>` (std).
-fn has_single_type_argument(args: &PathArguments) -> bool {
-    match args {
-        PathArguments::AngleBracketed(angle) => {
-            let type_args: Vec<_> = angle
-                .args
-                .iter()
-                .filter(|arg| matches!(arg, GenericArgument::Type(_)))
-                .collect();
-            type_args.len() == 1
+/// Whether a function body adds context to every error it returns: it has
+/// some `?` or `return Err(..)`, each `?` applies to a context method call
+/// (`load().context("..")?`), and each returned error is one (`e.context(..)`)
+/// or a new error made by `anyhow!` or `eyre!`. `bail!` and `ensure!` make new
+/// errors too. The body's tail expression and other returned results count as
+/// error paths unless they are `Ok(..)`: a tail `write(t)` passes on whatever
+/// error `write` returns.
+fn adds_context_everywhere(block: &Block, methods: &ContextMethodsConfig) -> bool {
+    let mut paths = ErrorPaths {
+        methods,
+        count: 0,
+        uncovered: false,
+    };
+    paths.visit_block(block);
+    paths.tail_of(block);
+    paths.count > 0 && !paths.uncovered
+}
+
+/// The error paths of a function body. Closures and nested items are
+/// skipped: their `?` operators belong to them, not to the function.
+struct ErrorPaths<'a> {
+    methods: &'a ContextMethodsConfig,
+    count: usize,
+    uncovered: bool,
+}
+
+impl ErrorPaths<'_> {
+    fn record(&mut self, covered: bool) {
+        self.count += 1;
+        self.uncovered |= !covered;
+    }
+
+    /// Record the result a block evaluates to, if it has a tail expression.
+    fn tail_of(&mut self, block: &Block) {
+        if let Some(Stmt::Expr(expr, None)) = block.stmts.last() {
+            self.returned(expr);
+        }
+    }
+
+    /// Record a result the function returns, looking into the branches of
+    /// `if`, `match` and blocks.
+    fn returned(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Paren(paren) => self.returned(&paren.expr),
+            Expr::Block(block) => self.tail_of(&block.block),
+            Expr::Unsafe(block) => self.tail_of(&block.block),
+            Expr::If(expr_if) => {
+                self.tail_of(&expr_if.then_branch);
+                if let Some((_, else_branch)) = &expr_if.else_branch {
+                    self.returned(else_branch);
+                }
+            }
+            Expr::Match(expr_match) => {
+                for arm in &expr_match.arms {
+                    self.returned(&arm.body);
+                }
+            }
+            Expr::Call(call) => match &*call.func {
+                Expr::Path(p) if p.path.is_ident("Ok") => {}
+                Expr::Path(p) if p.path.is_ident("Err") => {
+                    self.record(call.args.len() == 1 && self.adds_context(&call.args[0]));
+                }
+                _ => self.record(false),
+            },
+            // Visited on their own, or diverging (`bail!`, `unreachable!`).
+            Expr::Return(_) | Expr::Macro(_) => {}
+            _ => self.record(self.adds_context(expr)),
+        }
+    }
+
+    fn adds_context(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Paren(paren) => self.adds_context(&paren.expr),
+            Expr::MethodCall(call) => self.methods.is_context_method(&call.method.to_string()),
+            Expr::Macro(mac) => mac
+                .mac
+                .path
+                .segments
+                .last()
+                .is_some_and(|seg| matches!(seg.ident.to_string().as_str(), "anyhow" | "eyre")),
+            _ => false,
         }
-        _ => false,
     }
 }
 
-impl<'ast> Visit<'ast> for UnattributedChecker {
+impl<'ast> Visit<'ast> for ErrorPaths<'_> {
+    fn visit_expr_try(&mut self, node: &'ast ExprTry) {
+        self.record(self.adds_context(&node.expr));
+        syn::visit::visit_expr_try(self, node);
+    }
+
+    fn visit_expr_return(&mut self, node: &'ast ExprReturn) {
+        if let Some(expr) = &node.expr {
+            self.returned(expr);
+        }
+        syn::visit::visit_expr_return(self, node);
+    }
+
+    fn visit_expr_closure(&mut self, _node: &'ast ExprClosure) {}
+
+    fn visit_item(&mut self, _node: &'ast Item) {}
+}
+
+impl<'ast> Visit<'ast> for UnattributedChecker<'_> {
     fn visit_item_fn(&mut self, node: &'ast ItemFn) {
-        self.check_fn(&node.attrs, &node.sig, Some(&node.vis));
+        self.check_fn(&node.attrs, &node.sig, Some(&node.vis), &node.block);
         syn::visit::visit_item_fn(self, node);
     }
 
     fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
-        self.check_fn(&node.attrs, &node.sig, Some(&node.vis));
+        self.check_fn(&node.attrs, &node.sig, Some(&node.vis), &node.block);
         syn::visit::visit_impl_item_fn(self, node);
     }
 
     fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
         let prev_in_trait_impl = self.in_trait_impl;
 
-        // If this is `impl Trait for Type`, set the flag
-        if node.trait_.is_some() {
-            self.in_trait_impl = true;
+        // If this is `impl Trait for Type` of a trait we can't annotate, set
+        // the flag
+        if let Some((_, path, _)) = &node.trait_ {
+            let local = self.workspace.local_traits.as_ref().is_some_and(|traits| {
+                path.segments
+                    .last()
+                    .is_some_and(|seg| traits.contains(&seg.ident.to_string()))
+            });
+            if !local {
+                self.in_trait_impl = true;
+            }
         }
 
         syn::visit::visit_item_impl(self, node);
@@ -307,6 +947,20 @@ impl<'ast> Visit<'ast> for UnattributedChecker {
         self.in_trait_impl = prev_in_trait_impl;
     }
 
+    fn visit_item_type(&mut self, node: &'ast ItemType) {
+        // Resolve the alias through `type_aliases`, which also covers generic
+        // ones with defaults: `type Result<T, E = anyhow::Error> = ..;`
+        let ty = Type::Path(TypePath {
+            qself: None,
+            path: node.ident.clone().into(),
+        });
+        if !self.in_cfg_test && self.is_anyhow_result(&ty, &HashMap::new(), 0) {
+            let mut path = self.module_path.clone();
+            path.push(node.ident.to_string());
+            self.defined_aliases.push(path.join("::"));
+        }
+    }
+
     fn visit_item_mod(&mut self, node: &'ast ItemMod) {
         let prev_in_cfg_test = self.in_cfg_test;
 
@@ -315,8 +969,38 @@ impl<'ast> Visit<'ast> for UnattributedChecker {
             self.in_cfg_test = true;
         }
 
+        // An inline module has its own scope: the parent's imports and
+        // aliases are only visible through `use super::..`
+        let prev_scope = node.content.as_ref().map(|(_, items)| {
+            let inherited = names_from_super(items);
+            let mut aliases = collect_type_aliases(items);
+            let imported = has_anyhow_result_in_scope(items)
+                || (self.anyhow_result_imported
+                    && inherited.contains("Result")
+                    && !aliases.contains_key("Result"));
+            for (name, alias) in &self.type_aliases {
+                if inherited.contains(name) && !aliases.contains_key(name) {
+                    aliases.insert(name.clone(), alias.clone());
+                }
+            }
+            self.module_path.push(node.ident.to_string());
+            (
+                std::mem::replace(&mut self.anyhow_result_imported, imported),
+                std::mem::replace(&mut self.type_aliases, aliases),
+                std::mem::replace(&mut self.imports, collect_imports(items)),
+                std::mem::replace(&mut self.globs, collect_globs(items)),
+            )
+        });
+
         syn::visit::visit_item_mod(self, node);
 
+        if let Some((imported, aliases, imports, globs)) = prev_scope {
+            self.anyhow_result_imported = imported;
+            self.type_aliases = aliases;
+            self.imports = imports;
+            self.globs = globs;
+            self.module_path.pop();
+        }
         self.in_cfg_test = prev_in_cfg_test;
     }
 }
@@ -326,16 +1010,35 @@ mod tests {
     use super::*;
 
     fn check_source(source: &str) -> Vec<UnattributedFunction> {
-        let syntax: File = syn::parse_file(source).unwrap();
-        let has_import = has_anyhow_result_in_scope(&syntax);
-        let has_alias = has_non_anyhow_result_alias(&syntax);
+        check_source_with(source, &[])
+    }
 
+    fn check_source_with(
+        source: &str,
+        context_attributes: &[ContextAttribute],
+    ) -> Vec<UnattributedFunction> {
+        let syntax: syn::File = syn::parse_file(source).unwrap();
         let mut visitor = UnattributedChecker {
-            file_path: "test.rs".to_string(),
-            anyhow_result_imported: has_import && !has_alias,
+            file_path: "test.rs".into(),
+            base: 0,
+            anyhow_result_imported: has_anyhow_result_in_scope(&syntax.items),
+            type_aliases: collect_type_aliases(&syntax.items),
+            imports: collect_imports(&syntax.items),
+            globs: Vec::new(),
+            module_path: Vec::new(),
+            result_aliases: Vec::new(),
+            context_attributes,
+            error_attributes: Vec::new(),
+            covering_methods: None,
+            skip_delegation: true,
+            min_statements: 0,
+            workspace: &WorkspaceItems::default(),
             in_cfg_test: false,
             in_trait_impl: false,
+            coverage: Coverage::default(),
             results: Vec::new(),
+            defined_aliases: Vec::new(),
+            anyhow_result_fns: HashSet::new(),
         };
         visitor.visit_file(&syntax);
         visitor.results
@@ -353,7 +1056,7 @@ mod tests {
             "#,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].name, "do_something");
+        assert_eq!(&*results[0].name, "do_something");
     }
 
     #[test]
@@ -372,6 +1075,27 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn test_not_flagged_with_cfg_attr_context() {
+        let results = check_source(
+            r#"
+            use anyhow::Result;
+
+            #[cfg_attr(feature = "context", fn_error_context::context("Doing something"))]
+            fn do_something() -> Result<()> {
+                Ok(())
+            }
+
+            #[cfg_attr(feature = "serde", allow(dead_code))]
+            fn do_other() -> Result<()> {
+                Ok(())
+            }
+            "#,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(&*results[0].name, "do_other");
+    }
+
     #[test]
     fn test_not_flagged_test_fn() {
         let results = check_source(
@@ -495,7 +1219,7 @@ mod tests {
             "#,
         );
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].name, "do_something");
+        assert_eq!(&*results[0].name, "do_something");
     }
 
     #[test]
@@ -586,6 +1310,422 @@ mod tests {
         assert!(results[0].is_method);
     }
 
+    #[test]
+    fn test_delegation_skipped() {
+        let source = r#"
+            use anyhow::Result;
+
+            fn load() -> Result<Config> {
+                read_config(DEFAULT_PATH)
+            }
+
+            async fn fetch() -> Result<()> {
+                client.get(url).await?;
+                Ok(())
+            }
+
+            fn parse() -> Result<u32> {
+                let text = read()?;
+                Ok(text.parse()?)
+            }
+            "#;
+        let names = |results: Vec<UnattributedFunction>| -> Vec<String> {
+            results.into_iter().map(|r| r.name.to_string()).collect()
+        };
+        assert_eq!(names(check_source(source)), ["parse"]);
+
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let mut config = Config::default();
+        config.unattributed.skip_delegation = false;
+        assert_eq!(
+            names(check_file(&file, &config, &[], &WorkspaceItems::default())),
+            ["load", "fetch", "parse"]
+        );
+        assert_eq!(
+            coverage(&file, &config, &[], &WorkspaceItems::default()).functions,
+            3
+        );
+        assert_eq!(
+            coverage(&file, &Config::default(), &[], &WorkspaceItems::default()).functions,
+            1
+        );
+    }
+
+    #[test]
+    fn test_min_statements() {
+        let source = r#"
+            use anyhow::Result;
+
+            fn small() -> Result<u32> {
+                let n = read()?;
+                Ok(n)
+            }
+
+            fn large() -> Result<u32> {
+                let a = read()?;
+                if a > 1 {
+                    log(a);
+                }
+                Ok(a)
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let mut config = Config::default();
+        config.unattributed.min_statements = 3;
+        let names: Vec<String> = check_file(&file, &config, &[], &WorkspaceItems::default())
+            .into_iter()
+            .map(|r| r.name.to_string())
+            .collect();
+        assert_eq!(names, ["large"]);
+    }
+
+    #[test]
+    fn test_local_trait_impls() {
+        let source = r#"
+            use anyhow::Result;
+
+            mod store {
+                pub trait Store {
+                    fn load(&self) -> anyhow::Result<()>;
+                }
+            }
+
+            impl store::Store for Disk {
+                fn load(&self) -> Result<()> {
+                    Ok(())
+                }
+            }
+
+            impl std::str::FromStr for Disk {
+                type Err = anyhow::Error;
+                fn from_str(s: &str) -> Result<Self> {
+                    Ok(Disk)
+                }
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let config = Config::default();
+        assert!(check_file(&file, &config, &[], &WorkspaceItems::default()).is_empty());
+
+        let traits = local_traits([&file]);
+        assert_eq!(traits, HashSet::from(["Store".to_string()]));
+        let workspace = WorkspaceItems {
+            local_traits: Some(traits),
+            ..Default::default()
+        };
+        let results = check_file(&file, &config, &[], &workspace);
+        assert_eq!(results.len(), 1);
+        assert_eq!(&*results[0].name, "load");
+    }
+
+    #[test]
+    fn test_annotated_in_pass_1() {
+        let source = r#"
+            use anyhow::Result;
+
+            pub fn query(sql: &str) -> Result<u8> {
+                run(sql)
+            }
+
+            pub fn other(sql: &str) -> Result<u8> {
+                run(sql)
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let mut config = Config::default();
+        config.unattributed.skip_delegation = false;
+        let workspace = WorkspaceItems {
+            annotated: HashMap::from([("test.rs".into(), HashSet::from([4]))]),
+            ..Default::default()
+        };
+        let results = check_file(&file, &config, &[], &workspace);
+        let names: Vec<&str> = results.iter().map(|r| &*r.name).collect();
+        assert_eq!(names, ["other"]);
+    }
+
+    #[test]
+    fn test_workspace_result_aliases() {
+        let parse = |path: &str, source: &str| {
+            ParsedFile::from_source(std::path::Path::new(path), source.to_string())
+        };
+        let error = parse(
+            "src/error.rs",
+            "pub type Result<T, E = anyhow::Error> = std::result::Result<T, E>;\n\
+             pub type IoResult<T> = std::io::Result<T>;",
+        );
+        let prelude = parse("src/prelude.rs", "pub use crate::error::*;");
+        let user = parse(
+            "src/user.rs",
+            r#"
+            use crate::error::Result;
+
+            pub fn load() -> Result<()> { todo!() }
+
+            mod nested {
+                use crate::prelude::*;
+
+                fn save() -> Result<()> { todo!() }
+                fn read() -> IoResult<()> { todo!() }
+            }
+
+            mod shadowed {
+                use crate::prelude::*;
+                type Result<T> = std::result::Result<T, String>;
+
+                fn parse() -> Result<()> { todo!() }
+            }
+            "#,
+        );
+        let path = |module: &str| vec!["demo".to_string(), module.to_string()];
+        let (error_path, prelude_path, user_path) = (path("error"), path("prelude"), path("user"));
+
+        let config = Config::default();
+        let files = [
+            (&error, error_path.as_slice()),
+            (&prelude, prelude_path.as_slice()),
+            (&user, user_path.as_slice()),
+        ];
+        let aliases = result_aliases(files, &config);
+        assert_eq!(
+            aliases,
+            HashSet::from([
+                "demo::error::Result".to_string(),
+                "demo::prelude::Result".to_string(),
+            ])
+        );
+
+        assert!(check_file(&user, &config, &user_path, &WorkspaceItems::default()).is_empty());
+        let workspace = WorkspaceItems {
+            result_aliases: aliases,
+            ..Default::default()
+        };
+        let names: Vec<String> = check_file(&user, &config, &user_path, &workspace)
+            .into_iter()
+            .map(|r| r.name.to_string())
+            .collect();
+        assert_eq!(names, ["load", "save"]);
+    }
+
+    #[test]
+    fn test_error_attributes() {
+        let source = r#"
+            use anyhow::Result;
+            use tracing::instrument;
+
+            #[tracing::instrument(skip(db), err)]
+            fn load(db: &Db) -> Result<u32> {
+                let n = db.read()?;
+                Ok(n)
+            }
+
+            #[instrument(err(Debug))]
+            fn store(n: u32) -> Result<u32> {
+                let m = write(n)?;
+                Ok(m)
+            }
+
+            #[instrument(fields(err = 1))]
+            fn traced() -> Result<u32> {
+                let m = write(1)?;
+                Ok(m)
+            }
+
+            #[report]
+            fn reported() -> Result<u32> {
+                let m = write(2)?;
+                Ok(m)
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let names = |config: &Config| -> Vec<String> {
+            check_file(&file, config, &[], &WorkspaceItems::default())
+                .into_iter()
+                .map(|r| r.name.to_string())
+                .collect()
+        };
+        let mut config = Config::default();
+        assert_eq!(names(&config), ["traced", "reported"]);
+        assert_eq!(
+            coverage(&file, &config, &[], &WorkspaceItems::default()).annotated,
+            2
+        );
+
+        config.unattributed.error_attributes = vec!["my_macros::report".to_string()];
+        assert_eq!(names(&config), ["load", "store", "traced"]);
+    }
+
+    #[test]
+    fn test_context_in_body() {
+        let source = r#"
+            use anyhow::{anyhow, Context, Result};
+
+            fn covered(path: &Path) -> Result<String> {
+                let text = read(path).with_context(|| format!("Reading {path:?}"))?;
+                if text.is_empty() {
+                    return Err(anyhow!("{path:?} is empty"));
+                }
+                let parsed = (parse(&text).context("Parsing"))?;
+                Ok(parsed)
+            }
+
+            fn partly(path: &Path) -> Result<String> {
+                let text = read(path).context("Reading")?;
+                let parsed = parse(&text)?;
+                Ok(parsed)
+            }
+
+            fn returned(path: &Path) -> Result<String> {
+                let text = read(path).context("Reading")?;
+                if text.is_empty() {
+                    return Err(Error::Empty.into());
+                }
+                Ok(text)
+            }
+
+            fn closures(paths: &[PathBuf]) -> Result<Vec<String>> {
+                let texts = paths.iter().map(|p| read(p)).collect::<Result<Vec<_>>>();
+                let texts = texts.context("Reading")?;
+                Ok(texts)
+            }
+
+            fn infallible() -> Result<u32> {
+                let n = 1;
+                Ok(n)
+            }
+
+            fn tail_call(path: &Path) -> Result<()> {
+                let text = read(path).context("Reading")?;
+                write(text)
+            }
+
+            fn tail_err(path: &Path) -> Result<String> {
+                let text = read(path).context("Reading")?;
+                if text.is_empty() {
+                    Err(Error::Empty.into())
+                } else {
+                    Ok(text)
+                }
+            }
+
+            fn tail_covered(path: &Path) -> Result<String> {
+                let text = read(path).context("Reading")?;
+                match parse(&text) {
+                    Ok(parsed) => Ok(parsed),
+                    Err(e) => Err(e.context("Parsing")),
+                }
+            }
+            "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let names = |config: &Config| -> Vec<String> {
+            check_file(&file, config, &[], &WorkspaceItems::default())
+                .into_iter()
+                .map(|r| r.name.to_string())
+                .collect()
+        };
+        let mut config = Config::default();
+        assert_eq!(names(&config).len(), 8);
+
+        config.unattributed.skip_covered = true;
+        assert_eq!(
+            names(&config),
+            ["partly", "returned", "infallible", "tail_call", "tail_err"]
+        );
+        assert_eq!(
+            coverage(&file, &config, &[], &WorkspaceItems::default()).annotated,
+            3
+        );
+    }
+
+    #[test]
+    fn test_module_scopes() {
+        let results = check_source(
+            r#"
+            mod with_import {
+                use anyhow::Result;
+
+                fn flagged() -> Result<()> {
+                    Ok(())
+                }
+            }
+
+            mod with_alias {
+                type Result<T> = anyhow::Result<T>;
+
+                fn flagged() -> Result<()> {
+                    Ok(())
+                }
+
+                mod glob {
+                    use super::*;
+
+                    fn flagged() -> Result<()> {
+                        Ok(())
+                    }
+                }
+
+                mod named {
+                    use super::{Result, Other};
+
+                    fn flagged() -> Result<()> {
+                        Ok(())
+                    }
+                }
+
+                mod unrelated {
+                    fn not_flagged() -> Result<()> {
+                        Ok(())
+                    }
+                }
+            }
+
+            fn not_flagged() -> Result<()> {
+                Ok(())
+            }
+            "#,
+        );
+        let lines: Vec<usize> = results.iter().map(|r| r.line).collect();
+        assert_eq!(lines, [5, 13, 20, 28]);
+        assert!(results.iter().all(|r| &*r.name == "flagged"));
+    }
+
+    #[test]
+    fn test_nested_module_shadows_import() {
+        let results = check_source(
+            r#"
+            use anyhow::Result;
+
+            fn flagged() -> Result<()> {
+                Ok(())
+            }
+
+            mod io {
+                type Result<T> = std::io::Result<T>;
+
+                fn not_flagged() -> Result<()> {
+                    Ok(())
+                }
+            }
+            "#,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(&*results[0].name, "flagged");
+    }
+
+    #[test]
+    fn test_signature_span() {
+        let source =
+            "use anyhow::Result;\n\npub fn do_something(x: u32) -> Result<()> {\n    Ok(())\n}\n";
+        let results = check_source(source);
+        assert_eq!(results.len(), 1);
+        let span = &results[0].span;
+        assert_eq!((span.line, span.column), (3, 5));
+        assert_eq!((span.end_line, span.end_column), (3, 42));
+        assert_eq!(
+            &source[span.bytes().unwrap()],
+            "fn do_something(x: u32) -> Result<()>"
+        );
+    }
+
     #[test]
     fn test_pub_visibility_tracked() {
         let results = check_source(
@@ -602,8 +1742,10 @@ mod tests {
             "#,
         );
         assert_eq!(results.len(), 2);
-        assert!(results.iter().any(|r| r.name == "public_fn" && r.is_pub));
-        assert!(results.iter().any(|r| r.name == "private_fn" && !r.is_pub));
+        assert!(results.iter().any(|r| &*r.name == "public_fn" && r.is_pub));
+        assert!(results
+            .iter()
+            .any(|r| &*r.name == "private_fn" && !r.is_pub));
     }
 
     #[test]
@@ -620,4 +1762,214 @@ mod tests {
         );
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_flagged_generic_alias() {
+        let results = check_source(
+            r#"
+            type MyResult<T> = anyhow::Result<T>;
+
+            fn load<T: DeserializeOwned>(p: &Path) -> MyResult<T> {
+                todo!()
+            }
+            "#,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(&*results[0].name, "load");
+    }
+
+    #[test]
+    fn test_flagged_alias_generic_over_error() {
+        let results = check_source(
+            r#"
+            type MyResult<T, E = anyhow::Error> = std::result::Result<T, E>;
+
+            fn defaulted() -> MyResult<()> {
+                Ok(())
+            }
+
+            fn explicit() -> MyResult<(), anyhow::Error> {
+                Ok(())
+            }
+
+            fn other_error() -> MyResult<(), std::io::Error> {
+                Ok(())
+            }
+            "#,
+        );
+        let names: Vec<&str> = results.iter().map(|r| &*r.name).collect();
+        assert_eq!(names, ["defaulted", "explicit"]);
+    }
+
+    #[test]
+    fn test_flagged_nested_alias() {
+        let results = check_source(
+            r#"
+            use anyhow::Result;
+
+            type Inner<T> = Result<T>;
+            type Outer<T> = Inner<Vec<T>>;
+
+            fn nested() -> Outer<u8> {
+                Ok(vec![])
+            }
+            "#,
+        );
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_not_flagged_cyclic_alias() {
+        let results = check_source(
+            r#"
+            type A<T> = B<T>;
+            type B<T> = A<T>;
+
+            fn cyclic() -> A<()> {
+                todo!()
+            }
+            "#,
+        );
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_flagged_eyre_result() {
+        let results = check_source(
+            r#"
+            fn explicit() -> eyre::Result<()> {
+                Ok(())
+            }
+
+            fn report() -> Result<(), color_eyre::eyre::Report> {
+                Ok(())
+            }
+            "#,
+        );
+        let names: Vec<&str> = results.iter().map(|r| &*r.name).collect();
+        assert_eq!(names, ["explicit", "report"]);
+    }
+
+    #[test]
+    fn test_flagged_with_eyre_import() {
+        for import in [
+            "use eyre::{Result, WrapErr};",
+            "use color_eyre::Result;",
+            "use color_eyre::eyre::Result;",
+        ] {
+            let results = check_source(&format!(
+                r#"
+                {import}
+
+                fn do_something() -> Result<()> {{
+                    Ok(())
+                }}
+                "#
+            ));
+            assert_eq!(results.len(), 1, "{import}");
+        }
+    }
+
+    #[test]
+    fn test_not_flagged_custom_attribute() {
+        let source = r#"
+            use anyhow::Result;
+
+            #[err_context("Loading")]
+            fn load() -> Result<()> {
+                Ok(())
+            }
+            "#;
+        assert_eq!(check_source(source).len(), 1);
+
+        let attributes = [ContextAttribute {
+            path: "my_macros::err_context".to_string(),
+            message_index: 0,
+            message_key: None,
+            context: None,
+            methods: Vec::new(),
+            rule: false,
+        }];
+        assert!(check_source_with(source, &attributes).is_empty());
+    }
+
+    #[test]
+    fn test_flagged_configured_result_alias() {
+        let source = r#"
+            use crate::Result;
+            use crate::cmd::CmdResult;
+
+            fn imported() -> Result<()> {
+                Ok(())
+            }
+
+            fn qualified() -> crate::Result<()> {
+                Ok(())
+            }
+
+            fn by_name() -> CmdResult {
+                Ok(())
+            }
+
+            fn other() -> io::Result<()> {
+                Ok(())
+            }
+            "#;
+        assert!(check_source(source).is_empty());
+
+        let syntax: syn::File = syn::parse_file(source).unwrap();
+        let mut visitor = UnattributedChecker {
+            file_path: "test.rs".into(),
+            base: 0,
+            anyhow_result_imported: has_anyhow_result_in_scope(&syntax.items),
+            type_aliases: collect_type_aliases(&syntax.items),
+            imports: collect_imports(&syntax.items),
+            globs: Vec::new(),
+            module_path: Vec::new(),
+            result_aliases: parse_result_aliases(&[
+                "crate::Result".to_string(),
+                "CmdResult".to_string(),
+            ]),
+            context_attributes: &[],
+            error_attributes: Vec::new(),
+            covering_methods: None,
+            skip_delegation: true,
+            min_statements: 0,
+            workspace: &WorkspaceItems::default(),
+            in_cfg_test: false,
+            in_trait_impl: false,
+            coverage: Coverage::default(),
+            results: Vec::new(),
+            defined_aliases: Vec::new(),
+            anyhow_result_fns: HashSet::new(),
+        };
+        visitor.visit_file(&syntax);
+        let names: Vec<&str> = visitor.results.iter().map(|r| &*r.name).collect();
+        assert_eq!(names, ["imported", "qualified", "by_name"]);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let source = r#"
+            use anyhow::Result;
+
+            #[context("Loading")]
+            fn annotated() -> Result<()> { Ok(()) }
+
+            fn unannotated() -> Result<()> { Ok(()) }
+
+            fn infallible() -> u32 { 0 }
+
+            fn main() -> Result<()> { Ok(()) }
+        "#;
+        let file = ParsedFile::from_source(std::path::Path::new("test.rs"), source.to_string());
+        let coverage = coverage(&file, &Config::default(), &[], &WorkspaceItems::default());
+        assert_eq!(
+            coverage,
+            Coverage {
+                functions: 2,
+                annotated: 1
+            }
+        );
+    }
 }